@@ -0,0 +1,124 @@
+//! Derive macro for [ilo-config](https://docs.rs/ilo-config).  Use it through the `derive` feature
+//! of ilo-config rather than depending on this crate directly.
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::{parse_macro_input, DeriveInput, Error, LitStr, Path};
+
+/// Implement `ilo_config::ConfigKey` for a config data type.
+///
+/// The type must also implement `Serialize`, `Deserialize` and `Default`.  Options are given in
+/// an `#[ilo_config(...)]` attribute:
+///
+/// - `key = "..."`: the config key, i.e. the file name without extension.  Defaults to the type
+///   name in kebab-case, e.g. `jira-config` for `JiraConfig` or `http-config` for `HTTPConfig`.
+/// - `format = "json" | "jsonc"`: the file format.
+/// - `default = "path::to::fn"`: a function returning the config data to start with when the
+///   config file doesn't exist yet, instead of the type's `Default` value.
+/// - `strict`, `preserve_unknown_fields`: turn on the `ConfigOptions` of the same name.
+#[proc_macro_derive(IloConfig, attributes(ilo_config))]
+pub fn derive_ilo_config(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let mut key = LitStr::new(&kebab_case(&input.ident.to_string()), Span::call_site());
+    let mut format = None;
+    let mut strict = false;
+    let mut preserve_unknown_fields = false;
+    let mut default = None;
+
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|a| a.path().is_ident("ilo_config"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("key") {
+                key = meta.value()?.parse()?;
+            } else if meta.path.is_ident("format") {
+                let value: LitStr = meta.value()?.parse()?;
+                format = Some(match value.value().as_str() {
+                    "json" => quote!(::ilo_config::ConfigFormat::Json),
+                    "jsonc" => quote!(::ilo_config::ConfigFormat::Jsonc),
+                    _ => return Err(Error::new(value.span(), "expected \"json\" or \"jsonc\"")),
+                });
+            } else if meta.path.is_ident("default") {
+                let value: LitStr = meta.value()?.parse()?;
+                default = Some(value.parse::<Path>()?);
+            } else if meta.path.is_ident("strict") {
+                strict = true;
+            } else if meta.path.is_ident("preserve_unknown_fields") {
+                preserve_unknown_fields = true;
+            } else {
+                return Err(meta.error("unsupported ilo_config attribute"));
+            }
+            Ok(())
+        })?;
+    }
+
+    let format = format.map(|format| quote!(options.format = #format;));
+    let default = default.map(|default| {
+        quote! {
+            fn default_data() -> Self {
+                #default()
+            }
+        }
+    });
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::ilo_config::ConfigKey for #name #type_generics #where_clause {
+            const KEY: &'static str = #key;
+
+            fn options() -> ::ilo_config::ConfigOptions {
+                let mut options = ::ilo_config::ConfigOptions::default();
+                options.strict = #strict;
+                options.preserve_unknown_fields = #preserve_unknown_fields;
+                #format
+                options
+            }
+
+            #default
+        }
+    })
+}
+
+/// Convert a type name to kebab-case.  A run of capitals is one word, so `HTTPConfig` becomes
+/// `http-config`.
+fn kebab_case(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut key = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            let previous = chars[i - 1];
+            let next_is_lowercase = chars.get(i + 1).is_some_and(|next| next.is_lowercase());
+            // A new word starts after a lowercase letter or digit, or at the last capital of a
+            // run that is followed by a lowercase letter (`HTTPConfig`)
+            if !previous.is_uppercase() || next_is_lowercase {
+                key.push('-');
+            }
+        }
+        key.extend(c.to_lowercase());
+    }
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::kebab_case;
+
+    #[test]
+    fn kebab_case_splits_words() {
+        assert_eq!(kebab_case("JiraConfig"), "jira-config");
+        assert_eq!(kebab_case("Config"), "config");
+        assert_eq!(kebab_case("HTTPConfig"), "http-config");
+        assert_eq!(kebab_case("MyHTTPServer"), "my-http-server");
+        assert_eq!(kebab_case("ApiURL"), "api-url");
+        assert_eq!(kebab_case("S3Config"), "s3-config");
+        assert_eq!(kebab_case("Oauth2Token"), "oauth2-token");
+    }
+}
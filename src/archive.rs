@@ -0,0 +1,199 @@
+//! Exporting and importing a whole config root as a `.tar.gz` archive, e.g. for backups or for
+//! moving to a new machine.
+use std::{
+    io::{self, Read},
+    path::{Component, Path},
+};
+
+use flate2::{read::GzDecoder, write::GzEncoder};
+use serde_json::Value;
+use tar::{Archive, Builder, EntryType, Header};
+
+use crate::{Config, ConfigError, ConfigRoot, RetryPolicy};
+
+/// Suffixes of files that only matter while a process is running, or are left behind by editors.
+const TRANSIENT_SUFFIXES: [&str; 5] = [".lock", ".pid", ".tmp", ".swp", "~"];
+
+/// Protocol metadata, which belongs to the root (or namespace) rather than to its configs.
+const METADATA_FILE: &str = ".ilo-config.json";
+
+impl ConfigRoot {
+    /// Write every file in the config root to a gzipped tar archive at `path`, keeping their
+    /// permissions.
+    ///
+    /// Lock files, PID files and editor temp files are left out.  With the `encryption` feature
+    /// and [`ConfigOptions::encryption`](crate::ConfigOptions::encryption) set, the archive is
+    /// encrypted as a whole, the same way as a config file.
+    ///
+    /// ```no_run
+    /// # use ilo_config::ConfigRoot;
+    /// ConfigRoot::new().export_archive("ilo-backup.tar.gz")?;
+    /// // On the new machine:
+    /// ConfigRoot::new().import_archive("ilo-backup.tar.gz")?;
+    /// # Ok::<(), ilo_config::ConfigError>(())
+    /// ```
+    pub fn export_archive(&self, path: impl AsRef<Path>) -> Result<(), ConfigError> {
+        let path = path.as_ref();
+        let options = &self.options;
+        let config_root = self.path()?;
+        let write_error = |e| ConfigError::ConfigFileWriteError(path.to_path_buf(), e);
+
+        let mut builder = Builder::new(GzEncoder::new(Vec::new(), flate2::Compression::default()));
+        let mtime = options.clock.unix_timestamp();
+        let mut pending = vec![config_root.clone()];
+        while let Some(dir) = pending.pop() {
+            let mut entries = options
+                .filesystem
+                .read_dir(&dir)
+                .map_err(|e| ConfigError::ConfigRootLoadError(dir.clone(), e))?;
+            entries.sort();
+            for entry in entries {
+                let relative = entry.strip_prefix(&config_root).unwrap_or(&entry);
+                if !is_archived(relative) {
+                    continue;
+                }
+                let mode = options
+                    .filesystem
+                    .permissions(&entry)
+                    .map_err(|e| ConfigError::ConfigFileLoadError(entry.clone(), e))?;
+
+                let mut header = Header::new_gnu();
+                header.set_mtime(mtime);
+                if options.filesystem.is_file(&entry) {
+                    let contents = RetryPolicy::run(
+                        options.retry.as_ref(),
+                        &entry,
+                        || options.filesystem.read(&entry),
+                        ConfigError::ConfigFileLoadError,
+                    )?;
+                    header.set_entry_type(EntryType::Regular);
+                    header.set_mode(mode.unwrap_or(options.file_mode));
+                    header.set_size(contents.len() as u64);
+                    builder
+                        .append_data(&mut header, relative, contents.as_slice())
+                        .map_err(write_error)?;
+                } else {
+                    header.set_entry_type(EntryType::Directory);
+                    header.set_mode(mode.unwrap_or(0o700));
+                    header.set_size(0);
+                    builder
+                        .append_data(&mut header, relative, io::empty())
+                        .map_err(write_error)?;
+                    pending.push(entry);
+                }
+            }
+        }
+        let bytes = builder
+            .into_inner()
+            .and_then(|encoder| encoder.finish())
+            .map_err(write_error)?;
+
+        #[cfg(feature = "encryption")]
+        let bytes = match &options.encryption {
+            Some(encryption) => encryption.seal(path, &bytes)?,
+            None => bytes,
+        };
+        options
+            .filesystem
+            .write(path, &bytes, Some(options.file_mode))
+            .map_err(write_error)
+    }
+
+    /// Restore the files in an archive written by [`export_archive`](Self::export_archive) into
+    /// the config root, creating it if needed.
+    ///
+    /// Files that exist in both are replaced by the archived version, other files in the root are
+    /// left alone.  The archived permissions are restored.  Entries that would end up outside the
+    /// root make the import fail before anything is written.
+    pub fn import_archive(&self, path: impl AsRef<Path>) -> Result<(), ConfigError> {
+        let path = path.as_ref();
+        let options = &self.options;
+        let load_error = |e| ConfigError::ConfigFileLoadError(path.to_path_buf(), e);
+
+        let bytes = RetryPolicy::run(
+            options.retry.as_ref(),
+            path,
+            || options.filesystem.read(path),
+            ConfigError::ConfigFileLoadError,
+        )?;
+        #[cfg(feature = "encryption")]
+        let bytes = crate::encryption::open_if_sealed(bytes, path, options)?;
+
+        // Unpack into memory first, so a bad entry doesn't leave a half-imported root behind
+        let mut files = Vec::new();
+        let mut archive = Archive::new(GzDecoder::new(bytes.as_slice()));
+        for entry in archive.entries().map_err(load_error)? {
+            let mut entry = entry.map_err(load_error)?;
+            let relative = entry.path().map_err(load_error)?.into_owned();
+            if !relative
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)))
+            {
+                return Err(load_error(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "archive entry {} is outside the config root",
+                        relative.display()
+                    ),
+                )));
+            }
+            if !is_archived(&relative) {
+                continue;
+            }
+            let mode = entry.header().mode().map_err(load_error)? & 0o777;
+            let contents = match entry.header().entry_type() {
+                EntryType::Directory => None,
+                EntryType::Regular => {
+                    let mut contents = Vec::new();
+                    entry.read_to_end(&mut contents).map_err(load_error)?;
+                    Some(contents)
+                }
+                // Links and other special files are never exported
+                _ => continue,
+            };
+            files.push((relative, mode, contents));
+        }
+
+        let config_root = Config::<Value>::ensure_config_root(options)?;
+        for (relative, mode, contents) in files {
+            let target = config_root.join(&relative);
+            let write_error = |e| ConfigError::ConfigFileWriteError(target.clone(), e);
+            match contents {
+                None => options
+                    .filesystem
+                    .create_dir_all(&target, Some(mode))
+                    .map_err(write_error)?,
+                Some(contents) => {
+                    if let Some(parent) = target.parent() {
+                        options
+                            .filesystem
+                            .create_dir_all(parent, Some(0o700))
+                            .map_err(write_error)?;
+                    }
+                    options
+                        .filesystem
+                        .write(&target, &contents, Some(mode))
+                        .map_err(write_error)?;
+                }
+            }
+            // Existing files and directories keep their permissions when written
+            options
+                .filesystem
+                .set_permissions(&target, mode)
+                .map_err(write_error)?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether a file or directory belongs in an archive.
+fn is_archived(path: &Path) -> bool {
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    file_name != METADATA_FILE
+        && !TRANSIENT_SUFFIXES
+            .iter()
+            .any(|suffix| file_name.ends_with(suffix))
+}
@@ -0,0 +1,166 @@
+//! Overriding config values with command-line arguments parsed by
+//! [clap](https://docs.rs/clap), for CLIs that take their settings from both.
+use clap::{parser::ValueSource, Arg, ArgAction, ArgMatches};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+use crate::{
+    diff::{self, Change},
+    pointer, Config, ConfigError,
+};
+
+/// Id of the flag that makes [`Config::merge_args`] save the config, see [`save_arg`].
+const SAVE: &str = "save";
+
+/// A `--save` flag for the command, which makes [`Config::merge_args`] save the values that were
+/// passed on the command line to the config file.
+pub fn save_arg() -> Arg {
+    Arg::new(SAVE)
+        .long(SAVE)
+        .action(ArgAction::SetTrue)
+        .help("Save the given settings to the config file")
+}
+
+impl<TConfigData: Serialize + DeserializeOwned> Config<TConfigData> {
+    /// Override config values with the arguments in `matches` that were given on the command
+    /// line or in an environment variable, returning what changed.  Arguments that fell back to
+    /// their default value are ignored, so they don't override the config file.
+    ///
+    /// Arguments are matched to fields by their id, as it appears in the config file (after any
+    /// `#[serde(rename)]`), with `-` also tried as `_`.  Ids with dots refer to nested fields,
+    /// e.g. `#[arg(id = "server.port", long = "port")]` sets `port` in the `server` object.
+    /// Values are converted to the type of the field: `8080` sets a number field, but a string
+    /// field gets `"8080"`.  Arguments without a matching field, like `--verbose`, are left
+    /// alone; a value that doesn't fit its field fails with `ConfigError::InvalidPointerError`,
+    /// leaving the config unchanged.
+    ///
+    /// If the command has the [`save_arg`] flag and it was given, the changes are saved to the
+    /// config file.  Otherwise they only apply to this run; call [`save`](Self::save) to keep
+    /// them.
+    ///
+    /// ```no_run
+    /// # use ilo_config::Config;
+    /// use clap::{Arg, Command};
+    ///
+    /// #[derive(Default, serde::Serialize, serde::Deserialize)]
+    /// struct Settings {
+    ///     theme: String,
+    ///     font_size: u8,
+    /// }
+    ///
+    /// let matches = Command::new("editor")
+    ///     .arg(Arg::new("theme").long("theme"))
+    ///     .arg(Arg::new("font-size").long("font-size"))
+    ///     .arg(ilo_config::save_arg())
+    ///     .get_matches();
+    /// let mut config = Config::<Settings>::load("editor")?;
+    /// // `editor --font-size 14 --save` changes the font size for good
+    /// config.merge_args(&matches)?;
+    /// # Ok::<(), ilo_config::ConfigError>(())
+    /// ```
+    pub fn merge_args(&mut self, matches: &ArgMatches) -> Result<Vec<Change>, ConfigError> {
+        let original = self.data_document()?;
+        let mut document = original.clone();
+        for id in matches.ids() {
+            let id = id.as_str();
+            let given = matches!(
+                matches.value_source(id),
+                Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+            );
+            if id == SAVE || !given {
+                continue;
+            }
+            let Ok(Some(raw_values)) = matches.try_get_raw(id) else {
+                continue;
+            };
+            let values: Vec<String> = raw_values
+                .map(|value| value.to_string_lossy().into_owned())
+                .collect();
+            merge_arg::<TConfigData>(&mut document, id, &values)?;
+        }
+        if document == original {
+            return Ok(Vec::new());
+        }
+
+        self.replace_data(document)?;
+        let changes = diff::diff(&original, &self.data_document()?);
+        let save = matches!(matches.try_get_one::<bool>(SAVE), Ok(Some(true)));
+        if save && !changes.is_empty() {
+            self.save()?;
+        }
+        Ok(changes)
+    }
+}
+
+/// Set the value of the argument `id` in `document`, if the config data type has a field for it.
+fn merge_arg<TConfigData: Serialize + DeserializeOwned>(
+    document: &mut Value,
+    id: &str,
+    values: &[String],
+) -> Result<(), ConfigError> {
+    let mut error = None;
+    for pointer in pointers(id) {
+        let known = document.pointer(&pointer).is_some();
+        for candidate in candidates(values) {
+            let mut merged = document.clone();
+            if pointer::set(&mut merged, &pointer, candidate).is_err() {
+                break;
+            }
+            match serde_json::from_value::<TConfigData>(merged.clone()) {
+                // Values of arguments the type has no field for are dropped on the way through
+                Ok(data) => {
+                    let kept = serde_json::to_value(&data)
+                        .is_ok_and(|value| value.pointer(&pointer).is_some());
+                    if kept {
+                        *document = merged;
+                        return Ok(());
+                    }
+                }
+                Err(e) if known && error.is_none() => {
+                    error = Some((pointer.clone(), e.to_string()))
+                }
+                Err(_) => {}
+            }
+        }
+    }
+    match error {
+        Some((pointer, message)) => Err(ConfigError::InvalidPointerError(pointer, message)),
+        None => Ok(()),
+    }
+}
+
+/// The pointers an argument id may refer to, e.g. `/server/max-connections` and
+/// `/server/max_connections` for `server.max-connections`.
+fn pointers(id: &str) -> Vec<String> {
+    let exact: String = id
+        .split('.')
+        .map(|token| format!("/{}", token.replace('~', "~0").replace('/', "~1")))
+        .collect();
+    let snake_case = exact.replace('-', "_");
+    if snake_case == exact {
+        vec![exact]
+    } else {
+        vec![exact, snake_case]
+    }
+}
+
+/// The JSON values the raw values of an argument may stand for, most specific first, with the
+/// values parsed as JSON tried before plain strings.
+fn candidates(values: &[String]) -> Vec<Value> {
+    let parsed: Option<Vec<Value>> = values
+        .iter()
+        .map(|value| serde_json::from_str(value).ok())
+        .collect();
+    let strings: Vec<Value> = values.iter().cloned().map(Value::String).collect();
+    let mut candidates = Vec::new();
+    if let [value] = values {
+        candidates.extend(parsed.iter().map(|parsed| parsed[0].clone()));
+        candidates.push(Value::String(value.clone()));
+        candidates.extend(parsed.map(Value::Array));
+        candidates.push(Value::Array(strings));
+    } else {
+        candidates.extend(parsed.map(Value::Array));
+        candidates.push(Value::Array(strings));
+    }
+    candidates
+}
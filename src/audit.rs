@@ -0,0 +1,135 @@
+//! Reports for checking how the configs under a config root are protected, and (with the
+//! `audit-log` feature) a log of when config files were loaded, saved and deleted.
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+use crate::{Config, ConfigError, ConfigOptions};
+
+#[cfg(feature = "audit-log")]
+pub(crate) mod log;
+
+#[cfg(feature = "audit-log")]
+pub use log::{read_audit_log, AuditEntry, AuditLog, AuditOperation};
+
+/// How one config file is protected at rest, as reported by [`encryption_report`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncryptionStatus {
+    /// The namespace the config is in, if any.
+    pub namespace: Option<String>,
+
+    /// The config key, e.g. `jira` for ~/.config/ilo/jira.json.
+    pub key: String,
+    pub path: PathBuf,
+
+    /// Whether the whole file is encrypted.
+    pub encrypted: bool,
+
+    /// The key derivation function and cipher recorded in an encrypted file, e.g.
+    /// `argon2id + aes-256-gcm`.
+    pub scheme: Option<String>,
+}
+
+/// Extensions of compressed config files, which are recognized even when the `gzip` and `zstd`
+/// features are off.
+const COMPRESSED_EXTENSIONS: [&str; 2] = ["gz", "zst"];
+
+/// Report the encryption status of every config under the config root, sorted by namespace and
+/// key.
+///
+/// If `options` sets a [`namespace`](ConfigOptions::namespace), only that namespace is reported;
+/// otherwise the configs in every namespace under the root are reported along with the root's
+/// own.  Only files in the format selected by `options` are considered, compressed or not.
+/// Files encrypted with a passphrase (see the `encryption` feature) are detected even when the
+/// feature is off.
+pub fn encryption_report(options: &ConfigOptions) -> Result<Vec<EncryptionStatus>, ConfigError> {
+    let config_root = Config::<Value>::get_config_root(options)?;
+    if !options
+        .filesystem
+        .try_exists(&config_root)
+        .map_err(|e| ConfigError::ConfigRootLoadError(config_root.clone(), e))?
+    {
+        return Ok(Vec::new());
+    }
+
+    let mut report = report_dir(&config_root, options.namespace.clone(), options)?;
+    if options.namespace.is_none() {
+        let entries = options
+            .filesystem
+            .read_dir(&config_root)
+            .map_err(|e| ConfigError::ConfigRootLoadError(config_root.clone(), e))?;
+        for dir in entries {
+            let Some(name) = dir.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            // Hidden directories hold history and caches, `.d` directories drop-in fragments
+            if name.starts_with('.') || name.ends_with(".d") || options.filesystem.is_file(&dir) {
+                continue;
+            }
+            report.extend(report_dir(&dir, Some(name.to_string()), options)?);
+        }
+    }
+    report.sort_by(|a, b| (&a.namespace, &a.key).cmp(&(&b.namespace, &b.key)));
+    Ok(report)
+}
+
+/// The encryption status of the configs directly in `dir`.
+fn report_dir(
+    dir: &Path,
+    namespace: Option<String>,
+    options: &ConfigOptions,
+) -> Result<Vec<EncryptionStatus>, ConfigError> {
+    let suffix = format!(".{}", options.format.extension());
+    let report = options
+        .filesystem
+        .read_dir(dir)
+        .map_err(|e| ConfigError::ConfigRootLoadError(dir.to_path_buf(), e))?
+        .into_iter()
+        .filter(|path| options.filesystem.is_file(path))
+        .filter_map(|path| {
+            let file_name = path.file_name()?.to_str()?;
+            let uncompressed = COMPRESSED_EXTENSIONS
+                .iter()
+                .find_map(|extension| file_name.strip_suffix(&format!(".{}", extension)))
+                .unwrap_or(file_name);
+            let key = uncompressed.strip_suffix(&suffix)?.to_string();
+            // Skip JSON Schemas written by `Config::save_schema` and the root's own metadata
+            if key.is_empty() || key.ends_with(".schema") || key.starts_with('.') {
+                return None;
+            }
+            let scheme = options
+                .filesystem
+                .read(&path)
+                .ok()
+                .and_then(|contents| encryption_scheme(&contents));
+            Some(EncryptionStatus {
+                namespace: namespace.clone(),
+                key,
+                path,
+                encrypted: scheme.is_some(),
+                scheme,
+            })
+        })
+        .collect();
+    Ok(report)
+}
+
+/// The scheme recorded in an envelope written by passphrase encryption, or `None` if the
+/// contents aren't one.
+fn encryption_scheme(contents: &[u8]) -> Option<String> {
+    let document = serde_json::from_slice::<serde_json::Map<String, Value>>(contents).ok()?;
+    document.get("ilo_encrypted")?;
+    let field = |name| {
+        document
+            .get(name)
+            .and_then(Value::as_str)
+            .unwrap_or("unknown")
+    };
+    Some(format!("{} + {}", field("kdf"), field("cipher")))
+}
+
+/// Whether file contents are an envelope written by passphrase encryption.
+#[cfg(feature = "encryption")]
+pub(crate) fn is_sealed(contents: &[u8]) -> bool {
+    encryption_scheme(contents).is_some()
+}
@@ -0,0 +1,154 @@
+//! Append-only log of when config files were loaded, saved and deleted, see
+//! [`ConfigOptions::audit_log`].
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::{Config, ConfigError, ConfigOptions};
+
+/// Where the log of [`ConfigOptions::audit_log`] is kept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuditLog {
+    /// `.audit.log` in the config root, shared by all configs.
+    Root,
+
+    /// `<key>.json.audit.log` next to each config file.
+    PerKey,
+
+    /// A file chosen by the app, e.g. on a volume that is collected for compliance reviews.  Its
+    /// directory must exist.
+    File(PathBuf),
+}
+
+/// What happened to a config file, as recorded in an [`AuditEntry`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditOperation {
+    Load,
+    Save,
+    Delete,
+}
+
+/// One line of the audit log, written as a JSON object.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// When the operation happened, in seconds since the UNIX epoch.
+    pub timestamp: u64,
+    pub operation: AuditOperation,
+
+    /// The config key, e.g. `jira` for ~/.config/ilo/jira.json.
+    pub key: String,
+
+    /// Hex-encoded SHA-256 of the file as it was read, written or deleted (i.e. after encryption
+    /// and compression), or `None` if a deleted file couldn't be read.
+    pub sha256: Option<String>,
+}
+
+/// Read the entries about `config_file_key` from the audit log that `options` point to, oldest
+/// first.  Returns no entries if the log doesn't exist or auditing is off.
+pub fn read_audit_log(
+    config_file_key: &str,
+    options: &ConfigOptions,
+) -> Result<Vec<AuditEntry>, ConfigError> {
+    let Some(path) = log_path(config_file_key, options)? else {
+        return Ok(Vec::new());
+    };
+    let contents = match options.filesystem.read(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(ConfigError::ConfigFileLoadError(path, e)),
+    };
+    let mut entries = Vec::new();
+    for line in contents
+        .split(|&b| b == b'\n')
+        .filter(|line| !line.is_empty())
+    {
+        let entry: AuditEntry = serde_json::from_slice(line)
+            .map_err(|e| ConfigError::ConfigFileParseError(path.clone(), e, None))?;
+        if entry.key == config_file_key {
+            entries.push(entry);
+        }
+    }
+    Ok(entries)
+}
+
+/// Append an entry for an operation on a config file with the given contents to the audit log,
+/// if there is one.
+pub(crate) fn record(
+    config_file_key: &str,
+    operation: AuditOperation,
+    contents: Option<&[u8]>,
+    options: &ConfigOptions,
+) -> Result<(), ConfigError> {
+    let sha256 = contents.map(|contents| hex(&Sha256::digest(contents)));
+    append(config_file_key, operation, sha256, options)
+}
+
+/// Like [`record`], hashing the file at `path` without reading it into memory at once, e.g.
+/// after a large collection was rewritten.
+pub(crate) fn record_file(
+    config_file_key: &str,
+    operation: AuditOperation,
+    path: &Path,
+    options: &ConfigOptions,
+) -> Result<(), ConfigError> {
+    if options.audit_log.is_none() {
+        return Ok(());
+    }
+    let mut hasher = Sha256::new();
+    options
+        .filesystem
+        .open(path)
+        .and_then(|mut file| io::copy(&mut file, &mut hasher))
+        .map_err(|e| ConfigError::ConfigFileLoadError(path.to_path_buf(), e))?;
+    let sha256 = hex(&hasher.finalize());
+    append(config_file_key, operation, Some(sha256), options)
+}
+
+fn append(
+    config_file_key: &str,
+    operation: AuditOperation,
+    sha256: Option<String>,
+    options: &ConfigOptions,
+) -> Result<(), ConfigError> {
+    let Some(path) = log_path(config_file_key, options)? else {
+        return Ok(());
+    };
+    let entry = AuditEntry {
+        timestamp: options.clock.unix_timestamp(),
+        operation,
+        key: config_file_key.to_string(),
+        sha256,
+    };
+    let mut line = serde_json::to_vec(&entry).map_err(ConfigError::ConfigFileSerializeError)?;
+    line.push(b'\n');
+    options
+        .filesystem
+        .append(&path, &line, Some(options.file_mode))
+        .map_err(|e| ConfigError::ConfigFileWriteError(path, e))
+}
+
+fn log_path(
+    config_file_key: &str,
+    options: &ConfigOptions,
+) -> Result<Option<PathBuf>, ConfigError> {
+    let path = match &options.audit_log {
+        None => return Ok(None),
+        Some(AuditLog::Root) => Config::<Value>::get_config_root(options)?.join(".audit.log"),
+        Some(AuditLog::PerKey) => {
+            let config_path = Config::<Value>::get_config_path(config_file_key, options)?;
+            Config::<Value>::sidecar_path(&config_path, "audit.log")
+        }
+        Some(AuditLog::File(path)) => path.clone(),
+    };
+    Ok(Some(path))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -0,0 +1,223 @@
+//! Debounced saving for configs that change constantly, e.g. in interactive TUIs.
+use std::{
+    sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard},
+    thread::{self, JoinHandle},
+    time::{Duration, SystemTime},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{telemetry::Feature, Clock, Config, ConfigError, SharedConfig};
+
+/// Saves a [`SharedConfig`] in a background thread once it has stopped changing for a while.
+///
+/// Dropped along with the last handle to the config, which stops the thread and saves any
+/// pending changes.
+pub(crate) struct Autosaver<TConfigData: Serialize + DeserializeOwned> {
+    config: Arc<RwLock<Config<TConfigData>>>,
+    pub(crate) debouncer: Arc<Debouncer>,
+    worker: Option<JoinHandle<()>>,
+}
+
+/// Shared between an [`Autosaver`] and its background thread.
+pub(crate) struct Debouncer {
+    quiet: Duration,
+    clock: Arc<dyn Clock>,
+    state: Mutex<State>,
+    changed: Condvar,
+    error: Mutex<Option<ConfigError>>,
+}
+
+struct State {
+    pending: bool,
+    last_change: SystemTime,
+    stopped: bool,
+}
+
+impl<TConfigData: Serialize + DeserializeOwned + Send + Sync + 'static> Config<TConfigData> {
+    /// Turn the config into a [`SharedConfig`] that is saved in a background thread once it
+    /// hasn't been changed for `quiet`, instead of on every change.
+    ///
+    /// Each [`write`](SharedConfig::write) that changes the data restarts the quiet period, so a
+    /// burst of changes is flushed to disk with a single save.  Pending changes are also saved
+    /// when the last handle to the config is dropped.  The quiet period is measured with
+    /// [`ConfigOptions::clock`](crate::ConfigOptions::clock).
+    ///
+    /// Saving in the background can't return errors, so the last one is kept for
+    /// [`take_autosave_error`](SharedConfig::take_autosave_error).  A failed save isn't retried
+    /// until the next change or [`flush`](SharedConfig::flush).
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use ilo_config::Config;
+    /// let config = Config::<Vec<String>>::load("history")?.autosave(Duration::from_millis(500));
+    /// for line in ["ls", "cd ..", "ls"] {
+    ///     config.write().data_mut().push(line.to_string());
+    /// }
+    /// // Saved once, half a second after the last push
+    /// # Ok::<(), ilo_config::ConfigError>(())
+    /// ```
+    pub fn autosave(self, quiet: Duration) -> SharedConfig<TConfigData> {
+        self.report(Feature::Autosave);
+        let clock = Arc::clone(&self.options.clock);
+        let config = Arc::new(RwLock::new(self));
+        let debouncer = Arc::new(Debouncer {
+            quiet,
+            state: Mutex::new(State {
+                pending: false,
+                last_change: clock.now(),
+                stopped: false,
+            }),
+            clock,
+            changed: Condvar::new(),
+            error: Mutex::new(None),
+        });
+        let worker = {
+            let config = Arc::clone(&config);
+            let debouncer = Arc::clone(&debouncer);
+            thread::spawn(move || debouncer.run(&config))
+        };
+        let autosaver = Autosaver {
+            config: Arc::clone(&config),
+            debouncer,
+            worker: Some(worker),
+        };
+        SharedConfig::from_parts(config, Some(Arc::new(autosaver)))
+    }
+}
+
+impl<TConfigData: Serialize + DeserializeOwned> Drop for Autosaver<TConfigData> {
+    fn drop(&mut self) {
+        self.debouncer.lock_state().stopped = true;
+        self.debouncer.changed.notify_all();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+        // There's nobody left to report the error to
+        let _ = flush(&read(&self.config), Some(&self.debouncer));
+    }
+}
+
+impl Debouncer {
+    fn lock_state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Restart the quiet period after a change.
+    pub(crate) fn touch(&self) {
+        let mut state = self.lock_state();
+        state.pending = true;
+        state.last_change = self.clock.now();
+        self.changed.notify_all();
+    }
+
+    pub(crate) fn take_error(&self) -> Option<ConfigError> {
+        self.error
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+    }
+
+    /// Wait for changes and save them once they've settled, until stopped.
+    fn run<TConfigData: Serialize + DeserializeOwned>(&self, config: &RwLock<Config<TConfigData>>) {
+        let mut state = self.lock_state();
+        loop {
+            if state.stopped {
+                return;
+            }
+            if !state.pending {
+                state = self
+                    .changed
+                    .wait(state)
+                    .unwrap_or_else(PoisonError::into_inner);
+                continue;
+            }
+            // A clock that went backwards restarts the quiet period
+            let elapsed = self
+                .clock
+                .now()
+                .duration_since(state.last_change)
+                .unwrap_or_default();
+            if elapsed < self.quiet {
+                state = self
+                    .changed
+                    .wait_timeout(state, self.quiet - elapsed)
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .0;
+                continue;
+            }
+
+            drop(state);
+            if let Err(e) = flush(&read(config), Some(self)) {
+                *self.error.lock().unwrap_or_else(PoisonError::into_inner) = Some(e);
+            }
+            state = self.lock_state();
+        }
+    }
+}
+
+/// Save a config if it has unsaved changes, cancelling any pending autosave.
+///
+/// The caller holds the config lock, so a change can't slip in between cancelling and saving.
+pub(crate) fn flush<TConfigData: Serialize + DeserializeOwned>(
+    config: &Config<TConfigData>,
+    debouncer: Option<&Debouncer>,
+) -> Result<(), ConfigError> {
+    if let Some(debouncer) = debouncer {
+        debouncer.lock_state().pending = false;
+    }
+    if config.has_unsaved_changes() {
+        config.save()?;
+    }
+    Ok(())
+}
+
+fn read<TConfigData: Serialize + DeserializeOwned>(
+    config: &RwLock<Config<TConfigData>>,
+) -> RwLockReadGuard<'_, Config<TConfigData>> {
+    config.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::Arc,
+        thread,
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    use crate::{Config, ConfigOptions, ManualClock};
+
+    #[test]
+    fn quiet_period_is_measured_with_the_clock() {
+        let root = tempfile::tempdir().unwrap();
+        let clock = Arc::new(ManualClock::new(UNIX_EPOCH + Duration::from_secs(1000)));
+        let options = ConfigOptions {
+            root: Some(root.path().to_path_buf()),
+            clock: clock.clone(),
+            ..Default::default()
+        };
+        let config_path = root.path().join("history.json");
+        let config = Config::<Vec<u32>>::load_with_options("history", options)
+            .unwrap()
+            .autosave(Duration::from_millis(20));
+        config.write().data_mut().push(1);
+
+        // Real time passing doesn't end the quiet period while the clock stands still
+        thread::sleep(Duration::from_millis(100));
+        assert!(!config_path.exists());
+
+        clock.advance(Duration::from_millis(20));
+        for _ in 0..100 {
+            if config_path.exists() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(
+            std::fs::read_to_string(&config_path).unwrap().trim(),
+            "[\n  1\n]"
+        );
+        assert!(config.take_autosave_error().is_none());
+    }
+}
@@ -0,0 +1,165 @@
+//! AWS Signature Version 4 request signing, shared by the S3 backend and the AWS resolvers.
+use std::{
+    env,
+    error::Error,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use hmac::{Hmac, Mac};
+use reqwest::{
+    blocking::{Client, RequestBuilder},
+    Method, Url,
+};
+use sha2::{Digest, Sha256};
+
+/// Credentials that requests are signed with.
+#[derive(Clone)]
+pub(crate) struct Credentials {
+    pub(crate) access_key_id: String,
+    pub(crate) secret_access_key: String,
+    pub(crate) session_token: Option<String>,
+}
+
+impl Credentials {
+    /// Credentials from `$AWS_ACCESS_KEY_ID`, `$AWS_SECRET_ACCESS_KEY` and (optionally)
+    /// `$AWS_SESSION_TOKEN`.
+    pub(crate) fn from_env() -> Self {
+        Self {
+            access_key_id: env::var("AWS_ACCESS_KEY_ID").unwrap_or_default(),
+            secret_access_key: env::var("AWS_SECRET_ACCESS_KEY").unwrap_or_default(),
+            session_token: env::var("AWS_SESSION_TOKEN").ok(),
+        }
+    }
+}
+
+/// Signs requests to an AWS service in a region.
+pub(crate) struct Signer<'a> {
+    pub(crate) service: &'a str,
+    pub(crate) region: &'a str,
+    pub(crate) credentials: &'a Credentials,
+}
+
+impl Signer<'_> {
+    /// Build a request signed with AWS Signature Version 4.  `headers` are sent and signed along
+    /// with `host` and the `x-amz-*` headers.
+    pub(crate) fn request(
+        &self,
+        client: &Client,
+        method: Method,
+        url: Url,
+        mut headers: Vec<(&'static str, String)>,
+        body: Vec<u8>,
+    ) -> Result<RequestBuilder, Box<dyn Error + Send + Sync>> {
+        let Self {
+            service,
+            region,
+            credentials,
+        } = *self;
+        let host = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{}:{}", host, port),
+            (Some(host), None) => host.to_string(),
+            (None, _) => return Err(format!("URL {} has no host", url).into()),
+        };
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let amz_date = amz_date(now);
+        let date = &amz_date[..8];
+        let payload_hash = hex(&Sha256::digest(&body));
+
+        headers.push(("host", host));
+        headers.push(("x-amz-date", amz_date.clone()));
+        if service == "s3" {
+            // S3 requires the payload hash as a header as well
+            headers.push(("x-amz-content-sha256", payload_hash.clone()));
+        }
+        if let Some(token) = &credentials.session_token {
+            headers.push(("x-amz-security-token", token.clone()));
+        }
+        headers.sort_by_key(|(name, _)| *name);
+        let canonical_headers: String = headers
+            .iter()
+            .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+            .collect();
+        let signed_headers = headers
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>()
+            .join(";");
+
+        let canonical_request = format!(
+            "{}\n{}\n\n{}\n{}\n{}",
+            method,
+            url.path(),
+            canonical_headers,
+            signed_headers,
+            payload_hash
+        );
+        let scope = format!("{}/{}/{}/aws4_request", date, region, service);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex(&Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let signing_key = [region, service, "aws4_request"].iter().fold(
+            hmac_sha256(
+                format!("AWS4{}", credentials.secret_access_key).as_bytes(),
+                date,
+            ),
+            |key, part| hmac_sha256(&key, part),
+        );
+        let signature = hex(&hmac_sha256(&signing_key, &string_to_sign));
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            credentials.access_key_id, scope, signed_headers, signature
+        );
+
+        let mut request = client
+            .request(method, url)
+            .header("authorization", authorization);
+        for (name, value) in headers.into_iter().filter(|(name, _)| *name != "host") {
+            request = request.header(name, value);
+        }
+        Ok(request.body(body))
+    }
+}
+
+fn hmac_sha256(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Format a UNIX timestamp as `YYYYMMDD'T'HHMMSS'Z'`.
+fn amz_date(timestamp: u64) -> String {
+    let days = (timestamp / 86400) as i64;
+    let seconds = timestamp % 86400;
+
+    // Convert days since the epoch to a civil date (Howard Hinnant's algorithm)
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
@@ -0,0 +1,169 @@
+//! Storage backends for keeping config documents somewhere other than the local config root.
+//!
+//! A backend is set with [`ConfigOptions::backend`](crate::ConfigOptions::backend).  It only
+//! stores and retrieves whole documents; parsing, validation and the rest of the [`Config`]
+//! API work the same as for local files.  Features that are specific to local files, such as
+//! conflict detection, revision history and write verification, don't apply to backends.
+//!
+//! [`Config`]: crate::Config
+use std::fmt::Debug;
+#[cfg(any(feature = "http", feature = "consul"))]
+use std::{fmt::Display, path::PathBuf, sync::Arc};
+
+use crate::ConfigError;
+#[cfg(any(feature = "http", feature = "consul"))]
+use crate::{ConfigOptions, FileSystem};
+
+#[cfg(feature = "consul")]
+pub mod consul;
+pub mod env;
+#[cfg(feature = "http")]
+pub mod http;
+#[cfg(feature = "kubernetes")]
+pub mod kubernetes;
+#[cfg(feature = "local-storage")]
+pub mod local_storage;
+#[cfg(feature = "redis")]
+pub mod redis;
+#[cfg(feature = "s3")]
+pub mod s3;
+pub mod ssh;
+
+/// Storage for config documents, keyed by config key.
+pub trait Backend: Debug + Send + Sync {
+    /// Human-readable location of the document for a key, e.g. a URL, used in error messages.
+    fn location(&self, config_file_key: &str) -> String;
+
+    /// Fetch the document for a key, or `None` if there isn't one yet.
+    fn load(&self, config_file_key: &str) -> Result<Option<Vec<u8>>, ConfigError>;
+
+    /// Store the document for a key.
+    fn save(&self, config_file_key: &str, contents: &[u8]) -> Result<(), ConfigError>;
+
+    /// Store the document for a key only if it still is `expected`, i.e. what
+    /// [`load`](Self::load) returned before.  Returns `false` without storing anything if another
+    /// writer changed it in the meantime.
+    ///
+    /// The default implementation loads the document again to compare it, which leaves a short
+    /// window for another writer.  Backends whose store supports conditional writes override it
+    /// to compare and store in one step.
+    fn save_if_unchanged(
+        &self,
+        config_file_key: &str,
+        expected: Option<&[u8]>,
+        contents: &[u8],
+    ) -> Result<bool, ConfigError> {
+        if self.load(config_file_key)?.as_deref() != expected {
+            return Ok(false);
+        }
+        self.save(config_file_key, contents)?;
+        Ok(true)
+    }
+}
+
+/// Local copies of remotely stored documents, used when the remote store can't be reached.
+///
+/// The copies may hold secrets, so they get the same permissions as config files: 0600 for files
+/// and 0700 for the directories created for them.
+#[cfg(any(feature = "http", feature = "consul"))]
+#[derive(Debug, Clone)]
+pub(crate) struct OfflineCache {
+    pub(crate) dir: Option<PathBuf>,
+    pub(crate) filesystem: Arc<dyn FileSystem>,
+    pub(crate) file_mode: u32,
+}
+
+#[cfg(any(feature = "http", feature = "consul"))]
+impl Default for OfflineCache {
+    fn default() -> Self {
+        let options = ConfigOptions::default();
+        Self {
+            dir: None,
+            filesystem: options.filesystem,
+            file_mode: options.file_mode,
+        }
+    }
+}
+
+#[cfg(any(feature = "http", feature = "consul"))]
+impl OfflineCache {
+    pub(crate) fn new(dir: Option<PathBuf>) -> Self {
+        Self {
+            dir,
+            ..Self::default()
+        }
+    }
+
+    /// Access the cache with the filesystem and file mode of `options`.
+    pub(crate) fn set_options(&mut self, options: &ConfigOptions) {
+        self.filesystem = options.filesystem.clone();
+        self.file_mode = options.file_mode;
+    }
+
+    fn path(&self, config_file_key: &str) -> Option<PathBuf> {
+        self.dir
+            .as_ref()
+            .map(|dir| dir.join(format!("{}.json", config_file_key)))
+    }
+
+    /// Cache the result of a successful fetch, or fall back to the cached copy if it failed.
+    pub(crate) fn fetched<E>(
+        &self,
+        config_file_key: &str,
+        location: String,
+        fetched: Result<Option<Vec<u8>>, E>,
+    ) -> Result<Option<Vec<u8>>, ConfigError>
+    where
+        E: Display + Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        let cache_path = self.path(config_file_key);
+        match fetched {
+            Ok(contents) => {
+                if let Some(contents) = &contents {
+                    self.store(config_file_key, contents);
+                }
+                Ok(contents)
+            }
+            Err(e) => match cache_path.filter(|path| self.filesystem.is_file(path)) {
+                Some(cache_path) => {
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(
+                        %location,
+                        error = %e,
+                        cache_path = %cache_path.display(),
+                        "could not fetch config; using cached copy"
+                    );
+                    #[cfg(not(feature = "tracing"))]
+                    eprintln!(
+                        "WARNING: could not fetch {} ({}); using cached copy from {}",
+                        location,
+                        e,
+                        cache_path.display()
+                    );
+                    self.filesystem
+                        .read(&cache_path)
+                        .map(Some)
+                        .map_err(|e| ConfigError::ConfigFileLoadError(cache_path, e))
+                }
+                None => Err(ConfigError::ConfigBackendError(location, e.into())),
+            },
+        }
+    }
+
+    /// Update the cached copy.  The cache is best-effort, so failures are ignored.
+    pub(crate) fn store(&self, config_file_key: &str, contents: &[u8]) {
+        if let Some(cache_path) = self.path(config_file_key) {
+            let _ = cache_path
+                .parent()
+                .map_or(Ok(()), |dir| {
+                    self.filesystem.create_dir_all(dir, Some(0o700))
+                })
+                .and_then(|_| {
+                    self.filesystem
+                        .write(&cache_path, contents, Some(self.file_mode))
+                })
+                // Copies written by older versions may be readable by others
+                .and_then(|_| self.filesystem.set_permissions(&cache_path, self.file_mode));
+        }
+    }
+}
@@ -0,0 +1,203 @@
+//! Backend that shares config documents between service instances through Consul's KV store.
+use std::{path::PathBuf, time::Duration};
+
+use reqwest::{blocking::Client, StatusCode};
+use serde_json::Value;
+
+use crate::{
+    backend::{Backend, OfflineCache},
+    Config, ConfigError, ConfigOptions, Secret,
+};
+
+/// Reads and writes config documents as values in Consul's KV store.
+///
+/// Each config is stored under `<prefix><key>`, `ilo/<key>` by default.  Every document that is
+/// fetched or saved is also written to `.cache/consul/` in the local config root
+/// (`$ILO_CONFIG_HOME` or `~/.config/ilo/`), so an instance that loses its connection to Consul
+/// keeps working with the last known version.  The copies are kept apart from the config files,
+/// which they would otherwise overwrite.
+///
+/// ```no_run
+/// # use std::sync::Arc;
+/// # use ilo_config::{backend::consul::ConsulBackend, Config, ConfigOptions};
+/// let backend = ConsulBackend::new("http://127.0.0.1:8500").token("my-acl-token");
+/// let options = ConfigOptions {
+///     backend: Some(Arc::new(backend)),
+///     ..Default::default()
+/// };
+/// let config: Config<serde_json::Value> = Config::load_with_options("billing", options).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct ConsulBackend {
+    address: String,
+    prefix: String,
+    token: Option<Secret>,
+    cache: OfflineCache,
+    timeout: Duration,
+}
+
+impl ConsulBackend {
+    /// Create a backend for the Consul agent at `address`, e.g. `http://127.0.0.1:8500`.
+    pub fn new(address: impl Into<String>) -> Self {
+        Self {
+            address: address.into().trim_end_matches('/').to_string(),
+            prefix: String::from("ilo/"),
+            token: None,
+            cache: OfflineCache::new(
+                Config::<Value>::get_config_root(&ConfigOptions::default())
+                    .ok()
+                    .map(|config_root| config_root.join(".cache").join("consul")),
+            ),
+            timeout: Duration::from_secs(10),
+        }
+    }
+
+    /// Store configs under this KV prefix instead of `ilo/`.
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// ACL token to send with every request.
+    pub fn token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(Secret::new(token));
+        self
+    }
+
+    /// Keep the fallback copies in this directory instead of `.cache/consul/` in the config root.
+    pub fn cache_dir(mut self, cache_dir: impl Into<PathBuf>) -> Self {
+        self.cache.dir = Some(cache_dir.into());
+        self
+    }
+
+    /// Don't keep fallback copies, so loading fails when Consul can't be reached.
+    pub fn no_cache(mut self) -> Self {
+        self.cache.dir = None;
+        self
+    }
+
+    /// Access the cache through the [`filesystem`](ConfigOptions::filesystem) and with the
+    /// [`file_mode`](ConfigOptions::file_mode) of `options`, usually the options the backend is
+    /// used with.  By default the real filesystem and mode 0600 are used.
+    pub fn cache_options(mut self, options: &ConfigOptions) -> Self {
+        self.cache.set_options(options);
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    fn url(&self, config_file_key: &str) -> String {
+        format!("{}/v1/kv/{}{}", self.address, self.prefix, config_file_key)
+    }
+
+    fn request(
+        &self,
+        method: reqwest::Method,
+        config_file_key: &str,
+    ) -> reqwest::Result<reqwest::blocking::RequestBuilder> {
+        let client = Client::builder().timeout(self.timeout).build()?;
+        let request = client.request(method, self.url(config_file_key));
+        Ok(match &self.token {
+            Some(token) => request.header("X-Consul-Token", token.expose()),
+            None => request,
+        })
+    }
+
+    fn fetch(&self, config_file_key: &str) -> reqwest::Result<Option<Vec<u8>>> {
+        self.fetch_indexed(config_file_key)
+            .map(|fetched| fetched.map(|(contents, _)| contents))
+    }
+
+    /// Fetch a document together with its modify index, for a check-and-set write.
+    fn fetch_indexed(&self, config_file_key: &str) -> reqwest::Result<Option<(Vec<u8>, u64)>> {
+        let response = self
+            .request(reqwest::Method::GET, config_file_key)?
+            .query(&[("raw", "true")])
+            .send()?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let response = response.error_for_status()?;
+        let index = response
+            .headers()
+            .get("X-Consul-Index")
+            .and_then(|index| index.to_str().ok())
+            .and_then(|index| index.parse().ok())
+            .unwrap_or_default();
+        let body = response.bytes()?;
+        Ok(Some((body.to_vec(), index)))
+    }
+
+    /// Write a document, only if its modify index is still `cas` if that is given.  Returns
+    /// whether Consul accepted the write.
+    fn put(
+        &self,
+        config_file_key: &str,
+        contents: &[u8],
+        cas: Option<u64>,
+    ) -> Result<bool, ConfigError> {
+        let location = self.location(config_file_key);
+        let stored = self
+            .request(reqwest::Method::PUT, config_file_key)
+            .and_then(|request| {
+                let request = match cas {
+                    Some(index) => request.query(&[("cas", index)]),
+                    None => request,
+                };
+                request.body(contents.to_vec()).send()
+            })
+            .and_then(|response| response.error_for_status())
+            .and_then(|response| response.text())
+            .map_err(|e| ConfigError::ConfigBackendError(location, e.into()))?;
+        let stored = stored.trim() == "true";
+        if stored {
+            self.cache.store(config_file_key, contents);
+        }
+        Ok(stored)
+    }
+}
+
+impl Backend for ConsulBackend {
+    fn location(&self, config_file_key: &str) -> String {
+        self.url(config_file_key)
+    }
+
+    fn load(&self, config_file_key: &str) -> Result<Option<Vec<u8>>, ConfigError> {
+        let fetched = self.fetch(config_file_key);
+        self.cache
+            .fetched(config_file_key, self.location(config_file_key), fetched)
+    }
+
+    fn save(&self, config_file_key: &str, contents: &[u8]) -> Result<(), ConfigError> {
+        // Consul answers `false` if the write was rejected, e.g. because of a lock
+        if !self.put(config_file_key, contents, None)? {
+            return Err(ConfigError::ConfigBackendError(
+                self.location(config_file_key),
+                "Consul rejected the write".into(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Uses Consul's check-and-set, with the modify index the document had when it was compared
+    /// (0 for a document that must not exist yet).
+    fn save_if_unchanged(
+        &self,
+        config_file_key: &str,
+        expected: Option<&[u8]>,
+        contents: &[u8],
+    ) -> Result<bool, ConfigError> {
+        let current = self.fetch_indexed(config_file_key).map_err(|e| {
+            ConfigError::ConfigBackendError(self.location(config_file_key), e.into())
+        })?;
+        let index = match (current, expected) {
+            (None, None) => 0,
+            (Some((current, index)), Some(expected)) if current == expected => index,
+            _ => return Ok(false),
+        };
+        self.put(config_file_key, contents, Some(index))
+    }
+}
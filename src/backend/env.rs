@@ -0,0 +1,136 @@
+//! Backend that builds config documents from environment variables alone.
+use std::env;
+
+use serde_json::{Map, Value};
+
+use crate::{backend::Backend, ConfigError};
+
+/// Reads configs from environment variables instead of files, e.g. for containers that don't
+/// have a writable home directory.
+///
+/// Variables are mapped to fields like envy does: the prefix is stripped and the rest of the
+/// name is lowercased, so `JIRA_BASE_URL` sets `base_url` of the config `jira`.  A double
+/// underscore separates the fields of nested objects (`JIRA_AUTH__USER` sets `auth.user`).
+/// Values that are valid JSON are used as such (numbers, booleans, arrays, ...), anything else
+/// as a string; quote a value to keep e.g. `"0123"` a string.
+///
+/// If no variable has the prefix, the config counts as missing, so the usual defaults apply.
+/// Saving fails, unless [`discard_saves`](Self::discard_saves) makes it a no-op.
+///
+/// ```no_run
+/// # use std::sync::Arc;
+/// # use ilo_config::{backend::env::EnvBackend, Config, ConfigOptions};
+/// let options = ConfigOptions {
+///     backend: Some(Arc::new(EnvBackend::new())),
+///     ..Default::default()
+/// };
+/// let config: Config<serde_json::Value> = Config::load_with_options("jira", options).unwrap();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct EnvBackend {
+    prefix: Option<String>,
+    discard_saves: bool,
+}
+
+impl EnvBackend {
+    /// Map variables prefixed with the uppercased config key and an underscore, e.g. `JIRA_`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Map variables with this prefix instead, e.g. `MYAPP_`, for all configs.
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = Some(prefix.into());
+        self
+    }
+
+    /// Silently ignore saves instead of failing, for code that saves configs as a matter of
+    /// course.
+    pub fn discard_saves(mut self) -> Self {
+        self.discard_saves = true;
+        self
+    }
+
+    fn prefix_for(&self, config_file_key: &str) -> String {
+        match &self.prefix {
+            Some(prefix) => prefix.clone(),
+            None => prefix_for_key(config_file_key),
+        }
+    }
+}
+
+/// The default prefix of the variables for a config, e.g. `JIRA_` for `jira`.
+pub(crate) fn prefix_for_key(config_file_key: &str) -> String {
+    let key: String = config_file_key
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{}_", key)
+}
+
+impl Backend for EnvBackend {
+    fn location(&self, config_file_key: &str) -> String {
+        format!(
+            "environment variables {}*",
+            self.prefix_for(config_file_key)
+        )
+    }
+
+    fn load(&self, config_file_key: &str) -> Result<Option<Vec<u8>>, ConfigError> {
+        let prefix = self.prefix_for(config_file_key);
+        let mut document = Map::new();
+        // Variables that aren't valid Unicode can't be JSON values anyway
+        for (name, value) in env::vars_os() {
+            let (Some(name), Some(value)) = (name.to_str(), value.to_str()) else {
+                continue;
+            };
+            let Some(path) = name.strip_prefix(&prefix).filter(|path| !path.is_empty()) else {
+                continue;
+            };
+            let value = serde_json::from_str(value).unwrap_or_else(|_| Value::from(value));
+            insert(&mut document, &path.to_lowercase(), value);
+        }
+        if document.is_empty() {
+            return Ok(None);
+        }
+        serde_json::to_vec(&document)
+            .map(Some)
+            .map_err(ConfigError::ConfigFileSerializeError)
+    }
+
+    fn save(&self, config_file_key: &str, _contents: &[u8]) -> Result<(), ConfigError> {
+        if self.discard_saves {
+            return Ok(());
+        }
+        Err(ConfigError::ConfigBackendError(
+            self.location(config_file_key),
+            "configs read from environment variables can't be saved".into(),
+        ))
+    }
+}
+
+/// Set the member at a `__`-separated path, creating objects along the way.
+pub(crate) fn insert(document: &mut Map<String, Value>, path: &str, value: Value) {
+    match path.split_once("__") {
+        Some((name, rest)) => {
+            let child = document
+                .entry(name)
+                .or_insert_with(|| Value::Object(Map::new()));
+            if !child.is_object() {
+                *child = Value::Object(Map::new());
+            }
+            if let Value::Object(child) = child {
+                insert(child, rest, value);
+            }
+        }
+        None => {
+            document.insert(path.to_string(), value);
+        }
+    }
+}
@@ -0,0 +1,217 @@
+//! Backend that fetches config documents from an HTTP(S) server.
+use std::{path::PathBuf, time::Duration};
+
+use reqwest::{
+    blocking::{Client, Response},
+    header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE, ETAG, IF_MATCH, IF_NONE_MATCH},
+    StatusCode,
+};
+
+use crate::{
+    backend::{Backend, OfflineCache},
+    environment, ConfigError, ConfigOptions,
+};
+
+/// Loads config documents with GET requests and, if enabled, saves them with PUT requests.
+///
+/// Every successfully fetched document is cached on disk, and the cached copy is used when the
+/// server can't be reached, so apps keep working offline.  By default the cache lives in
+/// `http-cache/` under the state directory (`$ILO_STATE_HOME` or `~/.local/state/ilo/`).
+///
+/// [`save_if_unchanged`](Backend::save_if_unchanged) sends `If-Match` with the document's ETag
+/// (or `If-None-Match: *` for new documents), as WebDAV servers support.  If the server doesn't
+/// send ETags, the document is only compared before the PUT.
+///
+/// ```no_run
+/// # use std::sync::Arc;
+/// # use ilo_config::{backend::http::HttpBackend, Config, ConfigOptions};
+/// let backend = HttpBackend::new("https://config.example.com/defaults/{key}.json")
+///     .header("Authorization", "Bearer my-token")?;
+/// let options = ConfigOptions {
+///     backend: Some(Arc::new(backend)),
+///     ..Default::default()
+/// };
+/// let config: Config<serde_json::Value> = Config::load_with_options("team", options)?;
+/// # Ok::<(), ilo_config::ConfigError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct HttpBackend {
+    url: String,
+    headers: HeaderMap,
+    allow_put: bool,
+    cache: OfflineCache,
+    timeout: Duration,
+}
+
+impl HttpBackend {
+    /// Create a backend for a URL.  A `{key}` placeholder in the URL is replaced with the config
+    /// key.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            headers: HeaderMap::new(),
+            allow_put: false,
+            cache: OfflineCache::new(
+                environment::state_home()
+                    .ok()
+                    .map(|state_home| state_home.join("http-cache")),
+            ),
+            timeout: Duration::from_secs(10),
+        }
+    }
+
+    /// Send a header with every request, e.g. for authentication.  Header values are kept out
+    /// of `Debug` output, since they often hold credentials.
+    ///
+    /// Fails with `ConfigError::ConfigBackendError` if the name or value is not a valid HTTP
+    /// header.
+    pub fn header(mut self, name: &str, value: &str) -> Result<Self, ConfigError> {
+        let invalid = |e: Box<dyn std::error::Error + Send + Sync>| {
+            ConfigError::ConfigBackendError(self.url.clone(), e)
+        };
+        let name = HeaderName::try_from(name).map_err(|e| invalid(e.into()))?;
+        let mut value = HeaderValue::try_from(value).map_err(|e| invalid(e.into()))?;
+        value.set_sensitive(true);
+        self.headers.insert(name, value);
+        Ok(self)
+    }
+
+    /// Save documents back to the server with PUT requests.  Without this, saving fails.
+    pub fn allow_put(mut self) -> Self {
+        self.allow_put = true;
+        self
+    }
+
+    /// Cache fetched documents in this directory instead of the default one.
+    pub fn cache_dir(mut self, cache_dir: impl Into<PathBuf>) -> Self {
+        self.cache.dir = Some(cache_dir.into());
+        self
+    }
+
+    /// Don't cache fetched documents, so loading fails when the server can't be reached.
+    pub fn no_cache(mut self) -> Self {
+        self.cache.dir = None;
+        self
+    }
+
+    /// Access the cache through the [`filesystem`](ConfigOptions::filesystem) and with the
+    /// [`file_mode`](ConfigOptions::file_mode) of `options`, usually the options the backend is
+    /// used with.  By default the real filesystem and mode 0600 are used.
+    pub fn cache_options(mut self, options: &ConfigOptions) -> Self {
+        self.cache.set_options(options);
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    fn client(&self) -> reqwest::Result<Client> {
+        Client::builder()
+            .default_headers(self.headers.clone())
+            .timeout(self.timeout)
+            .build()
+    }
+
+    fn put(
+        &self,
+        location: &str,
+        condition: Option<(HeaderName, HeaderValue)>,
+        contents: &[u8],
+    ) -> reqwest::Result<Response> {
+        let mut request = self
+            .client()?
+            .put(location)
+            .header(CONTENT_TYPE, "application/json");
+        if let Some((name, value)) = condition {
+            request = request.header(name, value);
+        }
+        request.body(contents.to_vec()).send()
+    }
+
+    fn fetch(&self, config_file_key: &str) -> Result<Option<Vec<u8>>, reqwest::Error> {
+        let response = self.client()?.get(self.location(config_file_key)).send()?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let body = response.error_for_status()?.bytes()?;
+        Ok(Some(body.to_vec()))
+    }
+}
+
+impl Backend for HttpBackend {
+    fn location(&self, config_file_key: &str) -> String {
+        self.url.replace("{key}", config_file_key)
+    }
+
+    fn load(&self, config_file_key: &str) -> Result<Option<Vec<u8>>, ConfigError> {
+        let fetched = self.fetch(config_file_key);
+        self.cache
+            .fetched(config_file_key, self.location(config_file_key), fetched)
+    }
+
+    fn save(&self, config_file_key: &str, contents: &[u8]) -> Result<(), ConfigError> {
+        let location = self.location(config_file_key);
+        if !self.allow_put {
+            return Err(ConfigError::ConfigBackendError(
+                location,
+                "saving is not enabled for this HTTP backend".into(),
+            ));
+        }
+
+        self.put(&location, None, contents)
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| ConfigError::ConfigBackendError(location, e.into()))?;
+
+        self.cache.store(config_file_key, contents);
+        Ok(())
+    }
+
+    fn save_if_unchanged(
+        &self,
+        config_file_key: &str,
+        expected: Option<&[u8]>,
+        contents: &[u8],
+    ) -> Result<bool, ConfigError> {
+        let location = self.location(config_file_key);
+        if !self.allow_put {
+            return Err(ConfigError::ConfigBackendError(
+                location,
+                "saving is not enabled for this HTTP backend".into(),
+            ));
+        }
+        let backend_error =
+            |e: reqwest::Error| ConfigError::ConfigBackendError(location.clone(), e.into());
+
+        let response = self
+            .client()
+            .and_then(|client| client.get(&location).send())
+            .map_err(backend_error)?;
+        let condition = if response.status() == StatusCode::NOT_FOUND {
+            if expected.is_some() {
+                return Ok(false);
+            }
+            Some((IF_NONE_MATCH, HeaderValue::from_static("*")))
+        } else {
+            let response = response.error_for_status().map_err(backend_error)?;
+            let etag = response.headers().get(ETAG).cloned();
+            let current = response.bytes().map_err(backend_error)?;
+            if expected != Some(&current[..]) {
+                return Ok(false);
+            }
+            etag.map(|etag| (IF_MATCH, etag))
+        };
+
+        let response = self
+            .put(&location, condition, contents)
+            .map_err(backend_error)?;
+        if response.status() == StatusCode::PRECONDITION_FAILED {
+            return Ok(false);
+        }
+        response.error_for_status().map_err(backend_error)?;
+
+        self.cache.store(config_file_key, contents);
+        Ok(true)
+    }
+}
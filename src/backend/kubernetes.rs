@@ -0,0 +1,212 @@
+//! Backend that reads config documents from Kubernetes ConfigMaps and Secrets.
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use reqwest::{blocking::Client, Certificate, StatusCode};
+use serde_json::Value;
+
+use crate::{backend::Backend, ConfigError};
+
+/// Where the service account credentials of a pod are mounted.
+const SERVICE_ACCOUNT_DIR: &str = "/var/run/secrets/kubernetes.io/serviceaccount";
+
+#[derive(Debug, Clone)]
+enum Source {
+    /// A ConfigMap or Secret volume, with a file per key.
+    Mounted(PathBuf),
+
+    /// A ConfigMap or Secret fetched from the API server.
+    Api { kind: Kind, name: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    ConfigMap,
+    Secret,
+}
+
+/// Loads config documents from a ConfigMap or Secret, so that the same `Config<T>` code works for
+/// a CLI on a laptop and for the same binary running as a pod.
+///
+/// Each config is the entry `<key>.json` of the ConfigMap or Secret.  It is either read from
+/// where the ConfigMap or Secret is mounted as a volume, or fetched from the API server with the
+/// pod's service account.  Documents are read-only: saving fails.
+///
+/// ```no_run
+/// # use std::sync::Arc;
+/// # use ilo_config::{backend::kubernetes::KubernetesBackend, Config, ConfigOptions};
+/// // The pod mounts the ConfigMap `billing-config` at /etc/billing.
+/// let backend = if KubernetesBackend::in_cluster() {
+///     Some(Arc::new(KubernetesBackend::mounted("/etc/billing")) as _)
+/// } else {
+///     None
+/// };
+/// let options = ConfigOptions {
+///     backend,
+///     ..Default::default()
+/// };
+/// let config: Config<serde_json::Value> = Config::load_with_options("billing", options).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct KubernetesBackend {
+    source: Source,
+    namespace: Option<String>,
+    timeout: Duration,
+}
+
+impl KubernetesBackend {
+    /// Read configs from a mounted ConfigMap or Secret volume.
+    pub fn mounted(dir: impl Into<PathBuf>) -> Self {
+        Self::new(Source::Mounted(dir.into()))
+    }
+
+    /// Fetch configs from the ConfigMap called `name` through the API server.
+    pub fn config_map(name: impl Into<String>) -> Self {
+        Self::new(Source::Api {
+            kind: Kind::ConfigMap,
+            name: name.into(),
+        })
+    }
+
+    /// Fetch configs from the Secret called `name` through the API server.
+    pub fn secret(name: impl Into<String>) -> Self {
+        Self::new(Source::Api {
+            kind: Kind::Secret,
+            name: name.into(),
+        })
+    }
+
+    fn new(source: Source) -> Self {
+        Self {
+            source,
+            namespace: None,
+            timeout: Duration::from_secs(10),
+        }
+    }
+
+    /// Whether the app is running in a Kubernetes pod.
+    pub fn in_cluster() -> bool {
+        env::var_os("KUBERNETES_SERVICE_HOST").is_some()
+    }
+
+    /// Namespace of the ConfigMap or Secret, instead of the pod's own namespace.
+    pub fn namespace(mut self, namespace: impl Into<String>) -> Self {
+        self.namespace = Some(namespace.into());
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    fn entry(config_file_key: &str) -> String {
+        format!("{}.json", config_file_key)
+    }
+
+    fn fetch(
+        &self,
+        kind: Kind,
+        name: &str,
+        config_file_key: &str,
+    ) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error + Send + Sync>> {
+        let service_account = Path::new(SERVICE_ACCOUNT_DIR);
+        let host = env::var("KUBERNETES_SERVICE_HOST")
+            .map_err(|_| "not running in a Kubernetes cluster")?;
+        let port = env::var("KUBERNETES_SERVICE_PORT").unwrap_or_else(|_| String::from("443"));
+        let namespace = match &self.namespace {
+            Some(namespace) => namespace.clone(),
+            None => fs::read_to_string(service_account.join("namespace"))?
+                .trim()
+                .to_string(),
+        };
+        let token = fs::read_to_string(service_account.join("token"))?;
+        let ca = Certificate::from_pem(&fs::read(service_account.join("ca.crt"))?)?;
+
+        let client = Client::builder()
+            .add_root_certificate(ca)
+            .timeout(self.timeout)
+            .build()?;
+        let response = client
+            .get(self.api_url(&host, &port, &namespace, kind, name))
+            .bearer_auth(token.trim())
+            .send()?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let object: Value = serde_json::from_slice(&response.error_for_status()?.bytes()?)?;
+        let Some(data) = object["data"][Self::entry(config_file_key)].as_str() else {
+            return Ok(None);
+        };
+        Ok(Some(match kind {
+            Kind::ConfigMap => data.as_bytes().to_vec(),
+            Kind::Secret => STANDARD.decode(data)?,
+        }))
+    }
+
+    fn api_url(&self, host: &str, port: &str, namespace: &str, kind: Kind, name: &str) -> String {
+        let resource = match kind {
+            Kind::ConfigMap => "configmaps",
+            Kind::Secret => "secrets",
+        };
+        // IPv6 service hosts need brackets
+        let host = if host.contains(':') {
+            format!("[{}]", host)
+        } else {
+            host.to_string()
+        };
+        format!(
+            "https://{}:{}/api/v1/namespaces/{}/{}/{}",
+            host, port, namespace, resource, name
+        )
+    }
+}
+
+impl Backend for KubernetesBackend {
+    fn location(&self, config_file_key: &str) -> String {
+        match &self.source {
+            Source::Mounted(dir) => dir.join(Self::entry(config_file_key)).display().to_string(),
+            Source::Api { kind, name } => {
+                let kind = match kind {
+                    Kind::ConfigMap => "configmap",
+                    Kind::Secret => "secret",
+                };
+                let namespace = self.namespace.as_deref().unwrap_or("<pod namespace>");
+                format!(
+                    "{}/{}/{} ({})",
+                    namespace,
+                    kind,
+                    name,
+                    Self::entry(config_file_key)
+                )
+            }
+        }
+    }
+
+    fn load(&self, config_file_key: &str) -> Result<Option<Vec<u8>>, ConfigError> {
+        match &self.source {
+            Source::Mounted(dir) => {
+                let path = dir.join(Self::entry(config_file_key));
+                match fs::read(&path) {
+                    Ok(contents) => Ok(Some(contents)),
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+                    Err(e) => Err(ConfigError::ConfigFileLoadError(path, e)),
+                }
+            }
+            Source::Api { kind, name } => self
+                .fetch(*kind, name, config_file_key)
+                .map_err(|e| ConfigError::ConfigBackendError(self.location(config_file_key), e)),
+        }
+    }
+
+    fn save(&self, config_file_key: &str, _contents: &[u8]) -> Result<(), ConfigError> {
+        Err(ConfigError::ConfigBackendError(
+            self.location(config_file_key),
+            "configs in Kubernetes ConfigMaps and Secrets are read-only".into(),
+        ))
+    }
+}
@@ -0,0 +1,94 @@
+//! Backend that keeps config documents in the browser's `localStorage`, for web UIs built for
+//! `wasm32-unknown-unknown` that share config types with a CLI.
+//!
+//! IndexedDB only has an asynchronous API, which a [`Backend`] can't wait for in the browser's
+//! main thread, so documents are kept in `localStorage`.  Browsers limit it to a few megabytes
+//! per origin, which is plenty for settings.
+use web_sys::Storage;
+
+use crate::{backend::Backend, ConfigError};
+
+/// Stores config documents as `localStorage` items of the page's origin.
+///
+/// Each config is stored under `<prefix><key>`, `ilo:<key>` by default.  Items are strings, so
+/// encrypted or compressed configs can't be stored.  Where there is no `localStorage` (e.g. in a
+/// web worker), loading and saving fail with `ConfigError::ConfigBackendError`; the backend
+/// can't be used outside of `wasm32` builds at all.
+///
+/// ```no_run
+/// # use std::sync::Arc;
+/// # use ilo_config::{backend::local_storage::LocalStorageBackend, Config, ConfigOptions};
+/// let options = ConfigOptions {
+///     backend: Some(Arc::new(LocalStorageBackend::new())),
+///     ..Default::default()
+/// };
+/// let config: Config<serde_json::Value> = Config::load_with_options("jira", options).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct LocalStorageBackend {
+    prefix: String,
+}
+
+impl LocalStorageBackend {
+    pub fn new() -> Self {
+        Self {
+            prefix: String::from("ilo:"),
+        }
+    }
+
+    /// Store configs under this key prefix instead of `ilo:`, e.g. to keep the configs of several
+    /// apps on the same origin apart.
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    fn key(&self, config_file_key: &str) -> String {
+        format!("{}{}", self.prefix, config_file_key)
+    }
+
+    // `Storage` can't be sent between threads, so it is looked up on every access
+    fn storage(&self, config_file_key: &str) -> Result<Storage, ConfigError> {
+        let window = web_sys::window()
+            .ok_or_else(|| self.backend_error(config_file_key, "there is no browser window"))?;
+        match window.local_storage() {
+            Ok(Some(storage)) => Ok(storage),
+            Ok(None) => Err(self.backend_error(config_file_key, "localStorage is not available")),
+            // e.g. when the user blocked storage for the site
+            Err(e) => Err(self.backend_error(config_file_key, format!("{:?}", e))),
+        }
+    }
+
+    fn backend_error(&self, config_file_key: &str, message: impl Into<String>) -> ConfigError {
+        ConfigError::ConfigBackendError(self.location(config_file_key), message.into().into())
+    }
+}
+
+impl Default for LocalStorageBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Backend for LocalStorageBackend {
+    fn location(&self, config_file_key: &str) -> String {
+        format!("localStorage item {}", self.key(config_file_key))
+    }
+
+    fn load(&self, config_file_key: &str) -> Result<Option<Vec<u8>>, ConfigError> {
+        self.storage(config_file_key)?
+            .get_item(&self.key(config_file_key))
+            .map(|item| item.map(String::into_bytes))
+            .map_err(|e| self.backend_error(config_file_key, format!("{:?}", e)))
+    }
+
+    fn save(&self, config_file_key: &str, contents: &[u8]) -> Result<(), ConfigError> {
+        let contents = std::str::from_utf8(contents).map_err(|_| {
+            self.backend_error(config_file_key, "only text documents can be stored")
+        })?;
+        // Fails with a `QuotaExceededError` when the origin's storage is full
+        self.storage(config_file_key)?
+            .set_item(&self.key(config_file_key), contents)
+            .map_err(|e| self.backend_error(config_file_key, format!("{:?}", e)))
+    }
+}
@@ -0,0 +1,166 @@
+//! Backend that shares short-lived config documents between worker processes through Redis.
+use std::{fmt, time::Duration};
+
+use redis::{Client, Connection, RedisResult};
+
+use crate::{backend::Backend, ConfigError};
+
+/// Stores config documents as Redis string values, optionally expiring them after a TTL.
+///
+/// Each config is stored under `<prefix><key>`, `ilo:<key>` by default.  Unlike the other
+/// backends, this is meant for shared configuration that changes often and doesn't need to
+/// outlive the workers using it, so there is no offline fallback.
+///
+/// ```no_run
+/// # use std::{sync::Arc, time::Duration};
+/// # use ilo_config::{backend::redis::RedisBackend, Config, ConfigOptions};
+/// let backend = RedisBackend::new("redis://127.0.0.1:6379/0").ttl(Duration::from_secs(600));
+/// let options = ConfigOptions {
+///     backend: Some(Arc::new(backend)),
+///     ..Default::default()
+/// };
+/// let config: Config<serde_json::Value> = Config::load_with_options("workers", options).unwrap();
+/// ```
+#[derive(Clone)]
+pub struct RedisBackend {
+    url: String,
+    prefix: String,
+    ttl: Option<Duration>,
+    timeout: Duration,
+}
+
+impl RedisBackend {
+    /// Create a backend for the Redis server at `url`, e.g. `redis://127.0.0.1:6379/0` or
+    /// `rediss://:password@cache.example.com/` for TLS.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            prefix: String::from("ilo:"),
+            ttl: None,
+            timeout: Duration::from_secs(10),
+        }
+    }
+
+    /// Store configs under this key prefix instead of `ilo:`.
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Let saved configs expire after `ttl`, so they disappear when no worker refreshes them.
+    /// Without a TTL, they are kept until deleted.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    fn key(&self, config_file_key: &str) -> String {
+        format!("{}{}", self.prefix, config_file_key)
+    }
+
+    fn connect(&self) -> RedisResult<Connection> {
+        Client::open(self.url.as_str())?.get_connection_with_timeout(self.timeout)
+    }
+
+    fn set_command(&self, config_file_key: &str, contents: &[u8]) -> redis::Cmd {
+        let mut command = redis::cmd("SET");
+        command.arg(self.key(config_file_key)).arg(contents);
+        if let Some(ttl) = self.ttl {
+            // Redis rejects a TTL of zero
+            command.arg("PX").arg(ttl.as_millis().max(1) as u64);
+        }
+        command
+    }
+
+    fn backend_error(&self, config_file_key: &str, e: redis::RedisError) -> ConfigError {
+        ConfigError::ConfigBackendError(self.location(config_file_key), e.into())
+    }
+}
+
+// The URL usually carries a password, so its user info is left out
+impl fmt::Debug for RedisBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RedisBackend")
+            .field("url", &redacted_url(&self.url))
+            .field("prefix", &self.prefix)
+            .field("ttl", &self.ttl)
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+/// `url` with the user and password replaced, e.g. `redis://***@cache.example.com/`.
+fn redacted_url(url: &str) -> String {
+    let Some((scheme, rest)) = url.split_once("://") else {
+        return url.to_string();
+    };
+    let authority_end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+    match rest[..authority_end].rfind('@') {
+        Some(at) => format!("{}://***{}", scheme, &rest[at..]),
+        None => url.to_string(),
+    }
+}
+
+impl Backend for RedisBackend {
+    fn location(&self, config_file_key: &str) -> String {
+        // The URL may contain a password, so it's left out
+        format!("Redis key {}", self.key(config_file_key))
+    }
+
+    fn load(&self, config_file_key: &str) -> Result<Option<Vec<u8>>, ConfigError> {
+        let mut connection = self
+            .connect()
+            .map_err(|e| self.backend_error(config_file_key, e))?;
+        redis::cmd("GET")
+            .arg(self.key(config_file_key))
+            .query(&mut connection)
+            .map_err(|e| self.backend_error(config_file_key, e))
+    }
+
+    fn save(&self, config_file_key: &str, contents: &[u8]) -> Result<(), ConfigError> {
+        let mut connection = self
+            .connect()
+            .map_err(|e| self.backend_error(config_file_key, e))?;
+        self.set_command(config_file_key, contents)
+            .query(&mut connection)
+            .map_err(|e| self.backend_error(config_file_key, e))
+    }
+
+    /// Compares under `WATCH` and sets in a `MULTI` transaction, which Redis aborts if the key was
+    /// written in between.
+    fn save_if_unchanged(
+        &self,
+        config_file_key: &str,
+        expected: Option<&[u8]>,
+        contents: &[u8],
+    ) -> Result<bool, ConfigError> {
+        let key = self.key(config_file_key);
+        let mut connection = self
+            .connect()
+            .map_err(|e| self.backend_error(config_file_key, e))?;
+        let saved = redis::cmd("WATCH")
+            .arg(&key)
+            .query::<()>(&mut connection)
+            .and_then(|_| redis::cmd("GET").arg(&key).query(&mut connection))
+            .and_then(|current: Option<Vec<u8>>| {
+                if current.as_deref() != expected {
+                    redis::cmd("UNWATCH").query::<()>(&mut connection)?;
+                    return Ok(false);
+                }
+                // `EXEC` answers nil if the transaction was aborted
+                let executed: Option<()> = redis::pipe()
+                    .atomic()
+                    .add_command(self.set_command(config_file_key, contents))
+                    .ignore()
+                    .query(&mut connection)?;
+                Ok(executed.is_some())
+            })
+            .map_err(|e| self.backend_error(config_file_key, e))?;
+        Ok(saved)
+    }
+}
@@ -0,0 +1,217 @@
+//! Backend that stores config documents as objects in an S3-compatible bucket.
+use reqwest::{blocking::Client, header::ETAG, Method, StatusCode, Url};
+
+use crate::{
+    aws::{Credentials, Signer},
+    backend::Backend,
+    ConfigError,
+};
+
+/// Loads config documents with `GetObject` and saves them with `PutObject`.
+///
+/// Each config is stored as `<prefix><key>.json` in the bucket.  Requests are signed with AWS
+/// Signature Version 4, using the credentials in `$AWS_ACCESS_KEY_ID`, `$AWS_SECRET_ACCESS_KEY`
+/// and (optionally) `$AWS_SESSION_TOKEN` unless others are given with
+/// [`credentials`](Self::credentials).  Other S3-compatible services (MinIO, R2, ...) can be used
+/// by setting an [`endpoint`](Self::endpoint).
+///
+/// [`save_if_unchanged`](Backend::save_if_unchanged) makes a conditional `PutObject` with
+/// `If-Match` (or `If-None-Match: *` for new objects), so the service has to support conditional
+/// writes.
+///
+/// ```no_run
+/// # use std::sync::Arc;
+/// # use ilo_config::{backend::s3::S3Backend, Config, ConfigOptions};
+/// let options = ConfigOptions {
+///     backend: Some(Arc::new(S3Backend::new("my-ci-configs", "eu-west-1").prefix("ilo/"))),
+///     ..Default::default()
+/// };
+/// let config: Config<serde_json::Value> = Config::load_with_options("deploy", options).unwrap();
+/// ```
+#[derive(Clone)]
+pub struct S3Backend {
+    bucket: String,
+    region: String,
+    endpoint: String,
+    prefix: String,
+    credentials: Credentials,
+}
+
+impl std::fmt::Debug for S3Backend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("S3Backend")
+            .field("bucket", &self.bucket)
+            .field("region", &self.region)
+            .field("endpoint", &self.endpoint)
+            .field("prefix", &self.prefix)
+            .field("access_key_id", &self.credentials.access_key_id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl S3Backend {
+    pub fn new(bucket: impl Into<String>, region: impl Into<String>) -> Self {
+        let region = region.into();
+        Self {
+            bucket: bucket.into(),
+            endpoint: format!("https://s3.{}.amazonaws.com", region),
+            region,
+            prefix: String::new(),
+            credentials: Credentials::from_env(),
+        }
+    }
+
+    /// Use an S3-compatible service at this base URL, e.g. `http://localhost:9000` for MinIO.
+    /// Buckets are always addressed path-style (`<endpoint>/<bucket>/<object>`).
+    pub fn endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.endpoint = endpoint.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Prepend `prefix` to the object name of every config, e.g. `ilo/` to keep them in a folder.
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    pub fn credentials(
+        mut self,
+        access_key_id: impl Into<String>,
+        secret_access_key: impl Into<String>,
+        session_token: Option<String>,
+    ) -> Self {
+        self.credentials = Credentials {
+            access_key_id: access_key_id.into(),
+            secret_access_key: secret_access_key.into(),
+            session_token,
+        };
+        self
+    }
+
+    fn object_path(&self, config_file_key: &str) -> String {
+        let object = format!("{}{}.json", self.prefix, config_file_key);
+        let encoded: Vec<String> = object.split('/').map(uri_encode).collect();
+        format!("/{}/{}", uri_encode(&self.bucket), encoded.join("/"))
+    }
+
+    /// Send a request for an object, signed with AWS Signature Version 4.
+    fn send(
+        &self,
+        method: Method,
+        config_file_key: &str,
+        headers: Vec<(&'static str, String)>,
+        body: Vec<u8>,
+    ) -> Result<reqwest::blocking::Response, Box<dyn std::error::Error + Send + Sync>> {
+        let url = Url::parse(&format!(
+            "{}{}",
+            self.endpoint,
+            self.object_path(config_file_key)
+        ))?;
+        let signer = Signer {
+            service: "s3",
+            region: &self.region,
+            credentials: &self.credentials,
+        };
+        let request = signer.request(&Client::new(), method, url, headers, body)?;
+        Ok(request.send()?)
+    }
+}
+
+impl Backend for S3Backend {
+    fn location(&self, config_file_key: &str) -> String {
+        format!(
+            "s3://{}/{}{}.json",
+            self.bucket, self.prefix, config_file_key
+        )
+    }
+
+    fn load(&self, config_file_key: &str) -> Result<Option<Vec<u8>>, ConfigError> {
+        let backend_error = |e| ConfigError::ConfigBackendError(self.location(config_file_key), e);
+        let response = self
+            .send(Method::GET, config_file_key, Vec::new(), Vec::new())
+            .map_err(backend_error)?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let body = response
+            .error_for_status()
+            .and_then(|response| response.bytes())
+            .map_err(|e| backend_error(e.into()))?;
+        Ok(Some(body.to_vec()))
+    }
+
+    fn save(&self, config_file_key: &str, contents: &[u8]) -> Result<(), ConfigError> {
+        let backend_error = |e| ConfigError::ConfigBackendError(self.location(config_file_key), e);
+        self.send(Method::PUT, config_file_key, Vec::new(), contents.to_vec())
+            .map_err(backend_error)?
+            .error_for_status()
+            .map_err(|e| backend_error(e.into()))?;
+        Ok(())
+    }
+
+    fn save_if_unchanged(
+        &self,
+        config_file_key: &str,
+        expected: Option<&[u8]>,
+        contents: &[u8],
+    ) -> Result<bool, ConfigError> {
+        let backend_error = |e| ConfigError::ConfigBackendError(self.location(config_file_key), e);
+        let response = self
+            .send(Method::GET, config_file_key, Vec::new(), Vec::new())
+            .map_err(backend_error)?;
+        let condition = if response.status() == StatusCode::NOT_FOUND {
+            if expected.is_some() {
+                return Ok(false);
+            }
+            ("if-none-match", String::from("*"))
+        } else {
+            let response = response
+                .error_for_status()
+                .map_err(|e| backend_error(e.into()))?;
+            let etag = response
+                .headers()
+                .get(ETAG)
+                .and_then(|etag| etag.to_str().ok())
+                .map(String::from)
+                .ok_or_else(|| backend_error("no ETag in the GetObject response".into()))?;
+            let current = response.bytes().map_err(|e| backend_error(e.into()))?;
+            if expected != Some(&current[..]) {
+                return Ok(false);
+            }
+            ("if-match", etag)
+        };
+
+        let response = self
+            .send(
+                Method::PUT,
+                config_file_key,
+                vec![condition],
+                contents.to_vec(),
+            )
+            .map_err(backend_error)?;
+        // 409 means that a concurrent conditional write won
+        if matches!(
+            response.status(),
+            StatusCode::PRECONDITION_FAILED | StatusCode::CONFLICT
+        ) {
+            return Ok(false);
+        }
+        response
+            .error_for_status()
+            .map_err(|e| backend_error(e.into()))?;
+        Ok(true)
+    }
+}
+
+/// Percent-encode everything except unreserved characters, as required for SigV4 paths.
+fn uri_encode(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
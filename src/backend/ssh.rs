@@ -0,0 +1,142 @@
+//! Backend that stores config documents in a directory on another machine, over SSH.
+use std::{
+    io::{self, Write},
+    path::PathBuf,
+    process::{Command, Output, Stdio},
+};
+
+use crate::{backend::Backend, ConfigError};
+
+/// Exit status of the remote script when the requested document doesn't exist.
+const MISSING: i32 = 44;
+
+/// Reads and writes `<dir>/<key>.json` on a remote host by running commands over `ssh`.
+///
+/// This shells out to the `ssh` command line tool, so the user's own SSH setup (keys, agent,
+/// `~/.ssh/config` aliases) is used.  The remote host needs a POSIX shell with `cat`, `mkdir` and
+/// `mv`.  Documents are written to a temporary file first and then moved into place, so readers
+/// never see a partially written document.
+///
+/// ```no_run
+/// # use std::sync::Arc;
+/// # use ilo_config::{backend::ssh::SshBackend, Config, ConfigOptions};
+/// let backend = SshBackend::new("me@nas.local", ".config/ilo-shared");
+/// let options = ConfigOptions {
+///     backend: Some(Arc::new(backend)),
+///     ..Default::default()
+/// };
+/// let config: Config<serde_json::Value> = Config::load_with_options("jira", options).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct SshBackend {
+    host: String,
+    dir: String,
+    port: Option<u16>,
+    identity_file: Option<PathBuf>,
+}
+
+impl SshBackend {
+    /// Create a backend for a directory on `host` (anything `ssh` accepts, e.g. `user@host` or
+    /// an alias).  Relative directories are relative to the remote user's home directory.
+    pub fn new(host: impl Into<String>, dir: impl Into<String>) -> Self {
+        Self {
+            host: host.into(),
+            dir: dir.into().trim_end_matches('/').to_string(),
+            port: None,
+            identity_file: None,
+        }
+    }
+
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = Some(port);
+        self
+    }
+
+    /// Authenticate with this private key instead of the ones `ssh` picks by default.
+    pub fn identity_file(mut self, identity_file: impl Into<PathBuf>) -> Self {
+        self.identity_file = Some(identity_file.into());
+        self
+    }
+
+    fn remote_path(&self, config_file_key: &str) -> String {
+        format!("{}/{}.json", self.dir, config_file_key)
+    }
+
+    /// Run a shell script on the remote host, feeding it `input` on stdin.
+    fn run(&self, script: &str, input: &[u8]) -> io::Result<Output> {
+        let mut command = Command::new("ssh");
+        // Never prompt, since there may be no terminal to answer on
+        command.args(["-o", "BatchMode=yes"]);
+        if let Some(port) = self.port {
+            command.arg("-p").arg(port.to_string());
+        }
+        if let Some(identity_file) = &self.identity_file {
+            command.arg("-i").arg(identity_file);
+        }
+        let mut child = command
+            .arg(&self.host)
+            .arg(script)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(input)?;
+        }
+        child.wait_with_output()
+    }
+
+    fn backend_error(&self, config_file_key: &str, e: io::Error) -> ConfigError {
+        ConfigError::ConfigBackendError(self.location(config_file_key), Box::new(e))
+    }
+}
+
+impl Backend for SshBackend {
+    fn location(&self, config_file_key: &str) -> String {
+        format!("{}:{}", self.host, self.remote_path(config_file_key))
+    }
+
+    fn load(&self, config_file_key: &str) -> Result<Option<Vec<u8>>, ConfigError> {
+        let path = quote(&self.remote_path(config_file_key));
+        let script = format!(
+            "if [ -e {path} ]; then cat {path}; else exit {missing}; fi",
+            path = path,
+            missing = MISSING,
+        );
+        let output = self
+            .run(&script, &[])
+            .map_err(|e| self.backend_error(config_file_key, e))?;
+        match output.status.code() {
+            Some(0) => Ok(Some(output.stdout)),
+            Some(MISSING) => Ok(None),
+            _ => Err(self.backend_error(config_file_key, failed(&output))),
+        }
+    }
+
+    fn save(&self, config_file_key: &str, contents: &[u8]) -> Result<(), ConfigError> {
+        let path = self.remote_path(config_file_key);
+        let script = format!(
+            "mkdir -p {dir} && cat > {tmp} && mv {tmp} {path}",
+            dir = quote(&self.dir),
+            tmp = quote(&format!("{}.tmp", path)),
+            path = quote(&path),
+        );
+        let output = self
+            .run(&script, contents)
+            .map_err(|e| self.backend_error(config_file_key, e))?;
+        if !output.status.success() {
+            return Err(self.backend_error(config_file_key, failed(&output)));
+        }
+        Ok(())
+    }
+}
+
+/// Quote a word for a POSIX shell.
+fn quote(word: &str) -> String {
+    format!("'{}'", word.replace('\'', r"'\''"))
+}
+
+fn failed(output: &Output) -> io::Error {
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    io::Error::other(format!("`ssh` failed ({}): {}", output.status, stderr))
+}
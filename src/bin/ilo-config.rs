@@ -0,0 +1,458 @@
+//! Command-line tool for inspecting and editing the configs in an ilo-config root, so apps don't
+//! each need their own `config` subcommand.
+//!
+//! ```sh
+//! ilo-config list
+//! ilo-config show jira
+//! ilo-config get jira server.url
+//! ilo-config set jira server.port 8443
+//! ilo-config validate jira
+//! ilo-config edit jira
+//! ilo-config doctor
+//! ```
+#[cfg(unix)]
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::{
+    env,
+    fs::{self, DirBuilder, OpenOptions},
+    io::{self, BufRead, Write},
+    path::{Path, PathBuf},
+    process::{self, Command, ExitCode},
+};
+
+use ilo_config::{list_keys, Config, ConfigFormat, ConfigOptions, ConfigRoot};
+use serde_json::{Map, Value};
+
+const USAGE: &str = "\
+Usage: ilo-config [options] <command>
+
+Commands:
+  list                        List the keys of all configs
+  show <key>                  Print a config
+  get <key> <path>            Print the value at a dot-separated path, e.g. `servers.0.url`
+  set <key> <path> <value>    Set the value at a path; strings may be given without quotes
+  validate <key> [<schema>]   Check a config against a JSON Schema, `<key>.schema.json` next to
+                              the config by default
+  edit <key>                  Open a config in $VISUAL or $EDITOR, checking it after editing
+  doctor                      Check the config root for unreadable configs, loose permissions
+                              and leftover files
+
+Options:
+  --root <dir>                Use this config root instead of $ILO_CONFIG_HOME or ~/.config/ilo/
+  --namespace <name>          Use the configs of an app's namespace
+  --jsonc                     Configs are JSONC (JSON with comments)
+  -h, --help                  Show this message";
+
+fn main() -> ExitCode {
+    match run(env::args().skip(1).collect()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("error: {}", message);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: Vec<String>) -> Result<(), String> {
+    let mut options = ConfigOptions::default();
+    let mut args = args.into_iter();
+    let mut positional = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--root" => options.root = Some(PathBuf::from(value_of(&arg, args.next())?)),
+            "--namespace" => options.namespace = Some(value_of(&arg, args.next())?),
+            "--jsonc" => options.format = ConfigFormat::Jsonc,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            _ if arg.starts_with('-') && arg.len() > 1 => {
+                return Err(format!("unknown option `{}`\n\n{}", arg, USAGE))
+            }
+            _ => positional.push(arg),
+        }
+    }
+
+    let positional: Vec<&str> = positional.iter().map(String::as_str).collect();
+    match positional.as_slice() {
+        ["list"] => {
+            for key in list_keys(&options).map_err(|e| e.to_string())? {
+                println!("{}", key);
+            }
+            Ok(())
+        }
+        ["show", key] => {
+            let config = load(key, options)?;
+            config
+                .to_writer(io::stdout().lock())
+                .map_err(|e| e.to_string())?;
+            println!();
+            Ok(())
+        }
+        ["get", key, path] => {
+            let config = load(key, options)?;
+            let value = config
+                .data()
+                .pointer(&to_pointer(path))
+                .ok_or_else(|| format!("no value at `{}`", path))?;
+            match value {
+                Value::String(value) => println!("{}", value),
+                value => println!("{}", pretty(value)),
+            }
+            Ok(())
+        }
+        ["set", key, path, value] => {
+            let mut config = load(key, options)?;
+            let value = serde_json::from_str(value).unwrap_or_else(|_| Value::from(*value));
+            let pointer = to_pointer(path);
+            // Create missing objects along the way
+            for (end, _) in pointer.match_indices('/').skip(1) {
+                let parent = &pointer[..end];
+                if config.data().pointer(parent).is_none() {
+                    config
+                        .set_pointer(parent, Value::Object(Map::new()))
+                        .map_err(|e| e.to_string())?;
+                }
+            }
+            config
+                .set_pointer(&pointer, value)
+                .map_err(|e| e.to_string())?;
+            check_schema(&config, None)?;
+            config.save().map_err(|e| e.to_string())
+        }
+        ["validate", key] => check_schema(&load(key, options)?, None),
+        ["validate", key, schema] => {
+            check_schema(&load(key, options)?, Some(PathBuf::from(schema)))
+        }
+        ["edit", key] => edit(key, options),
+        ["doctor"] => {
+            let report = ConfigRoot::with_options(options)
+                .doctor()
+                .map_err(|e| e.to_string())?;
+            println!(
+                "Checked {} configs in {}",
+                report.keys.len(),
+                report.root.display()
+            );
+            if report.is_healthy() {
+                println!("No problems found");
+                return Ok(());
+            }
+            for issue in &report.issues {
+                println!("- {}", issue);
+            }
+            Err(format!("found {} problems", report.issues.len()))
+        }
+        [] => Err(format!("no command given\n\n{}", USAGE)),
+        _ => Err(format!("invalid command\n\n{}", USAGE)),
+    }
+}
+
+fn value_of(option: &str, value: Option<String>) -> Result<String, String> {
+    value.ok_or_else(|| format!("`{}` needs a value", option))
+}
+
+fn load(key: &str, options: ConfigOptions) -> Result<Config<Value>, String> {
+    Config::load_or_init_with_options(key, options, || Value::Object(Map::new()))
+        .map_err(|e| e.to_string())
+}
+
+/// Convert a dot-separated path (`servers.0.url`) into a JSON Pointer (`/servers/0/url`).
+fn to_pointer(path: &str) -> String {
+    path.split('.')
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| format!("/{}", segment.replace('~', "~0").replace('/', "~1")))
+        .collect()
+}
+
+fn pretty(value: &Value) -> String {
+    serde_json::to_string_pretty(value).expect("JSON values always serialize")
+}
+
+/// Check a config against a JSON Schema, by default the one next to the config file (if any).
+fn check_schema(config: &Config<Value>, schema_path: Option<PathBuf>) -> Result<(), String> {
+    let schema_path = match schema_path {
+        Some(schema_path) => schema_path,
+        None => {
+            let Some(config_path) = config.file_path() else {
+                return Ok(());
+            };
+            let default = config_path.with_file_name(format!("{}.schema.json", config.key()));
+            if !default.is_file() {
+                return Ok(());
+            }
+            default
+        }
+    };
+    let schema = fs::read(&schema_path)
+        .map_err(|e| format!("could not read {}: {}", schema_path.display(), e))?;
+    let schema: Value = serde_json::from_slice(&schema)
+        .map_err(|e| format!("{} is not valid JSON: {}", schema_path.display(), e))?;
+
+    let mut errors = Vec::new();
+    schema::validate(config.data(), &schema, &schema, "", &mut errors);
+    if errors.is_empty() {
+        return Ok(());
+    }
+    Err(format!(
+        "config `{}` doesn't match {}:\n  {}",
+        config.key(),
+        schema_path.display(),
+        errors.join("\n  ")
+    ))
+}
+
+/// Open the config in the user's editor, and only save it once it is valid.
+fn edit(key: &str, options: ConfigOptions) -> Result<(), String> {
+    let config = load(key, options.clone())?;
+    let mut contents = Vec::new();
+    config.to_writer(&mut contents).map_err(|e| e.to_string())?;
+
+    // The config may hold secrets, so only the user gets to see the copy
+    let temp_dir = private_temp_dir()?;
+    let temp_path = temp_dir.join(format!(
+        "{}.{}",
+        key.replace('/', "_"),
+        options.format.extension()
+    ));
+    let written = private_file(&temp_path).and_then(|mut file| file.write_all(&contents));
+    let result = match written {
+        Ok(()) => edit_until_valid(key, options, &temp_path),
+        Err(e) => Err(format!("could not write {}: {}", temp_path.display(), e)),
+    };
+    let _ = fs::remove_dir_all(&temp_dir);
+    let Some(edited) = result? else {
+        eprintln!("Changes discarded");
+        return Ok(());
+    };
+    edited.save_overwrite().map_err(|e| e.to_string())
+}
+
+/// Create a new directory in the system temp directory that only the current user can access.
+fn private_temp_dir() -> Result<PathBuf, String> {
+    let mut builder = DirBuilder::new();
+    #[cfg(unix)]
+    builder.mode(0o700);
+    for attempt in 0.. {
+        let dir = env::temp_dir().join(format!("ilo-config-{}-{}", process::id(), attempt));
+        // Not recursive, so an existing directory (or a planted symlink) is never reused
+        match builder.create(&dir) {
+            Ok(()) => return Ok(dir),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(format!("could not create {}: {}", dir.display(), e)),
+        }
+    }
+    unreachable!("ran out of temp directory names")
+}
+
+/// Create a new file that only the current user can access.
+fn private_file(path: &Path) -> io::Result<fs::File> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    options.open(path)
+}
+
+/// Run the editor until the file is a valid config, or the user gives up (returning `None`).
+fn edit_until_valid(
+    key: &str,
+    options: ConfigOptions,
+    path: &PathBuf,
+) -> Result<Option<Config<Value>>, String> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| String::from("vi"));
+    let mut editor = editor.split_whitespace();
+    let program = editor.next().unwrap_or("vi");
+    let editor_args: Vec<&str> = editor.collect();
+    loop {
+        let status = Command::new(program)
+            .args(&editor_args)
+            .arg(path)
+            .status()
+            .map_err(|e| format!("could not run editor `{}`: {}", program, e))?;
+        if !status.success() {
+            return Err(format!("editor `{}` failed ({})", program, status));
+        }
+
+        let file = fs::File::open(path)
+            .map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+        let error = match Config::from_reader_with_options(key, file, options.clone()) {
+            Ok(config) => match check_schema(&config, None) {
+                Ok(()) => return Ok(Some(config)),
+                Err(e) => e,
+            },
+            Err(e) => e.to_string(),
+        };
+        eprintln!("{}", error);
+        eprint!("Edit again? [Y/n] ");
+        let _ = io::stderr().flush();
+        let mut answer = String::new();
+        io::stdin()
+            .lock()
+            .read_line(&mut answer)
+            .map_err(|e| e.to_string())?;
+        if answer.trim().eq_ignore_ascii_case("n") {
+            return Ok(None);
+        }
+    }
+}
+
+/// A subset of JSON Schema validation, covering what schemas generated for config types use.
+mod schema {
+    use serde_json::Value;
+
+    pub(crate) fn validate(
+        value: &Value,
+        schema: &Value,
+        root: &Value,
+        path: &str,
+        errors: &mut Vec<String>,
+    ) {
+        let location = if path.is_empty() { "/" } else { path };
+        let schema = match schema {
+            Value::Bool(true) => return,
+            Value::Bool(false) => {
+                errors.push(format!("{}: no value is allowed here", location));
+                return;
+            }
+            Value::Object(schema) => schema,
+            _ => return,
+        };
+
+        if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+            match reference
+                .strip_prefix('#')
+                .and_then(|pointer| root.pointer(pointer))
+            {
+                Some(target) => validate(value, target, root, path, errors),
+                None => errors.push(format!("{}: unsupported $ref {}", location, reference)),
+            }
+        }
+
+        if let Some(types) = schema.get("type") {
+            let allowed: Vec<&str> = match types {
+                Value::String(name) => vec![name.as_str()],
+                Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+                _ => Vec::new(),
+            };
+            if !allowed.is_empty() && !allowed.iter().any(|name| has_type(value, name)) {
+                errors.push(format!(
+                    "{}: expected {}, found {}",
+                    location,
+                    allowed.join(" or "),
+                    type_of(value)
+                ));
+                return;
+            }
+        }
+        if let Some(Value::Array(options)) = schema.get("enum") {
+            if !options.contains(value) {
+                errors.push(format!(
+                    "{}: {} is not one of the allowed values",
+                    location, value
+                ));
+            }
+        }
+        if let Some(constant) = schema.get("const") {
+            if constant != value {
+                errors.push(format!("{}: must be {}", location, constant));
+            }
+        }
+
+        if let Some(number) = value.as_f64() {
+            if let Some(minimum) = schema.get("minimum").and_then(Value::as_f64) {
+                if number < minimum {
+                    errors.push(format!("{}: must be at least {}", location, minimum));
+                }
+            }
+            if let Some(maximum) = schema.get("maximum").and_then(Value::as_f64) {
+                if number > maximum {
+                    errors.push(format!("{}: must be at most {}", location, maximum));
+                }
+            }
+        }
+        if let Some(string) = value.as_str() {
+            let length = string.chars().count() as u64;
+            if let Some(min) = schema.get("minLength").and_then(Value::as_u64) {
+                if length < min {
+                    errors.push(format!("{}: must be at least {} characters", location, min));
+                }
+            }
+            if let Some(max) = schema.get("maxLength").and_then(Value::as_u64) {
+                if length > max {
+                    errors.push(format!("{}: must be at most {} characters", location, max));
+                }
+            }
+        }
+
+        if let Value::Object(members) = value {
+            let properties = schema.get("properties").and_then(Value::as_object);
+            if let Some(Value::Array(required)) = schema.get("required") {
+                for name in required.iter().filter_map(Value::as_str) {
+                    if !members.contains_key(name) {
+                        errors.push(format!("{}: missing required field `{}`", location, name));
+                    }
+                }
+            }
+            for (name, member) in members {
+                let member_path = format!("{}/{}", path, name);
+                match properties.and_then(|properties| properties.get(name)) {
+                    Some(member_schema) => {
+                        validate(member, member_schema, root, &member_path, errors)
+                    }
+                    None => {
+                        if let Some(additional) = schema.get("additionalProperties") {
+                            validate(member, additional, root, &member_path, errors);
+                        }
+                    }
+                }
+            }
+        }
+        if let (Value::Array(items), Some(item_schema)) = (value, schema.get("items")) {
+            for (i, item) in items.iter().enumerate() {
+                validate(item, item_schema, root, &format!("{}/{}", path, i), errors);
+            }
+        }
+
+        if let Some(Value::Array(all)) = schema.get("allOf") {
+            for branch in all {
+                validate(value, branch, root, path, errors);
+            }
+        }
+        for keyword in ["anyOf", "oneOf"] {
+            if let Some(Value::Array(branches)) = schema.get(keyword) {
+                let matches = branches.iter().any(|branch| {
+                    let mut branch_errors = Vec::new();
+                    validate(value, branch, root, path, &mut branch_errors);
+                    branch_errors.is_empty()
+                });
+                if !matches {
+                    errors.push(format!(
+                        "{}: doesn't match any of the allowed shapes",
+                        location
+                    ));
+                }
+            }
+        }
+    }
+
+    fn has_type(value: &Value, name: &str) -> bool {
+        match name {
+            "integer" => value.is_i64() || value.is_u64(),
+            name => type_of(value) == name || (name == "number" && value.is_number()),
+        }
+    }
+
+    fn type_of(value: &Value) -> &'static str {
+        match value {
+            Value::Null => "null",
+            Value::Bool(_) => "boolean",
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Array(_) => "array",
+            Value::Object(_) => "object",
+        }
+    }
+}
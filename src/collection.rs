@@ -0,0 +1,125 @@
+//! Bookkeeping for list-like configs whose entries are created, edited and deleted over time.
+//!
+//! Wrapping each element of a collection config in a [`Record`] adds creation and modification
+//! timestamps and soft deletion: deleted entries stay in the file as tombstones until they are
+//! purged, so they can be shown in a "recently deleted" view, restored, or propagated by a sync
+//! process.
+use std::time::Duration;
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::Config;
+
+/// An element of a collection config along with its bookkeeping timestamps, all in seconds since
+/// the UNIX epoch.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Record<T> {
+    pub value: T,
+    pub created_at: u64,
+    pub modified_at: u64,
+
+    /// When the record was soft-deleted, or `None` if it is live.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<u64>,
+}
+
+impl<T> Record<T> {
+    #[inline]
+    pub fn is_deleted(&self) -> bool {
+        self.deleted_at.is_some()
+    }
+}
+
+impl<TItem: Serialize + DeserializeOwned> Config<Vec<Record<TItem>>> {
+    /// Append a new record, returning its index.
+    pub fn insert(&mut self, value: TItem) -> usize {
+        let now = self.options.clock.unix_timestamp();
+        let records = self.data_mut();
+        records.push(Record {
+            value,
+            created_at: now,
+            modified_at: now,
+            deleted_at: None,
+        });
+        records.len() - 1
+    }
+
+    /// Apply `f` to the value of a live record and bump its modification time.
+    ///
+    /// Returns `None` without calling `f` if there is no live record at `index`.
+    pub fn modify<R>(&mut self, index: usize, f: impl FnOnce(&mut TItem) -> R) -> Option<R> {
+        let now = self.options.clock.unix_timestamp();
+        let record = self
+            .data_mut()
+            .get_mut(index)
+            .filter(|record| !record.is_deleted())?;
+        record.modified_at = now;
+        Some(f(&mut record.value))
+    }
+
+    /// Soft-delete a record, keeping it as a tombstone until it is purged.
+    ///
+    /// Returns whether there was a live record at `index`.
+    pub fn delete(&mut self, index: usize) -> bool {
+        let now = self.options.clock.unix_timestamp();
+        match self.data_mut().get_mut(index) {
+            Some(record) if !record.is_deleted() => {
+                record.deleted_at = Some(now);
+                record.modified_at = now;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Bring a soft-deleted record back.
+    ///
+    /// Returns whether there was a deleted record at `index`.
+    pub fn undelete(&mut self, index: usize) -> bool {
+        let now = self.options.clock.unix_timestamp();
+        match self.data_mut().get_mut(index) {
+            Some(record) if record.is_deleted() => {
+                record.deleted_at = None;
+                record.modified_at = now;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Permanently remove records that were deleted at least `older_than` ago, returning how many
+    /// were removed.  Pass `Duration::ZERO` to remove all of them.
+    ///
+    /// Purging shifts the indices of the records that follow the removed ones.
+    pub fn purge(&mut self, older_than: Duration) -> usize {
+        let cutoff = self
+            .options
+            .clock
+            .unix_timestamp()
+            .saturating_sub(older_than.as_secs());
+        let records = self.data_mut();
+        let len = records.len();
+        records.retain(|record| {
+            record
+                .deleted_at
+                .is_none_or(|deleted_at| deleted_at > cutoff)
+        });
+        len - records.len()
+    }
+
+    /// Live records along with their indices.
+    pub fn live(&self) -> impl Iterator<Item = (usize, &Record<TItem>)> {
+        self.data()
+            .iter()
+            .enumerate()
+            .filter(|(_, record)| !record.is_deleted())
+    }
+
+    /// Soft-deleted records along with their indices, e.g. for a "recently deleted" view.
+    pub fn deleted(&self) -> impl Iterator<Item = (usize, &Record<TItem>)> {
+        self.data()
+            .iter()
+            .enumerate()
+            .filter(|(_, record)| record.is_deleted())
+    }
+}
@@ -0,0 +1,98 @@
+//! Transparent compression of config files, see
+//! [`ConfigOptions::compression`](crate::ConfigOptions::compression).
+//!
+//! Compressed files are recognized by their magic bytes when loading, so a config can be switched
+//! between compressed and uncompressed storage without losing data: the old file is still loaded
+//! if it is copied to the new name.
+use std::{
+    io::{self, Read},
+    path::Path,
+};
+
+use crate::ConfigError;
+
+#[cfg(feature = "gzip")]
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+#[cfg(feature = "zstd")]
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// Compression algorithm for config files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// Stored as `<key>.json.gz`.
+    #[cfg(feature = "gzip")]
+    Gzip,
+
+    /// Stored as `<key>.json.zst`.  Compresses and decompresses considerably faster than gzip,
+    /// which makes it the better choice for large configs.
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+impl Compression {
+    /// File extension appended to the format's extension, without the leading dot.
+    pub fn extension(self) -> &'static str {
+        match self {
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => "gz",
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => "zst",
+        }
+    }
+
+    pub(crate) fn compress(self, bytes: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => {
+                use std::io::Write;
+
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(bytes)?;
+                encoder.finish()
+            }
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => zstd::encode_all(bytes, zstd::DEFAULT_COMPRESSION_LEVEL),
+        }
+    }
+
+    /// The compression that `contents` were compressed with, going by their first bytes.
+    fn detect(contents: &[u8]) -> Option<Self> {
+        #[cfg(feature = "gzip")]
+        if contents.starts_with(GZIP_MAGIC) {
+            return Some(Compression::Gzip);
+        }
+        #[cfg(feature = "zstd")]
+        if contents.starts_with(ZSTD_MAGIC) {
+            return Some(Compression::Zstd);
+        }
+        None
+    }
+
+    /// Wrap a reader of compressed data in a decompressing one.
+    pub(crate) fn decoder(self, reader: Box<dyn Read + Send>) -> io::Result<Box<dyn Read + Send>> {
+        Ok(match self {
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => Box::new(flate2::read::GzDecoder::new(reader)),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Box::new(zstd::Decoder::new(reader)?),
+        })
+    }
+}
+
+/// Decompress the contents of a config file if they are compressed, and pass them through
+/// unchanged otherwise.
+pub(crate) fn decompress_if_compressed(
+    contents: Vec<u8>,
+    config_path: &Path,
+) -> Result<Vec<u8>, ConfigError> {
+    let Some(compression) = Compression::detect(&contents) else {
+        return Ok(contents);
+    };
+    let mut decompressed = Vec::new();
+    compression
+        .decoder(Box::new(io::Cursor::new(contents)))
+        .and_then(|mut decoder| decoder.read_to_end(&mut decompressed))
+        .map_err(|e| ConfigError::ConfigFileLoadError(config_path.to_path_buf(), e))?;
+    Ok(decompressed)
+}
@@ -0,0 +1,400 @@
+//! Git-style conflict markers for changes that can't be merged automatically.
+//!
+//! A conflict file contains the lines both versions agree on as-is, and each differing region as
+//!
+//! ```text
+//! <<<<<<< ours
+//! (lines from this process's version)
+//! =======
+//! (lines from the version on disk)
+//! >>>>>>> theirs
+//! ```
+//!
+//! so that it can be resolved by hand in any editor that understands merge conflicts.
+const OURS_MARKER: &str = "<<<<<<<";
+const SEPARATOR: &str = "=======";
+const THEIRS_MARKER: &str = ">>>>>>>";
+
+/// Part of a document with conflict markers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    /// Lines that both versions have in common.
+    Common(Vec<String>),
+
+    /// Region where the versions differ.
+    Conflict {
+        ours: Vec<String>,
+        theirs: Vec<String>,
+    },
+}
+
+/// A document containing conflict markers, split into its common and conflicting parts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConflictDocument {
+    pub segments: Vec<Segment>,
+}
+
+impl ConflictDocument {
+    /// Compare two versions of a document line by line.
+    pub fn new(ours: &str, theirs: &str) -> Self {
+        let ours: Vec<&str> = ours.lines().collect();
+        let theirs: Vec<&str> = theirs.lines().collect();
+        let mut ours_changed = vec![false; ours.len()];
+        let mut theirs_changed = vec![false; theirs.len()];
+        diff(&ours, &theirs, &mut ours_changed, &mut theirs_changed);
+
+        let mut segments = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        let mut conflict: (Vec<String>, Vec<String>) = (Vec::new(), Vec::new());
+        let flush = |segments: &mut Vec<Segment>, conflict: &mut (Vec<String>, Vec<String>)| {
+            if !conflict.0.is_empty() || !conflict.1.is_empty() {
+                let (ours, theirs) = std::mem::take(conflict);
+                segments.push(Segment::Conflict { ours, theirs });
+            }
+        };
+        while i < ours.len() || j < theirs.len() {
+            if i < ours.len() && j < theirs.len() && !ours_changed[i] && !theirs_changed[j] {
+                flush(&mut segments, &mut conflict);
+                match segments.last_mut() {
+                    Some(Segment::Common(lines)) => lines.push(ours[i].to_string()),
+                    _ => segments.push(Segment::Common(vec![ours[i].to_string()])),
+                }
+                i += 1;
+                j += 1;
+            } else if i < ours.len() && ours_changed[i] {
+                conflict.0.push(ours[i].to_string());
+                i += 1;
+            } else {
+                conflict.1.push(theirs[j].to_string());
+                j += 1;
+            }
+        }
+        flush(&mut segments, &mut conflict);
+
+        Self { segments }
+    }
+
+    /// Parse a document with conflict markers.  Returns `None` if the markers are malformed, e.g.
+    /// a conflict that is never closed.
+    pub fn parse(text: &str) -> Option<Self> {
+        enum State {
+            Common,
+            Ours,
+            Theirs,
+        }
+
+        let mut segments: Vec<Segment> = Vec::new();
+        let mut state = State::Common;
+        let (mut ours, mut theirs) = (Vec::new(), Vec::new());
+        for line in text.lines() {
+            match state {
+                State::Common if line.starts_with(OURS_MARKER) => state = State::Ours,
+                State::Common => match segments.last_mut() {
+                    Some(Segment::Common(lines)) => lines.push(line.to_string()),
+                    _ => segments.push(Segment::Common(vec![line.to_string()])),
+                },
+                State::Ours if line == SEPARATOR => state = State::Theirs,
+                State::Ours => ours.push(line.to_string()),
+                State::Theirs if line.starts_with(THEIRS_MARKER) => {
+                    segments.push(Segment::Conflict {
+                        ours: std::mem::take(&mut ours),
+                        theirs: std::mem::take(&mut theirs),
+                    });
+                    state = State::Common;
+                }
+                State::Theirs => theirs.push(line.to_string()),
+            }
+        }
+        matches!(state, State::Common).then_some(Self { segments })
+    }
+
+    /// Whether the text contains any conflict markers, i.e. still needs to be resolved.
+    pub fn has_markers(text: &str) -> bool {
+        text.lines().any(|line| {
+            line.starts_with(OURS_MARKER) || line == SEPARATOR || line.starts_with(THEIRS_MARKER)
+        })
+    }
+
+    pub fn has_conflicts(&self) -> bool {
+        self.segments
+            .iter()
+            .any(|segment| matches!(segment, Segment::Conflict { .. }))
+    }
+
+    /// The document as it was in our version.
+    pub fn ours(&self) -> String {
+        self.render(|ours, _| ours)
+    }
+
+    /// The document as it was in their version.
+    pub fn theirs(&self) -> String {
+        self.render(|_, theirs| theirs)
+    }
+
+    /// The document with conflict markers around each conflicting region.
+    pub fn to_marked_string(&self) -> String {
+        let mut output = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Common(lines) => {
+                    lines.iter().for_each(|line| push_line(&mut output, line))
+                }
+                Segment::Conflict { ours, theirs } => {
+                    push_line(&mut output, &format!("{OURS_MARKER} ours"));
+                    ours.iter().for_each(|line| push_line(&mut output, line));
+                    push_line(&mut output, SEPARATOR);
+                    theirs.iter().for_each(|line| push_line(&mut output, line));
+                    push_line(&mut output, &format!("{THEIRS_MARKER} theirs"));
+                }
+            }
+        }
+        output
+    }
+
+    fn render<'a>(&'a self, pick: impl Fn(&'a [String], &'a [String]) -> &'a [String]) -> String {
+        let mut output = String::new();
+        for segment in &self.segments {
+            let lines = match segment {
+                Segment::Common(lines) => lines,
+                Segment::Conflict { ours, theirs } => pick(ours, theirs),
+            };
+            lines.iter().for_each(|line| push_line(&mut output, line));
+        }
+        output
+    }
+}
+
+fn push_line(output: &mut String, line: &str) {
+    output.push_str(line);
+    output.push('\n');
+}
+
+/// Mark the lines that aren't part of a shortest edit script between `a` and `b`, using Myers'
+/// linear-space diff so that large documents don't need a table of every pair of lines.
+fn diff(a: &[&str], b: &[&str], a_changed: &mut [bool], b_changed: &mut [bool]) {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let (a, b) = (&a[prefix..], &b[prefix..]);
+    let (a_changed, b_changed) = (&mut a_changed[prefix..], &mut b_changed[prefix..]);
+    let suffix = a
+        .iter()
+        .rev()
+        .zip(b.iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let (a, b) = (&a[..a.len() - suffix], &b[..b.len() - suffix]);
+    let a_changed = &mut a_changed[..a.len()];
+    let b_changed = &mut b_changed[..b.len()];
+
+    if a.is_empty() || b.is_empty() {
+        a_changed.fill(true);
+        b_changed.fill(true);
+        return;
+    }
+
+    let (x_start, y_start, x_end, y_end) = middle_snake(a, b);
+    let (a_head, a_tail) = a_changed.split_at_mut(x_end);
+    let (b_head, b_tail) = b_changed.split_at_mut(y_end);
+    diff(
+        &a[..x_start],
+        &b[..y_start],
+        &mut a_head[..x_start],
+        &mut b_head[..y_start],
+    );
+    diff(&a[x_end..], &b[y_end..], a_tail, b_tail);
+}
+
+/// Find the middle snake of an optimal path through the edit graph of `a` and `b`, as
+/// `(x_start, y_start, x_end, y_end)`.  Both must be non-empty and differ in their first and
+/// last lines, so that the snake never starts at the beginning or ends at the end.
+fn middle_snake(a: &[&str], b: &[&str]) -> (usize, usize, usize, usize) {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let delta = n - m;
+    let odd = delta % 2 != 0;
+    let max = (n + m + 1) / 2;
+    let offset = max + 1;
+    // Furthest x reached on each diagonal, going forwards from the start and backwards from the
+    // end (in reversed coordinates)
+    let mut forward = vec![0isize; (2 * max + 3) as usize];
+    let mut backward = forward.clone();
+    let index = |k: isize| (k + offset) as usize;
+
+    for d in 0..=max {
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && forward[index(k - 1)] < forward[index(k + 1)]) {
+                forward[index(k + 1)]
+            } else {
+                forward[index(k - 1)] + 1
+            };
+            let (x_start, y_start) = (x, x - k);
+            while x < n && x - k < m && a[x as usize] == b[(x - k) as usize] {
+                x += 1;
+            }
+            forward[index(k)] = x;
+            let c = delta - k;
+            if odd && (-(d - 1)..=d - 1).contains(&c) && x + backward[index(c)] >= n {
+                return (
+                    x_start as usize,
+                    y_start as usize,
+                    x as usize,
+                    (x - k) as usize,
+                );
+            }
+        }
+        for c in (-d..=d).step_by(2) {
+            let mut x = if c == -d || (c != d && backward[index(c - 1)] < backward[index(c + 1)]) {
+                backward[index(c + 1)]
+            } else {
+                backward[index(c - 1)] + 1
+            };
+            let (x_end, y_end) = (n - x, m - (x - c));
+            while x < n && x - c < m && a[(n - 1 - x) as usize] == b[(m - 1 - (x - c)) as usize] {
+                x += 1;
+            }
+            backward[index(c)] = x;
+            let k = delta - c;
+            if !odd && (-d..=d).contains(&k) && forward[index(k)] + x >= n {
+                return (
+                    (n - x) as usize,
+                    (m - (x - c)) as usize,
+                    x_end as usize,
+                    y_end as usize,
+                );
+            }
+        }
+    }
+    unreachable!("the forward and backward paths always meet by the middle")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConflictDocument, Segment};
+
+    fn lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    const OURS: &str = "{\n  \"board\": \"DEV\",\n  \"limit\": 10,\n  \"user\": \"ana\"\n}\n";
+    const THEIRS: &str = "{\n  \"board\": \"QA\",\n  \"limit\": 10,\n  \"user\": \"bo\"\n}\n";
+
+    #[test]
+    fn differing_lines_become_conflicts() {
+        let document = ConflictDocument::new(OURS, THEIRS);
+        assert!(document.has_conflicts());
+        assert_eq!(
+            document.segments,
+            [
+                Segment::Common(lines(&["{"])),
+                Segment::Conflict {
+                    ours: lines(&["  \"board\": \"DEV\","]),
+                    theirs: lines(&["  \"board\": \"QA\","]),
+                },
+                Segment::Common(lines(&["  \"limit\": 10,"])),
+                Segment::Conflict {
+                    ours: lines(&["  \"user\": \"ana\""]),
+                    theirs: lines(&["  \"user\": \"bo\""]),
+                },
+                Segment::Common(lines(&["}"])),
+            ]
+        );
+    }
+
+    #[test]
+    fn identical_documents_have_no_conflicts() {
+        let document = ConflictDocument::new(OURS, OURS);
+        assert!(!document.has_conflicts());
+        assert_eq!(document.to_marked_string(), OURS);
+        assert!(!ConflictDocument::has_markers(OURS));
+    }
+
+    #[test]
+    fn marked_documents_parse_back() {
+        for (ours, theirs) in [
+            (OURS, THEIRS),
+            (OURS, ""),
+            ("", THEIRS),
+            ("a\nb\nc\n", "a\nc\nd\n"),
+            ("x\n", "y\nx\ny\n"),
+        ] {
+            let document = ConflictDocument::new(ours, theirs);
+            let marked = document.to_marked_string();
+            assert_eq!(
+                ConflictDocument::has_markers(&marked),
+                document.has_conflicts()
+            );
+
+            let parsed = ConflictDocument::parse(&marked).unwrap();
+            assert_eq!(parsed, document);
+            assert_eq!(parsed.ours(), ours);
+            assert_eq!(parsed.theirs(), theirs);
+        }
+    }
+
+    #[test]
+    fn hand_edited_markers_parse() {
+        let marked = "{\n<<<<<<< HEAD\n  \"a\": 1\n=======\n  \"a\": 2\n>>>>>>> main\n}\n";
+        let document = ConflictDocument::parse(marked).unwrap();
+        assert_eq!(document.ours(), "{\n  \"a\": 1\n}\n");
+        assert_eq!(document.theirs(), "{\n  \"a\": 2\n}\n");
+    }
+
+    #[test]
+    fn unclosed_conflicts_do_not_parse() {
+        assert_eq!(ConflictDocument::parse("a\n<<<<<<< ours\nb\n"), None);
+        assert_eq!(
+            ConflictDocument::parse("a\n<<<<<<< ours\nb\n=======\nc\n"),
+            None
+        );
+        assert!(ConflictDocument::has_markers("a\n=======\nb\n"));
+    }
+
+    #[test]
+    fn the_diff_keeps_as_many_common_lines_as_possible() {
+        /// Length of the longest common subsequence, the slow way.
+        fn lcs(a: &[&str], b: &[&str]) -> usize {
+            let mut table = vec![vec![0; b.len() + 1]; a.len() + 1];
+            for i in (0..a.len()).rev() {
+                for j in (0..b.len()).rev() {
+                    table[i][j] = if a[i] == b[j] {
+                        table[i + 1][j + 1] + 1
+                    } else {
+                        table[i + 1][j].max(table[i][j + 1])
+                    };
+                }
+            }
+            table[0][0]
+        }
+
+        /// Up to 9 lines drawn from a small alphabet, so that there is a lot in common.
+        fn random_lines(random: &mut impl FnMut(u64) -> u64) -> Vec<&'static str> {
+            let len = random(10) as usize;
+            (0..len)
+                .map(|_| ["a", "b", "c"][random(3) as usize])
+                .collect()
+        }
+
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let mut random = |n: u64| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state % n
+        };
+        for _ in 0..500 {
+            let ours = random_lines(&mut random);
+            let theirs = random_lines(&mut random);
+            let document = ConflictDocument::new(&ours.join("\n"), &theirs.join("\n"));
+
+            let common: usize = document
+                .segments
+                .iter()
+                .map(|segment| match segment {
+                    Segment::Common(lines) => lines.len(),
+                    Segment::Conflict { .. } => 0,
+                })
+                .sum();
+            assert_eq!(common, lcs(&ours, &theirs), "{:?} vs {:?}", ours, theirs);
+            assert_eq!(document.ours().lines().collect::<Vec<_>>(), ours);
+            assert_eq!(document.theirs().lines().collect::<Vec<_>>(), theirs);
+        }
+    }
+}
@@ -0,0 +1,278 @@
+//! Conflict-free merging for collection configs that are edited on several devices.
+//!
+//! When a config directory is synced between machines with a file syncing tool (Dropbox,
+//! Syncthing, ...), edits made on two machines before a sync would normally clobber each other.
+//! An [`LwwSet`] is a last-writer-wins element set: every element has a stable ID and remembers
+//! when it was last written, and removals are kept as tombstones, so two versions can always be
+//! merged into the same result regardless of the order in which they are combined.
+use std::{cmp::Ordering, collections::BTreeMap, path::PathBuf, time::UNIX_EPOCH};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{Config, ConfigError};
+
+/// A set of values keyed by stable IDs that merges with last-writer-wins semantics.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct LwwSet<T> {
+    entries: BTreeMap<String, LwwEntry<T>>,
+}
+
+/// The latest write to an element of an [`LwwSet`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LwwEntry<T> {
+    /// The element's value, or `None` if it was removed.
+    pub value: Option<T>,
+
+    /// Milliseconds since the UNIX epoch.
+    pub written_at: u64,
+}
+
+impl<T> Default for LwwSet<T> {
+    fn default() -> Self {
+        Self {
+            entries: BTreeMap::new(),
+        }
+    }
+}
+
+impl<T: Serialize> LwwSet<T> {
+    pub fn get(&self, id: &str) -> Option<&T> {
+        self.entries.get(id).and_then(|entry| entry.value.as_ref())
+    }
+
+    /// Elements that haven't been removed, ordered by ID.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &T)> {
+        self.entries
+            .iter()
+            .filter_map(|(id, entry)| entry.value.as_ref().map(|value| (id.as_str(), value)))
+    }
+
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.iter().next().is_none()
+    }
+
+    /// Write an element at the given time, unless it has a later write already.
+    pub fn insert(&mut self, id: impl Into<String>, value: T, written_at: u64) {
+        self.apply(id.into(), Some(value), written_at);
+    }
+
+    /// Remove an element at the given time, unless it has a later write already.
+    pub fn remove(&mut self, id: impl Into<String>, written_at: u64) {
+        self.apply(id.into(), None, written_at);
+    }
+
+    /// Combine another version of the set into this one, keeping the latest write to each
+    /// element.
+    ///
+    /// Writes with the same timestamp are ordered by their serialized value, so merging is
+    /// commutative and every device ends up with the same result.
+    pub fn merge(&mut self, other: LwwSet<T>) {
+        for (id, entry) in other.entries {
+            self.apply(id, entry.value, entry.written_at);
+        }
+    }
+
+    fn apply(&mut self, id: String, value: Option<T>, written_at: u64) {
+        let entry = LwwEntry { value, written_at };
+        match self.entries.get(&id) {
+            Some(current) if compare(current, &entry) != Ordering::Less => (),
+            _ => {
+                self.entries.insert(id, entry);
+            }
+        }
+    }
+}
+
+/// Order writes by time, breaking ties deterministically.
+fn compare<T: Serialize>(a: &LwwEntry<T>, b: &LwwEntry<T>) -> Ordering {
+    a.written_at.cmp(&b.written_at).then_with(|| {
+        let a = serde_json::to_string(&a.value).unwrap_or_default();
+        let b = serde_json::to_string(&b.value).unwrap_or_default();
+        a.cmp(&b)
+    })
+}
+
+impl<TItem: Serialize + DeserializeOwned> Config<LwwSet<TItem>> {
+    /// Add or replace an element, timestamped with the config's clock.
+    pub fn put(&mut self, id: impl Into<String>, value: TItem) {
+        let now = self.now_millis();
+        self.data_mut().insert(id, value, now);
+    }
+
+    /// Remove an element, timestamped with the config's clock.  Returns whether it was present.
+    pub fn remove(&mut self, id: &str) -> bool {
+        let now = self.now_millis();
+        let present = self.data().get(id).is_some();
+        self.data_mut().remove(id, now);
+        present
+    }
+
+    /// Merge the version on disk into the in-memory data and save the result.
+    ///
+    /// Use this instead of [`save`](Self::save) for configs that may be edited on several
+    /// devices: changes made elsewhere since the config was loaded are combined with local
+    /// changes instead of causing a conflict.  The file is locked while merging, the same way as
+    /// with [`update`](Self::update).
+    pub fn sync(&mut self) -> Result<(), ConfigError> {
+        if let Some(backend) = self.options.backend.clone() {
+            if let Some(contents) = backend.load(&self.config_file_key)? {
+                let location = PathBuf::from(backend.location(&self.config_file_key));
+                let theirs =
+                    Self::parse_config_file(contents, &location, &self.options)?.config_data;
+                self.config_data.merge(theirs);
+            }
+            return self.save_overwrite();
+        }
+
+        let lock_path =
+            Self::ensure_config_root(&self.options)?.join(format!("{}.lock", self.config_file_key));
+        let _lock = self
+            .options
+            .filesystem
+            .lock(&lock_path)
+            .map_err(|e| ConfigError::ConfigLockError(lock_path.clone(), e))?;
+
+        let config_path = Self::get_config_path(&self.config_file_key, &self.options)?;
+        if self.options.filesystem.is_file(&config_path) {
+            let contents = Self::read_config_file(&config_path, &self.options)?;
+            let theirs =
+                Self::parse_config_file(contents, &config_path, &self.options)?.config_data;
+            self.config_data.merge(theirs);
+        }
+        self.save_overwrite()
+    }
+
+    fn now_millis(&self) -> u64 {
+        self.options
+            .clock
+            .now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::Arc,
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    use super::LwwSet;
+    use crate::{Config, ConfigOptions, ManualClock};
+
+    /// Two devices' versions of the same set, edited independently since a common state.
+    fn replicas() -> (LwwSet<String>, LwwSet<String>, LwwSet<String>) {
+        let mut base = LwwSet::default();
+        base.insert("a", String::from("apple"), 1);
+        base.insert("b", String::from("banana"), 1);
+        base.insert("c", String::from("cherry"), 1);
+
+        let mut laptop = base.clone();
+        laptop.insert("a", String::from("apricot"), 5);
+        laptop.remove("b", 3);
+        laptop.insert("d", String::from("date"), 4);
+
+        let mut desktop = base.clone();
+        desktop.insert("b", String::from("blueberry"), 4);
+        desktop.remove("c", 2);
+        desktop.insert("d", String::from("durian"), 4);
+
+        let mut phone = base;
+        phone.remove("a", 6);
+        phone.insert("e", String::from("elderberry"), 2);
+        (laptop, desktop, phone)
+    }
+
+    fn merged(mut a: LwwSet<String>, b: LwwSet<String>) -> LwwSet<String> {
+        a.merge(b);
+        a
+    }
+
+    #[test]
+    fn merging_is_commutative() {
+        let (laptop, desktop, _) = replicas();
+        assert_eq!(
+            merged(laptop.clone(), desktop.clone()),
+            merged(desktop, laptop)
+        );
+    }
+
+    #[test]
+    fn merging_is_associative() {
+        let (laptop, desktop, phone) = replicas();
+        assert_eq!(
+            merged(merged(laptop.clone(), desktop.clone()), phone.clone()),
+            merged(laptop, merged(desktop, phone))
+        );
+    }
+
+    #[test]
+    fn merging_is_idempotent() {
+        let (laptop, desktop, _) = replicas();
+        let once = merged(laptop, desktop.clone());
+        assert_eq!(merged(once.clone(), desktop), once);
+        assert_eq!(merged(once.clone(), once.clone()), once);
+    }
+
+    #[test]
+    fn latest_write_wins() {
+        let (laptop, desktop, phone) = replicas();
+        let all = merged(merged(laptop, desktop), phone);
+        let elements: Vec<(&str, &str)> = all.iter().map(|(id, v)| (id, v.as_str())).collect();
+        // `a` was removed after it was changed, `b` changed after it was removed and `c` removed
+        // after it was written; `d` was written at the same time on two devices
+        assert_eq!(
+            elements,
+            [("b", "blueberry"), ("d", "durian"), ("e", "elderberry")]
+        );
+    }
+
+    #[test]
+    fn removals_keep_tombstones() {
+        let mut set = LwwSet::default();
+        set.insert("a", 1, 10);
+        set.remove("a", 20);
+        // An older write arriving later doesn't resurrect the element
+        set.insert("a", 2, 15);
+        assert_eq!(set.get("a"), None);
+        assert!(set.is_empty());
+        set.insert("a", 3, 25);
+        assert_eq!(set.get("a"), Some(&3));
+    }
+
+    #[test]
+    fn configs_converge_after_syncing() {
+        let root = tempfile::tempdir().unwrap();
+        let clock = Arc::new(ManualClock::new(UNIX_EPOCH + Duration::from_secs(1_000)));
+        let options = ConfigOptions {
+            root: Some(root.path().to_path_buf()),
+            clock: clock.clone(),
+            ..Default::default()
+        };
+        let mut laptop: Config<LwwSet<String>> =
+            Config::load_with_options("bookmarks", options.clone()).unwrap();
+        laptop.put("docs", String::from("https://docs.rs"));
+        laptop.sync().unwrap();
+
+        let mut desktop: Config<LwwSet<String>> =
+            Config::load_with_options("bookmarks", options).unwrap();
+        clock.advance(Duration::from_secs(1));
+        laptop.put("crates", String::from("https://crates.io"));
+        clock.advance(Duration::from_secs(1));
+        assert!(desktop.remove("docs"));
+        desktop.put("rust", String::from("https://rust-lang.org"));
+
+        desktop.sync().unwrap();
+        laptop.sync().unwrap();
+        desktop.sync().unwrap();
+        assert_eq!(laptop.data(), desktop.data());
+        let ids: Vec<&str> = laptop.data().iter().map(|(id, _)| id).collect();
+        assert_eq!(ids, ["crates", "rust"]);
+    }
+}
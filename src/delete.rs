@@ -0,0 +1,94 @@
+//! Deleting configs, optionally through the OS trash so a "reset" can be undone.
+use std::{io, path::Path};
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+use crate::{Config, ConfigError, ConfigOptions};
+
+impl<TConfigData: Serialize + DeserializeOwned> Config<TConfigData> {
+    /// Delete the config file, along with its backup and conflict files.  (For collection
+    /// configs, `delete` soft-deletes a single record instead.)
+    ///
+    /// With [`ConfigOptions::trash`], the files are moved to the OS trash (recycle bin) instead,
+    /// so users can get them back.  History and drop-in fragments are left alone.  Not supported
+    /// with a [`backend`](ConfigOptions::backend).
+    pub fn delete_file(self) -> Result<(), ConfigError> {
+        if let Some(backend) = &self.options.backend {
+            return Err(ConfigError::ConfigBackendError(
+                backend.location(&self.config_file_key),
+                "configs stored in a backend can't be deleted".into(),
+            ));
+        }
+        let config_path = Self::get_config_path(&self.config_file_key, &self.options)?;
+        // Hashed before it's gone, for the audit log
+        #[cfg(feature = "audit-log")]
+        let deleted = self
+            .options
+            .filesystem
+            .is_file(&config_path)
+            .then(|| self.options.filesystem.read(&config_path).ok());
+        let paths = [
+            Self::sidecar_path(&config_path, "bak"),
+            Self::sidecar_path(&config_path, "conflict"),
+            Self::sidecar_path(&config_path, "sig"),
+            config_path,
+        ];
+        for path in paths {
+            if self.options.filesystem.is_file(&path) {
+                remove(&path, &self.options, |path| {
+                    self.options.filesystem.remove_file(path)
+                })?;
+            }
+        }
+        #[cfg(feature = "audit-log")]
+        if let Some(contents) = deleted {
+            crate::audit::log::record(
+                &self.config_file_key,
+                crate::audit::AuditOperation::Delete,
+                contents.as_deref(),
+                &self.options,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Delete the app's entire config root: every config in it, with their history and other files.
+///
+/// To make sure other apps' configs are never touched, this requires a
+/// [`namespace`](ConfigOptions::namespace) or an explicit [`root`](ConfigOptions::root) and
+/// otherwise fails with `ConfigError::SharedRootPurgeError`.  Honors
+/// [`ConfigOptions::trash`].  Does nothing if the root doesn't exist.
+pub fn purge_root(options: &ConfigOptions) -> Result<(), ConfigError> {
+    let config_root = Config::<Value>::get_config_root(options)?;
+    if options.namespace.is_none() && options.root.is_none() {
+        return Err(ConfigError::SharedRootPurgeError(config_root));
+    }
+    let exists = options
+        .filesystem
+        .try_exists(&config_root)
+        .map_err(|e| ConfigError::ConfigRootLoadError(config_root.clone(), e))?;
+    if exists {
+        remove(&config_root, options, |path| {
+            options.filesystem.remove_dir_all(path)
+        })?;
+    }
+    Ok(())
+}
+
+/// Move `path` to the trash if [`ConfigOptions::trash`] is set, or else delete it with `delete`.
+#[cfg_attr(not(feature = "trash"), allow(unused_variables))]
+fn remove(
+    path: &Path,
+    options: &ConfigOptions,
+    delete: impl FnOnce(&Path) -> io::Result<()>,
+) -> Result<(), ConfigError> {
+    #[cfg(feature = "trash")]
+    if options.trash {
+        return trash::delete(path).map_err(|e| {
+            ConfigError::ConfigFileWriteError(path.to_path_buf(), io::Error::other(e))
+        });
+    }
+    delete(path).map_err(|e| ConfigError::ConfigFileWriteError(path.to_path_buf(), e))
+}
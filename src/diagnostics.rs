@@ -0,0 +1,119 @@
+//! Pointing at the location of parse errors in config files, so users editing a config by hand
+//! can see what is wrong.
+use std::fmt::{self, Display};
+
+use crate::ConfigError;
+
+/// Lines of context shown before the line with the error.
+const CONTEXT_LINES: usize = 2;
+
+/// Where in a config file a parse error occurred, along with the file's contents for rendering a
+/// snippet of the surrounding lines.
+///
+/// `Display` renders the snippet with a caret under the offending character:
+///
+/// ```text
+///   3 |   "name": "example"
+///   4 |   "port": 8080
+///     |   ^
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseDiagnostic {
+    /// 1-based line number.
+    pub line: usize,
+
+    /// 1-based column number.
+    pub column: usize,
+
+    source: String,
+}
+
+impl ParseDiagnostic {
+    /// The contents of the file that failed to parse.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Byte offset of the error in [`source`](Self::source).
+    pub fn offset(&self) -> usize {
+        let line_start: usize = self
+            .source
+            .split_inclusive('\n')
+            .take(self.line - 1)
+            .map(str::len)
+            .sum();
+        let line = self.source[line_start..].lines().next().unwrap_or_default();
+        line_start + (self.column.saturating_sub(1)).min(line.len())
+    }
+}
+
+impl Display for ParseDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let first = self.line.saturating_sub(CONTEXT_LINES).max(1);
+        let width = self.line.to_string().len();
+        for (number, line) in self
+            .source
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line))
+            .skip(first - 1)
+            .take(self.line + 1 - first)
+        {
+            writeln!(f, "{:>width$} | {}", number, line)?;
+            if number == self.line {
+                // Keep tabs so the caret lines up with the offending character
+                let indent: String = line
+                    .chars()
+                    .take(self.column.saturating_sub(1))
+                    .map(|c| if c == '\t' { '\t' } else { ' ' })
+                    .collect();
+                write!(f, "{:>width$} | {}^", "", indent)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Attach a diagnostic to a parse error, if it has a location in `contents`.
+pub(crate) fn attach(error: ConfigError, contents: &[u8]) -> ConfigError {
+    match error {
+        ConfigError::ConfigFileParseError(path, e, None) if e.line() > 0 => {
+            let diagnostic = ParseDiagnostic {
+                line: e.line(),
+                column: e.column(),
+                source: String::from_utf8_lossy(contents).into_owned(),
+            };
+            ConfigError::ConfigFileParseError(path, e, Some(Box::new(diagnostic)))
+        }
+        error => error,
+    }
+}
+
+#[cfg(feature = "miette")]
+impl miette::Diagnostic for ConfigError {
+    fn code<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        match self {
+            ConfigError::ConfigFileParseError(..) => Some(Box::new("ilo_config::parse")),
+            _ => None,
+        }
+    }
+
+    fn source_code(&self) -> Option<&dyn miette::SourceCode> {
+        match self {
+            ConfigError::ConfigFileParseError(_, _, Some(diagnostic)) => Some(&diagnostic.source),
+            _ => None,
+        }
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = miette::LabeledSpan> + '_>> {
+        match self {
+            ConfigError::ConfigFileParseError(_, e, Some(diagnostic)) => {
+                Some(Box::new(std::iter::once(miette::LabeledSpan::at_offset(
+                    diagnostic.offset(),
+                    e.to_string(),
+                ))))
+            }
+            _ => None,
+        }
+    }
+}
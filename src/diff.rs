@@ -0,0 +1,87 @@
+//! Structural comparison of JSON documents, e.g. for showing unsaved changes with
+//! [`Config::diff`](crate::Config::diff).
+use serde_json::Value;
+
+/// A single difference between two documents, located by JSON Pointer (e.g. `/servers/0/url`).
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Added {
+        pointer: String,
+        new: Value,
+    },
+    Removed {
+        pointer: String,
+        old: Value,
+    },
+    Modified {
+        pointer: String,
+        old: Value,
+        new: Value,
+    },
+}
+
+impl Change {
+    pub fn pointer(&self) -> &str {
+        match self {
+            Change::Added { pointer, .. }
+            | Change::Removed { pointer, .. }
+            | Change::Modified { pointer, .. } => pointer,
+        }
+    }
+}
+
+/// List the differences between `old` and `new`, descending into objects and arrays.
+pub fn diff(old: &Value, new: &Value) -> Vec<Change> {
+    let mut changes = Vec::new();
+    diff_at(String::new(), old, new, &mut changes);
+    changes
+}
+
+fn diff_at(pointer: String, old: &Value, new: &Value, changes: &mut Vec<Change>) {
+    match (old, new) {
+        (Value::Object(old_map), Value::Object(new_map)) => {
+            for (key, old_value) in old_map {
+                let child = format!("{pointer}/{}", key.replace('~', "~0").replace('/', "~1"));
+                match new_map.get(key) {
+                    Some(new_value) => diff_at(child, old_value, new_value, changes),
+                    None => changes.push(Change::Removed {
+                        pointer: child,
+                        old: old_value.clone(),
+                    }),
+                }
+            }
+            for (key, new_value) in new_map {
+                if !old_map.contains_key(key) {
+                    changes.push(Change::Added {
+                        pointer: format!("{pointer}/{}", key.replace('~', "~0").replace('/', "~1")),
+                        new: new_value.clone(),
+                    });
+                }
+            }
+        }
+        (Value::Array(old_items), Value::Array(new_items)) => {
+            for (i, old_value) in old_items.iter().enumerate() {
+                let child = format!("{}/{}", pointer, i);
+                match new_items.get(i) {
+                    Some(new_value) => diff_at(child, old_value, new_value, changes),
+                    None => changes.push(Change::Removed {
+                        pointer: child,
+                        old: old_value.clone(),
+                    }),
+                }
+            }
+            for (i, new_value) in new_items.iter().enumerate().skip(old_items.len()) {
+                changes.push(Change::Added {
+                    pointer: format!("{}/{}", pointer, i),
+                    new: new_value.clone(),
+                });
+            }
+        }
+        _ if old != new => changes.push(Change::Modified {
+            pointer,
+            old: old.clone(),
+            new: new.clone(),
+        }),
+        _ => (),
+    }
+}
@@ -0,0 +1,186 @@
+//! Health checks for a config root, e.g. for a `doctor` command that support staff can ask users
+//! to run.
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use serde_json::Value;
+
+use crate::{
+    error::ErrorKind,
+    instance::{is_alive, read_pid},
+    Config, ConfigError, ConfigRoot,
+};
+
+/// A problem found by [`ConfigRoot::doctor`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DoctorIssue {
+    /// A config file couldn't be read, e.g. because of its permissions or a wrong passphrase.
+    Unreadable { path: PathBuf, error: String },
+
+    /// A config file could be read, but isn't valid JSON (or JSONC).
+    ParseFailure { path: PathBuf, error: String },
+
+    /// The config root or a file in it is accessible by other users.
+    InsecurePermissions { path: PathBuf, mode: u32 },
+
+    /// A [`single_instance`](crate::single_instance) lock file left behind by a process that is no
+    /// longer running, or that doesn't hold a valid process ID.
+    StaleLock { path: PathBuf, pid: Option<u32> },
+
+    /// A backup kept from a failed write verification whose config file no longer exists.
+    OrphanedBackup { path: PathBuf },
+}
+
+impl DoctorIssue {
+    pub fn path(&self) -> &Path {
+        match self {
+            DoctorIssue::Unreadable { path, .. }
+            | DoctorIssue::ParseFailure { path, .. }
+            | DoctorIssue::InsecurePermissions { path, .. }
+            | DoctorIssue::StaleLock { path, .. }
+            | DoctorIssue::OrphanedBackup { path } => path,
+        }
+    }
+}
+
+impl fmt::Display for DoctorIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DoctorIssue::Unreadable { path, error } => {
+                write!(f, "{} can't be read: {}", path.display(), error)
+            }
+            DoctorIssue::ParseFailure { path, error } => {
+                write!(f, "{} is not a valid config: {}", path.display(), error)
+            }
+            DoctorIssue::InsecurePermissions { path, mode } => write!(
+                f,
+                "{} is accessible by other users (permissions {:o})",
+                path.display(),
+                mode
+            ),
+            DoctorIssue::StaleLock {
+                path,
+                pid: Some(pid),
+            } => write!(
+                f,
+                "{} is left over from process {}, which is no longer running",
+                path.display(),
+                pid
+            ),
+            DoctorIssue::StaleLock { path, pid: None } => {
+                write!(f, "{} doesn't hold a valid process ID", path.display())
+            }
+            DoctorIssue::OrphanedBackup { path } => {
+                write!(
+                    f,
+                    "{} is a backup of a config that no longer exists",
+                    path.display()
+                )
+            }
+        }
+    }
+}
+
+/// The result of [`ConfigRoot::doctor`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DoctorReport {
+    /// The config root that was checked.
+    pub root: PathBuf,
+
+    /// The keys of the configs that were checked.
+    pub keys: Vec<String>,
+
+    pub issues: Vec<DoctorIssue>,
+}
+
+impl DoctorReport {
+    pub fn is_healthy(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+impl ConfigRoot {
+    /// Check the config root for problems: config files that can't be read or parsed, loose
+    /// permissions, stale instance locks and orphaned backups.
+    ///
+    /// Problems with individual files are collected in the report rather than returned as errors;
+    /// an error means the root itself couldn't be examined.  Nothing is changed.
+    ///
+    /// ```no_run
+    /// # use ilo_config::ConfigRoot;
+    /// let report = ConfigRoot::new().doctor()?;
+    /// for issue in &report.issues {
+    ///     println!("- {}", issue);
+    /// }
+    /// # Ok::<(), ilo_config::ConfigError>(())
+    /// ```
+    pub fn doctor(&self) -> Result<DoctorReport, ConfigError> {
+        let options = &self.options;
+        let filesystem = &options.filesystem;
+        let root = self.path()?;
+        let keys = self.keys()?;
+        let mut issues = Vec::new();
+        if !filesystem
+            .try_exists(&root)
+            .map_err(|e| ConfigError::ConfigRootLoadError(root.clone(), e))?
+        {
+            return Ok(DoctorReport { root, keys, issues });
+        }
+
+        let mut entries = filesystem
+            .read_dir(&root)
+            .map_err(|e| ConfigError::ConfigRootLoadError(root.clone(), e))?;
+        entries.sort();
+        for path in [root.clone()].into_iter().chain(
+            entries
+                .iter()
+                .filter(|path| filesystem.is_file(path))
+                .cloned(),
+        ) {
+            if let Ok(Some(mode)) = filesystem.permissions(&path) {
+                if mode & 0o077 != 0 {
+                    issues.push(DoctorIssue::InsecurePermissions { path, mode });
+                }
+            }
+        }
+
+        for key in &keys {
+            let path = Config::<Value>::get_config_path(key, options)?;
+            let parsed = Config::<Value>::read_config_file(&path, options).and_then(|contents| {
+                Config::<Value>::parse_config_file(contents, &path, options).map(|_| ())
+            });
+            match parsed {
+                Ok(()) => {}
+                Err(e) if e.kind() == ErrorKind::Parse => issues.push(DoctorIssue::ParseFailure {
+                    path,
+                    error: e.to_string(),
+                }),
+                Err(e) => issues.push(DoctorIssue::Unreadable {
+                    path,
+                    error: e.to_string(),
+                }),
+            }
+        }
+
+        for path in entries.into_iter().filter(|path| filesystem.is_file(path)) {
+            let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            if file_name.ends_with(".pid") {
+                let pid = read_pid(filesystem, &path);
+                if pid.is_none_or(|pid| !is_alive(pid)) {
+                    issues.push(DoctorIssue::StaleLock { path, pid });
+                }
+            } else if let Some(config_name) = file_name.strip_suffix(".bak") {
+                if !filesystem.is_file(&path.with_file_name(config_name)) {
+                    issues.push(DoctorIssue::OrphanedBackup { path });
+                }
+            }
+        }
+
+        Ok(DoctorReport { root, keys, issues })
+    }
+}
@@ -0,0 +1,192 @@
+//! Merging the variables in a `.env` file over a config, see
+//! [`ConfigOptions::dotenv`](crate::ConfigOptions::dotenv).
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+use serde_json::{Map, Value};
+
+use crate::{backend::env as env_backend, Config, ConfigError, ConfigOptions};
+
+const FILE_NAME: &str = ".env";
+
+/// Where to find the `.env` file for [`ConfigOptions::dotenv`], and which of its variables
+/// belong to a config.
+///
+/// Variables are mapped to fields like with [`EnvBackend`](crate::backend::env::EnvBackend):
+/// `JIRA_BASE_URL=https://jira.example.com` sets `base_url` of the config `jira`, and
+/// `JIRA_AUTH__USER` sets `auth.user`.  A variable that is also set in the environment takes the
+/// value from the environment instead, so a `.env` file can be committed with development
+/// defaults that individual runs override.
+///
+/// The file has one `NAME=value` per line, optionally preceded by `export`.  Values can be
+/// single-quoted (taken literally) or double-quoted (with backslash escapes like `\n` and
+/// `\"`); lines starting with `#` and unquoted text after ` #` are comments.
+///
+/// ```no_run
+/// # use ilo_config::{Config, ConfigOptions, DotEnv};
+/// let options = ConfigOptions {
+///     dotenv: Some(DotEnv::project_dir()),
+///     ..Default::default()
+/// };
+/// let config: Config<serde_json::Value> = Config::load_with_options("jira", options)?;
+/// # Ok::<(), ilo_config::ConfigError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct DotEnv {
+    location: Location,
+    prefix: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+enum Location {
+    ConfigRoot,
+    ProjectDir,
+    File(PathBuf),
+}
+
+impl DotEnv {
+    /// Read `.env` in the config root, next to the config files.
+    pub fn config_root() -> Self {
+        Self::at(Location::ConfigRoot)
+    }
+
+    /// Read `.env` in the current directory, or the closest parent directory that has one, like
+    /// the dotenv tools of most languages do.
+    pub fn project_dir() -> Self {
+        Self::at(Location::ProjectDir)
+    }
+
+    /// Read the variables from `path`.
+    pub fn file(path: impl Into<PathBuf>) -> Self {
+        Self::at(Location::File(path.into()))
+    }
+
+    /// Map variables with this prefix instead of the uppercased config key, e.g. `MYAPP_`.
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = Some(prefix.into());
+        self
+    }
+
+    fn at(location: Location) -> Self {
+        Self {
+            location,
+            prefix: None,
+        }
+    }
+
+    /// The path of the `.env` file, or `None` if there is none.
+    fn find(&self, options: &ConfigOptions) -> Result<Option<PathBuf>, ConfigError> {
+        let path = match &self.location {
+            Location::ConfigRoot => Config::<Value>::get_config_root(options)?.join(FILE_NAME),
+            Location::ProjectDir => {
+                let Ok(current_dir) = env::current_dir() else {
+                    return Ok(None);
+                };
+                let found = current_dir
+                    .ancestors()
+                    .map(|dir| dir.join(FILE_NAME))
+                    .find(|path| options.filesystem.is_file(path));
+                return Ok(found);
+            }
+            Location::File(path) => path.clone(),
+        };
+        Ok(options.filesystem.is_file(&path).then_some(path))
+    }
+
+    /// The document that the variables for the config at `config_path` make up, or `None` if
+    /// there is no `.env` file or none of its variables belong to the config.
+    pub(crate) fn document(
+        &self,
+        config_path: &Path,
+        options: &ConfigOptions,
+    ) -> Result<Option<Value>, ConfigError> {
+        let Some(path) = self.find(options)? else {
+            return Ok(None);
+        };
+        let contents = options
+            .filesystem
+            .read(&path)
+            .map_err(|e| ConfigError::ConfigFileLoadError(path.clone(), e))?;
+        let contents = String::from_utf8_lossy(&contents);
+
+        let prefix = match &self.prefix {
+            Some(prefix) => prefix.clone(),
+            None => {
+                let key = config_path.file_stem().unwrap_or_default();
+                env_backend::prefix_for_key(&key.to_string_lossy())
+            }
+        };
+        let mut document = Map::new();
+        for (index, line) in contents.lines().enumerate() {
+            let Some((name, value)) = parse_line(line).map_err(|message| {
+                ConfigError::DotEnvParseError(path.clone(), index + 1, message)
+            })?
+            else {
+                continue;
+            };
+            let Some(field) = name.strip_prefix(&prefix).filter(|field| !field.is_empty()) else {
+                continue;
+            };
+            let value = env::var(name).unwrap_or(value);
+            let value = serde_json::from_str(&value).unwrap_or_else(|_| Value::from(value));
+            env_backend::insert(&mut document, &field.to_lowercase(), value);
+        }
+        Ok((!document.is_empty()).then_some(Value::Object(document)))
+    }
+}
+
+/// Parse a line of a `.env` file into the variable name and value, or `None` for blank lines and
+/// comments.
+fn parse_line(line: &str) -> Result<Option<(&str, String)>, String> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+    let line = line.strip_prefix("export ").unwrap_or(line);
+    let Some((name, value)) = line.split_once('=') else {
+        return Err(String::from("expected `NAME=value`"));
+    };
+    let name = name.trim();
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(format!("`{}` is not a valid variable name", name));
+    }
+
+    let value = value.trim();
+    let value = if let Some(quoted) = value.strip_prefix('\'') {
+        let (value, _) = quoted
+            .split_once('\'')
+            .ok_or_else(|| String::from("missing closing `'`"))?;
+        value.to_string()
+    } else if let Some(quoted) = value.strip_prefix('"') {
+        unescape(quoted)?
+    } else {
+        let value = value.split_once(" #").map_or(value, |(value, _)| value);
+        value.trim_end().to_string()
+    };
+    Ok(Some((name, value)))
+}
+
+/// The contents of a double-quoted value up to the closing quote, with escapes replaced.
+fn unescape(quoted: &str) -> Result<String, String> {
+    let mut value = String::new();
+    let mut chars = quoted.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => return Ok(value),
+            '\\' => match chars.next() {
+                Some('n') => value.push('\n'),
+                Some('t') => value.push('\t'),
+                Some(c @ ('"' | '\\' | '$')) => value.push(c),
+                Some(c) => {
+                    value.push('\\');
+                    value.push(c);
+                }
+                None => break,
+            },
+            c => value.push(c),
+        }
+    }
+    Err(String::from("missing closing `\"`"))
+}
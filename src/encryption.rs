@@ -0,0 +1,224 @@
+//! Passphrase-based encryption of whole config documents.
+//!
+//! An encrypted config file is a small JSON envelope holding the AES-256-GCM ciphertext of the
+//! document, along with the salt and Argon2id parameters used to derive the key from the
+//! passphrase.  The passphrase comes from a [`PassphraseProvider`], which lets CLIs hook in their
+//! own prompt.
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::Debug,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use aes_gcm::{
+    aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng},
+    Aes256Gcm, Nonce,
+};
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+
+use crate::{audit::is_sealed, ConfigError, ConfigOptions, Secret};
+
+/// Why a passphrase is needed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PassphrasePurpose {
+    /// An existing file is being decrypted.
+    Decrypt,
+
+    /// A file is being encrypted for the first time, so a prompt may want to ask for
+    /// confirmation.
+    Encrypt,
+}
+
+/// Source of passphrases, e.g. an interactive prompt.
+pub trait PassphraseProvider: Debug + Send + Sync {
+    fn passphrase(
+        &self,
+        config_path: &Path,
+        purpose: PassphrasePurpose,
+    ) -> Result<Secret, Box<dyn Error + Send + Sync>>;
+}
+
+/// [`PassphraseProvider`] that always returns the same passphrase, e.g. one read from an
+/// environment variable.
+#[derive(Debug, Clone)]
+pub struct StaticPassphrase(pub Secret);
+
+impl PassphraseProvider for StaticPassphrase {
+    fn passphrase(
+        &self,
+        _config_path: &Path,
+        _purpose: PassphrasePurpose,
+    ) -> Result<Secret, Box<dyn Error + Send + Sync>> {
+        Ok(self.0.clone())
+    }
+}
+
+/// Encryption settings, set with [`ConfigOptions::encryption`](crate::ConfigOptions::encryption).
+///
+/// Passphrases are remembered per file once provided, so the provider is asked at most once per
+/// config rather than on every load and save.
+#[derive(Debug, Clone)]
+pub struct Encryption {
+    provider: Arc<dyn PassphraseProvider>,
+    passphrases: Arc<Mutex<HashMap<PathBuf, Secret>>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Envelope {
+    ilo_encrypted: u32,
+    kdf: String,
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+    salt: String,
+    cipher: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+impl Encryption {
+    pub fn new(provider: impl PassphraseProvider + 'static) -> Self {
+        Self {
+            provider: Arc::new(provider),
+            passphrases: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    fn passphrase(
+        &self,
+        config_path: &Path,
+        purpose: PassphrasePurpose,
+    ) -> Result<Secret, ConfigError> {
+        let mut passphrases = self
+            .passphrases
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some(passphrase) = passphrases.get(config_path) {
+            return Ok(passphrase.clone());
+        }
+        let passphrase = self
+            .provider
+            .passphrase(config_path, purpose)
+            .map_err(|e| encryption_error(config_path, e.to_string()))?;
+        passphrases.insert(config_path.to_path_buf(), passphrase.clone());
+        Ok(passphrase)
+    }
+
+    fn forget(&self, config_path: &Path) {
+        self.passphrases
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(config_path);
+    }
+
+    /// Encrypt a serialized document into an envelope.
+    pub(crate) fn seal(
+        &self,
+        config_path: &Path,
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, ConfigError> {
+        let passphrase = self.passphrase(config_path, PassphrasePurpose::Encrypt)?;
+        let params = Params::default();
+        let mut salt = [0u8; 16];
+        OsRng.fill_bytes(&mut salt);
+        let cipher = cipher(config_path, &passphrase, &salt, &params)?;
+
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = cipher
+            .encrypt(&nonce, plaintext)
+            .map_err(|e| encryption_error(config_path, e.to_string()))?;
+        let envelope = Envelope {
+            ilo_encrypted: 1,
+            kdf: String::from("argon2id"),
+            m_cost: params.m_cost(),
+            t_cost: params.t_cost(),
+            p_cost: params.p_cost(),
+            salt: STANDARD.encode(salt),
+            cipher: String::from("aes-256-gcm"),
+            nonce: STANDARD.encode(nonce),
+            ciphertext: STANDARD.encode(ciphertext),
+        };
+        serde_json::to_vec_pretty(&envelope).map_err(ConfigError::ConfigFileSerializeError)
+    }
+
+    /// Decrypt an envelope back into the serialized document.
+    fn open(&self, config_path: &Path, envelope: Envelope) -> Result<Vec<u8>, ConfigError> {
+        if envelope.ilo_encrypted != 1
+            || envelope.kdf != "argon2id"
+            || envelope.cipher != "aes-256-gcm"
+        {
+            return Err(encryption_error(
+                config_path,
+                String::from("unsupported encryption scheme"),
+            ));
+        }
+        let decode = |field: &str| {
+            STANDARD
+                .decode(field)
+                .map_err(|e| encryption_error(config_path, e.to_string()))
+        };
+        let salt = decode(&envelope.salt)?;
+        let nonce = decode(&envelope.nonce)?;
+        let ciphertext = decode(&envelope.ciphertext)?;
+        if nonce.len() != 12 {
+            return Err(encryption_error(config_path, String::from("invalid nonce")));
+        }
+        let params = Params::new(envelope.m_cost, envelope.t_cost, envelope.p_cost, Some(32))
+            .map_err(|e| encryption_error(config_path, e.to_string()))?;
+
+        let passphrase = self.passphrase(config_path, PassphrasePurpose::Decrypt)?;
+        let cipher = cipher(config_path, &passphrase, &salt, &params)?;
+        cipher
+            .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+            .map_err(|_| {
+                // Ask again next time rather than retrying the same wrong passphrase
+                self.forget(config_path);
+                encryption_error(
+                    config_path,
+                    String::from("wrong passphrase or corrupted file"),
+                )
+            })
+    }
+}
+
+/// Derive the key for a passphrase and set up the cipher.
+fn cipher(
+    config_path: &Path,
+    passphrase: &Secret,
+    salt: &[u8],
+    params: &Params,
+) -> Result<Aes256Gcm, ConfigError> {
+    let mut key = [0u8; 32];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params.clone())
+        .hash_password_into(passphrase.expose().as_bytes(), salt, &mut key)
+        .map_err(|e| encryption_error(config_path, e.to_string()))?;
+    Aes256Gcm::new_from_slice(&key).map_err(|e| encryption_error(config_path, e.to_string()))
+}
+
+/// Decrypt file contents if they are an encrypted envelope, or return them as they are.
+pub(crate) fn open_if_sealed(
+    contents: Vec<u8>,
+    config_path: &Path,
+    options: &ConfigOptions,
+) -> Result<Vec<u8>, ConfigError> {
+    if !is_sealed(&contents) {
+        return Ok(contents);
+    }
+    let envelope: Envelope = serde_json::from_slice(&contents)
+        .map_err(|e| ConfigError::ConfigFileParseError(config_path.to_path_buf(), e, None))?;
+    match &options.encryption {
+        Some(encryption) => encryption.open(config_path, envelope),
+        None => Err(encryption_error(
+            config_path,
+            String::from("the file is encrypted but no passphrase provider is configured"),
+        )),
+    }
+}
+
+fn encryption_error(config_path: &Path, message: String) -> ConfigError {
+    ConfigError::ConfigEncryptionError(config_path.to_path_buf(), message)
+}
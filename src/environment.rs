@@ -2,12 +2,20 @@
 //!
 //! Users of ilo-config may set the root environment variable `ILO_CONFIG_HOME` to customize where
 //! the rest of their configs are stored. If not set, this variable defaults to `~/.config/ilo/`.
+//! Apps can rename the variable with [`ConfigOptions::root_env_var`](crate::ConfigOptions).
+//!
+//! Similarly, `ILO_STATE_HOME` customizes where app state that isn't configuration (e.g. recently
+//! used values) is stored, defaulting to `~/.local/state/ilo/`.
+use std::{env, path::PathBuf};
+
 use serde::Deserialize;
 
+use crate::ConfigError;
+
 /// Env vars as a typed struct - for loading using the `envy` crate.
 #[derive(Deserialize, Debug)]
 pub struct IloConfigEnvironment {
-    pub ilo_config_home: Option<String>,
+    pub ilo_state_home: Option<String>,
 }
 
 /// Load the environment from environment variables.
@@ -19,3 +27,30 @@ pub struct IloConfigEnvironment {
 pub fn load_env() -> IloConfigEnvironment {
     envy::from_env().expect("Failed to load configuration from environment variables")
 }
+
+/// Read the config root from the root environment variable, `ILO_CONFIG_HOME` unless the app
+/// renamed it.
+pub fn config_home(var: &str) -> Option<String> {
+    env::var(var).ok()
+}
+
+/// The user's home directory, if there is one.  Browsers have none, so this is always `None` in
+/// `wasm32` builds.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn home_dir() -> Option<PathBuf> {
+    home::home_dir()
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn home_dir() -> Option<PathBuf> {
+    None
+}
+
+/// Resolve the directory for app state, from `$ILO_STATE_HOME` or `~/.local/state/ilo/`.
+pub fn state_home() -> Result<PathBuf, ConfigError> {
+    load_env()
+        .ilo_state_home
+        .map(PathBuf::from)
+        .or(home_dir().map(|d| d.join(".local").join("state").join("ilo")))
+        .ok_or(ConfigError::NoHome)
+}
@@ -0,0 +1,161 @@
+//! Categories of [`ConfigError`]s, for apps that react to errors programmatically.
+use std::{io, path::Path};
+
+use crate::ConfigError;
+
+/// Broad category of a [`ConfigError`], stable across releases even as error variants are added
+/// or changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// No config root could be determined, e.g. because there is no home directory.
+    NoRoot,
+
+    /// A file or directory doesn't exist.
+    NotFound,
+
+    /// Access to a file or directory was denied, or a config file's permissions are too loose.
+    PermissionDenied,
+
+    /// Any other IO failure, e.g. a full disk.
+    Io,
+
+    /// A config file (or a file it refers to) isn't valid, e.g. malformed JSON or a field of the
+    /// wrong type.
+    Parse,
+
+    /// The config data couldn't be serialized.
+    Serialize,
+
+    /// The config file was changed by someone else, or a conflict is still unresolved.
+    Conflict,
+
+    /// A config [`backend`](crate::ConfigOptions::backend),
+    /// [`resolver`](crate::ConfigOptions::resolvers) or
+    /// [`secret_refresher`](crate::ConfigOptions::secret_refresher) failed.
+    Backend,
+
+    /// Encrypting or decrypting a config failed, e.g. because of a wrong passphrase, or its
+    /// signing key couldn't be retrieved.
+    Encryption,
+
+    /// A signed config file doesn't match its signature, see
+    /// [`ConfigOptions::signing`](crate::ConfigOptions::signing).
+    Tampered,
+
+    /// Saving would exceed the [`quota`](crate::ConfigOptions::quota).
+    QuotaExceeded,
+
+    /// The config root was written by a newer version of ilo-config.
+    Incompatible,
+
+    /// Another instance of the app is running, see [`single_instance`](crate::single_instance).
+    AlreadyRunning,
+
+    /// The config was used in a way that isn't possible, e.g. through a handle whose config was
+    /// dropped.
+    InvalidUse,
+}
+
+impl From<io::ErrorKind> for ErrorKind {
+    fn from(kind: io::ErrorKind) -> Self {
+        match kind {
+            io::ErrorKind::NotFound => ErrorKind::NotFound,
+            io::ErrorKind::PermissionDenied => ErrorKind::PermissionDenied,
+            _ => ErrorKind::Io,
+        }
+    }
+}
+
+impl ConfigError {
+    /// The category of the error.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            ConfigError::NoHome => ErrorKind::NoRoot,
+            ConfigError::NotFound(_) => ErrorKind::NotFound,
+            ConfigError::ConfigRootLoadError(_, e)
+            | ConfigError::ConfigRootCreateError(_, e)
+            | ConfigError::ConfigFileLoadError(_, e)
+            | ConfigError::ConfigFileWriteError(_, e)
+            | ConfigError::ConfigHistoryError(_, e)
+            | ConfigError::ConfigGitError(_, e)
+            | ConfigError::ConfigLockError(_, e)
+            | ConfigError::ConfigSetupError(e) => e.kind().into(),
+            ConfigError::ConfigFileVerifyError(..) => ErrorKind::Io,
+            ConfigError::ConfigFileParseError(..)
+            | ConfigError::ConfigFileValidationError(..)
+            | ConfigError::ConfigFileUnknownFieldsError(..)
+            | ConfigError::ConfigIncludeError(..)
+            | ConfigError::ConfigInterpolationError(..)
+            | ConfigError::DotEnvParseError(..) => ErrorKind::Parse,
+            ConfigError::ConfigFileSerializeError(_) => ErrorKind::Serialize,
+            ConfigError::ConflictError(_)
+            | ConfigError::UnresolvedConflictError(_)
+            | ConfigError::MergeConflictError(..) => ErrorKind::Conflict,
+            ConfigError::ConfigBackendError(..)
+            | ConfigError::ConfigResolveError(..)
+            | ConfigError::SecretRefreshError(..) => ErrorKind::Backend,
+            ConfigError::IncompatibleLayoutError(..) => ErrorKind::Incompatible,
+            ConfigError::AlreadyRunningError(..) => ErrorKind::AlreadyRunning,
+            ConfigError::QuotaExceededError(..) => ErrorKind::QuotaExceeded,
+            ConfigError::InsecurePermissionsError(..) => ErrorKind::PermissionDenied,
+            ConfigError::ConfigEncryptionError(..) | ConfigError::SigningKeyError(..) => {
+                ErrorKind::Encryption
+            }
+            ConfigError::TamperDetected(_) => ErrorKind::Tampered,
+            ConfigError::InvalidDataError(_) => ErrorKind::Parse,
+            ConfigError::ConfigDroppedError(_)
+            | ConfigError::ConfigTypeMismatchError(_)
+            | ConfigError::InvalidPointerError(..)
+            | ConfigError::ConfigPatchError(..)
+            | ConfigError::SharedRootPurgeError(_) => ErrorKind::InvalidUse,
+            ConfigError::RetriesExhausted(_, errors) => {
+                errors.last().map_or(ErrorKind::Io, |e| e.kind().into())
+            }
+        }
+    }
+
+    /// The file or directory the error is about, if any.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            ConfigError::ConfigRootLoadError(path, _)
+            | ConfigError::ConfigRootCreateError(path, _)
+            | ConfigError::NotFound(path)
+            | ConfigError::ConfigFileLoadError(path, _)
+            | ConfigError::ConfigFileParseError(path, ..)
+            | ConfigError::ConfigFileValidationError(path, _)
+            | ConfigError::ConfigFileUnknownFieldsError(path, _)
+            | ConfigError::ConfigFileWriteError(path, _)
+            | ConfigError::ConfigFileVerifyError(path, _)
+            | ConfigError::ConflictError(path)
+            | ConfigError::ConfigHistoryError(path, _)
+            | ConfigError::ConfigGitError(path, _)
+            | ConfigError::ConfigIncludeError(path, _)
+            | ConfigError::ConfigInterpolationError(path, _)
+            | ConfigError::DotEnvParseError(path, ..)
+            | ConfigError::UnresolvedConflictError(path)
+            | ConfigError::MergeConflictError(path, _)
+            | ConfigError::ConfigLockError(path, _)
+            | ConfigError::ConfigResolveError(path, ..)
+            | ConfigError::IncompatibleLayoutError(path, _)
+            | ConfigError::QuotaExceededError(path, ..)
+            | ConfigError::InsecurePermissionsError(path, _)
+            | ConfigError::ConfigEncryptionError(path, _)
+            | ConfigError::SigningKeyError(path, _)
+            | ConfigError::TamperDetected(path)
+            | ConfigError::SharedRootPurgeError(path)
+            | ConfigError::AlreadyRunningError(path, _)
+            | ConfigError::RetriesExhausted(path, _) => Some(path),
+            ConfigError::NoHome
+            | ConfigError::ConfigFileSerializeError(_)
+            | ConfigError::ConfigSetupError(_)
+            | ConfigError::ConfigBackendError(..)
+            | ConfigError::SecretRefreshError(..)
+            | ConfigError::ConfigDroppedError(_)
+            | ConfigError::InvalidPointerError(..)
+            | ConfigError::ConfigPatchError(..)
+            | ConfigError::InvalidDataError(_)
+            | ConfigError::ConfigTypeMismatchError(_) => None,
+        }
+    }
+}
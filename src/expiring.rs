@@ -0,0 +1,129 @@
+//! Wrapper for config values that go stale, such as cached access tokens.
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Clock, SystemClock};
+
+/// `expires_at` of values that never expire, because their TTL reaches past what `SystemTime`
+/// can represent.
+const NEVER: u64 = u64::MAX;
+
+/// The end of the year 9999, which every platform can represent as a `SystemTime`.
+const LATEST_REPRESENTABLE: u64 = 253_402_300_799;
+
+/// A value that is only valid until a point in time.
+///
+/// Serializes as the value along with its expiry time in seconds since the UNIX epoch, e.g.
+/// `{ "value": "...", "expires_at": 1718000000 }`, and [`get`](Self::get) stops returning it once
+/// that time has passed:
+///
+/// ```
+/// # use std::time::Duration;
+/// use ilo_config::Expiring;
+///
+/// let mut token = Expiring::with_ttl(String::from("abc"), Duration::from_secs(3600));
+/// assert_eq!(token.get().map(String::as_str), Some("abc"));
+///
+/// // Fetches a new token only once the cached one has expired.
+/// let token = token.get_or_refresh(|| {
+///     Ok::<_, std::io::Error>((String::from("def"), Duration::from_secs(3600)))
+/// })?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+///
+/// Methods that check the expiry against the [`SystemClock`] have an `_at` variant that takes the
+/// current time instead, e.g. from [`ConfigOptions::clock`](crate::ConfigOptions::clock).  A TTL
+/// too long to add to the current time makes the value never expire.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct Expiring<T> {
+    value: T,
+
+    /// Seconds since the UNIX epoch.
+    expires_at: u64,
+}
+
+impl<T> Expiring<T> {
+    /// A value that expires at `expires_at`, rounded down to the second.
+    pub fn new(value: T, expires_at: SystemTime) -> Self {
+        Self {
+            value,
+            expires_at: expires_at
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+        }
+    }
+
+    /// A value that expires `ttl` from now.
+    pub fn with_ttl(value: T, ttl: Duration) -> Self {
+        Self::with_ttl_at(value, ttl, SystemClock.now())
+    }
+
+    pub fn with_ttl_at(value: T, ttl: Duration, now: SystemTime) -> Self {
+        match now.checked_add(ttl) {
+            Some(expires_at) => Self::new(value, expires_at),
+            None => Self {
+                value,
+                expires_at: NEVER,
+            },
+        }
+    }
+
+    /// The value, unless it has expired.
+    pub fn get(&self) -> Option<&T> {
+        self.get_at(SystemClock.now())
+    }
+
+    pub fn get_at(&self, now: SystemTime) -> Option<&T> {
+        (!self.is_expired_at(now)).then_some(&self.value)
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.is_expired_at(SystemClock.now())
+    }
+
+    pub fn is_expired_at(&self, now: SystemTime) -> bool {
+        let now = now.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        now >= self.expires_at
+    }
+
+    /// When the value expires.  For values that never expire, and expiry times the platform
+    /// can't represent, this is the end of the year 9999.
+    pub fn expires_at(&self) -> SystemTime {
+        UNIX_EPOCH
+            .checked_add(Duration::from_secs(self.expires_at))
+            .unwrap_or(UNIX_EPOCH + Duration::from_secs(LATEST_REPRESENTABLE))
+    }
+
+    /// The value, whether it has expired or not.
+    pub fn peek(&self) -> &T {
+        &self.value
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+
+    /// The value if it hasn't expired, otherwise replace it with a fresh one from `refresh`,
+    /// which returns the new value and how long it stays valid.  Remember to save the config
+    /// afterwards so the new value is cached.
+    pub fn get_or_refresh<E>(
+        &mut self,
+        refresh: impl FnOnce() -> Result<(T, Duration), E>,
+    ) -> Result<&T, E> {
+        self.get_or_refresh_at(SystemClock.now(), refresh)
+    }
+
+    pub fn get_or_refresh_at<E>(
+        &mut self,
+        now: SystemTime,
+        refresh: impl FnOnce() -> Result<(T, Duration), E>,
+    ) -> Result<&T, E> {
+        if self.is_expired_at(now) {
+            let (value, ttl) = refresh()?;
+            *self = Self::with_ttl_at(value, ttl, now);
+        }
+        Ok(&self.value)
+    }
+}
@@ -0,0 +1,464 @@
+//! Ready-made types for config fields that people like to write in a humane way, with serde
+//! support that parses and validates them on load.
+//!
+//! ```
+//! # use serde::{Deserialize, Serialize};
+//! use ilo_config::fields::{ByteSize, ExpandedPath, HumanDuration};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Cache {
+//!     dir: ExpandedPath,      // "~/.cache/my-app" or "$XDG_CACHE_HOME/my-app"
+//!     max_size: ByteSize,     // "500MB"
+//!     ttl: HumanDuration,     // "1h30m"
+//! }
+//! ```
+use std::{
+    fmt::{self, Display},
+    ops::Deref,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use serde::{
+    de::{self, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+/// A [`Duration`] written like `"30s"`, `"5m"` or `"1h30m"`.
+///
+/// The units are `ms`, `s`, `m`, `h` and `d`.  A plain number in the file counts as seconds.
+/// Saved as the shortest equivalent string, e.g. 90 seconds as `"1m30s"`.
+///
+/// ```
+/// # use std::time::Duration;
+/// # use ilo_config::fields::HumanDuration;
+/// let duration: HumanDuration = "1h30m".parse().unwrap();
+/// assert_eq!(*duration, Duration::from_secs(5400));
+/// assert_eq!(duration.to_string(), "1h30m");
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "schemars", schemars(with = "String"))]
+pub struct HumanDuration(pub Duration);
+
+const DURATION_UNITS: [(&str, u128); 5] = [
+    ("d", 86_400_000),
+    ("h", 3_600_000),
+    ("m", 60_000),
+    ("s", 1_000),
+    ("ms", 1),
+];
+
+impl FromStr for HumanDuration {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        let invalid = || {
+            format!(
+                "invalid duration `{}`, expected e.g. `30s` or `1h30m`",
+                text
+            )
+        };
+        let mut rest = text.trim();
+        if rest == "0" {
+            return Ok(Self(Duration::ZERO));
+        }
+        if rest.is_empty() {
+            return Err(invalid());
+        }
+
+        let mut millis: u128 = 0;
+        while !rest.is_empty() {
+            let digits = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .ok_or_else(invalid)?;
+            let amount: u128 = rest[..digits].parse().map_err(|_| invalid())?;
+            rest = rest[digits..].trim_start();
+            let unit_len = rest
+                .find(|c: char| !c.is_ascii_alphabetic())
+                .unwrap_or(rest.len());
+            let (_, factor) = DURATION_UNITS
+                .iter()
+                .find(|(unit, _)| *unit == &rest[..unit_len])
+                .ok_or_else(invalid)?;
+            millis = amount
+                .checked_mul(*factor)
+                .and_then(|part| millis.checked_add(part))
+                .ok_or_else(invalid)?;
+            rest = rest[unit_len..].trim_start();
+        }
+        let millis = u64::try_from(millis).map_err(|_| invalid())?;
+        Ok(Self(Duration::from_millis(millis)))
+    }
+}
+
+impl Display for HumanDuration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut millis = self.0.as_millis();
+        if millis == 0 {
+            return f.write_str("0s");
+        }
+        for (unit, factor) in DURATION_UNITS {
+            if millis >= factor {
+                write!(f, "{}{}", millis / factor, unit)?;
+                millis %= factor;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Deref for HumanDuration {
+    type Target = Duration;
+
+    fn deref(&self) -> &Duration {
+        &self.0
+    }
+}
+
+impl From<Duration> for HumanDuration {
+    fn from(duration: Duration) -> Self {
+        Self(duration)
+    }
+}
+
+impl From<HumanDuration> for Duration {
+    fn from(duration: HumanDuration) -> Self {
+        duration.0
+    }
+}
+
+impl Serialize for HumanDuration {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for HumanDuration {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(TextOrNumber::new("a duration such as `30s`", |seconds| {
+            Self(Duration::from_secs(seconds))
+        }))
+    }
+}
+
+/// A number of bytes written like `"10MB"`, `"512 KiB"` or `"1.5GB"`.
+///
+/// `KB`, `MB`, `GB` and `TB` are powers of 1000, `KiB`, `MiB`, `GiB` and `TiB` powers of 1024,
+/// and units are case-insensitive.  A plain number counts as bytes.  Saved with the largest unit
+/// that represents the size exactly, e.g. `"10MB"`.
+///
+/// ```
+/// # use ilo_config::fields::ByteSize;
+/// let size: ByteSize = "1.5 KiB".parse().unwrap();
+/// assert_eq!(size.bytes(), 1536);
+/// assert_eq!(size.to_string(), "1536B");
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "schemars", schemars(with = "String"))]
+pub struct ByteSize(pub u64);
+
+const BYTE_UNITS: [(&str, u64); 9] = [
+    ("TiB", 1 << 40),
+    ("TB", 1_000_000_000_000),
+    ("GiB", 1 << 30),
+    ("GB", 1_000_000_000),
+    ("MiB", 1 << 20),
+    ("MB", 1_000_000),
+    ("KiB", 1 << 10),
+    ("KB", 1_000),
+    ("B", 1),
+];
+
+impl ByteSize {
+    pub fn bytes(&self) -> u64 {
+        self.0
+    }
+}
+
+impl FromStr for ByteSize {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        let invalid = || format!("invalid size `{}`, expected e.g. `10MB` or `512KiB`", text);
+        let text = text.trim();
+        let number_len = text
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(text.len());
+        let (number, unit) = text.split_at(number_len);
+        let unit = unit.trim();
+        let factor = match unit {
+            "" => 1,
+            _ => {
+                BYTE_UNITS
+                    .iter()
+                    .find(|(name, _)| name.eq_ignore_ascii_case(unit))
+                    .ok_or_else(invalid)?
+                    .1
+            }
+        };
+
+        // Fractions are only allowed as long as they come out to whole bytes
+        let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+        let whole: u64 = whole.parse().map_err(|_| invalid())?;
+        let mut bytes = whole.checked_mul(factor).ok_or_else(invalid)?;
+        if !fraction.is_empty() {
+            let digits = u32::try_from(fraction.len()).map_err(|_| invalid())?;
+            let scale = 10u128.checked_pow(digits).ok_or_else(invalid)?;
+            let numerator =
+                u128::from(fraction.parse::<u64>().map_err(|_| invalid())?) * u128::from(factor);
+            if numerator % scale != 0 {
+                return Err(invalid());
+            }
+            let extra = u64::try_from(numerator / scale).map_err(|_| invalid())?;
+            bytes = bytes.checked_add(extra).ok_or_else(invalid)?;
+        }
+        Ok(Self(bytes))
+    }
+}
+
+impl Display for ByteSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (unit, factor) = BYTE_UNITS
+            .iter()
+            .find(|(_, factor)| self.0 != 0 && self.0.is_multiple_of(*factor))
+            .unwrap_or(&("B", 1));
+        write!(f, "{}{}", self.0 / factor, unit)
+    }
+}
+
+impl From<u64> for ByteSize {
+    fn from(bytes: u64) -> Self {
+        Self(bytes)
+    }
+}
+
+impl Serialize for ByteSize {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ByteSize {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(TextOrNumber::new("a size such as `10MB`", Self))
+    }
+}
+
+/// A path in which a leading `~` and environment variables (`$NAME` or `${NAME}`) are expanded
+/// on load.
+///
+/// The path is saved the way it was written, so `~/Downloads` stays `~/Downloads` in the file.
+/// Variables that aren't set are left as they are, so the path visibly doesn't exist rather than
+/// silently pointing somewhere else.  `$$` stands for a literal `$`.
+///
+/// ```
+/// # use ilo_config::fields::ExpandedPath;
+/// let path: ExpandedPath = serde_json::from_str(r#""~/Downloads""#).unwrap();
+/// assert!(path.ends_with("Downloads"));
+/// assert_eq!(serde_json::to_string(&path).unwrap(), r#""~/Downloads""#);
+///
+/// std::env::set_var("PROJECTS", "/srv/projects");
+/// let path = ExpandedPath::from("${PROJECTS}/ilo");
+/// assert_eq!(&*path, std::path::Path::new("/srv/projects/ilo"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "schemars", schemars(with = "String"))]
+pub struct ExpandedPath {
+    original: String,
+    expanded: PathBuf,
+}
+
+impl ExpandedPath {
+    /// The path as it was written, before expansion.
+    pub fn original(&self) -> &str {
+        &self.original
+    }
+
+    pub fn into_path_buf(self) -> PathBuf {
+        self.expanded
+    }
+}
+
+impl From<&str> for ExpandedPath {
+    fn from(original: &str) -> Self {
+        let home = || crate::environment::home_dir().unwrap_or_else(|| PathBuf::from("~"));
+        let with_variables = expand_variables(original);
+        let expanded = match with_variables.strip_prefix('~') {
+            Some("") => home(),
+            Some(rest) if rest.starts_with(['/', '\\']) => home().join(&rest[1..]),
+            _ => PathBuf::from(with_variables),
+        };
+        Self {
+            original: original.to_string(),
+            expanded,
+        }
+    }
+}
+
+impl From<PathBuf> for ExpandedPath {
+    fn from(path: PathBuf) -> Self {
+        Self {
+            original: path.to_string_lossy().into_owned(),
+            expanded: path,
+        }
+    }
+}
+
+impl Deref for ExpandedPath {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.expanded
+    }
+}
+
+impl AsRef<Path> for ExpandedPath {
+    fn as_ref(&self) -> &Path {
+        &self.expanded
+    }
+}
+
+impl Serialize for ExpandedPath {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.original)
+    }
+}
+
+impl<'de> Deserialize<'de> for ExpandedPath {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(|original| Self::from(original.as_str()))
+    }
+}
+
+/// Replace `$NAME` and `${NAME}` with the values of environment variables that are set.
+fn expand_variables(text: &str) -> String {
+    let mut expanded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('$') {
+        expanded.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+        if let Some(after) = rest.strip_prefix('$') {
+            expanded.push('$');
+            rest = after;
+            continue;
+        }
+
+        let (name, reference_len) = match rest.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(end) => (&braced[..end], end + 2),
+                None => ("", 0),
+            },
+            None => {
+                let end = rest
+                    .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .unwrap_or(rest.len());
+                (&rest[..end], end)
+            }
+        };
+        match std::env::var(name).ok().filter(|_| !name.is_empty()) {
+            Some(value) => expanded.push_str(&value),
+            None => {
+                expanded.push('$');
+                expanded.push_str(&rest[..reference_len]);
+            }
+        }
+        rest = &rest[reference_len..];
+    }
+    expanded.push_str(rest);
+    expanded
+}
+
+/// An absolute URL with a host, such as `https://example.com/api`.
+///
+/// Loading fails for strings that aren't URLs, and for ones that only look like URLs by accident,
+/// like `localhost:8080` (which would otherwise parse as a URL with the scheme `localhost`).
+///
+/// ```
+/// # use ilo_config::fields::Url;
+/// assert!(serde_json::from_str::<Url>(r#""https://example.com/api""#).is_ok());
+/// assert!(serde_json::from_str::<Url>(r#""localhost:8080""#).is_err());
+/// ```
+#[cfg(feature = "url")]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "schemars", schemars(with = "String"))]
+pub struct Url(pub url::Url);
+
+#[cfg(feature = "url")]
+impl FromStr for Url {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        let url = url::Url::parse(text).map_err(|e| format!("invalid URL `{}`: {}", text, e))?;
+        if url.host().is_none() {
+            return Err(format!("invalid URL `{}`: no host", text));
+        }
+        Ok(Self(url))
+    }
+}
+
+#[cfg(feature = "url")]
+impl Display for Url {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Display::fmt(&self.0, f)
+    }
+}
+
+#[cfg(feature = "url")]
+impl Deref for Url {
+    type Target = url::Url;
+
+    fn deref(&self) -> &url::Url {
+        &self.0
+    }
+}
+
+#[cfg(feature = "url")]
+impl Serialize for Url {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0.as_str())
+    }
+}
+
+#[cfg(feature = "url")]
+impl<'de> Deserialize<'de> for Url {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        text.parse().map_err(de::Error::custom)
+    }
+}
+
+/// Visitor for values written as a string to parse, or as a plain number.
+struct TextOrNumber<T> {
+    expecting: &'static str,
+    from_number: fn(u64) -> T,
+}
+
+impl<T> TextOrNumber<T> {
+    fn new(expecting: &'static str, from_number: fn(u64) -> T) -> Self {
+        Self {
+            expecting,
+            from_number,
+        }
+    }
+}
+
+impl<T: FromStr<Err = String>> Visitor<'_> for TextOrNumber<T> {
+    type Value = T;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.expecting)
+    }
+
+    fn visit_str<E: de::Error>(self, text: &str) -> Result<T, E> {
+        text.parse().map_err(E::custom)
+    }
+
+    fn visit_u64<E: de::Error>(self, number: u64) -> Result<T, E> {
+        Ok((self.from_number)(number))
+    }
+}
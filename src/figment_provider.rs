@@ -0,0 +1,101 @@
+//! Using ilo-config files as a layer in a [figment](https://docs.rs/figment) configuration.
+use figment::{
+    value::{Dict, Map, Value as FigmentValue},
+    Error, Metadata, Profile, Provider, Source,
+};
+use serde_json::Value;
+
+use crate::{Config, ConfigOptions};
+
+/// A figment [`Provider`] that reads an ilo-config file.
+///
+/// The file is loaded like any other config, with its permissions, encryption, includes and
+/// other options applied, each time the figment is extracted.  A missing file provides nothing.
+/// Apps that already layer their settings with figment can use this for the settings they save
+/// on the user's behalf through a [`Config`], which keeps the file private to the user.
+///
+/// ```no_run
+/// # use figment::{Figment, providers::Serialized};
+/// # use serde::{Deserialize, Serialize};
+/// use ilo_config::ConfigProvider;
+///
+/// #[derive(Default, Serialize, Deserialize)]
+/// struct Settings {
+///     theme: String,
+/// }
+///
+/// let settings: Settings = Figment::from(Serialized::defaults(Settings::default()))
+///     .merge(ConfigProvider::new("settings"))
+///     .extract()?;
+/// # Ok::<(), figment::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct ConfigProvider {
+    config_file_key: String,
+    options: ConfigOptions,
+    profile: Option<Profile>,
+}
+
+impl ConfigProvider {
+    /// Provide the config stored under `config_file_key` as the default profile.
+    pub fn new(config_file_key: impl Into<String>) -> Self {
+        Self::with_options(config_file_key, ConfigOptions::default())
+    }
+
+    /// Like [`new`](Self::new), loading the config with `options`.
+    pub fn with_options(config_file_key: impl Into<String>, options: ConfigOptions) -> Self {
+        Self {
+            config_file_key: config_file_key.into(),
+            options,
+            profile: Some(Profile::Default),
+        }
+    }
+
+    /// Provide the whole config as `profile` instead of the default profile.
+    pub fn profile(mut self, profile: impl Into<Profile>) -> Self {
+        self.profile = Some(profile.into());
+        self
+    }
+
+    /// Treat the top-level keys of the config as profile names, like figment's own `nested`
+    /// file providers, e.g. `{ "default": {...}, "debug": {...} }`.
+    pub fn nested(mut self) -> Self {
+        self.profile = None;
+        self
+    }
+}
+
+impl Provider for ConfigProvider {
+    fn metadata(&self) -> Metadata {
+        let name = format!("ilo-config `{}`", self.config_file_key);
+        match Config::<Value>::get_config_path(&self.config_file_key, &self.options) {
+            Ok(config_path) if self.options.backend.is_none() => {
+                Metadata::from(name, Source::File(config_path))
+            }
+            _ => Metadata::named(name),
+        }
+    }
+
+    fn data(&self) -> Result<Map<Profile, Dict>, Error> {
+        let config: Config<Value> =
+            Config::load_with_options(&self.config_file_key, self.options.clone())
+                .map_err(|e| Error::from(e.to_string()))?;
+        if config.data().is_null() {
+            return Ok(Map::new());
+        }
+
+        let value = FigmentValue::serialize(config.data())?;
+        match &self.profile {
+            Some(profile) => {
+                let dict = value.into_dict().ok_or_else(|| {
+                    Error::from(format!(
+                        "ilo-config `{}` is not a JSON object",
+                        self.config_file_key
+                    ))
+                })?;
+                Ok(profile.collect(dict))
+            }
+            None => value.deserialize(),
+        }
+    }
+}
@@ -0,0 +1,330 @@
+//! Filesystem abstraction used for all config file access.
+//!
+//! The default [`StdFileSystem`] goes straight to `std::fs`.  Test suites can plug in their own
+//! [`FileSystem`] through [`ConfigOptions::filesystem`](crate::ConfigOptions::filesystem) to
+//! exercise error handling without touching the real disk.
+use std::{
+    fmt::Debug,
+    fs::{self, DirBuilder, File, OpenOptions},
+    io::{self, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+#[cfg(unix)]
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt};
+
+/// A held lock, released when dropped.
+pub type LockGuard = Box<dyn Send>;
+
+/// Filesystem operations needed by [`Config`](crate::Config).
+pub trait FileSystem: Debug + Send + Sync {
+    fn is_file(&self, path: &Path) -> bool;
+
+    /// Whether `path` itself is a symbolic link, without following it.  `false` by default, for
+    /// filesystems without links.
+    fn is_symlink(&self, _path: &Path) -> bool {
+        false
+    }
+
+    fn try_exists(&self, path: &Path) -> io::Result<bool>;
+
+    /// Create a directory and any missing parents.
+    ///
+    /// If `mode` is given, directories created by this call get those UNIX permissions.
+    fn create_dir_all(&self, path: &Path, mode: Option<u32>) -> io::Result<()>;
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+
+    /// Open a file for incremental reading.  By default the whole file is read with
+    /// [`read`](Self::read) up front.
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(io::Cursor::new(self.read(path)?)))
+    }
+
+    /// Create or replace the file at `path` with `contents`.
+    ///
+    /// If the file is created by this call and `mode` is given, the file gets those UNIX
+    /// permissions.  [`StdFileSystem`] writes a temporary file next to `path` and renames it into
+    /// place, so readers and crashes see either the old or the new contents, never a mix.
+    fn write(&self, path: &Path, contents: &[u8], mode: Option<u32>) -> io::Result<()>;
+
+    /// Like [`write`](Self::write), but also flush the file and the directory entry that points
+    /// to it to stable storage before returning.  By default the file is written and then both
+    /// are passed to [`sync`](Self::sync).
+    fn write_synced(&self, path: &Path, contents: &[u8], mode: Option<u32>) -> io::Result<()> {
+        self.write(path, contents, mode)?;
+        self.sync(path)?;
+        match path.parent() {
+            Some(parent) if parent.as_os_str().is_empty() => self.sync(Path::new(".")),
+            Some(parent) => self.sync(parent),
+            None => Ok(()),
+        }
+    }
+
+    /// Create or truncate the file at `path` for incremental writing, with `mode` applied like
+    /// in [`write`](Self::write).  Unsupported by default.
+    fn create(&self, _path: &Path, _mode: Option<u32>) -> io::Result<Box<dyn Write + Send>> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    /// Append `contents` to the file at `path`, creating it with `mode` applied like in
+    /// [`write`](Self::write) if it doesn't exist.  By default the file is read and written
+    /// again as a whole.
+    fn append(&self, path: &Path, contents: &[u8], mode: Option<u32>) -> io::Result<()> {
+        let mut existing = match self.read(path) {
+            Ok(existing) => existing,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        existing.extend_from_slice(contents);
+        self.write(path, &existing, mode)
+    }
+
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<()>;
+
+    /// Atomically move a file or directory, like `rename(2)`.  Unsupported by default.
+    fn rename(&self, _from: &Path, _to: &Path) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    /// Size of the file at `path` in bytes.  By default the whole file is read with
+    /// [`read`](Self::read) to find out.
+    fn len(&self, path: &Path) -> io::Result<u64> {
+        self.read(path).map(|contents| contents.len() as u64)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()>;
+
+    /// Remove a directory and everything in it.  Unsupported by default.
+    fn remove_dir_all(&self, _path: &Path) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    /// UNIX permissions of a file or directory, or `None` where they don't apply.
+    fn permissions(&self, _path: &Path) -> io::Result<Option<u32>> {
+        Ok(None)
+    }
+
+    /// Change the UNIX permissions of a file or directory.  Does nothing where they don't apply.
+    fn set_permissions(&self, _path: &Path, _mode: u32) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Flush a file or directory to stable storage, like `fsync`.  Does nothing by default, for
+    /// filesystems without a notion of durability.
+    fn sync(&self, _path: &Path) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Paths of the entries in a directory, in no particular order.
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>>;
+
+    /// Block until an exclusive lock on the lock file at `path` is acquired, creating the file if
+    /// needed.
+    fn lock(&self, path: &Path) -> io::Result<LockGuard>;
+}
+
+/// [`FileSystem`] backed by the real filesystem.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StdFileSystem;
+
+// Modes only apply on UNIX
+#[cfg_attr(not(unix), allow(unused_variables))]
+impl FileSystem for StdFileSystem {
+    fn is_file(&self, path: &Path) -> bool {
+        path.is_file()
+    }
+
+    fn is_symlink(&self, path: &Path) -> bool {
+        fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_symlink())
+    }
+
+    fn try_exists(&self, path: &Path) -> io::Result<bool> {
+        path.try_exists()
+    }
+
+    fn create_dir_all(&self, path: &Path, mode: Option<u32>) -> io::Result<()> {
+        let mut builder = DirBuilder::new();
+        builder.recursive(true);
+        #[cfg(unix)]
+        if let Some(mode) = mode {
+            builder.mode(mode);
+        }
+        builder.create(path)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(File::open(path)?))
+    }
+
+    fn write(&self, path: &Path, contents: &[u8], mode: Option<u32>) -> io::Result<()> {
+        replace(path, contents, mode, false)
+    }
+
+    fn write_synced(&self, path: &Path, contents: &[u8], mode: Option<u32>) -> io::Result<()> {
+        replace(path, contents, mode, true)
+    }
+
+    fn create(&self, path: &Path, mode: Option<u32>) -> io::Result<Box<dyn Write + Send>> {
+        let mut options = OpenOptions::new();
+        options.create(true).write(true).truncate(true);
+        #[cfg(unix)]
+        if let Some(mode) = mode {
+            options.mode(mode);
+        }
+        Ok(Box::new(BufWriter::new(options.open(path)?)))
+    }
+
+    fn append(&self, path: &Path, contents: &[u8], mode: Option<u32>) -> io::Result<()> {
+        let mut options = OpenOptions::new();
+        options.create(true).append(true);
+        #[cfg(unix)]
+        if let Some(mode) = mode {
+            options.mode(mode);
+        }
+        options.open(path)?.write_all(contents)
+    }
+
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::copy(from, to).map(|_| ())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::rename(from, to)
+    }
+
+    fn len(&self, path: &Path) -> io::Result<u64> {
+        fs::metadata(path).map(|metadata| metadata.len())
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()> {
+        fs::remove_dir_all(path)
+    }
+
+    #[cfg(unix)]
+    fn permissions(&self, path: &Path) -> io::Result<Option<u32>> {
+        fs::metadata(path).map(|metadata| Some(metadata.permissions().mode() & 0o777))
+    }
+
+    #[cfg(unix)]
+    fn set_permissions(&self, path: &Path, mode: u32) -> io::Result<()> {
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))
+    }
+
+    fn sync(&self, path: &Path) -> io::Result<()> {
+        // Directories can't be opened (or synced) like files on Windows
+        if !cfg!(unix) && path.is_dir() {
+            return Ok(());
+        }
+        File::open(path)?.sync_all()
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect()
+    }
+
+    fn lock(&self, path: &Path) -> io::Result<LockGuard> {
+        // Lock files are left in place after use; removing them would let two processes end up
+        // holding locks on different files for the same config.
+        let mut options = OpenOptions::new();
+        options.create(true).truncate(false).write(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+
+        let file: File = options.open(path)?;
+        file.lock()?;
+        Ok(Box::new(file))
+    }
+}
+
+/// Replace the file at `path` by writing `contents` to a temporary file and renaming it into
+/// place, for [`StdFileSystem::write`] and, with `sync`, [`StdFileSystem::write_synced`].
+fn replace(path: &Path, contents: &[u8], mode: Option<u32>, sync: bool) -> io::Result<()> {
+    // Replace the file a symlink points to rather than the link itself
+    let path = match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_symlink() => fs::canonicalize(path)?,
+        _ => path.to_path_buf(),
+    };
+    let existing = match fs::metadata(&path) {
+        Ok(metadata) => Some(metadata.permissions()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e),
+    };
+
+    // Existing files keep their permissions; `mode` only applies when the file is created.
+    // Nobody else may read the new contents before the old permissions are copied over.
+    let temp_mode = match existing {
+        Some(_) => Some(0o600),
+        None => mode,
+    };
+    let (temp_path, mut file) = create_temp_file(&path, temp_mode)?;
+    let written = (|| {
+        if let Some(permissions) = existing {
+            file.set_permissions(permissions)?;
+        }
+        file.write_all(contents)?;
+        if sync {
+            file.sync_all()?;
+        }
+        drop(file);
+        fs::rename(&temp_path, &path)
+    })();
+    if let Err(e) = written {
+        let _ = fs::remove_file(&temp_path);
+        return Err(e);
+    }
+
+    // The rename is only durable once the directory entry is
+    match path.parent() {
+        Some(parent) if sync && cfg!(unix) => {
+            let parent = if parent.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                parent
+            };
+            File::open(parent)?.sync_all()
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Create a new, empty file next to `path` to write its replacement to, with `mode` applied like
+/// in [`FileSystem::write`].  The name ends in `.tmp` so archive exports leave out any that a
+/// crash didn't clean up.
+#[cfg_attr(not(unix), allow(unused_variables))]
+fn create_temp_file(path: &Path, mode: Option<u32>) -> io::Result<(PathBuf, File)> {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    loop {
+        let temp_path = path.with_file_name(format!(
+            ".{}.{}-{}.tmp",
+            file_name,
+            process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        if let Some(mode) = mode {
+            options.mode(mode);
+        }
+        match options.open(&temp_path) {
+            Ok(file) => return Ok((temp_path, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
@@ -0,0 +1,130 @@
+//! File formats that configs can be stored in.
+use serde::Serialize;
+use serde_json::{ser::PrettyFormatter, Serializer, Value};
+
+/// Format of a config file on disk.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConfigFormat {
+    /// Plain JSON, stored as `<key>.json`.
+    #[default]
+    Json,
+
+    /// JSON with `//` and `/* */` comments and trailing commas, stored as `<key>.jsonc`.
+    ///
+    /// Comments preceding a member or array element are written back in the same place on save,
+    /// so hand-maintained configs keep their documentation.
+    Jsonc,
+}
+
+impl ConfigFormat {
+    /// File extension used for configs in this format, without the leading dot.
+    pub fn extension(self) -> &'static str {
+        match self {
+            ConfigFormat::Json => "json",
+            ConfigFormat::Jsonc => "jsonc",
+        }
+    }
+}
+
+/// Layout of saved config files, set with
+/// [`ConfigOptions::formatting`](crate::ConfigOptions::formatting).
+///
+/// The default matches `serde_json`'s pretty printing.  Configs kept in version control (e.g. in
+/// a dotfiles repository) can use `sort_keys` to get deterministic output even for map types with
+/// no stable order, like `HashMap`.
+#[derive(Debug, Clone, Default)]
+pub struct Formatting {
+    /// Write the members of every object in lexical order of their keys.
+    pub sort_keys: bool,
+
+    pub indent: Indent,
+
+    /// End the file with a newline.  JSONC files always end with one.
+    pub trailing_newline: bool,
+}
+
+/// Indentation of nested values in saved config files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Indent {
+    /// Everything on one line.  JSONC files are still pretty-printed (with two spaces) so that
+    /// comments have lines to go on.
+    Compact,
+
+    /// The given number of spaces per level.
+    Spaces(usize),
+
+    /// One tab per level.
+    Tabs,
+}
+
+impl Default for Indent {
+    fn default() -> Self {
+        Indent::Spaces(2)
+    }
+}
+
+impl Indent {
+    /// The string that makes up one level of indentation, or `None` for compact output.
+    pub(crate) fn unit(self) -> Option<String> {
+        match self {
+            Indent::Compact => None,
+            Indent::Spaces(n) => Some(" ".repeat(n)),
+            Indent::Tabs => Some("\t".to_string()),
+        }
+    }
+}
+
+impl Formatting {
+    /// Serialize `value` as JSON with this layout.
+    pub(crate) fn to_vec<T: Serialize + ?Sized>(&self, value: &T) -> serde_json::Result<Vec<u8>> {
+        let mut output = Vec::new();
+        if self.sort_keys {
+            let mut document = serde_json::to_value(value)?;
+            sort_keys(&mut document);
+            self.write(&mut output, &document)?;
+        } else {
+            self.write(&mut output, value)?;
+        }
+        if self.trailing_newline {
+            output.push(b'\n');
+        }
+        Ok(output)
+    }
+
+    /// Apply the key order to a document that is written by other means (i.e. JSONC).
+    pub(crate) fn order(&self, document: &mut Value) {
+        if self.sort_keys {
+            sort_keys(document);
+        }
+    }
+
+    /// One level of indentation for pretty-printed JSONC.
+    pub(crate) fn jsonc_indent(&self) -> String {
+        self.indent.unit().unwrap_or_else(|| "  ".to_string())
+    }
+
+    fn write<T: Serialize + ?Sized>(
+        &self,
+        output: &mut Vec<u8>,
+        value: &T,
+    ) -> serde_json::Result<()> {
+        match self.indent.unit() {
+            Some(unit) => {
+                let formatter = PrettyFormatter::with_indent(unit.as_bytes());
+                value.serialize(&mut Serializer::with_formatter(output, formatter))
+            }
+            None => serde_json::to_writer(output, value),
+        }
+    }
+}
+
+fn sort_keys(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            map.sort_keys();
+            map.values_mut().for_each(sort_keys);
+        }
+        Value::Array(items) => items.iter_mut().for_each(sort_keys),
+        _ => {}
+    }
+}
@@ -0,0 +1,153 @@
+//! Git-backed config roots: every save is committed to a git repository at the config root.
+//!
+//! This shells out to the `git` command line tool, so it works with the user's own git setup
+//! (identity, hooks, remotes for syncing) and always goes to the real filesystem.
+use std::{
+    io,
+    path::Path,
+    process::Command,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::ConfigError;
+
+/// A commit that changed a config, as returned by [`Config::git_log`](crate::Config::git_log).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitCommit {
+    /// Full commit hash.
+    pub id: String,
+    pub message: String,
+    pub committed_at: SystemTime,
+}
+
+/// Run git in the config root and return its standard output.
+fn git(config_root: &Path, args: &[&str]) -> Result<Vec<u8>, ConfigError> {
+    let git_error = |e| ConfigError::ConfigGitError(config_root.to_path_buf(), e);
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(config_root)
+        .args(args)
+        .output()
+        .map_err(git_error)?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(git_error(io::Error::other(format!(
+            "`git {}` failed: {}",
+            args.join(" "),
+            stderr
+        ))));
+    }
+    Ok(output.stdout)
+}
+
+/// Commit the current version of a config file, creating the repository if needed.
+///
+/// Nothing is committed if the file didn't change.
+pub(crate) fn commit(
+    config_root: &Path,
+    config_path: &Path,
+    config_file_key: &str,
+) -> Result<(), ConfigError> {
+    if !config_root.join(".git").exists() {
+        git(config_root, &["init", "--quiet"])?;
+    }
+
+    let file_name = file_name(config_path);
+    git(config_root, &["add", "--", &file_name])?;
+    let staged = Command::new("git")
+        .arg("-C")
+        .arg(config_root)
+        .args(["diff", "--cached", "--quiet", "--", &file_name])
+        .status()
+        .map_err(|e| ConfigError::ConfigGitError(config_root.to_path_buf(), e))?;
+    if staged.success() {
+        return Ok(());
+    }
+
+    let message = format!("Update {}", config_file_key);
+    let mut args = Vec::new();
+    // Fall back to a generic identity so that commits work on machines where git isn't set up
+    if git(config_root, &["config", "user.email"]).is_err() {
+        args.extend([
+            "-c",
+            "user.name=ilo-config",
+            "-c",
+            "user.email=ilo-config@localhost",
+        ]);
+    }
+    args.extend(["commit", "--quiet", "-m", &message, "--", &file_name]);
+    git(config_root, &args).map(|_| ())
+}
+
+/// Commits that changed a config file, newest first.
+pub(crate) fn log(config_root: &Path, config_path: &Path) -> Result<Vec<GitCommit>, ConfigError> {
+    if !config_root.join(".git").exists() {
+        return Ok(Vec::new());
+    }
+    // A repository without any commits yet has no log
+    let has_commits = Command::new("git")
+        .arg("-C")
+        .arg(config_root)
+        .args(["rev-parse", "--verify", "--quiet", "HEAD"])
+        .output()
+        .map_err(|e| ConfigError::ConfigGitError(config_root.to_path_buf(), e))?
+        .status
+        .success();
+    if !has_commits {
+        return Ok(Vec::new());
+    }
+
+    let output = git(
+        config_root,
+        &[
+            "log",
+            "--format=%H%x1f%ct%x1f%s",
+            "--",
+            &file_name(config_path),
+        ],
+    )?;
+
+    Ok(String::from_utf8_lossy(&output)
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\x1f');
+            let id = fields.next()?.to_string();
+            let seconds: u64 = fields.next()?.parse().ok()?;
+            let message = fields.next()?.to_string();
+            Some(GitCommit {
+                id,
+                message,
+                committed_at: UNIX_EPOCH + Duration::from_secs(seconds),
+            })
+        })
+        .collect())
+}
+
+/// Contents of a config file as of a commit.
+///
+/// Only hex commit ids are accepted, so an id can't be taken for a git option or a revision
+/// expression.
+pub(crate) fn show(
+    config_root: &Path,
+    config_path: &Path,
+    commit_id: &str,
+) -> Result<Vec<u8>, ConfigError> {
+    if commit_id.is_empty() || !commit_id.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(ConfigError::ConfigGitError(
+            config_root.to_path_buf(),
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("`{}` is not a commit id", commit_id),
+            ),
+        ));
+    }
+    let spec = format!("{}:{}", commit_id, file_name(config_path));
+    git(config_root, &["show", &spec])
+}
+
+fn file_name(config_path: &Path) -> String {
+    config_path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
@@ -0,0 +1,168 @@
+//! Process-wide configs that are loaded on first use, for CLIs that don't want to thread a
+//! [`Config`] through every function.
+use std::sync::{Mutex, OnceLock, PoisonError, RwLockReadGuard};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{Config, ConfigError, ConfigOptions, ConfigWriteGuard, SharedConfig};
+
+/// Every [`StaticConfig`] that has been loaded, for [`flush_all`].
+static LOADED: Mutex<Vec<&'static dyn Flush>> = Mutex::new(Vec::new());
+
+/// A config in a `static`, loaded the first time it is accessed.  Declare one with
+/// [`static_config!`](crate::static_config).
+///
+/// Changes are kept in memory until they are saved with [`flush`](Self::flush), or along with
+/// every other static config with [`flush_all`], e.g. before the process exits.
+pub struct StaticConfig<TConfigData: Serialize + DeserializeOwned + Send + Sync + 'static> {
+    config_file_key: &'static str,
+    options: fn() -> ConfigOptions,
+    config: OnceLock<SharedConfig<TConfigData>>,
+}
+
+/// Type-erased [`StaticConfig`], for [`flush_all`].
+trait Flush: Sync {
+    /// Save the config if it has unsaved changes, returning its key if it was saved.
+    fn flush(&self) -> Result<Option<&'static str>, ConfigError>;
+}
+
+impl<TConfigData> StaticConfig<TConfigData>
+where
+    TConfigData: Serialize + DeserializeOwned + Default + Send + Sync + 'static,
+{
+    #[doc(hidden)]
+    pub const fn new(config_file_key: &'static str, options: fn() -> ConfigOptions) -> Self {
+        Self {
+            config_file_key,
+            options,
+            config: OnceLock::new(),
+        }
+    }
+
+    /// The shared handle to the config, loading it if this is the first access.
+    ///
+    /// If loading fails, the error is returned and the next access tries again.
+    pub fn get(&'static self) -> Result<&'static SharedConfig<TConfigData>, ConfigError> {
+        if let Some(config) = self.config.get() {
+            return Ok(config);
+        }
+        let config = Config::load_with_options(self.config_file_key, (self.options)())?;
+        // If another thread got there first, its copy wins
+        if self.config.set(SharedConfig::new(config)).is_ok() {
+            LOADED
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(self);
+        }
+        Ok(self.config.get().expect("config was just set"))
+    }
+
+    /// Lock the config for reading, loading it if this is the first access.
+    pub fn read(
+        &'static self,
+    ) -> Result<RwLockReadGuard<'static, Config<TConfigData>>, ConfigError> {
+        self.get().map(SharedConfig::read)
+    }
+
+    /// Lock the config for writing, loading it if this is the first access.
+    pub fn write(&'static self) -> Result<ConfigWriteGuard<'static, TConfigData>, ConfigError> {
+        self.get().map(SharedConfig::write)
+    }
+
+    /// Save the config if it was loaded and has unsaved changes.
+    pub fn flush(&'static self) -> Result<(), ConfigError> {
+        Flush::flush(self).map(|_| ())
+    }
+}
+
+impl<TConfigData> Flush for StaticConfig<TConfigData>
+where
+    TConfigData: Serialize + DeserializeOwned + Send + Sync + 'static,
+{
+    fn flush(&self) -> Result<Option<&'static str>, ConfigError> {
+        let Some(config) = self.config.get() else {
+            return Ok(None);
+        };
+        let dirty = config.read().has_unsaved_changes();
+        config.flush()?;
+        Ok(dirty.then_some(self.config_file_key))
+    }
+}
+
+/// Save every [`StaticConfig`] that has unsaved changes, returning the keys of the configs that
+/// were saved.
+///
+/// All of them are attempted even if saving one fails; the first error is returned.
+pub fn flush_all() -> Result<Vec<String>, ConfigError> {
+    let loaded = LOADED
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone();
+    let mut saved = Vec::new();
+    let mut first_error = None;
+    for config in loaded {
+        match config.flush() {
+            Ok(Some(key)) => saved.push(key.to_string()),
+            Ok(None) => {}
+            Err(e) => {
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+    match first_error {
+        Some(e) => Err(e),
+        None => Ok(saved),
+    }
+}
+
+/// Declare process-wide configs that are loaded on first use, as [`StaticConfig`]s.
+///
+/// Each config is declared with its key and, optionally, an expression for its
+/// [`ConfigOptions`], which is evaluated when it is loaded.  Call [`flush_all`] before the
+/// process exits to save the changes.
+///
+/// ```no_run
+/// # use serde::{Deserialize, Serialize};
+/// use ilo_config::{static_config, ConfigOptions};
+///
+/// #[derive(Default, Serialize, Deserialize)]
+/// struct JiraConfig {
+///     url: Option<String>,
+/// }
+///
+/// static_config! {
+///     static JIRA: JiraConfig = "jira";
+///     pub static HISTORY: Vec<String> = "history", ConfigOptions {
+///         strict: true,
+///         ..Default::default()
+///     };
+/// }
+///
+/// fn record(command: &str) -> Result<(), ilo_config::ConfigError> {
+///     HISTORY.write()?.data_mut().push(command.to_string());
+///     Ok(())
+/// }
+///
+/// println!("{:?}", JIRA.read()?.data().url);
+/// record("login")?;
+/// ilo_config::flush_all()?;
+/// # Ok::<(), ilo_config::ConfigError>(())
+/// ```
+#[macro_export]
+macro_rules! static_config {
+    ($($(#[$attr:meta])* $vis:vis static $name:ident: $ty:ty = $key:expr $(, $options:expr)?;)*) => {
+        $(
+            $(#[$attr])*
+            $vis static $name: $crate::StaticConfig<$ty> = $crate::StaticConfig::new(
+                $key,
+                || $crate::static_config!(@options $($options)?),
+            );
+        )*
+    };
+    (@options) => {
+        $crate::ConfigOptions::default()
+    };
+    (@options $options:expr) => {
+        $options
+    };
+}
@@ -0,0 +1,175 @@
+//! Revision history: snapshots of every saved version of a config.
+//!
+//! Snapshots are stored as `<root>/.history/<key>/<milliseconds since epoch>.<ext>`, or
+//! `<milliseconds>-<n>.<ext>` for the `n`th further snapshot taken in the same millisecond.
+use std::{
+    cmp::Reverse,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{ConfigError, ConfigOptions};
+
+/// Retention policy for revision history, set with
+/// [`ConfigOptions::history`](crate::ConfigOptions::history).
+///
+/// Without any limits, every revision is kept forever.
+#[derive(Debug, Clone, Default)]
+pub struct HistoryOptions {
+    /// Keep at most this many revisions, dropping the oldest ones.
+    pub max_revisions: Option<usize>,
+
+    /// Drop revisions older than this.
+    pub max_age: Option<Duration>,
+}
+
+/// A saved version of a config.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Revision {
+    pub saved_at: SystemTime,
+    pub path: PathBuf,
+}
+
+pub(crate) fn history_dir(config_root: &Path, config_file_key: &str) -> PathBuf {
+    config_root.join(".history").join(config_file_key)
+}
+
+/// All revisions in a history directory, newest first.
+pub(crate) fn list(
+    history_dir: &Path,
+    options: &ConfigOptions,
+) -> Result<Vec<Revision>, ConfigError> {
+    Ok(list_numbered(history_dir, options)?
+        .into_iter()
+        .map(|(revision, _)| revision)
+        .collect())
+}
+
+/// All revisions in a history directory along with their number within their millisecond,
+/// newest first.
+fn list_numbered(
+    history_dir: &Path,
+    options: &ConfigOptions,
+) -> Result<Vec<(Revision, u64)>, ConfigError> {
+    if !options
+        .filesystem
+        .try_exists(history_dir)
+        .map_err(|e| ConfigError::ConfigHistoryError(history_dir.to_path_buf(), e))?
+    {
+        return Ok(Vec::new());
+    }
+
+    let mut revisions: Vec<(Revision, u64)> = options
+        .filesystem
+        .read_dir(history_dir)
+        .map_err(|e| ConfigError::ConfigHistoryError(history_dir.to_path_buf(), e))?
+        .into_iter()
+        .filter(|path| {
+            path.extension().and_then(|e| e.to_str()) == Some(options.format.extension())
+        })
+        .filter_map(|path| {
+            let stem = path.file_stem()?.to_str()?;
+            let (millis, sequence) = match stem.split_once('-') {
+                Some((millis, sequence)) => (millis.parse().ok()?, sequence.parse().ok()?),
+                None => (stem.parse().ok()?, 0),
+            };
+            let revision = Revision {
+                saved_at: UNIX_EPOCH + Duration::from_millis(millis),
+                path,
+            };
+            Some((revision, sequence))
+        })
+        .collect();
+    revisions.sort_by_key(|(revision, sequence)| Reverse((revision.saved_at, *sequence)));
+    Ok(revisions)
+}
+
+/// Store a snapshot of freshly saved config contents, then apply the retention policy.
+pub(crate) fn snapshot(
+    history_dir: &Path,
+    contents: &[u8],
+    options: &ConfigOptions,
+    retention: &HistoryOptions,
+) -> Result<(), ConfigError> {
+    let filesystem = &options.filesystem;
+    let history_error = |e| ConfigError::ConfigHistoryError(history_dir.to_path_buf(), e);
+
+    filesystem
+        .create_dir_all(history_dir, Some(0o700))
+        .map_err(history_error)?;
+    let now = options.clock.now();
+    let millis = now.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis());
+    // Number snapshots taken in the same millisecond after the last one, rather than filling
+    // gaps left by the retention policy, so they still sort in the order they were taken
+    let saved_at = UNIX_EPOCH + Duration::from_millis(millis as u64);
+    let sequence = list_numbered(history_dir, options)?
+        .into_iter()
+        .filter(|(revision, _)| revision.saved_at == saved_at)
+        .map(|(_, sequence)| sequence + 1)
+        .max();
+    let extension = options.format.extension();
+    let path = match sequence {
+        Some(sequence) => history_dir.join(format!("{}-{}.{}", millis, sequence, extension)),
+        None => history_dir.join(format!("{}.{}", millis, extension)),
+    };
+    filesystem
+        .write(&path, contents, Some(options.file_mode))
+        .map_err(|e| ConfigError::ConfigFileWriteError(path.clone(), e))?;
+
+    let revisions = list(history_dir, options)?;
+    for (i, revision) in revisions.iter().enumerate() {
+        let too_many = retention.max_revisions.is_some_and(|max| i >= max);
+        let too_old = retention.max_age.is_some_and(|max_age| {
+            now.duration_since(revision.saved_at)
+                .is_ok_and(|age| age > max_age)
+        });
+        if too_many || too_old {
+            filesystem
+                .remove_file(&revision.path)
+                .map_err(history_error)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        sync::Arc,
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    use super::HistoryOptions;
+    use crate::{Config, ConfigOptions, ManualClock};
+
+    #[test]
+    fn saves_at_the_same_time_keep_separate_revisions() {
+        let root = tempfile::tempdir().unwrap();
+        let options = ConfigOptions {
+            root: Some(root.path().to_path_buf()),
+            clock: Arc::new(ManualClock::new(UNIX_EPOCH + Duration::from_secs(1000))),
+            history: Some(HistoryOptions {
+                max_revisions: Some(3),
+                max_age: None,
+            }),
+            ..Default::default()
+        };
+        let mut config = Config::<u32>::load_with_options("counter", options).unwrap();
+        for n in 1..=5 {
+            *config.data_mut() = n;
+            config.save().unwrap();
+        }
+
+        let revisions = config.revisions().unwrap();
+        let contents: Vec<String> = revisions
+            .iter()
+            .map(|revision| fs::read_to_string(&revision.path).unwrap())
+            .collect();
+        // Newest first, with the oldest one dropped by the retention policy
+        assert_eq!(contents, ["5", "4", "3"]);
+        assert!(revisions
+            .iter()
+            .all(|revision| revision.saved_at == UNIX_EPOCH + Duration::from_secs(1000)));
+    }
+}
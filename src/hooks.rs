@@ -0,0 +1,119 @@
+//! Hooks for recording how long config operations take and how often they fail, for feeding
+//! into the app's own metrics.
+//!
+//! Like [`telemetry`](crate::telemetry), nothing is recorded by this crate itself; events only go
+//! to the [`Hooks`] implementation the app plugs into
+//! [`ConfigOptions::hooks`](crate::ConfigOptions::hooks).
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+use std::time::Instant;
+use std::{fmt::Debug, time::Duration};
+
+use crate::ConfigError;
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+use crate::{Clock, SystemClock};
+
+/// A config operation that [`Hooks`] are told about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Operation {
+    /// Loading a config, including parsing and validating it.
+    Load,
+    /// Saving a config with [`Config::save`](crate::Config::save) or
+    /// [`Config::save_overwrite`](crate::Config::save_overwrite).
+    Save,
+}
+
+/// Receiver for config operation events.  All methods do nothing by default, so implementations
+/// only need to override the ones they record.
+///
+/// ```
+/// # use std::sync::{atomic::{AtomicU64, Ordering}, Arc};
+/// use ilo_config::{
+///     hooks::{Hooks, Operation},
+///     ConfigError, ConfigOptions,
+/// };
+///
+/// #[derive(Debug, Default)]
+/// struct SaveFailures(AtomicU64);
+///
+/// impl Hooks for SaveFailures {
+///     fn on_error(&self, _config_file_key: &str, operation: Operation, _error: &ConfigError) {
+///         if operation == Operation::Save {
+///             self.0.fetch_add(1, Ordering::Relaxed);
+///         }
+///     }
+/// }
+///
+/// let options = ConfigOptions {
+///     hooks: Some(Arc::new(SaveFailures::default())),
+///     ..Default::default()
+/// };
+/// ```
+pub trait Hooks: Debug + Send + Sync {
+    /// Called after a config (identified by its key) was loaded, whether or not its file
+    /// existed.
+    fn on_load(&self, config_file_key: &str, elapsed: Duration) {
+        let _ = (config_file_key, elapsed);
+    }
+
+    /// Called after a config was saved, including saves that found the file unchanged.
+    fn on_save(&self, config_file_key: &str, elapsed: Duration) {
+        let _ = (config_file_key, elapsed);
+    }
+
+    /// Called instead of [`on_load`](Self::on_load) or [`on_save`](Self::on_save) when the
+    /// operation failed, with the error that is about to be returned.
+    fn on_error(&self, config_file_key: &str, operation: Operation, error: &ConfigError) {
+        let _ = (config_file_key, operation, error);
+    }
+}
+
+/// Run `f` as `operation` on the config `config_file_key`, telling `hooks` how it went.
+pub(crate) fn observe<T>(
+    hooks: Option<&dyn Hooks>,
+    config_file_key: &str,
+    operation: Operation,
+    f: impl FnOnce() -> Result<T, ConfigError>,
+) -> Result<T, ConfigError> {
+    let Some(hooks) = hooks else {
+        return f();
+    };
+    let started = Stopwatch::start();
+    let result = f();
+    match (&result, operation) {
+        (Ok(_), Operation::Load) => hooks.on_load(config_file_key, started.elapsed()),
+        (Ok(_), Operation::Save) => hooks.on_save(config_file_key, started.elapsed()),
+        (Err(e), _) => hooks.on_error(config_file_key, operation, e),
+    }
+    result
+}
+
+/// Measures how long an operation takes.  `Instant` panics in browsers, which only have the
+/// wall clock.
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+struct Stopwatch(Instant);
+
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+struct Stopwatch(std::time::SystemTime);
+
+impl Stopwatch {
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn start() -> Self {
+        Self(Instant::now())
+    }
+
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    fn start() -> Self {
+        Self(SystemClock.now())
+    }
+
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn elapsed(&self) -> Duration {
+        self.0.elapsed()
+    }
+
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    fn elapsed(&self) -> Duration {
+        SystemClock.now().duration_since(self.0).unwrap_or_default()
+    }
+}
@@ -0,0 +1,130 @@
+//! Composing config files with an `"$include"` directive, see
+//! [`ConfigOptions::includes`](crate::ConfigOptions::includes).
+//!
+//! ```json
+//! { "$include": ["team-base.json"], "editor": "vim" }
+//! ```
+//!
+//! Included files are read from the config root and deep-merged in order, followed by the
+//! including file itself (see [`layers::merge`]).  Included files may include further files.
+//! Names are relative to the config root and may not be absolute or contain `..`, so only files
+//! inside it can be included.
+use std::path::{Component, Path, PathBuf};
+
+use serde_json::{Map, Value};
+
+use crate::{layers, Config, ConfigError, ConfigOptions};
+
+/// Name of the directive member at the top level of a config file.
+pub(crate) const DIRECTIVE: &str = "$include";
+
+/// The includes of a loaded config file, so only its own overrides are written back on save.
+#[derive(Debug, Clone)]
+pub(crate) struct Included {
+    /// The directive as written in the file.
+    directive: Value,
+
+    /// The merged contents of all included files.
+    base: Value,
+}
+
+impl Included {
+    /// Turn a merged document back into the including file: drop everything that is the same as
+    /// in the included files and put the directive back.
+    pub(crate) fn restore(&self, document: &mut Value) {
+        layers::strip(document, &self.base);
+        if let Value::Object(members) = document {
+            let mut restored = Map::new();
+            restored.insert(DIRECTIVE.to_string(), self.directive.clone());
+            restored.append(members);
+            *members = restored;
+        }
+    }
+}
+
+/// Remove the directive from `document` and merge `document` over the files it includes.
+pub(crate) fn resolve(
+    document: &mut Value,
+    config_path: &Path,
+    options: &ConfigOptions,
+) -> Result<Option<Included>, ConfigError> {
+    resolve_chain(
+        document,
+        config_path,
+        options,
+        &mut vec![config_path.to_path_buf()],
+    )
+}
+
+fn resolve_chain(
+    document: &mut Value,
+    config_path: &Path,
+    options: &ConfigOptions,
+    chain: &mut Vec<PathBuf>,
+) -> Result<Option<Included>, ConfigError> {
+    let Some(directive) = document
+        .as_object_mut()
+        .and_then(|members| members.shift_remove(DIRECTIVE))
+    else {
+        return Ok(None);
+    };
+
+    let names = match &directive {
+        Value::String(name) => vec![name.as_str()],
+        Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+        _ => Vec::new(),
+    };
+    if names.is_empty() || directive.as_array().is_some_and(|a| a.len() != names.len()) {
+        return Err(include_error(
+            config_path,
+            "must be a file name or an array of file names",
+        ));
+    }
+
+    let config_root = Config::<Value>::get_config_root(options)?;
+    let mut base = Value::Object(Map::new());
+    for name in names {
+        // Only files from the config root can be included
+        let is_relative = !name.is_empty()
+            && Path::new(name)
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)));
+        if !is_relative {
+            return Err(include_error(
+                config_path,
+                &format!("`{}` is not a path inside the config root", name),
+            ));
+        }
+        let path = config_root.join(name);
+        if chain.contains(&path) {
+            return Err(include_error(
+                config_path,
+                &format!("{} is included in a cycle", path.display()),
+            ));
+        }
+        chain.push(path.clone());
+        let included = load(&path, options, chain)?;
+        chain.pop();
+        layers::merge(&mut base, included);
+    }
+
+    let mut merged = base.clone();
+    layers::merge(&mut merged, document.take());
+    *document = merged;
+    Ok(Some(Included { directive, base }))
+}
+
+/// Read an included file, along with the files it includes in turn.
+fn load(
+    path: &Path,
+    options: &ConfigOptions,
+    chain: &mut Vec<PathBuf>,
+) -> Result<Value, ConfigError> {
+    let mut document = layers::read_document(path, options)?;
+    resolve_chain(&mut document, path, options, chain)?;
+    Ok(document)
+}
+
+fn include_error(config_path: &Path, message: &str) -> ConfigError {
+    ConfigError::ConfigIncludeError(config_path.to_path_buf(), message.to_string())
+}
@@ -0,0 +1,120 @@
+//! Making sure only one copy of an app runs at a time.
+//!
+//! The running instance records its process ID in `<key>.pid` in the config root.  A lock file
+//! left behind by an instance that crashed is detected by checking whether its process is still
+//! alive, and taken over.
+use std::{
+    fmt::{self, Debug},
+    path::{Path, PathBuf},
+    process::{self, Command, Stdio},
+    sync::Arc,
+};
+
+use serde_json::Value;
+
+use crate::{Config, ConfigError, ConfigOptions, FileSystem};
+
+/// Proof that this is the only running instance, held until dropped.
+pub struct InstanceLock {
+    path: PathBuf,
+    filesystem: Arc<dyn FileSystem>,
+}
+
+impl Debug for InstanceLock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "InstanceLock({})", self.path.display())
+    }
+}
+
+impl InstanceLock {
+    /// Path of the lock file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for InstanceLock {
+    fn drop(&mut self) {
+        // Only remove the lock file if it is still ours
+        if read_pid(&self.filesystem, &self.path) == Some(process::id()) {
+            let _ = self.filesystem.remove_file(&self.path);
+        }
+    }
+}
+
+/// Make sure no other instance of the app is running, failing with
+/// `ConfigError::AlreadyRunningError` if one is.
+///
+/// A lock file that records the current process ID is treated as stale, since in containers an
+/// app restarted after a crash often gets the same PID again.  This means calling this twice in
+/// one process succeeds both times, and dropping either lock releases it.
+///
+/// ```no_run
+/// let _instance = ilo_config::single_instance("my-app")?;
+/// // ... run the app; the lock is released when `_instance` goes out of scope.
+/// # Ok::<(), ilo_config::ConfigError>(())
+/// ```
+pub fn single_instance(key: &str) -> Result<InstanceLock, ConfigError> {
+    single_instance_with_options(key, &ConfigOptions::default())
+}
+
+/// Like [`single_instance`], using the config root (and namespace) given by `options`.
+pub fn single_instance_with_options(
+    key: &str,
+    options: &ConfigOptions,
+) -> Result<InstanceLock, ConfigError> {
+    let config_root = Config::<Value>::ensure_config_root(options)?;
+    let path = config_root.join(format!("{}.pid", key));
+    let filesystem = &options.filesystem;
+
+    // Hold an exclusive lock while checking and claiming, so that two instances starting at the
+    // same time can't both take over a stale lock file
+    let guard_path = config_root.join(format!("{}.pid.lock", key));
+    let _guard = filesystem
+        .lock(&guard_path)
+        .map_err(|e| ConfigError::ConfigLockError(guard_path.clone(), e))?;
+    if let Some(pid) = read_pid(filesystem, &path) {
+        if pid != process::id() && is_alive(pid) {
+            return Err(ConfigError::AlreadyRunningError(path, pid));
+        }
+    }
+    filesystem
+        .write(&path, process::id().to_string().as_bytes(), Some(0o644))
+        .map_err(|e| ConfigError::ConfigFileWriteError(path.clone(), e))?;
+
+    Ok(InstanceLock {
+        path,
+        filesystem: Arc::clone(filesystem),
+    })
+}
+
+/// The process ID recorded in a lock file, if there is a valid one.
+pub(crate) fn read_pid(filesystem: &Arc<dyn FileSystem>, path: &Path) -> Option<u32> {
+    let contents = filesystem.read(path).ok()?;
+    String::from_utf8(contents).ok()?.trim().parse().ok()
+}
+
+/// Whether a process with the given ID is running.  If that can't be determined, the process is
+/// assumed to be alive, so that a running instance is never taken over.
+pub(crate) fn is_alive(pid: u32) -> bool {
+    if cfg!(target_os = "linux") {
+        return Path::new("/proc").join(pid.to_string()).exists();
+    }
+    let status = if cfg!(unix) {
+        Command::new("kill")
+            .args(["-0", &pid.to_string()])
+            .stderr(Stdio::null())
+            .status()
+    } else if cfg!(windows) {
+        // `tasklist` exits successfully either way, but only mentions the PID if it's running
+        return Command::new("tasklist")
+            .args(["/FI", &format!("PID eq {}", pid), "/NH"])
+            .output()
+            .map_or(true, |output| {
+                String::from_utf8_lossy(&output.stdout).contains(&pid.to_string())
+            });
+    } else {
+        return true;
+    };
+    status.map_or(true, |status| status.success())
+}
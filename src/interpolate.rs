@@ -0,0 +1,169 @@
+//! Interpolation of `${...}` references in string values, see
+//! [`ConfigOptions::interpolate`](crate::ConfigOptions::interpolate).
+//!
+//! `${name.path}` is replaced with the value at that dotted path in the same document, if there
+//! is one, and otherwise with the environment variable `name.path`.  `$${` produces a literal
+//! `${`.  Strings are resolved against the document as written in the file, and references
+//! inside referenced strings are resolved in turn.
+use std::{collections::BTreeMap, env};
+
+use serde_json::Value;
+
+/// Referenced strings may themselves contain references, up to this depth.
+const MAX_DEPTH: usize = 16;
+
+/// Strings that were changed by interpolation, so their raw form can be written back on save.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Interpolations {
+    /// Raw and resolved value, keyed by JSON Pointer.
+    strings: BTreeMap<String, (String, String)>,
+}
+
+impl Interpolations {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    pub(crate) fn insert(&mut self, pointer: String, raw: String, resolved: String) {
+        self.strings.insert(pointer, (raw, resolved));
+    }
+
+    /// Combine with the strings changed by a later step: a string that both changed keeps the
+    /// raw form from this one and the resolved form from the later one.
+    pub(crate) fn then(mut self, later: Interpolations) -> Self {
+        for (pointer, (raw, resolved)) in later.strings {
+            match self.strings.get_mut(&pointer) {
+                Some(entry) => entry.1 = resolved,
+                None => {
+                    self.strings.insert(pointer, (raw, resolved));
+                }
+            }
+        }
+        self
+    }
+
+    pub(crate) fn raw(&self, pointer: &str) -> Option<&str> {
+        self.strings.get(pointer).map(|(raw, _)| raw.as_str())
+    }
+
+    /// Put back the raw form of interpolated strings that haven't been changed since loading.
+    pub(crate) fn restore(&self, document: &mut Value) {
+        for (pointer, (raw, resolved)) in &self.strings {
+            if let Some(value) = document.pointer_mut(pointer) {
+                if value.as_str() == Some(resolved) {
+                    *value = Value::String(raw.clone());
+                }
+            }
+        }
+    }
+}
+
+/// Resolve the references in all strings of a document.  Returns an error message if a
+/// reference can't be resolved.
+pub(crate) fn interpolate(document: &mut Value) -> Result<Interpolations, String> {
+    let original = document.clone();
+    let mut interpolations = Interpolations::default();
+    visit(document, String::new(), &original, &mut interpolations)?;
+    Ok(interpolations)
+}
+
+fn visit(
+    value: &mut Value,
+    pointer: String,
+    original: &Value,
+    interpolations: &mut Interpolations,
+) -> Result<(), String> {
+    match value {
+        Value::String(raw) if raw.contains("${") => {
+            let resolved = resolve(raw, original, 0)
+                .map_err(|e| format!("{} (at {})", e, display_pointer(&pointer)))?;
+            if resolved != *raw {
+                interpolations
+                    .strings
+                    .insert(pointer, (raw.clone(), resolved.clone()));
+                *raw = resolved;
+            }
+        }
+        Value::Object(map) => {
+            for (key, child) in map.iter_mut() {
+                let escaped = key.replace('~', "~0").replace('/', "~1");
+                visit(
+                    child,
+                    format!("{}/{}", pointer, escaped),
+                    original,
+                    interpolations,
+                )?;
+            }
+        }
+        Value::Array(items) => {
+            for (i, child) in items.iter_mut().enumerate() {
+                visit(
+                    child,
+                    format!("{}/{}", pointer, i),
+                    original,
+                    interpolations,
+                )?;
+            }
+        }
+        _ => (),
+    }
+    Ok(())
+}
+
+fn resolve(raw: &str, document: &Value, depth: usize) -> Result<String, String> {
+    if depth > MAX_DEPTH {
+        return Err(String::from("references are nested too deeply or circular"));
+    }
+
+    let mut output = String::new();
+    let mut rest = raw;
+    while let Some(start) = rest.find('$') {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if let Some(after) = rest.strip_prefix("$${") {
+            output.push_str("${");
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("${") {
+            let end = after
+                .find('}')
+                .ok_or_else(|| format!("unterminated reference in {:?}", raw))?;
+            output.push_str(&lookup(&after[..end], document, depth)?);
+            rest = &after[end + 1..];
+        } else {
+            output.push('$');
+            rest = &rest[1..];
+        }
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+fn lookup(name: &str, document: &Value, depth: usize) -> Result<String, String> {
+    let pointer: String = name
+        .split('.')
+        .map(|segment| format!("/{}", segment.replace('~', "~0").replace('/', "~1")))
+        .collect();
+    match document.pointer(&pointer) {
+        Some(Value::String(value)) => resolve(value, document, depth + 1),
+        Some(Value::Number(value)) => Ok(value.to_string()),
+        Some(Value::Bool(value)) => Ok(value.to_string()),
+        Some(_) => Err(format!(
+            "`{}` does not refer to a string, number or boolean",
+            name
+        )),
+        None => env::var(name).map_err(|_| {
+            format!(
+                "`{}` is neither a config value nor an environment variable",
+                name
+            )
+        }),
+    }
+}
+
+fn display_pointer(pointer: &str) -> &str {
+    if pointer.is_empty() {
+        "/"
+    } else {
+        pointer
+    }
+}
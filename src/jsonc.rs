@@ -0,0 +1,361 @@
+//! Support for JSON with comments (JSONC), as used by e.g. VS Code settings files.
+//!
+//! Loading strips `//` and `/* */` comments as well as trailing commas before handing the document
+//! to serde_json.  The comments are remembered by location (the JSON Pointer of the member or
+//! element they precede) so they can be written back in the same place on save.
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+/// Comments collected from a JSONC document, keyed by JSON Pointer.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Comments {
+    /// Comments directly preceding a member or array element.  The root pointer (`""`) holds the
+    /// comments at the top of the file.
+    leading: HashMap<String, Vec<String>>,
+
+    /// Comments after the last member or element of an object or array, before it is closed.
+    trailing: HashMap<String, Vec<String>>,
+
+    /// Comments after the root value.
+    footer: Vec<String>,
+}
+
+/// Container that the scanner is currently inside of.
+struct Frame {
+    is_array: bool,
+    index: usize,
+    key: Option<String>,
+    expect_key: bool,
+}
+
+fn pointer(stack: &[Frame]) -> String {
+    stack
+        .iter()
+        .map(|frame| {
+            if frame.is_array {
+                format!("/{}", frame.index)
+            } else {
+                let key = frame.key.as_deref().unwrap_or_default();
+                format!("/{}", key.replace('~', "~0").replace('/', "~1"))
+            }
+        })
+        .collect()
+}
+
+/// Strip comments and trailing commas from a JSONC document.
+///
+/// Stripped characters are replaced with spaces (newlines are kept) so that line and column
+/// numbers in parse errors still match the original file.  Malformed input is passed through as
+/// far as possible and left for serde_json to report.
+pub(crate) fn strip(input: &str) -> (String, Comments) {
+    let mut output: Vec<u8> = input.as_bytes().to_vec();
+    let bytes = input.as_bytes();
+    let mut comments = Comments::default();
+    let mut pending: Vec<String> = Vec::new();
+    let mut stack: Vec<Frame> = Vec::new();
+    let mut pending_comma: Option<usize> = None;
+    let mut root_done = false;
+
+    let blank = |output: &mut Vec<u8>, range: std::ops::Range<usize>| {
+        for b in &mut output[range] {
+            if *b != b'\n' {
+                *b = b' ';
+            }
+        }
+    };
+
+    let mut i = 0;
+    while i < bytes.len() {
+        let b = bytes[i];
+        match b {
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                let end = input[i..].find('\n').map_or(bytes.len(), |n| i + n);
+                pending.push(input[i..end].trim_end().to_string());
+                blank(&mut output, i..end);
+                i = end;
+                continue;
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                let end = input[i + 2..].find("*/").map_or(bytes.len(), |n| i + n + 4);
+                pending.push(input[i..end].to_string());
+                blank(&mut output, i..end);
+                i = end;
+                continue;
+            }
+            b' ' | b'\t' | b'\r' | b'\n' => {
+                i += 1;
+                continue;
+            }
+            _ => (),
+        }
+
+        // Any other token: a trailing comma is only allowed if the container is being closed.
+        if let Some(comma) = pending_comma.take() {
+            if b == b'}' || b == b']' {
+                blank(&mut output, comma..comma + 1);
+            }
+        }
+
+        match b {
+            b'}' | b']' => {
+                if !pending.is_empty() {
+                    let container = &stack[..stack.len().saturating_sub(1)];
+                    comments
+                        .trailing
+                        .insert(pointer(container), std::mem::take(&mut pending));
+                }
+                stack.pop();
+                root_done = stack.is_empty();
+                i += 1;
+            }
+            b',' => {
+                if let Some(frame) = stack.last_mut() {
+                    if frame.is_array {
+                        frame.index += 1;
+                    } else {
+                        frame.expect_key = true;
+                    }
+                }
+                pending_comma = Some(i);
+                i += 1;
+            }
+            b':' => i += 1,
+            _ => {
+                let is_key = stack.last().is_some_and(|frame| frame.expect_key);
+                let end = if b == b'"' {
+                    string_end(bytes, i)
+                } else if b == b'{' || b == b'[' {
+                    i + 1
+                } else {
+                    scalar_end(bytes, i)
+                };
+
+                if is_key {
+                    let frame = stack.last_mut().expect("key outside of object");
+                    frame.key = serde_json::from_str(&input[i..end]).ok();
+                    frame.expect_key = false;
+                }
+                if !pending.is_empty() {
+                    let pending = std::mem::take(&mut pending);
+                    if root_done {
+                        comments.footer.extend(pending);
+                    } else if is_key || stack.last().is_none_or(|frame| frame.is_array) {
+                        comments.leading.insert(pointer(&stack), pending);
+                    }
+                }
+                if b == b'{' || b == b'[' {
+                    stack.push(Frame {
+                        is_array: b == b'[',
+                        index: 0,
+                        key: None,
+                        expect_key: b == b'{',
+                    });
+                } else if stack.is_empty() {
+                    root_done = true;
+                }
+                i = end;
+            }
+        }
+    }
+    comments.footer.extend(pending);
+
+    let output = String::from_utf8(output).expect("only ASCII bytes were replaced");
+    (output, comments)
+}
+
+/// Index just past the closing quote of the string starting at `start`.
+fn string_end(bytes: &[u8], start: usize) -> usize {
+    let mut i = start + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'"' => return i + 1,
+            _ => i += 1,
+        }
+    }
+    bytes.len()
+}
+
+/// Index just past the number or literal (`true`, `null`, ...) starting at `start`.
+fn scalar_end(bytes: &[u8], start: usize) -> usize {
+    let mut i = start + 1;
+    while i < bytes.len() && !b" \t\r\n,:]}/\"".contains(&bytes[i]) {
+        i += 1;
+    }
+    i
+}
+
+/// Pretty-print a document the same way serde_json does (with `indent` for each level),
+/// putting comments back where they were.
+pub(crate) fn to_string_pretty(document: &Value, comments: &Comments, indent: &str) -> String {
+    let mut output = String::new();
+    let writer = Writer { comments, indent };
+    writer.write_comments(&mut output, comments.leading.get(""), 0);
+    writer.write_value(&mut output, document, "", 0);
+    output.push('\n');
+    writer.write_comments(&mut output, Some(&comments.footer), 0);
+    output
+}
+
+/// Child of an object or array: its JSON Pointer, its key (for objects) and its value.
+type Member<'a> = (String, Option<&'a String>, &'a Value);
+
+struct Writer<'a> {
+    comments: &'a Comments,
+    indent: &'a str,
+}
+
+impl Writer<'_> {
+    fn write_comments(&self, output: &mut String, comments: Option<&Vec<String>>, depth: usize) {
+        for comment in comments.into_iter().flatten() {
+            output.push_str(&self.indent.repeat(depth));
+            output.push_str(comment);
+            output.push('\n');
+        }
+    }
+
+    fn write_value(&self, output: &mut String, value: &Value, pointer: &str, depth: usize) {
+        let comments = self.comments;
+        let indent = self.indent.repeat(depth + 1);
+        let (open, close, members): (char, char, Vec<Member>) = match value {
+            Value::Object(map) if !map.is_empty() || comments.trailing.contains_key(pointer) => (
+                '{',
+                '}',
+                map.iter()
+                    .map(|(k, v)| {
+                        let escaped = k.replace('~', "~0").replace('/', "~1");
+                        (format!("{}/{}", pointer, escaped), Some(k), v)
+                    })
+                    .collect(),
+            ),
+            Value::Array(items) if !items.is_empty() || comments.trailing.contains_key(pointer) => {
+                (
+                    '[',
+                    ']',
+                    items
+                        .iter()
+                        .enumerate()
+                        .map(|(i, v)| (format!("{}/{}", pointer, i), None, v))
+                        .collect(),
+                )
+            }
+            _ => {
+                output
+                    .push_str(&serde_json::to_string(value).expect("JSON values always serialize"));
+                return;
+            }
+        };
+
+        output.push(open);
+        output.push('\n');
+        let len = members.len();
+        for (i, (child_pointer, key, child)) in members.into_iter().enumerate() {
+            self.write_comments(output, comments.leading.get(&child_pointer), depth + 1);
+            output.push_str(&indent);
+            if let Some(key) = key {
+                output.push_str(&serde_json::to_string(key).expect("strings always serialize"));
+                output.push_str(": ");
+            }
+            self.write_value(output, child, &child_pointer, depth + 1);
+            if i + 1 < len {
+                output.push(',');
+            }
+            output.push('\n');
+        }
+        self.write_comments(output, comments.trailing.get(pointer), depth + 1);
+        output.push_str(&self.indent.repeat(depth));
+        output.push(close);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::{strip, to_string_pretty};
+
+    const SETTINGS: &str = r#"// Editor settings
+{
+  // Font size in points
+  "editor.fontSize": 14,
+  "files.exclude": {
+    /* Build output */
+    "target": true,
+    // Dependencies
+    "node_modules": true
+    // More patterns go here
+  },
+  "rulers": [
+    80,
+    // Rust
+    100
+  ]
+}
+// End of settings
+"#;
+
+    fn parse(input: &str) -> Value {
+        serde_json::from_str(&strip(input).0).unwrap()
+    }
+
+    #[test]
+    fn comments_and_trailing_commas_are_stripped() {
+        let input = "{\n  // comment\n  \"a\": [1, 2,], /* block */\n  \"b\": {\"c\": 3,},\n}\n";
+        assert_eq!(parse(input), json!({"a": [1, 2], "b": {"c": 3}}));
+    }
+
+    #[test]
+    fn stripping_keeps_line_and_column_numbers() {
+        let input = "{\n  /* a\n  multi-line comment */ \"a\": 1,\n}";
+        let (stripped, _) = strip(input);
+        assert_eq!(stripped.len(), input.len());
+        assert_eq!(stripped.lines().count(), input.lines().count());
+        assert_eq!(stripped.find("\"a\""), input.find("\"a\""));
+    }
+
+    #[test]
+    fn comment_markers_inside_strings_are_kept() {
+        let input = r#"{"url": "https://example.com/*", "path": "a//b"}"#;
+        assert_eq!(
+            parse(input),
+            json!({"url": "https://example.com/*", "path": "a//b"})
+        );
+    }
+
+    #[test]
+    fn comments_are_written_back_in_place() {
+        let (stripped, comments) = strip(SETTINGS);
+        let document: Value = serde_json::from_str(&stripped).unwrap();
+        assert_eq!(to_string_pretty(&document, &comments, "  "), SETTINGS);
+    }
+
+    #[test]
+    fn comments_survive_changes_to_the_document() {
+        let (stripped, comments) = strip(SETTINGS);
+        let mut document: Value = serde_json::from_str(&stripped).unwrap();
+        document["editor.fontSize"] = json!(16);
+        document["files.exclude"]["dist"] = json!(true);
+
+        let written = to_string_pretty(&document, &comments, "  ");
+        assert!(written.contains("  // Font size in points\n  \"editor.fontSize\": 16,\n"));
+        assert!(written.contains("    \"node_modules\": true,\n    \"dist\": true\n"));
+        assert!(written.contains("    // More patterns go here\n  },\n"));
+        let (_, rewritten) = strip(&written);
+        assert_eq!(to_string_pretty(&document, &rewritten, "  "), written);
+    }
+
+    #[test]
+    fn comments_of_removed_members_are_dropped() {
+        let (stripped, comments) = strip(SETTINGS);
+        let mut document: Value = serde_json::from_str(&stripped).unwrap();
+        document
+            .as_object_mut()
+            .unwrap()
+            .shift_remove("editor.fontSize");
+
+        let written = to_string_pretty(&document, &comments, "  ");
+        assert!(!written.contains("Font size"));
+        assert!(written.starts_with("// Editor settings\n{\n  \"files.exclude\""));
+    }
+}
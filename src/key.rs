@@ -0,0 +1,43 @@
+//! Typed config keys, so that loading a config doesn't need a stringly-typed key at every call
+//! site.
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{Config, ConfigError, ConfigOptions};
+
+/// A config data type that knows its own key and load options.
+///
+/// With the `derive` feature this can be derived with `#[derive(IloConfig)]`; see the macro for
+/// the available attributes.
+///
+/// ```no_run
+/// # use ilo_config::{Config, ConfigKey};
+/// # use serde::{Deserialize, Serialize};
+/// #[derive(Debug, Default, Serialize, Deserialize)]
+/// struct JiraConfig {
+///     url: Option<String>,
+/// }
+///
+/// impl ConfigKey for JiraConfig {
+///     const KEY: &'static str = "jira";
+/// }
+///
+/// let config: Config<JiraConfig> = JiraConfig::load().unwrap();
+/// ```
+pub trait ConfigKey: Serialize + DeserializeOwned + Default {
+    /// The config key, e.g. `jira` for ~/.config/ilo/jira.json.
+    const KEY: &'static str;
+
+    /// Options to load the config with.
+    fn options() -> ConfigOptions {
+        ConfigOptions::default()
+    }
+
+    /// The config data to start with when the config file doesn't exist yet.
+    fn default_data() -> Self {
+        Self::default()
+    }
+
+    fn load() -> Result<Config<Self>, ConfigError> {
+        Config::load_or_init_with_options(Self::KEY, Self::options(), Self::default_data)
+    }
+}
@@ -0,0 +1,255 @@
+//! Merging config documents on top of each other, for [`includes`](crate::include), drop-in
+//! directories (see [`ConfigOptions::drop_ins`](crate::ConfigOptions::drop_ins)), platform- and
+//! host-specific override files (see
+//! [`ConfigOptions::host_overrides`](crate::ConfigOptions::host_overrides)), `.env` files (see
+//! [`ConfigOptions::dotenv`](crate::ConfigOptions::dotenv)) and the system layer (see
+//! [`ConfigOptions::system_root`](crate::ConfigOptions::system_root)), and separating the config
+//! file's own values out again on save.
+use std::{
+    env,
+    ffi::OsString,
+    io,
+    path::{Path, PathBuf},
+};
+
+use serde_json::{Map, Value};
+
+use crate::{
+    diagnostics, jsonc, variables, Config, ConfigError, ConfigFormat, ConfigOptions, DotEnv,
+};
+
+/// Drop-in fragments or override files that were merged over a config file when it was loaded.
+#[derive(Debug, Clone)]
+pub(crate) struct Overlay {
+    /// The config file's own document, before the fragments were merged in.
+    own: Value,
+
+    /// The merged contents of all fragments.
+    overlay: Value,
+}
+
+impl Overlay {
+    /// Undo the overlay: values that still equal what a fragment set get the config file's own
+    /// value back (or are removed if the file didn't have one).  Changed values are kept, even
+    /// though a fragment will override them again on the next load.
+    pub(crate) fn restore(&self, document: &mut Value) {
+        unapply(document, &self.overlay, Some(&self.own));
+    }
+}
+
+/// A read-only system-wide config that was merged underneath a config file when it was loaded.
+#[derive(Debug, Clone)]
+pub(crate) struct SystemLayer {
+    base: Value,
+}
+
+impl SystemLayer {
+    /// Leave out the values that are the same as in the system layer, so that changes an admin
+    /// makes to the system-wide config later on aren't shadowed by stale copies.
+    pub(crate) fn restore(&self, document: &mut Value) {
+        strip(document, &self.base);
+    }
+}
+
+/// Merge `document` over the config with the same file name in `system_root` (and in the
+/// namespace, if set), if there is one.
+///
+/// If the config file doesn't exist and `document` holds the app's defaults instead, the system
+/// config is merged over the defaults, since admins should be able to override them.
+pub(crate) fn apply_system_layer(
+    document: &mut Value,
+    config_path: &Path,
+    system_root: &Path,
+    document_is_defaults: bool,
+    options: &ConfigOptions,
+) -> Result<Option<SystemLayer>, ConfigError> {
+    let mut path = system_root.to_path_buf();
+    if let Some(namespace) = &options.namespace {
+        path.push(namespace);
+    }
+    path.push(config_path.file_name().unwrap_or_default());
+    if !options.filesystem.is_file(&path) {
+        return Ok(None);
+    }
+
+    let system = read_document(&path, options)?;
+    let base = if document_is_defaults {
+        merge(document, system);
+        document.clone()
+    } else {
+        let mut merged = system.clone();
+        merge(&mut merged, document.take());
+        *document = merged;
+        system
+    };
+    Ok(Some(SystemLayer { base }))
+}
+
+/// Merge the `*.json` files (or `*.jsonc`, depending on the format) from the `<file>.d`
+/// directory next to the config file over `document`, in lexical order of their file names.
+pub(crate) fn apply_drop_ins(
+    document: &mut Value,
+    config_path: &Path,
+    options: &ConfigOptions,
+) -> Result<Option<Overlay>, ConfigError> {
+    let dir = Config::<Value>::sidecar_path(config_path, "d");
+    let mut fragments = match options.filesystem.read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(ConfigError::ConfigFileLoadError(dir, e)),
+    };
+    fragments.retain(|path| {
+        path.extension()
+            .is_some_and(|extension| extension == options.format.extension())
+            && options.filesystem.is_file(path)
+    });
+    if fragments.is_empty() {
+        return Ok(None);
+    }
+    fragments.sort();
+
+    let mut overlay = Value::Object(Map::new());
+    for path in &fragments {
+        merge(&mut overlay, read_document(path, options)?);
+    }
+    let own = document.clone();
+    merge(document, overlay.clone());
+    Ok(Some(Overlay { own, overlay }))
+}
+
+/// Merge the override files for this platform (`<key>.linux.json`, `<key>.macos.json` or
+/// `<key>.windows.json`) and this machine (`<key>.<hostname>.json`) next to the config file over
+/// `document`, in that order, if there are any.  The host name is shortened to its first label
+/// and lowercased, e.g. `laptop` for `Laptop.local`.
+pub(crate) fn apply_overrides(
+    document: &mut Value,
+    config_path: &Path,
+    options: &ConfigOptions,
+) -> Result<Option<Overlay>, ConfigError> {
+    let mut suffixes = Vec::new();
+    if options.platform_overrides {
+        suffixes.push(env::consts::OS.to_string());
+    }
+    if options.host_overrides {
+        if let Some(hostname) = variables::hostname() {
+            let hostname = hostname.split('.').next().unwrap_or_default();
+            suffixes.push(hostname.to_lowercase());
+        }
+    }
+
+    let mut overlay = None;
+    for suffix in suffixes {
+        let path = override_path(config_path, &suffix, options);
+        if options.filesystem.is_file(&path) {
+            let overrides = read_document(&path, options)?;
+            merge(overlay.get_or_insert(Value::Object(Map::new())), overrides);
+        }
+    }
+    let Some(overlay) = overlay else {
+        return Ok(None);
+    };
+    let own = document.clone();
+    merge(document, overlay.clone());
+    Ok(Some(Overlay { own, overlay }))
+}
+
+/// Merge the variables in the `.env` file that belong to the config over `document`, if there
+/// are any.
+pub(crate) fn apply_dotenv(
+    document: &mut Value,
+    config_path: &Path,
+    dotenv: &DotEnv,
+    options: &ConfigOptions,
+) -> Result<Option<Overlay>, ConfigError> {
+    let Some(overlay) = dotenv.document(config_path, options)? else {
+        return Ok(None);
+    };
+    let own = document.clone();
+    merge(document, overlay.clone());
+    Ok(Some(Overlay { own, overlay }))
+}
+
+/// `<key>.<suffix>.json` (or `.jsonc`) next to the config file `<key>.json`.
+fn override_path(config_path: &Path, suffix: &str, options: &ConfigOptions) -> PathBuf {
+    let mut file_name = OsString::from(config_path.file_stem().unwrap_or_default());
+    file_name.push(format!(".{}.{}", suffix, options.format.extension()));
+    config_path.with_file_name(file_name)
+}
+
+/// Read and parse a file that is merged into a config, e.g. an included file or a drop-in
+/// fragment.
+pub(crate) fn read_document(path: &Path, options: &ConfigOptions) -> Result<Value, ConfigError> {
+    let contents = options
+        .filesystem
+        .read(path)
+        .map_err(|e| ConfigError::ConfigFileLoadError(path.to_path_buf(), e))?;
+    #[cfg(feature = "encryption")]
+    let contents = crate::encryption::open_if_sealed(contents, path, options)?;
+    #[cfg(any(feature = "gzip", feature = "zstd"))]
+    let contents = crate::compression::decompress_if_compressed(contents, path)?;
+
+    let parse_error = |e| {
+        let error = ConfigError::ConfigFileParseError(PathBuf::from(path), e, None);
+        diagnostics::attach(error, &contents)
+    };
+    match options.format {
+        ConfigFormat::Json => serde_json::from_slice(&contents).map_err(parse_error),
+        ConfigFormat::Jsonc => {
+            let (stripped, _) = jsonc::strip(&String::from_utf8_lossy(&contents));
+            serde_json::from_str(&stripped).map_err(parse_error)
+        }
+    }
+}
+
+/// Deep-merge `overrides` into `base`: objects are merged member by member, any other value
+/// replaces the one it overrides.
+pub(crate) fn merge(base: &mut Value, overrides: Value) {
+    match (base, overrides) {
+        (Value::Object(base), Value::Object(overrides)) => {
+            for (key, value) in overrides {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overrides) => *base = overrides,
+    }
+}
+
+/// Remove the members of `document` that are the same in `base`.  Members that were removed
+/// from the merged document can't be expressed as overrides and come back on the next load.
+pub(crate) fn strip(document: &mut Value, base: &Value) {
+    let (Value::Object(members), Value::Object(base)) = (document, base) else {
+        return;
+    };
+    members.retain(|key, value| match base.get(key) {
+        Some(base_value) if base_value == value => false,
+        Some(base_value @ Value::Object(_)) if value.is_object() => {
+            strip(value, base_value);
+            value.as_object().is_some_and(|members| !members.is_empty())
+        }
+        _ => true,
+    });
+}
+
+fn unapply(document: &mut Value, overlay: &Value, own: Option<&Value>) {
+    let (Value::Object(members), Value::Object(overlay)) = (document, overlay) else {
+        return;
+    };
+    for (key, overlay_value) in overlay {
+        let own_value = own.and_then(|own| own.get(key));
+        match members.get_mut(key) {
+            Some(value) if value == overlay_value => match own_value {
+                Some(own_value) => *value = own_value.clone(),
+                None => {
+                    members.shift_remove(key);
+                }
+            },
+            Some(value) => unapply(value, overlay_value, own_value),
+            None => (),
+        }
+    }
+}
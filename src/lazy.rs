@@ -0,0 +1,96 @@
+//! Configs that are only loaded once they are used.
+use std::{
+    fmt::{self, Debug},
+    sync::OnceLock,
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{Config, ConfigError, ConfigOptions};
+
+/// A config that is loaded from disk the first time its data is accessed.
+///
+/// Useful for apps with many possible configs (e.g. one per plugin or subcommand), so that an
+/// invocation only pays for the configs it actually touches.  If loading fails, the error is
+/// returned and loading is tried again on the next access.
+pub struct LazyConfig<TConfigData: Serialize + DeserializeOwned + Default> {
+    config_file_key: String,
+    options: ConfigOptions,
+    config: OnceLock<Config<TConfigData>>,
+}
+
+impl<TConfigData: Serialize + DeserializeOwned + Default + Debug> Debug
+    for LazyConfig<TConfigData>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.config.get() {
+            Some(config) => config.fmt(f),
+            None => write!(f, "LazyConfig({}, not loaded)", self.config_file_key),
+        }
+    }
+}
+
+impl<TConfigData: Serialize + DeserializeOwned + Default> Config<TConfigData> {
+    /// Prepare to load a config, without doing any IO until its data is accessed.
+    pub fn lazy(config_file_key: &str) -> LazyConfig<TConfigData> {
+        Self::lazy_with_options(config_file_key, ConfigOptions::default())
+    }
+
+    /// Like [`lazy`](Self::lazy), customizing the behavior with `options`.
+    pub fn lazy_with_options(
+        config_file_key: &str,
+        options: ConfigOptions,
+    ) -> LazyConfig<TConfigData> {
+        LazyConfig {
+            config_file_key: config_file_key.to_string(),
+            options,
+            config: OnceLock::new(),
+        }
+    }
+}
+
+impl<TConfigData: Serialize + DeserializeOwned + Default> LazyConfig<TConfigData> {
+    /// The config, loading it if this is the first access.
+    pub fn config(&self) -> Result<&Config<TConfigData>, ConfigError> {
+        if let Some(config) = self.config.get() {
+            return Ok(config);
+        }
+        let config = Config::load_with_options(&self.config_file_key, self.options.clone())?;
+        // If another thread got there first, its config wins and ours is dropped.
+        Ok(self.config.get_or_init(|| config))
+    }
+
+    /// Mutable access to the config, loading it if this is the first access.
+    pub fn config_mut(&mut self) -> Result<&mut Config<TConfigData>, ConfigError> {
+        self.config()?;
+        Ok(self.config.get_mut().expect("config was just loaded"))
+    }
+
+    /// The config data, loading it if this is the first access.
+    pub fn data(&self) -> Result<&TConfigData, ConfigError> {
+        self.config().map(Config::data)
+    }
+
+    /// Mutable access to the config data, loading it if this is the first access.
+    pub fn data_mut(&mut self) -> Result<&mut TConfigData, ConfigError> {
+        self.config_mut().map(Config::data_mut)
+    }
+
+    /// Whether the config has been loaded yet.
+    pub fn is_loaded(&self) -> bool {
+        self.config.get().is_some()
+    }
+
+    #[inline]
+    pub fn key(&self) -> &str {
+        &self.config_file_key
+    }
+
+    /// Take the config out of the wrapper, loading it if it hasn't been accessed yet.
+    pub fn into_config(self) -> Result<Config<TConfigData>, ConfigError> {
+        match self.config.into_inner() {
+            Some(config) => Ok(config),
+            None => Config::load_with_options(&self.config_file_key, self.options),
+        }
+    }
+}
@@ -8,38 +8,296 @@
 //!
 //! # Features
 //!
-//! - Configs are stored in JSON format.
+//! - Configs are stored in JSON format, or optionally JSONC (JSON with comments).  Comments in
+//!   JSONC configs are kept intact when the config is saved.
 //! - Config files are created with user-only permissions (0600) in case they contain sensitive
-//!   data.
+//!   data, inside a config root that is only accessible to the user (0700).
+//! - Sensitive values can be typed as `Secret`, which is redacted from `Debug` output and wiped
+//!   from memory when dropped.
+//! - Cached values that go stale, such as access tokens, can be typed as `Expiring`, which stores
+//!   their expiry time and hides them once it has passed.  `Config::fresh_secret` refreshes
+//!   expired secrets through `ConfigOptions::secret_refresher` and saves the new value.
+//! - String values can refer to environment variables and other config values with `${...}`,
+//!   see `ConfigOptions::interpolate`.
+//! - Configs can pull in shared base configs with an `"$include"` directive, see
+//!   `ConfigOptions::includes`.
+//! - Provisioning tools can drop config fragments into a `<key>.json.d/` directory, which are
+//!   merged over the config without touching its file, see `ConfigOptions::drop_ins`.
+//! - Admins can set org-wide defaults in a read-only system layer (e.g. `/etc/ilo/`) that user
+//!   configs are merged over, see `ConfigOptions::system_root`.
+//! - A config root synced between machines can carry per-machine and per-OS differences in
+//!   `<key>.<hostname>.json` and `<key>.<platform>.json` files, see
+//!   `ConfigOptions::host_overrides` and `ConfigOptions::platform_overrides`.
+//! - The config root can be synced between machines through a remote (S3, WebDAV, SSH) with
+//!   per-key conflict detection, see the `sync` module.
+//! - When a config file has several invalid values, loading reports all of them with their
+//!   paths, not just the first.
+//! - Saved files can have sorted keys and custom indentation for clean diffs in version control,
+//!   see `ConfigOptions::formatting`.
+//! - Config data types can be declared as an enum of versions that is upgraded to the latest one
+//!   on load, see `Versioned`.
+//! - Durations (`"5m"`), byte sizes (`"10MB"`) and paths with `~` or `$VARS` in them can be
+//!   written the way people naturally write them, see the `fields` module.
+//! - Config data types don't need a `Default`: `Config::load_or_init_with` takes the initial
+//!   value for when the config file doesn't exist yet.
+//! - Saving fails instead of silently overwriting changes that another process made to the file
+//!   since it was loaded.
+//! - Large list and map configs used as data stores can be iterated and rewritten one element at
+//!   a time without loading the whole file, see `Config::stream` and `Config::rewrite`.
+//! - Configs that change constantly, e.g. in TUIs, can be saved in the background once changes
+//!   settle down instead of on every change, see `Config::autosave`.
+//! - Process-wide configs can be declared with `static_config!`, loaded on first use and saved
+//!   together with `flush_all`.
+//! - Configs dropped with changes that were never saved can be reported, see
+//!   `ConfigOptions::unsaved_changes`.
+//! - Configs can be read from environment variables alone, with no file at all, see
+//!   `backend::env::EnvBackend`.
+//! - Variables in a `.env` file can override config values the same way, so development setups
+//!   don't need to export them, see `ConfigOptions::dotenv`.
+//! - How long loads and saves take and how often they fail can be recorded in the app's own
+//!   metrics, see the `hooks` module.
+//! - Apps can make sure only one copy of them runs at a time with `single_instance`, which
+//!   takes over lock files left behind by crashed instances.
+//!
+//! # Optional Features
+//!
+//! - `schemars`: generate a JSON Schema for a config type with `Config::json_schema()` and write
+//!   it next to the config file with `Config::save_schema()`.
+//!   With it, the `setup` module can also ask the user for required values on first run.
+//! - `repl`: interactive prompt for inspecting and editing a loaded config while debugging, see
+//!   the `repl` module.
+//! - `testing`: a filesystem that can be scripted to fail, for testing how an application handles
+//!   config IO errors, see the `testing` module.
+//! - `http`: load configs from an HTTP(S) server, with an offline cache, see the `backend::http`
+//!   module.
+//! - `derive`: `#[derive(IloConfig)]` for giving config data types their own key and options,
+//!   so they can be loaded with `MyConfig::load()`.
+//! - `consul`: share configs between service instances through Consul's KV store, with the local
+//!   config root as a fallback cache, see the `backend::consul` module.
+//! - `encryption`: encrypt whole config files with a key derived from a passphrase (Argon2id +
+//!   AES-256-GCM), see `ConfigOptions::encryption`.
+//! - `miette`: render config parse errors as miette diagnostics, with the offending part of the
+//!   file highlighted.
+//! - `gzip`, `zstd`: compress config files that hold large amounts of data, see
+//!   `ConfigOptions::compression`.
+//! - `trash`: move deleted configs to the OS trash instead of removing them, see
+//!   `ConfigOptions::trash`.
+//! - `secrecy`: conversions between `Secret` and `secrecy::SecretString`.
+//! - `cli`: the `ilo-config` command-line tool for listing, showing, getting and setting values
+//!   in, validating and editing the configs in a config root.
+//! - `s3`: store configs in an S3-compatible bucket, see the `backend::s3` module.
+//! - `redis`: share short-lived configs between worker processes through Redis, with an optional
+//!   TTL, see the `backend::redis` module.
+//! - `kubernetes`: load configs from mounted ConfigMap and Secret volumes, or from the API server
+//!   with the pod's service account, see the `backend::kubernetes` module.
+//! - `aws`: resolve `ssm://<name>` and `aws-secrets://<name>` references to values in SSM
+//!   Parameter Store and Secrets Manager at load time, see the `resolve::aws` module.
+//! - `fast-json`: deserialize large config files (64 KiB and up) with simd-json, for apps that
+//!   keep multi-megabyte documents in a config and load them at startup.
+//! - `url`: a `fields::Url` type for URL fields, validated on load.
+//! - `archive`: export the whole config root to a `.tar.gz` archive and import it again, e.g. for
+//!   backups or moving to a new machine, see `ConfigRoot::export_archive`.
+//! - `vault`: resolve `vault://<path>#<key>` references to secrets in HashiCorp Vault at load
+//!   time, so they are never stored on disk, see the `resolve::vault` module.
+//! - `hot-reload`: reload a config in the background when its file changes, with lock-free
+//!   `Arc` snapshots of the current data for readers, see `Config::watch`.
+//! - `figment`: use config files as a layer in a figment configuration, optionally with one
+//!   profile per top-level key, see `ConfigProvider`.
+//! - `clap`: override config values with the arguments a clap command was given, and save them
+//!   with a `--save` flag, see `Config::merge_args`.
+//! - `signing`: sign config files with an HMAC and refuse to load files that were modified
+//!   without the key, see `ConfigOptions::signing`.
+//! - `tracing`: `tracing` spans for loading and saving configs, with events for the files that
+//!   are read and written (with their paths and sizes), migrated fields and invalid configs.
+//! - `audit-log`: append an entry with a timestamp and the file's SHA-256 to an audit log
+//!   whenever a config file is loaded, saved or deleted, see `ConfigOptions::audit_log`.
+//! - `local-storage`: keep configs in the browser's `localStorage` when the crate is built for
+//!   `wasm32-unknown-unknown`, e.g. for a web UI that shares config types with a CLI, see the
+//!   `backend::local_storage` module.  There is no config root in the browser, so configs need a
+//!   backend there.
 
 use std::{
     any,
     fmt::{self, Debug},
-    fs::{self, File, OpenOptions},
-    io::{self, BufReader, BufWriter},
-    os::unix::fs::OpenOptionsExt,
-    path::PathBuf,
+    io::{self, Read, Write},
+    mem,
+    path::{Path, PathBuf},
+    sync::{Mutex, PoisonError},
 };
 
 use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{Map, Value};
 use thiserror::Error as ThisError;
 
+use conflict::ConflictDocument;
+use include::Included;
+use interpolate::Interpolations;
+use layers::{Overlay, SystemLayer};
+use telemetry::Feature;
+use unsaved::UnsavedChanges;
+
+#[cfg(feature = "archive")]
+mod archive;
+#[cfg(feature = "clap")]
+mod args;
+pub mod audit;
+mod autosave;
+#[cfg(any(feature = "aws", feature = "s3"))]
+mod aws;
+pub mod backend;
+pub mod clock;
+mod collection;
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+mod compression;
+pub mod conflict;
+pub mod connection;
+mod crdt;
+mod delete;
+mod diagnostics;
+pub mod diff;
+mod doctor;
+mod dotenv;
+#[cfg(feature = "encryption")]
+mod encryption;
 mod environment;
+mod error;
+mod expiring;
+pub mod fields;
+#[cfg(feature = "figment")]
+mod figment_provider;
+pub mod filesystem;
+mod format;
+mod git;
+mod global;
+mod history;
+pub mod hooks;
+mod include;
+mod instance;
+mod interpolate;
+mod jsonc;
+mod key;
+mod layers;
+mod lazy;
+mod listing;
+#[cfg(feature = "hot-reload")]
+mod live;
+mod manager;
+mod merge;
+mod migrate;
+mod options;
+mod patch;
+mod pointer;
+pub mod protocol;
+mod quota;
+mod read_only;
+mod recents;
+mod recovery;
+mod refresh;
+mod renames;
+#[cfg(feature = "repl")]
+pub mod repl;
+pub mod resolve;
+mod retry;
+mod root;
+mod sample;
+mod secret;
+#[cfg(feature = "schemars")]
+pub mod setup;
+mod shared;
+#[cfg(feature = "signing")]
+mod signing;
+mod stream;
+pub mod sync;
+pub mod telemetry;
+#[cfg(feature = "schemars")]
+mod template;
+#[cfg(feature = "testing")]
+pub mod testing;
+mod unknown;
+mod unsaved;
+mod validation;
+mod variables;
+mod versioned;
+
+#[cfg(feature = "clap")]
+pub use args::save_arg;
+pub use clock::{Clock, ManualClock, SystemClock};
+pub use collection::Record;
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+pub use compression::Compression;
+pub use crdt::{LwwEntry, LwwSet};
+pub use delete::purge_root;
+pub use diagnostics::ParseDiagnostic;
+pub use doctor::{DoctorIssue, DoctorReport};
+pub use dotenv::DotEnv;
+#[cfg(feature = "encryption")]
+pub use encryption::{Encryption, PassphraseProvider, PassphrasePurpose, StaticPassphrase};
+pub use error::ErrorKind;
+pub use expiring::Expiring;
+#[cfg(feature = "figment")]
+pub use figment_provider::ConfigProvider;
+pub use filesystem::{FileSystem, StdFileSystem};
+pub use format::{ConfigFormat, Formatting, Indent};
+pub use git::GitCommit;
+pub use global::{flush_all, StaticConfig};
+pub use history::{HistoryOptions, Revision};
+#[cfg(feature = "derive")]
+pub use ilo_config_derive::IloConfig;
+pub use instance::{single_instance, single_instance_with_options, InstanceLock};
+pub use key::ConfigKey;
+pub use lazy::LazyConfig;
+pub use listing::list_keys;
+#[cfg(feature = "hot-reload")]
+pub use live::LiveConfig;
+pub use manager::ConfigManager;
+pub use migrate::MigrationReport;
+pub use options::{
+    ConfigOptions, PermissionCheck, PermissionHandler, RootFallback, SaveOptions, ROOT_ENV_VAR,
+    SYSTEM_ROOT,
+};
+pub use read_only::ReadOnlyConfig;
+pub use recents::{RecentEntry, Recents};
+pub use recovery::{CorruptFileHandler, CorruptFilePolicy};
+pub use refresh::{RefreshableSecret, SecretRefresher};
+pub use renames::{DeprecationHandler, RenamedFields};
+pub use retry::RetryPolicy;
+pub use root::ConfigRoot;
+pub use secret::Secret;
+pub use shared::{ConfigWriteGuard, SharedConfig, WeakConfig};
+#[cfg(feature = "signing")]
+pub use signing::{EnvSigningKey, Signing, SigningKeyProvider, StaticSigningKey};
+pub use stream::{Entries, MapEntries, Page};
+pub use unsaved::{UnsavedChangesHandler, UnsavedChangesPolicy};
+pub use validation::FieldError;
+pub use variables::TemplateVariables;
+pub use versioned::{Versioned, VersionedConfig};
 
 /// Generic struct for managing an app's chunk of config data on disk.
 ///
-/// Saves config files in $ILO_CONFIG_HOME, or ~/.config/ilo/ if the former is not set.
+/// Saves config files in $ILO_CONFIG_HOME, or ~/.config/ilo/ if the former is not set.  If neither
+/// is available, the [`ConfigOptions::root_fallbacks`] are tried in order.
 ///
 /// About the DeserializeOwned trait bound: see https://serde.rs/lifetimes.html.
 /// Since the struct itself is loading the data from a file, it's in command of its own deserializer
 /// lifetimes.
-pub struct Config<TConfigData: Serialize + DeserializeOwned + Default> {
+pub struct Config<TConfigData: Serialize + DeserializeOwned> {
     config_data: TConfigData,
     config_file_key: String, // e.g. `jira` for ~/.config/ilo/jira.json
+    options: ConfigOptions,
+    unknown_fields: Map<String, Value>, // Only populated with `options.preserve_unknown_fields`
+    comments: jsonc::Comments,          // Only populated for `ConfigFormat::Jsonc`
+    interpolations: Interpolations,     // Only populated with `options.interpolate`/`resolvers`
+    included: Option<Included>,         // Only populated with `options.includes`
+    overlay: Option<Overlay>,           // Only populated with `options.drop_ins`
+    overrides: Option<Overlay>,         // Only populated with `options.*_overrides`
+    dotenv: Option<Overlay>,            // Only populated with `options.dotenv`
+    system_layer: Option<SystemLayer>,  // Only populated with `options.system_root`
+    saved: Mutex<Option<Vec<u8>>>,      // File contents as of the last load or save, if it existed
+    unsaved: UnsavedChanges,            // Set by changes to the data, cleared by saving
 }
 
 // If the config_data type is Debug, also implement Debug for the Config wrapper.
-impl<TConfigData: Serialize + DeserializeOwned + Default + Debug> Debug for Config<TConfigData> {
+impl<TConfigData: Serialize + DeserializeOwned + Debug> Debug for Config<TConfigData> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
@@ -51,89 +309,1446 @@ impl<TConfigData: Serialize + DeserializeOwned + Default + Debug> Debug for Conf
     }
 }
 
-impl<TConfigData: Serialize + DeserializeOwned + Default> Config<TConfigData> {
+impl<TConfigData: Serialize + DeserializeOwned> Config<TConfigData> {
     /// Load a config based on a key.
     ///
     /// The file and directory creation is lazy, i.e. if the JSON file does not exist, a default
     /// config will be loaded and the file will not actually be created until there is a write.
-    pub fn load(config_file_key: &str) -> Result<Self, ConfigError> {
-        let config_path = Self::get_config_path(config_file_key)?;
-
-        let config_data = if config_path.is_file() {
-            let file = File::open(&config_path)
-                .map_err(|e| ConfigError::ConfigFileLoadError(config_path.clone(), e))?;
-            let reader = BufReader::new(file);
-            serde_json::from_reader(reader)
-                .map_err(|e| ConfigError::ConfigFileParseError(config_path, e))?
-        } else {
-            TConfigData::default()
+    pub fn load(config_file_key: &str) -> Result<Self, ConfigError>
+    where
+        TConfigData: Default,
+    {
+        Self::load_with_options(config_file_key, ConfigOptions::default())
+    }
+
+    /// Load a config based on a key, customizing the behavior with `options`.
+    pub fn load_with_options(
+        config_file_key: &str,
+        options: ConfigOptions,
+    ) -> Result<Self, ConfigError>
+    where
+        TConfigData: Default,
+    {
+        Self::load_or_init_with_options(config_file_key, options, TConfigData::default)
+    }
+
+    /// Like [`load`](Self::load), for config data types without a sensible `Default`, e.g.
+    /// because they must contain a server URL.  If the config file doesn't exist, `init` is
+    /// called for the config data instead.
+    ///
+    /// ```no_run
+    /// # use ilo_config::Config;
+    /// #[derive(serde::Serialize, serde::Deserialize)]
+    /// struct ServerConfig {
+    ///     url: String,
+    /// }
+    ///
+    /// let config: Config<ServerConfig> = Config::load_or_init_with("server", || ServerConfig {
+    ///     url: "https://example.com".to_string(),
+    /// })?;
+    /// # Ok::<(), ilo_config::ConfigError>(())
+    /// ```
+    pub fn load_or_init_with(
+        config_file_key: &str,
+        init: impl FnOnce() -> TConfigData,
+    ) -> Result<Self, ConfigError> {
+        Self::load_or_init_with_options(config_file_key, ConfigOptions::default(), init)
+    }
+
+    /// Like [`load_or_init_with`](Self::load_or_init_with), customizing the behavior with
+    /// `options`.
+    ///
+    /// `init` is not called when [`ConfigOptions::defaults`] provides the initial document.
+    pub fn load_or_init_with_options(
+        config_file_key: &str,
+        options: ConfigOptions,
+        init: impl FnOnce() -> TConfigData,
+    ) -> Result<Self, ConfigError> {
+        Self::load_or_else(config_file_key, options, |config_path, options| {
+            Self::load_defaults(config_path, options, init)
+        })
+    }
+
+    /// Load a config that must already exist, failing with `ConfigError::NotFound` instead of
+    /// falling back to defaults if the config file is missing, e.g. for credentials that would
+    /// otherwise only fail later with confusing authentication errors.
+    pub fn load_required(config_file_key: &str) -> Result<Self, ConfigError> {
+        Self::load_required_with_options(config_file_key, ConfigOptions::default())
+    }
+
+    /// Like [`load_required`](Self::load_required), customizing the behavior with `options`.
+    pub fn load_required_with_options(
+        config_file_key: &str,
+        options: ConfigOptions,
+    ) -> Result<Self, ConfigError> {
+        Self::load_or_else(config_file_key, options, |config_path, _| {
+            Err(ConfigError::NotFound(config_path.to_path_buf()))
+        })
+    }
+
+    /// Load a config, calling `missing` for the config to use if the config file doesn't exist
+    /// (or was quarantined).
+    fn load_or_else(
+        config_file_key: &str,
+        options: ConfigOptions,
+        missing: impl FnOnce(&Path, &ConfigOptions) -> Result<Parsed<TConfigData>, ConfigError>,
+    ) -> Result<Self, ConfigError> {
+        let hooks = options.hooks.clone();
+        hooks::observe(
+            hooks.as_deref(),
+            config_file_key,
+            hooks::Operation::Load,
+            || Self::load_file(config_file_key, options, missing),
+        )
+    }
+
+    fn load_file(
+        config_file_key: &str,
+        options: ConfigOptions,
+        missing: impl FnOnce(&Path, &ConfigOptions) -> Result<Parsed<TConfigData>, ConfigError>,
+    ) -> Result<Self, ConfigError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("load", key = %config_file_key).entered();
+        let (config_path, contents) = match &options.backend {
+            Some(backend) => (
+                PathBuf::from(backend.location(config_file_key)),
+                backend.load(config_file_key)?,
+            ),
+            None => {
+                let config_path = Self::get_config_path(config_file_key, &options)?;
+                if let Some(config_root) = config_path.parent() {
+                    protocol::check(config_root, &options)?;
+                }
+                let contents = if options.filesystem.is_file(&config_path) {
+                    Self::check_permissions(&config_path, &options)?;
+                    let contents = Self::read_config_file(&config_path, &options)?;
+                    #[cfg(feature = "signing")]
+                    if let Some(signing) = &options.signing {
+                        let signed = signing::Contents::Bytes(&contents);
+                        signing.verify(config_file_key, &config_path, signed, &options)?;
+                    }
+                    #[cfg(feature = "audit-log")]
+                    audit::log::record(
+                        config_file_key,
+                        audit::AuditOperation::Load,
+                        Some(&contents),
+                        &options,
+                    )?;
+                    Some(contents)
+                } else {
+                    None
+                };
+                (config_path, contents)
+            }
         };
 
-        Ok(Self {
-            config_data,
+        #[cfg(feature = "tracing")]
+        match &contents {
+            Some(contents) => {
+                tracing::debug!(path = %config_path.display(), bytes = contents.len(), "read config")
+            }
+            None => tracing::debug!(path = %config_path.display(), "config not found"),
+        }
+        let loaded = match contents {
+            Some(contents) => Self::parse_or_recover(contents, &config_path, &options)?,
+            None => None,
+        };
+        let (parsed, saved) = match loaded {
+            Some((parsed, saved)) => (parsed, Some(saved)),
+            None => (missing(&config_path, &options)?, None),
+        };
+
+        Ok(Self::from_parsed(config_file_key, options, parsed, saved))
+    }
+
+    /// Load a config from a reader instead of its file, e.g. from stdin for
+    /// `mytool --config - < config.json`.
+    ///
+    /// The contents are parsed and validated exactly as if they had been read from the config
+    /// file of `config_file_key`, which is where [`save`](Self::save) writes to.  Since that file
+    /// wasn't loaded, saving over an existing one counts as a conflict; use
+    /// [`save_overwrite`](Self::save_overwrite) or [`to_writer`](Self::to_writer) instead.
+    pub fn from_reader(config_file_key: &str, reader: impl Read) -> Result<Self, ConfigError> {
+        Self::from_reader_with_options(config_file_key, reader, ConfigOptions::default())
+    }
+
+    /// Like [`from_reader`](Self::from_reader), customizing the behavior with `options`.
+    pub fn from_reader_with_options(
+        config_file_key: &str,
+        mut reader: impl Read,
+        options: ConfigOptions,
+    ) -> Result<Self, ConfigError> {
+        let source = Path::new("<input>");
+        let mut contents = Vec::new();
+        reader
+            .read_to_end(&mut contents)
+            .map_err(|e| ConfigError::ConfigFileLoadError(source.to_path_buf(), e))?;
+        // Only needed to find the files layered over the config, which may not exist anyway
+        let config_path = Self::get_config_path(config_file_key, &options)
+            .unwrap_or_else(|_| source.to_path_buf());
+        let parsed = Self::parse_contents(contents, source, &config_path, &options)?;
+        Ok(Self::from_parsed(config_file_key, options, parsed, None))
+    }
+
+    fn from_parsed(
+        config_file_key: &str,
+        options: ConfigOptions,
+        parsed: Parsed<TConfigData>,
+        saved: Option<Vec<u8>>,
+    ) -> Self {
+        telemetry::report_options(config_file_key, &options);
+        let unsaved = UnsavedChanges::new(config_file_key, options.unsaved_changes.clone());
+        Self {
+            config_data: parsed.config_data,
             config_file_key: config_file_key.to_string(),
-        })
+            options,
+            unknown_fields: parsed.unknown_fields,
+            comments: parsed.comments,
+            interpolations: parsed.interpolations,
+            included: parsed.included,
+            overlay: parsed.overlay,
+            overrides: parsed.overrides,
+            dotenv: parsed.dotenv,
+            system_layer: parsed.system_layer,
+            saved: Mutex::new(saved),
+            unsaved,
+        }
     }
 
-    /// Flush config changes to disk.
-    pub fn save(&self) -> Result<(), ConfigError> {
-        // First check the directory
-        let config_root = Self::get_config_root()?;
-        match config_root.try_exists() {
-            Ok(true) => (),
-            Ok(false) => {
-                fs::create_dir_all(config_root.clone())
-                    .map_err(|e| ConfigError::ConfigRootCreateError(config_root, e))?;
+    /// Load the latest version of a config, apply `f` to it and save it, all while holding an
+    /// exclusive lock.
+    ///
+    /// Concurrent processes that go through `update` for the same key wait for each other, so
+    /// read-modify-write cycles like incrementing a counter or appending to a list don't lose each
+    /// other's writes.  The lock is held in a `<key>.lock` file next to the config.
+    pub fn update<R>(
+        config_file_key: &str,
+        f: impl FnOnce(&mut TConfigData) -> R,
+    ) -> Result<R, ConfigError>
+    where
+        TConfigData: Default,
+    {
+        Self::update_with_options(config_file_key, ConfigOptions::default(), f)
+    }
+
+    /// Like [`update`](Self::update), customizing the behavior with `options`.
+    pub fn update_with_options<R>(
+        config_file_key: &str,
+        options: ConfigOptions,
+        f: impl FnOnce(&mut TConfigData) -> R,
+    ) -> Result<R, ConfigError>
+    where
+        TConfigData: Default,
+    {
+        let lock_path =
+            Self::ensure_config_root(&options)?.join(format!("{}.lock", config_file_key));
+        let _lock = options
+            .filesystem
+            .lock(&lock_path)
+            .map_err(|e| ConfigError::ConfigLockError(lock_path.clone(), e))?;
+
+        let mut config = Self::load_with_options(config_file_key, options)?;
+        config.report(Feature::LockedUpdate);
+        let result = f(config.data_mut());
+        config.save()?;
+        Ok(result)
+    }
+
+    /// Move the config stored under `old_key` to `new_key` (e.g. after the app was renamed) and
+    /// load it under the new key.
+    ///
+    /// Its backup, conflict and drop-in files, history, schema and audit log move along with it.
+    /// Each file is moved with an atomic rename, the config file itself last.  Fails with
+    /// `ConfigError::NotFound` if there is no config under `old_key`, and without changing
+    /// anything if there already is one under `new_key`.  Not supported with a
+    /// [`backend`](ConfigOptions::backend).
+    pub fn rename_key(old_key: &str, new_key: &str) -> Result<Self, ConfigError> {
+        Self::rename_key_with_options(old_key, new_key, ConfigOptions::default())
+    }
+
+    /// Like [`rename_key`](Self::rename_key), customizing the behavior with `options`.
+    pub fn rename_key_with_options(
+        old_key: &str,
+        new_key: &str,
+        options: ConfigOptions,
+    ) -> Result<Self, ConfigError> {
+        if let Some(backend) = &options.backend {
+            return Err(ConfigError::ConfigBackendError(
+                backend.location(old_key),
+                "configs stored in a backend can't be renamed".into(),
+            ));
+        }
+        let config_root = Self::ensure_config_root(&options)?;
+        let lock_path = config_root.join(format!("{}.lock", old_key));
+        let filesystem = &options.filesystem;
+        let _lock = filesystem
+            .lock(&lock_path)
+            .map_err(|e| ConfigError::ConfigLockError(lock_path.clone(), e))?;
+
+        let old_path = Self::get_config_path(old_key, &options)?;
+        let new_path = Self::get_config_path(new_key, &options)?;
+        if !filesystem.is_file(&old_path) {
+            return Err(ConfigError::NotFound(old_path));
+        }
+        let exists = |path: &Path| {
+            filesystem
+                .try_exists(path)
+                .map_err(|e| ConfigError::ConfigFileWriteError(path.to_path_buf(), e))
+        };
+        if exists(&new_path)? {
+            return Err(ConfigError::ConfigFileWriteError(new_path, key_taken()));
+        }
+
+        // Signatures are bound to the key, so the file is signed again under its new one
+        #[cfg(feature = "signing")]
+        let resigned = match &options.signing {
+            Some(signing) => {
+                let signed = signing::Contents::File(&old_path);
+                signing.verify(old_key, &old_path, signed, &options)?;
+                signing.sign(new_key, &new_path, signed, &options)?;
+                true
             }
-            Err(e) => {
-                return Err(ConfigError::ConfigRootLoadError(config_root, e));
+            None => false,
+        };
+        #[cfg(not(feature = "signing"))]
+        let resigned = false;
+        let old_signature_path = Self::sidecar_path(&old_path, "sig");
+
+        let mut moves: Vec<(PathBuf, PathBuf)> = ["bak", "conflict", "d", "audit.log", "sig"]
+            .iter()
+            .filter(|extension| !(resigned && **extension == "sig"))
+            .map(|extension| {
+                (
+                    Self::sidecar_path(&old_path, extension),
+                    Self::sidecar_path(&new_path, extension),
+                )
+            })
+            .collect();
+        moves.push((
+            history::history_dir(&config_root, old_key),
+            history::history_dir(&config_root, new_key),
+        ));
+        moves.push((
+            config_root.join(format!("{}.schema.json", old_key)),
+            config_root.join(format!("{}.schema.json", new_key)),
+        ));
+        moves.push((old_path, new_path));
+        for (from, to) in moves {
+            if exists(&from)? {
+                filesystem
+                    .rename(&from, &to)
+                    .map_err(|e| ConfigError::ConfigFileWriteError(to.clone(), e))?;
             }
         }
+        if resigned && exists(&old_signature_path)? {
+            filesystem
+                .remove_file(&old_signature_path)
+                .map_err(|e| ConfigError::ConfigFileWriteError(old_signature_path, e))?;
+        }
+
+        Self::load_required_with_options(new_key, options)
+    }
+
+    /// Save a copy of the config under `new_key` and load the copy, e.g. to duplicate a profile
+    /// or to keep a known-good version before experimenting.
+    ///
+    /// The copy holds the current data, including changes that haven't been saved yet.  Fails
+    /// without writing anything if there already is a config under `new_key`.
+    pub fn copy_to(&self, new_key: &str) -> Result<Self, ConfigError> {
+        let bytes = self.serialize()?;
+        match &self.options.backend {
+            Some(backend) => {
+                let location = backend.location(new_key);
+                if backend.load(new_key)?.is_some() {
+                    return Err(ConfigError::ConfigBackendError(
+                        location,
+                        key_taken().into(),
+                    ));
+                }
+                let bytes = self.encode(Path::new(&location), bytes)?;
+                backend.save(new_key, &bytes)?;
+            }
+            None => {
+                Self::ensure_config_root(&self.options)?;
+                let new_path = Self::get_config_path(new_key, &self.options)?;
+                let filesystem = &self.options.filesystem;
+                match filesystem.try_exists(&new_path) {
+                    Ok(false) => {}
+                    Ok(true) => {
+                        return Err(ConfigError::ConfigFileWriteError(new_path, key_taken()));
+                    }
+                    Err(e) => return Err(ConfigError::ConfigFileWriteError(new_path, e)),
+                }
+                let bytes = self.encode(&new_path, bytes)?;
+                if let Some(quota) = self.options.quota {
+                    self.check_quota(&new_path, bytes.len() as u64, quota)?;
+                }
+                #[cfg(feature = "signing")]
+                if let Some(signing) = &self.options.signing {
+                    let signed = signing::Contents::Bytes(&bytes);
+                    signing.sign(new_key, &new_path, signed, &self.options)?;
+                }
+                RetryPolicy::run(
+                    self.options.retry.as_ref(),
+                    &new_path,
+                    || filesystem.write(&new_path, &bytes, Some(self.options.file_mode)),
+                    ConfigError::ConfigFileWriteError,
+                )?;
+                #[cfg(feature = "audit-log")]
+                audit::log::record(
+                    new_key,
+                    audit::AuditOperation::Save,
+                    Some(&bytes),
+                    &self.options,
+                )?;
+            }
+        }
+        Self::load_required_with_options(new_key, self.options.clone())
+    }
+
+    /// Flush config changes to disk.
+    ///
+    /// Fails with `ConfigError::ConflictError` if the file was changed by someone else since it
+    /// was loaded (or last saved), instead of silently discarding their changes.  Use
+    /// [`save_overwrite`](Self::save_overwrite) to replace the file anyway,
+    /// [`save_merged`](Self::save_merged) to merge with their changes, or
+    /// [`update`](Self::update) to avoid the conflict in the first place.
+    pub fn save(&self) -> Result<(), ConfigError> {
+        self.write_to_disk(true).map(|()| self.unsaved.clear())
+    }
+
+    /// Flush config changes to disk, overwriting any changes made to the file since it was loaded.
+    pub fn save_overwrite(&self) -> Result<(), ConfigError> {
+        self.write_to_disk(false).map(|()| self.unsaved.clear())
+    }
+
+    fn write_to_disk(&self, check_conflicts: bool) -> Result<(), ConfigError> {
+        hooks::observe(
+            self.options.hooks.as_deref(),
+            &self.config_file_key,
+            hooks::Operation::Save,
+            || self.write_file(check_conflicts),
+        )
+    }
+
+    fn write_file(&self, check_conflicts: bool) -> Result<(), ConfigError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("save", key = %self.config_file_key).entered();
+        let bytes = self.serialize()?;
+        if let Some(backend) = &self.options.backend {
+            let location = PathBuf::from(backend.location(&self.config_file_key));
+            let bytes = self.encode(&location, bytes)?;
+            #[cfg(feature = "tracing")]
+            tracing::debug!(path = %location.display(), bytes = bytes.len(), "writing config");
+            return backend.save(&self.config_file_key, &bytes);
+        }
 
-        let config_path = Self::get_config_path(&self.config_file_key)?;
-        match config_path.try_exists() {
+        // First check the directory
+        Self::ensure_config_root(&self.options)?;
+
+        let filesystem = &self.options.filesystem;
+        let config_path = Self::get_config_path(&self.config_file_key, &self.options)?;
+        let bytes = self.encode(&config_path, bytes)?;
+        if let Some(quota) = self.options.quota {
+            self.check_quota(&config_path, bytes.len() as u64, quota)?;
+        }
+        match filesystem.try_exists(&config_path) {
             Ok(exists) => {
-                let mut options = OpenOptions::new();
-                options.create(true).write(true).truncate(true);
-
-                // If file needs to be created and we are on UNIX, set permissions to user-only
-                #[cfg(unix)]
-                {
-                    if !exists {
-                        options.mode(0o600);
+                let mut saved = self.saved.lock().unwrap_or_else(PoisonError::into_inner);
+                let current = if exists && (check_conflicts || !self.options.always_write) {
+                    Some(Self::read_config_file(&config_path, &self.options)?)
+                } else {
+                    None
+                };
+                if check_conflicts && current != *saved {
+                    // Conflict files are plaintext, so they're not written for encrypted or
+                    // compressed configs
+                    if self.options.conflict_markers
+                        && !self.is_encrypted()
+                        && !self.is_compressed()
+                    {
+                        self.write_conflict_file(&config_path, &bytes)?;
                     }
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(path = %config_path.display(), "config was changed on disk");
+                    return Err(ConfigError::ConflictError(config_path));
                 }
+                // Leave the file (and its mtime) alone if nothing changed
+                if !self.options.always_write && current.as_ref() == Some(&bytes) {
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(path = %config_path.display(), "config unchanged");
+                    // Files loaded without a signature get one even if nothing changed
+                    #[cfg(feature = "signing")]
+                    if let Some(signing) = &self.options.signing {
+                        let signed = signing::Contents::Bytes(&bytes);
+                        signing.ensure_signed(
+                            &self.config_file_key,
+                            &config_path,
+                            signed,
+                            &self.options,
+                        )?;
+                    }
+                    *saved = current;
+                    return Ok(());
+                }
+
+                #[cfg(feature = "tracing")]
+                tracing::debug!(path = %config_path.display(), bytes = bytes.len(), "writing config");
 
-                match options.open(config_path.clone()) {
-                    Ok(f) => {
-                        let writer = BufWriter::new(f);
-                        serde_json::to_writer_pretty(writer, &self.config_data)
-                            .map_err(ConfigError::ConfigFileSerializeError)
+                let backup_path = match (self.options.verify_writes, exists) {
+                    (true, true) => {
+                        let backup_path = Self::sidecar_path(&config_path, "bak");
+                        filesystem.copy(&config_path, &backup_path).map_err(|e| {
+                            ConfigError::ConfigFileWriteError(backup_path.clone(), e)
+                        })?;
+                        Some(backup_path)
                     }
-                    Err(e) => Err(ConfigError::ConfigFileWriteError(config_path, e)),
+                    _ => None,
+                };
+
+                // The signature goes first, and keeps the old one in case this write fails
+                #[cfg(feature = "signing")]
+                let signed = match &self.options.signing {
+                    Some(signing) => {
+                        let contents = signing::Contents::Bytes(&bytes);
+                        let counter = signing.sign(
+                            &self.config_file_key,
+                            &config_path,
+                            contents,
+                            &self.options,
+                        )?;
+                        Some((signing, counter))
+                    }
+                    None => None,
+                };
+
+                // If file needs to be created, set permissions (user-only by default)
+                let mode = (!exists).then_some(self.options.file_mode);
+                RetryPolicy::run(
+                    self.options.retry.as_ref(),
+                    &config_path,
+                    || {
+                        if self.options.save.fsync {
+                            filesystem.write_synced(&config_path, &bytes, mode)
+                        } else {
+                            filesystem.write(&config_path, &bytes, mode)
+                        }
+                    },
+                    ConfigError::ConfigFileWriteError,
+                )?;
+                #[cfg(feature = "signing")]
+                if let Some((signing, counter)) = signed {
+                    signing.written(&config_path, counter);
+                }
+                *saved = Some(bytes.clone());
+                #[cfg(feature = "audit-log")]
+                audit::log::record(
+                    &self.config_file_key,
+                    audit::AuditOperation::Save,
+                    Some(&bytes),
+                    &self.options,
+                )?;
+
+                if self.options.verify_writes {
+                    self.verify(&config_path, backup_path)?;
+                }
+                if let Some(retention) = &self.options.history {
+                    let history_dir = self.history_dir()?;
+                    history::snapshot(&history_dir, &bytes, &self.options, retention)?;
                 }
+                if self.options.git {
+                    let config_root = Self::get_config_root(&self.options)?;
+                    git::commit(&config_root, &config_path, &self.config_file_key)?;
+                }
+                Ok(())
             }
             Err(e) => Err(ConfigError::ConfigFileWriteError(config_path, e)),
         }
     }
 
+    /// Flush a written file and the directory entry that points to it to stable storage.
+    fn sync_to_disk(config_path: &Path, options: &ConfigOptions) -> Result<(), ConfigError> {
+        let sync = |path: &Path| {
+            options
+                .filesystem
+                .sync(path)
+                .map_err(|e| ConfigError::ConfigFileWriteError(path.to_path_buf(), e))
+        };
+        sync(config_path)?;
+        match config_path.parent() {
+            Some(parent) => sync(parent),
+            None => Ok(()),
+        }
+    }
+
+    /// Apply [`ConfigOptions::permission_check`] to an existing config file.
+    fn check_permissions(config_path: &Path, options: &ConfigOptions) -> Result<(), ConfigError> {
+        if let PermissionCheck::Off = options.permission_check {
+            return Ok(());
+        }
+        let mode = options
+            .filesystem
+            .permissions(config_path)
+            .map_err(|e| ConfigError::ConfigFileLoadError(config_path.to_path_buf(), e))?;
+        match mode {
+            Some(mode) if mode & 0o077 != 0 => match &options.permission_check {
+                PermissionCheck::Deny => Err(ConfigError::InsecurePermissionsError(
+                    config_path.to_path_buf(),
+                    mode,
+                )),
+                PermissionCheck::Report(handler) => {
+                    handler.insecure_permissions(config_path, mode);
+                    Ok(())
+                }
+                PermissionCheck::Off | PermissionCheck::Warn => {
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(
+                        path = %config_path.display(),
+                        mode = %format_args!("{:o}", mode),
+                        "config file is accessible by other users"
+                    );
+                    #[cfg(not(feature = "tracing"))]
+                    eprintln!(
+                        "WARNING: config file {} is accessible by other users (permissions {:o})",
+                        config_path.display(),
+                        mode
+                    );
+                    Ok(())
+                }
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// Remove group and world access from the config file and the config root, if they exist.
+    pub fn fix_permissions(&self) -> Result<(), ConfigError> {
+        let filesystem = &self.options.filesystem;
+        let config_root = Self::get_config_root(&self.options)?;
+        let config_path = Self::get_config_path(&self.config_file_key, &self.options)?;
+        for (path, is_file) in [(config_root, false), (config_path, true)] {
+            let exists = if is_file {
+                filesystem.is_file(&path)
+            } else {
+                filesystem
+                    .try_exists(&path)
+                    .map_err(|e| ConfigError::ConfigRootLoadError(path.clone(), e))?
+            };
+            if !exists {
+                continue;
+            }
+            let mode = filesystem
+                .permissions(&path)
+                .map_err(|e| ConfigError::ConfigFileLoadError(path.clone(), e))?;
+            if let Some(mode) = mode.filter(|mode| mode & 0o077 != 0) {
+                filesystem
+                    .set_permissions(&path, mode & 0o700)
+                    .map_err(|e| ConfigError::ConfigFileWriteError(path.clone(), e))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Fail if writing `new_len` bytes to the config file would take the config root over its
+    /// quota.
+    fn check_quota(&self, config_path: &Path, new_len: u64, quota: u64) -> Result<(), ConfigError> {
+        let filesystem = &self.options.filesystem;
+        let current_len = if filesystem.is_file(config_path) {
+            filesystem
+                .len(config_path)
+                .map_err(|e| ConfigError::ConfigFileLoadError(config_path.to_path_buf(), e))?
+        } else {
+            0
+        };
+        // A history snapshot takes up as much space again
+        let new_len = match self.options.history {
+            Some(_) => new_len * 2,
+            None => new_len,
+        };
+
+        let projected = self.usage()?.saturating_sub(current_len) + new_len;
+        if projected > quota {
+            return Err(ConfigError::QuotaExceededError(
+                Self::get_config_root(&self.options)?,
+                projected,
+                quota,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Total size in bytes of all files in the config root (or the namespace, if set) and the
+    /// caches in the state directory, which is what counts towards [`ConfigOptions::quota`].
+    /// Symbolic links are skipped.
+    pub fn usage(&self) -> Result<u64, ConfigError> {
+        quota::usage(&Self::get_config_root(&self.options)?, &self.options)
+    }
+
+    /// Re-read a freshly written config file and check that it matches the in-memory data.
+    ///
+    /// On success the backup of the previous version is removed; on mismatch it is kept and an
+    /// error pointing to it is returned.
+    fn verify(&self, config_path: &Path, backup_path: Option<PathBuf>) -> Result<(), ConfigError> {
+        let expected = serde_json::to_value(&self.config_data).ok();
+        let actual = Self::read_config_file(config_path, &self.options)
+            .and_then(|contents| Self::parse_config_file(contents, config_path, &self.options))
+            .ok()
+            .and_then(|parsed| serde_json::to_value(parsed.config_data).ok());
+
+        if expected.is_none() || expected != actual {
+            return Err(ConfigError::ConfigFileVerifyError(
+                config_path.to_path_buf(),
+                backup_path,
+            ));
+        }
+        if let Some(backup_path) = backup_path {
+            self.options
+                .filesystem
+                .remove_file(&backup_path)
+                .map_err(|e| ConfigError::ConfigFileWriteError(backup_path, e))?;
+        }
+        Ok(())
+    }
+
+    /// Path of a file that sits next to the config file, e.g. `jira.json.bak` for the backup of
+    /// the previous version kept while a save is verified.
+    fn sidecar_path(config_path: &Path, extension: &str) -> PathBuf {
+        let mut file_name = config_path.file_name().unwrap_or_default().to_os_string();
+        file_name.push(".");
+        file_name.push(extension);
+        config_path.with_file_name(file_name)
+    }
+
+    /// Write both our version and the version on disk into a conflict file with git-style
+    /// markers, for resolving by hand.
+    fn write_conflict_file(&self, config_path: &Path, ours: &[u8]) -> Result<(), ConfigError> {
+        let theirs = Self::read_config_file(config_path, &self.options)?;
+        let document = ConflictDocument::new(
+            &String::from_utf8_lossy(ours),
+            &String::from_utf8_lossy(&theirs),
+        );
+        let conflict_path = Self::sidecar_path(config_path, "conflict");
+        self.options
+            .filesystem
+            .write(
+                &conflict_path,
+                document.to_marked_string().as_bytes(),
+                Some(self.options.file_mode),
+            )
+            .map_err(|e| ConfigError::ConfigFileWriteError(conflict_path, e))
+    }
+
+    /// Path of the conflict file written when a save conflicts and
+    /// [`ConfigOptions::conflict_markers`] is set, e.g. `jira.json.conflict`.
+    pub fn conflict_path(&self) -> Result<PathBuf, ConfigError> {
+        Self::get_config_path(&self.config_file_key, &self.options)
+            .map(|config_path| Self::sidecar_path(&config_path, "conflict"))
+    }
+
+    /// Re-ingest a conflict file that was resolved by hand.
+    ///
+    /// If there is no conflict file, returns `Ok(false)`.  If it still contains conflict markers,
+    /// fails with `ConfigError::UnresolvedConflictError`.  Otherwise the resolved version replaces
+    /// both the config data and the file on disk, the conflict file is removed and `Ok(true)` is
+    /// returned.
+    pub fn resolve_conflict(&mut self) -> Result<bool, ConfigError> {
+        let conflict_path = self.conflict_path()?;
+        if !self.options.filesystem.is_file(&conflict_path) {
+            return Ok(false);
+        }
+
+        let contents = Self::read_config_file(&conflict_path, &self.options)?;
+        if ConflictDocument::has_markers(&String::from_utf8_lossy(&contents)) {
+            return Err(ConfigError::UnresolvedConflictError(conflict_path));
+        }
+        self.report(Feature::ConflictResolution);
+        self.read_from(&conflict_path)?;
+        self.save_overwrite()?;
+
+        self.options
+            .filesystem
+            .remove_file(&conflict_path)
+            .map_err(|e| ConfigError::ConfigFileWriteError(conflict_path, e))?;
+        Ok(true)
+    }
+
+    /// Write the config to an arbitrary path, e.g. to back it up or share it with a teammate.
+    ///
+    /// The file is written in the config's format and, if newly created, with
+    /// [`ConfigOptions::file_mode`] permissions.
+    pub fn export_to(&self, path: impl AsRef<Path>) -> Result<(), ConfigError> {
+        let path = path.as_ref();
+        self.report(Feature::Export);
+        let bytes = self.encode(path, self.serialize()?)?;
+        self.options
+            .filesystem
+            .write(path, &bytes, Some(self.options.file_mode))
+            .map_err(|e| ConfigError::ConfigFileWriteError(path.to_path_buf(), e))
+    }
+
+    /// Write the config document to a writer, e.g. to stdout for piping it into another tool.
+    ///
+    /// The document is written as it would be saved, but never encrypted or compressed.
+    pub fn to_writer(&self, mut writer: impl Write) -> Result<(), ConfigError> {
+        writer
+            .write_all(&self.serialize()?)
+            .and_then(|_| writer.flush())
+            .map_err(|e| ConfigError::ConfigFileWriteError(PathBuf::from("<output>"), e))
+    }
+
+    /// Replace the config data with the contents of a file at an arbitrary path, e.g. one created
+    /// with [`export_to`](Self::export_to).
+    ///
+    /// The file is validated the same way as when loading (including `strict` mode, if enabled)
+    /// and the config is left untouched if it isn't valid.  Call [`save`](Self::save) to persist
+    /// the imported data.
+    pub fn import_from(&mut self, path: impl AsRef<Path>) -> Result<(), ConfigError> {
+        self.report(Feature::Import);
+        self.read_from(path.as_ref())
+    }
+
+    /// Replace the config data with the contents of a file, as for
+    /// [`import_from`](Self::import_from).
+    fn read_from(&mut self, path: &Path) -> Result<(), ConfigError> {
+        let contents = Self::read_config_file(path, &self.options)?;
+        let parsed = Self::parse_config_file(contents, path, &self.options)?;
+        self.replace_with(parsed);
+        Ok(())
+    }
+
+    fn replace_with(&mut self, parsed: Parsed<TConfigData>) {
+        self.unsaved.mark();
+        self.config_data = parsed.config_data;
+        self.unknown_fields = parsed.unknown_fields;
+        self.comments = parsed.comments;
+        self.interpolations = parsed.interpolations;
+        self.included = parsed.included;
+        self.overlay = parsed.overlay;
+        self.overrides = parsed.overrides;
+        self.dotenv = parsed.dotenv;
+        self.system_layer = parsed.system_layer;
+    }
+
+    /// Compare the current data to the version on disk, e.g. to show the user a summary of unsaved
+    /// changes before saving.
+    ///
+    /// Changes are reported from the perspective of saving, i.e. `Added` members exist in memory
+    /// but not on disk.  If the file doesn't exist yet, all data counts as added.
+    pub fn diff(&self) -> Result<Vec<diff::Change>, ConfigError> {
+        let current = serde_json::to_value(&self.config_data)
+            .map_err(ConfigError::ConfigFileSerializeError)?;
+
+        let config_path = Self::get_config_path(&self.config_file_key, &self.options)?;
+        let persisted = if self.options.filesystem.is_file(&config_path) {
+            let contents = Self::read_config_file(&config_path, &self.options)?;
+            let parsed = Self::parse_config_file(contents, &config_path, &self.options)?;
+            serde_json::to_value(parsed.config_data)
+                .map_err(ConfigError::ConfigFileSerializeError)?
+        } else {
+            match current {
+                Value::Object(_) => Value::Object(Map::new()),
+                Value::Array(_) => Value::Array(Vec::new()),
+                _ => Value::Null,
+            }
+        };
+
+        Ok(diff::diff(&persisted, &current))
+    }
+
+    /// Saved versions of this config, newest first.
+    ///
+    /// Revisions are only recorded when the config is loaded with [`ConfigOptions::history`].
+    pub fn revisions(&self) -> Result<Vec<Revision>, ConfigError> {
+        history::list(&self.history_dir()?, &self.options)
+    }
+
+    /// Replace the config data with an earlier revision and save it.
+    ///
+    /// The restored version is itself recorded as a new revision, so restoring can be undone.
+    pub fn restore_revision(&mut self, revision: &Revision) -> Result<(), ConfigError> {
+        self.report(Feature::RevisionRestore);
+        self.read_from(&revision.path)?;
+        self.save()
+    }
+
+    /// Commits that changed this config, newest first.
+    ///
+    /// Commits are only made when the config is saved with [`ConfigOptions::git`].
+    pub fn git_log(&self) -> Result<Vec<GitCommit>, ConfigError> {
+        let config_root = Self::get_config_root(&self.options)?;
+        let config_path = Self::get_config_path(&self.config_file_key, &self.options)?;
+        git::log(&config_root, &config_path)
+    }
+
+    /// Replace the config data with the version from an earlier commit and save it.
+    ///
+    /// Like [`restore_revision`](Self::restore_revision), this creates a new commit rather than
+    /// rewriting history.  `commit_id` is a full or abbreviated hash such as
+    /// [`GitCommit::id`]; branch names and other revision expressions are rejected.
+    pub fn checkout_commit(&mut self, commit_id: &str) -> Result<(), ConfigError> {
+        let config_root = Self::get_config_root(&self.options)?;
+        let config_path = Self::get_config_path(&self.config_file_key, &self.options)?;
+        self.report(Feature::RevisionRestore);
+        let contents = git::show(&config_root, &config_path, commit_id)?;
+        let parsed = Self::parse_config_file(contents, &config_path, &self.options)?;
+        self.replace_with(parsed);
+        self.save()
+    }
+
+    fn report(&self, feature: Feature) {
+        if let Some(telemetry) = &self.options.telemetry {
+            telemetry.feature_used(&self.config_file_key, feature);
+        }
+    }
+
+    fn history_dir(&self) -> Result<PathBuf, ConfigError> {
+        Self::get_config_root(&self.options)
+            .map(|root| history::history_dir(&root, &self.config_file_key))
+    }
+
+    /// Apply a multi-field update with all-or-nothing semantics.
+    ///
+    /// The closure works on a copy of the data.  The copy only replaces the current data if the
+    /// closure succeeds and the result is saved successfully; otherwise the config is left exactly
+    /// as it was and the error is returned.
+    pub fn transaction<R, E: From<ConfigError>>(
+        &mut self,
+        f: impl FnOnce(&mut TConfigData) -> Result<R, E>,
+    ) -> Result<R, E>
+    where
+        TConfigData: Clone,
+    {
+        self.report(Feature::Transaction);
+        let mut draft = self.config_data.clone();
+        let result = f(&mut draft)?;
+
+        let original = mem::replace(&mut self.config_data, draft);
+        if let Err(e) = self.save() {
+            self.config_data = original;
+            return Err(e.into());
+        }
+        Ok(result)
+    }
+
+    /// Fields that were present in the config file but not in the config data type, keyed by
+    /// JSON Pointer (e.g. `/servers/0/proxy`).
+    ///
+    /// This is only populated if the config was loaded with
+    /// [`ConfigOptions::preserve_unknown_fields`].
+    #[inline]
+    pub fn unknown_fields(&self) -> &Map<String, Value> {
+        &self.unknown_fields
+    }
+
+    /// The string at `pointer` (e.g. `/database/url`) as written in the config file, before
+    /// `${...}` references in it (or a [resolver](ConfigOptions::resolvers) reference) were
+    /// resolved.
+    ///
+    /// Returns `None` if the string didn't contain any references, or if the config wasn't loaded
+    /// with [`ConfigOptions::interpolate`] or resolvers.  Interpolated strings that haven't been
+    /// changed are saved in their raw form, so references survive a load/save round-trip.
+    pub fn raw_value(&self, pointer: &str) -> Option<&str> {
+        self.interpolations.raw(pointer)
+    }
+
+    /// The key this config was loaded with, e.g. `jira` for ~/.config/ilo/jira.json.
+    #[inline]
+    pub fn key(&self) -> &str {
+        &self.config_file_key
+    }
+
+    /// The config file's path, or `None` if the config is stored in a
+    /// [`backend`](ConfigOptions::backend).
+    pub fn file_path(&self) -> Option<PathBuf> {
+        if self.options.backend.is_some() {
+            return None;
+        }
+        Self::get_config_path(&self.config_file_key, &self.options).ok()
+    }
+
     #[inline]
     pub fn data(&self) -> &TConfigData {
         &self.config_data
     }
 
+    /// Mutable access to the config data.  From here on the config counts as having unsaved
+    /// changes until it is saved, see [`ConfigOptions::unsaved_changes`].
     #[inline]
     pub fn data_mut(&mut self) -> &mut TConfigData {
+        self.unsaved.mark();
         &mut self.config_data
     }
 
-    fn get_config_root() -> Result<PathBuf, ConfigError> {
-        let environment = environment::load_env();
-        let config_root = environment
-            .ilo_config_home
-            .as_deref()
+    /// Whether the data may have been changed since the config was loaded or last saved, i.e.
+    /// [`data_mut`](Self::data_mut) or a method that replaces the data was called.
+    #[inline]
+    pub fn has_unsaved_changes(&self) -> bool {
+        self.unsaved.is_set()
+    }
+
+    /// Parse the contents of the config file, applying [`ConfigOptions::corrupt_file_policy`] if
+    /// they can't be parsed.  Returns the parsed config along with the original contents, or
+    /// `None` if the file was quarantined and the defaults should be loaded instead.
+    fn parse_or_recover(
+        contents: Vec<u8>,
+        config_path: &Path,
+        options: &ConfigOptions,
+    ) -> Result<Option<Loaded<TConfigData>>, ConfigError> {
+        let raw = contents.clone();
+        let error = match Self::parse_config_file(contents, config_path, options) {
+            Ok(parsed) => return Ok(Some((parsed, raw))),
+            // Only the config file itself is recovered, not e.g. included files
+            Err(
+                e @ (ConfigError::ConfigFileParseError(..)
+                | ConfigError::ConfigFileValidationError(..)),
+            ) if e.path() == Some(config_path) => e,
+            Err(e) => return Err(e),
+        };
+        #[cfg(feature = "tracing")]
+        tracing::warn!(path = %config_path.display(), error = %error, "config is invalid");
+        match &options.corrupt_file_policy {
+            CorruptFilePolicy::Quarantine if options.backend.is_none() => {
+                recovery::quarantine(config_path, &raw, &error, options)?;
+                Ok(None)
+            }
+            CorruptFilePolicy::Handler(handler) => {
+                match handler.recover(config_path, &raw, &error) {
+                    Some(repaired) => Self::parse_config_file(repaired, config_path, options)
+                        .map(|parsed| Some((parsed, raw))),
+                    None => Err(error),
+                }
+            }
+            _ => Err(error),
+        }
+    }
+
+    /// The config to use when the config file doesn't exist, built from `init` unless
+    /// [`ConfigOptions::defaults`] is set.
+    fn load_defaults(
+        config_path: &Path,
+        options: &ConfigOptions,
+        init: impl FnOnce() -> TConfigData,
+    ) -> Result<Parsed<TConfigData>, ConfigError> {
+        let defaults = match (&options.defaults, &options.template_variables) {
+            (Some(defaults), _) => defaults.to_string(),
+            (None, variables)
+                if variables.is_some()
+                    || options.drop_ins
+                    || options.host_overrides
+                    || options.platform_overrides
+                    || options.system_root.is_some()
+                    || options.dotenv.is_some() =>
+            {
+                serde_json::to_string(&init()).map_err(ConfigError::ConfigFileSerializeError)?
+            }
+            (None, _) => return Ok(Parsed::from(init())),
+        };
+        let defaults = match &options.template_variables {
+            Some(variables) => variables.substitute(&defaults),
+            None => defaults,
+        };
+        Self::parse_contents(
+            defaults.into_bytes(),
+            Path::new("<embedded defaults>"),
+            config_path,
+            options,
+        )
+    }
+
+    fn read_config_file(
+        config_path: &Path,
+        options: &ConfigOptions,
+    ) -> Result<Vec<u8>, ConfigError> {
+        RetryPolicy::run(
+            options.retry.as_ref(),
+            config_path,
+            || options.filesystem.read(config_path),
+            ConfigError::ConfigFileLoadError,
+        )
+    }
+
+    /// Parse the contents of a config file, returning the config data along with what needs to
+    /// be preserved on save.
+    fn parse_config_file(
+        contents: Vec<u8>,
+        config_path: &Path,
+        options: &ConfigOptions,
+    ) -> Result<Parsed<TConfigData>, ConfigError> {
+        Self::parse_contents(contents, config_path, config_path, options)
+    }
+
+    /// Like [`parse_config_file`](Self::parse_config_file), for contents that didn't come from
+    /// the config file itself (e.g. embedded defaults).  `source` is used in error messages,
+    /// `config_path` to find files that are layered over the contents.
+    fn parse_contents(
+        contents: Vec<u8>,
+        source: &Path,
+        config_path: &Path,
+        options: &ConfigOptions,
+    ) -> Result<Parsed<TConfigData>, ConfigError> {
+        #[cfg(feature = "encryption")]
+        let contents = encryption::open_if_sealed(contents, source, options)?;
+        #[cfg(any(feature = "gzip", feature = "zstd"))]
+        let contents = compression::decompress_if_compressed(contents, source)?;
+
+        let parsed = match options.format {
+            ConfigFormat::Json => Self::parse(&contents, source, config_path, options),
+            ConfigFormat::Jsonc => {
+                let text = std::str::from_utf8(&contents).map_err(|e| {
+                    let e = io::Error::new(io::ErrorKind::InvalidData, e);
+                    ConfigError::ConfigFileLoadError(source.to_path_buf(), e)
+                })?;
+                let (stripped, comments) = jsonc::strip(text);
+                Self::parse(stripped.as_bytes(), source, config_path, options)
+                    .map(|parsed| Parsed { comments, ..parsed })
+            }
+        };
+        parsed.map_err(|e| match e {
+            ConfigError::ConfigFileParseError(_, ref json_error, None)
+                if Self::locates_in_source(json_error, options) =>
+            {
+                diagnostics::attach(e, &contents)
+            }
+            e => e,
+        })
+    }
+
+    /// Whether the location of a parse error refers to the original contents.  Options that
+    /// transform the document before deserializing it (e.g. includes) only keep locations for
+    /// syntax errors, and diagnostics of encrypted configs would leak their contents.
+    fn locates_in_source(error: &serde_json::Error, options: &ConfigOptions) -> bool {
+        #[cfg(feature = "encryption")]
+        if options.encryption.is_some() {
+            return false;
+        }
+        let transformed = options.includes
+            || options.drop_ins
+            || options.host_overrides
+            || options.platform_overrides
+            || !options.renamed_fields.is_empty()
+            || options.system_root.is_some()
+            || options.dotenv.is_some()
+            || options.interpolate
+            || !options.resolvers.is_empty();
+        !transformed || error.is_syntax() || error.is_eof()
+    }
+
+    /// Deserialize config data from the contents of a file, applying the load-time options.
+    ///
+    /// Returns the config data along with any unknown fields and interpolated strings that
+    /// should be preserved.
+    fn parse(
+        contents: &[u8],
+        source: &Path,
+        config_path: &Path,
+        options: &ConfigOptions,
+    ) -> Result<Parsed<TConfigData>, ConfigError> {
+        let parse_error = |e| ConfigError::ConfigFileParseError(source.to_path_buf(), e, None);
+
+        if options.includes {
+            let mut document: Value = serde_json::from_slice(contents).map_err(parse_error)?;
+            let included = include::resolve(&mut document, source, options)?;
+            let options = ConfigOptions {
+                includes: false,
+                ..options.clone()
+            };
+            let merged = serde_json::to_vec(&document).map_err(parse_error)?;
+            let parsed = Self::parse(merged.as_slice(), source, config_path, &options)?;
+            return Ok(Parsed { included, ..parsed });
+        }
+
+        if let (Some(system_root), None) = (&options.system_root, &options.backend) {
+            let mut document: Value = serde_json::from_slice(contents).map_err(parse_error)?;
+            let system_layer = layers::apply_system_layer(
+                &mut document,
+                config_path,
+                system_root,
+                source != config_path,
+                options,
+            )?;
+            let options = ConfigOptions {
+                system_root: None,
+                ..options.clone()
+            };
+            let merged = serde_json::to_vec(&document).map_err(parse_error)?;
+            let parsed = Self::parse(merged.as_slice(), source, config_path, &options)?;
+            return Ok(Parsed {
+                system_layer,
+                ..parsed
+            });
+        }
+
+        if (options.host_overrides || options.platform_overrides) && options.backend.is_none() {
+            let mut document: Value = serde_json::from_slice(contents).map_err(parse_error)?;
+            let overrides = layers::apply_overrides(&mut document, config_path, options)?;
+            let options = ConfigOptions {
+                host_overrides: false,
+                platform_overrides: false,
+                ..options.clone()
+            };
+            let merged = serde_json::to_vec(&document).map_err(parse_error)?;
+            let parsed = Self::parse(merged.as_slice(), source, config_path, &options)?;
+            return Ok(Parsed {
+                overrides,
+                ..parsed
+            });
+        }
+
+        if options.drop_ins && options.backend.is_none() {
+            let mut document: Value = serde_json::from_slice(contents).map_err(parse_error)?;
+            let overlay = layers::apply_drop_ins(&mut document, config_path, options)?;
+            let options = ConfigOptions {
+                drop_ins: false,
+                ..options.clone()
+            };
+            let merged = serde_json::to_vec(&document).map_err(parse_error)?;
+            let parsed = Self::parse(merged.as_slice(), source, config_path, &options)?;
+            return Ok(Parsed { overlay, ..parsed });
+        }
+
+        if let (Some(dotenv), None) = (&options.dotenv, &options.backend) {
+            let mut document: Value = serde_json::from_slice(contents).map_err(parse_error)?;
+            let dotenv = layers::apply_dotenv(&mut document, config_path, dotenv, options)?;
+            let options = ConfigOptions {
+                dotenv: None,
+                ..options.clone()
+            };
+            let merged = serde_json::to_vec(&document).map_err(parse_error)?;
+            let parsed = Self::parse(merged.as_slice(), source, config_path, &options)?;
+            return Ok(Parsed { dotenv, ..parsed });
+        }
+
+        if !options.renamed_fields.is_empty() {
+            let mut document: Value = serde_json::from_slice(contents).map_err(parse_error)?;
+            options.renamed_fields.apply(&mut document, source);
+            let options = ConfigOptions {
+                renamed_fields: RenamedFields::default(),
+                ..options.clone()
+            };
+            let renamed = serde_json::to_vec(&document).map_err(parse_error)?;
+            return Self::parse(renamed.as_slice(), source, config_path, &options);
+        }
+
+        if options.interpolate {
+            let mut document: Value = serde_json::from_slice(contents).map_err(parse_error)?;
+            let interpolations = interpolate::interpolate(&mut document)
+                .map_err(|e| ConfigError::ConfigInterpolationError(source.to_path_buf(), e))?;
+            let options = ConfigOptions {
+                interpolate: false,
+                ..options.clone()
+            };
+            let interpolated = serde_json::to_vec(&document).map_err(parse_error)?;
+            let parsed = Self::parse(interpolated.as_slice(), source, config_path, &options)?;
+            return Ok(Parsed {
+                interpolations: interpolations.then(parsed.interpolations),
+                ..parsed
+            });
+        }
+
+        if !options.resolvers.is_empty() {
+            let mut document: Value = serde_json::from_slice(contents).map_err(parse_error)?;
+            let resolved = resolve::resolve(&mut document, &options.resolvers, source)?;
+            let options = ConfigOptions {
+                resolvers: Vec::new(),
+                ..options.clone()
+            };
+            let resolved_contents = serde_json::to_vec(&document).map_err(parse_error)?;
+            let parsed = Self::parse(resolved_contents.as_slice(), source, config_path, &options)?;
+            return Ok(Parsed {
+                interpolations: resolved.then(parsed.interpolations),
+                ..parsed
+            });
+        }
+
+        Self::deserialize(contents, source, options)
+            .map_err(|e| validation::aggregate::<TConfigData>(e, contents))
+    }
+
+    /// Deserialize config data once all transformations of the document are done.
+    fn deserialize(
+        contents: &[u8],
+        source: &Path,
+        options: &ConfigOptions,
+    ) -> Result<Parsed<TConfigData>, ConfigError> {
+        let parse_error = |e| ConfigError::ConfigFileParseError(source.to_path_buf(), e, None);
+
+        if options.preserve_unknown_fields && !options.strict {
+            let document: Value = serde_json::from_slice(contents).map_err(parse_error)?;
+            let mut unknown_fields = Map::new();
+            let config_data: TConfigData = serde_ignored::deserialize(&document, |path| {
+                let pointer = unknown::json_pointer(&path);
+                if let Some(value) = document.pointer(&pointer) {
+                    unknown_fields.insert(pointer, value.clone());
+                }
+            })
+            .map_err(parse_error)?;
+            return Ok(Parsed {
+                unknown_fields,
+                ..Parsed::from(config_data)
+            });
+        }
+
+        #[cfg(feature = "fast-json")]
+        if !options.strict && contents.len() >= FAST_JSON_MIN_LEN {
+            // simd-json parses in place, and its errors lack the locations that serde_json gives,
+            // so documents that it rejects are parsed again below for the error
+            if let Ok(config_data) =
+                simd_json::serde::from_slice::<TConfigData>(&mut contents.to_vec())
+            {
+                return Ok(Parsed::from(config_data));
+            }
+        }
+
+        let mut deserializer = serde_json::Deserializer::from_slice(contents);
+        let config_data = if options.strict {
+            let mut unknown_fields = Vec::new();
+            let config_data = serde_ignored::deserialize(&mut deserializer, |path| {
+                unknown_fields.push(path.to_string())
+            })
+            .map_err(parse_error)?;
+            if !unknown_fields.is_empty() {
+                return Err(ConfigError::ConfigFileUnknownFieldsError(
+                    source.to_path_buf(),
+                    unknown_fields,
+                ));
+            }
+            config_data
+        } else {
+            TConfigData::deserialize(&mut deserializer).map_err(parse_error)?
+        };
+
+        deserializer.end().map_err(parse_error)?;
+        Ok(Parsed::from(config_data))
+    }
+
+    /// Encrypt serialized config data if [`ConfigOptions::encryption`] is set.
+    #[cfg(feature = "encryption")]
+    fn seal(&self, config_path: &Path, bytes: Vec<u8>) -> Result<Vec<u8>, ConfigError> {
+        match &self.options.encryption {
+            Some(encryption) => encryption.seal(config_path, &bytes),
+            None => Ok(bytes),
+        }
+    }
+
+    #[cfg(not(feature = "encryption"))]
+    fn seal(&self, _config_path: &Path, bytes: Vec<u8>) -> Result<Vec<u8>, ConfigError> {
+        Ok(bytes)
+    }
+
+    /// Compress serialized config data if [`ConfigOptions::compression`] is set, then encrypt it
+    /// if [`ConfigOptions::encryption`] is set.
+    fn encode(&self, config_path: &Path, bytes: Vec<u8>) -> Result<Vec<u8>, ConfigError> {
+        #[cfg(any(feature = "gzip", feature = "zstd"))]
+        let bytes = match self.options.compression {
+            Some(compression) => compression
+                .compress(&bytes)
+                .map_err(|e| ConfigError::ConfigFileWriteError(config_path.to_path_buf(), e))?,
+            None => bytes,
+        };
+        self.seal(config_path, bytes)
+    }
+
+    fn is_compressed(&self) -> bool {
+        #[cfg(any(feature = "gzip", feature = "zstd"))]
+        return self.options.compression.is_some();
+        #[cfg(not(any(feature = "gzip", feature = "zstd")))]
+        return false;
+    }
+
+    fn is_encrypted(&self) -> bool {
+        #[cfg(feature = "encryption")]
+        return self.options.encryption.is_some();
+        #[cfg(not(feature = "encryption"))]
+        return false;
+    }
+
+    /// Serialize the config data into the bytes that get written to disk.
+    fn serialize(&self) -> Result<Vec<u8>, ConfigError> {
+        let formatting = &self.options.formatting;
+        if self.options.format == ConfigFormat::Jsonc {
+            let mut document = self.to_document()?;
+            formatting.order(&mut document);
+            let indent = formatting.jsonc_indent();
+            return Ok(jsonc::to_string_pretty(&document, &self.comments, &indent).into_bytes());
+        }
+
+        if self.unknown_fields.is_empty()
+            && self.interpolations.is_empty()
+            && self.included.is_none()
+            && self.overlay.is_none()
+            && self.overrides.is_none()
+            && self.dotenv.is_none()
+            && self.system_layer.is_none()
+        {
+            formatting.to_vec(&self.config_data)
+        } else {
+            formatting.to_vec(&self.to_document()?)
+        }
+        .map_err(ConfigError::ConfigFileSerializeError)
+    }
+
+    /// The document to write to the config file, with everything that was preserved from loading
+    /// put back.
+    fn to_document(&self) -> Result<Value, ConfigError> {
+        let mut document = serde_json::to_value(&self.config_data)
+            .map_err(ConfigError::ConfigFileSerializeError)?;
+        unknown::restore(&mut document, &self.unknown_fields);
+        self.interpolations.restore(&mut document);
+        if let Some(dotenv) = &self.dotenv {
+            dotenv.restore(&mut document);
+        }
+        if let Some(overlay) = &self.overlay {
+            overlay.restore(&mut document);
+        }
+        if let Some(overrides) = &self.overrides {
+            overrides.restore(&mut document);
+        }
+        if let Some(system_layer) = &self.system_layer {
+            system_layer.restore(&mut document);
+        }
+        if let Some(included) = &self.included {
+            included.restore(&mut document);
+        }
+        Ok(document)
+    }
+
+    /// Create the config root directory if it does not exist yet.
+    fn ensure_config_root(options: &ConfigOptions) -> Result<PathBuf, ConfigError> {
+        let config_root = Self::get_config_root(options)?;
+        match options.filesystem.try_exists(&config_root) {
+            Ok(true) => (),
+            Ok(false) => options
+                .filesystem
+                .create_dir_all(&config_root, Some(0o700))
+                .map_err(|e| ConfigError::ConfigRootCreateError(config_root.clone(), e))?,
+            Err(e) => return Err(ConfigError::ConfigRootLoadError(config_root, e)),
+        }
+        protocol::ensure(&config_root, options)?;
+        Ok(config_root)
+    }
+
+    fn get_config_root(options: &ConfigOptions) -> Result<PathBuf, ConfigError> {
+        let config_root = Self::get_base_root(options)?;
+        Ok(match &options.namespace {
+            Some(namespace) => config_root.join(namespace),
+            None => config_root,
+        })
+    }
+
+    /// The config root, not taking the namespace into account.
+    fn get_base_root(options: &ConfigOptions) -> Result<PathBuf, ConfigError> {
+        if let Some(root) = &options.root {
+            return Ok(root.clone());
+        }
+
+        let config_root = environment::config_home(&options.root_env_var)
             .map(PathBuf::from)
-            .or(home::home_dir().map(|d| d.join(".config").join("ilo")));
+            .or(environment::home_dir().map(|d| d.join(".config").join("ilo")))
+            .or_else(|| {
+                options
+                    .root_fallbacks
+                    .iter()
+                    .find_map(RootFallback::resolve)
+            });
 
         match config_root {
             None => Err(ConfigError::NoHome),
@@ -141,31 +1756,295 @@ impl<TConfigData: Serialize + DeserializeOwned + Default> Config<TConfigData> {
         }
     }
 
-    fn get_config_path(config_file_key: &str) -> Result<PathBuf, ConfigError> {
-        Self::get_config_root().map(|root| root.join(format!("{}.json", config_file_key)))
+    fn get_config_path(
+        config_file_key: &str,
+        options: &ConfigOptions,
+    ) -> Result<PathBuf, ConfigError> {
+        Self::get_config_root(options).map(|root| {
+            let file_name = format!("{}.{}", config_file_key, options.format.extension());
+            #[cfg(any(feature = "gzip", feature = "zstd"))]
+            let file_name = match options.compression {
+                Some(compression) => format!("{}.{}", file_name, compression.extension()),
+                None => file_name,
+            };
+            root.join(file_name)
+        })
+    }
+}
+
+#[cfg(feature = "schemars")]
+impl<TConfigData: Serialize + DeserializeOwned + Default + schemars::JsonSchema>
+    Config<TConfigData>
+{
+    /// Generate a JSON Schema describing the config data type.
+    pub fn json_schema() -> schemars::Schema {
+        schemars::schema_for!(TConfigData)
+    }
+
+    /// Write the JSON Schema for the config data type to `<key>.schema.json` next to the config
+    /// file, returning the path that was written.
+    ///
+    /// Editors like VS Code can use the schema to validate and autocomplete hand-edited configs,
+    /// e.g. by mapping the config file to the schema in their `json.schemas` setting.
+    pub fn save_schema(&self) -> Result<PathBuf, ConfigError> {
+        let schema_path = Self::ensure_config_root(&self.options)?
+            .join(format!("{}.schema.json", self.config_file_key));
+        let bytes = serde_json::to_vec_pretty(&Self::json_schema())
+            .map_err(ConfigError::ConfigFileSerializeError)?;
+        self.options
+            .filesystem
+            .write(&schema_path, &bytes, None)
+            .map_err(|e| ConfigError::ConfigFileWriteError(schema_path.clone(), e))?;
+        Ok(schema_path)
+    }
+
+    /// Generate a first-run template holding the default config data.
+    ///
+    /// For JSONC, the doc comments of the config data type's fields are included as comments,
+    /// and optional fields without a value are included as commented-out example values if they
+    /// have any (e.g. `#[schemars(example = ...)]`).  Use the template as
+    /// [`ConfigOptions::defaults`] so that it's what gets written when the file is created:
+    ///
+    /// ```no_run
+    /// # use ilo_config::{Config, ConfigFormat, ConfigOptions};
+    /// # #[derive(Default, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+    /// # struct JiraConfig {}
+    /// let options = ConfigOptions {
+    ///     format: ConfigFormat::Jsonc,
+    ///     defaults: Some(Config::<JiraConfig>::template(ConfigFormat::Jsonc)?.into()),
+    ///     ..Default::default()
+    /// };
+    /// let config: Config<JiraConfig> = Config::load_with_options("jira", options)?;
+    /// # Ok::<(), ilo_config::ConfigError>(())
+    /// ```
+    pub fn template(format: ConfigFormat) -> Result<String, ConfigError> {
+        let value = serde_json::to_value(TConfigData::default())
+            .map_err(ConfigError::ConfigFileSerializeError)?;
+        match format {
+            ConfigFormat::Json => {
+                serde_json::to_string_pretty(&value).map_err(ConfigError::ConfigFileSerializeError)
+            }
+            ConfigFormat::Jsonc => Ok(template::render(Self::json_schema().as_value(), &value)),
+        }
+    }
+}
+
+/// Config data parsed from a file, along with what needs to be preserved when it is saved again.
+struct Parsed<T> {
+    config_data: T,
+    unknown_fields: Map<String, Value>,
+    comments: jsonc::Comments,
+    interpolations: Interpolations,
+    included: Option<Included>,
+    overlay: Option<Overlay>,
+    overrides: Option<Overlay>,
+    dotenv: Option<Overlay>,
+    system_layer: Option<SystemLayer>,
+}
+
+impl<T> From<T> for Parsed<T> {
+    fn from(config_data: T) -> Self {
+        Self {
+            config_data,
+            unknown_fields: Map::new(),
+            comments: jsonc::Comments::default(),
+            interpolations: Interpolations::default(),
+            included: None,
+            overlay: None,
+            overrides: None,
+            dotenv: None,
+            system_layer: None,
+        }
+    }
+}
+
+fn render_diagnostic(diagnostic: &Option<Box<ParseDiagnostic>>) -> String {
+    match diagnostic {
+        Some(diagnostic) if cfg!(not(feature = "miette")) => format!("\n{}", diagnostic),
+        _ => String::new(),
     }
 }
 
+/// Error for writing a config under a key that is already taken.
+fn key_taken() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        "a config with that key exists",
+    )
+}
+
+/// Documents at least this long are deserialized with simd-json, which only pays off once the
+/// copy it needs is cheap compared to the parsing.
+#[cfg(feature = "fast-json")]
+const FAST_JSON_MIN_LEN: usize = 64 * 1024;
+
+/// A parsed config file along with its original contents.
+type Loaded<T> = (Parsed<T>, Vec<u8>);
+
+/// Errors from loading, saving and managing configs.
+///
+/// More variants may be added in future releases; use [`kind`](Self::kind) to react to categories
+/// of errors, and [`path`](Self::path) to find out which file an error is about.
 #[derive(ThisError, Debug)]
+#[non_exhaustive]
 pub enum ConfigError {
-    #[error("$ILO_CONFIG_HOME is not set and user home directory could not be determined")]
+    #[error(
+        "The config root environment variable ($ILO_CONFIG_HOME by default) is not set, user home \
+        directory could not be determined and no fallback root was usable"
+    )]
     NoHome,
 
     #[error("Config root dir {0} could not be loaded: {1}")]
-    ConfigRootLoadError(PathBuf, io::Error),
+    ConfigRootLoadError(PathBuf, #[source] io::Error),
 
     #[error("Config root dir does not exist at {0} and could not be created: {1}")]
-    ConfigRootCreateError(PathBuf, io::Error),
+    ConfigRootCreateError(PathBuf, #[source] io::Error),
+
+    #[error("Config file {0} does not exist")]
+    NotFound(PathBuf),
 
     #[error("Config path exists at {0} but config could not be loaded: {1}")]
-    ConfigFileLoadError(PathBuf, io::Error),
+    ConfigFileLoadError(PathBuf, #[source] io::Error),
+
+    /// The diagnostic points at the error in the file, if its location is known.  It is shown
+    /// below the message, except with the `miette` feature, where miette renders it instead.
+    #[error(
+        "Config path exists at {0} but JSON could not be parsed: {1}{}",
+        render_diagnostic(.2)
+    )]
+    ConfigFileParseError(
+        PathBuf,
+        #[source] serde_json::Error,
+        Option<Box<ParseDiagnostic>>,
+    ),
 
-    #[error("Config path exists at {0} but JSON could not be parsed: {1}")]
-    ConfigFileParseError(PathBuf, serde_json::Error),
+    #[error(
+        "Config file {0} has {} invalid values: {}",
+        .1.len(),
+        .1.iter().map(ToString::to_string).collect::<Vec<_>>().join("; ")
+    )]
+    ConfigFileValidationError(PathBuf, Vec<FieldError>),
+
+    #[error("Config file {0} contains unknown fields: {}", .1.join(", "))]
+    ConfigFileUnknownFieldsError(PathBuf, Vec<String>),
 
     #[error("Config path location {0} could not be opened for writing: {1}")]
-    ConfigFileWriteError(PathBuf, io::Error),
+    ConfigFileWriteError(PathBuf, #[source] io::Error),
 
     #[error("There was an error serializing config to disk: {0}")]
-    ConfigFileSerializeError(serde_json::Error),
+    ConfigFileSerializeError(#[source] serde_json::Error),
+
+    #[error(
+        "Config file {0} did not match the saved data when read back; {}",
+        .1.as_ref().map_or(
+            String::from("there was no previous version to keep"),
+            |backup| format!("the previous version was kept at {}", backup.display()),
+        )
+    )]
+    ConfigFileVerifyError(PathBuf, Option<PathBuf>),
+
+    #[error(
+        "Config file {0} was changed by someone else since it was loaded; reload it, or use \
+        save_overwrite() to replace those changes"
+    )]
+    ConflictError(PathBuf),
+
+    #[error("Config history at {0} could not be accessed: {1}")]
+    ConfigHistoryError(PathBuf, #[source] io::Error),
+
+    #[error("Git repository at {0} could not be updated: {1}")]
+    ConfigGitError(PathBuf, #[source] io::Error),
+
+    #[error("Config file {0} has an invalid $include directive: {1}")]
+    ConfigIncludeError(PathBuf, String),
+
+    #[error("Config file {0} has a reference that could not be resolved: {1}")]
+    ConfigInterpolationError(PathBuf, String),
+
+    #[error("Line {1} of {0} could not be parsed: {2}")]
+    DotEnvParseError(PathBuf, usize, String),
+
+    #[error("Conflict file {0} still contains conflict markers")]
+    UnresolvedConflictError(PathBuf),
+
+    #[error(
+        "Config file {0} was changed by someone else and could not be merged; both sides changed {}",
+        .1.join(", ")
+    )]
+    MergeConflictError(PathBuf, Vec<String>),
+
+    #[error("Config lock file {0} could not be locked: {1}")]
+    ConfigLockError(PathBuf, #[source] io::Error),
+
+    #[error("Config backend location {0} could not be accessed: {1}")]
+    ConfigBackendError(String, #[source] Box<dyn std::error::Error + Send + Sync>),
+
+    #[error("Reference {1} in {0} could not be resolved: {2}")]
+    ConfigResolveError(
+        PathBuf,
+        String,
+        #[source] Box<dyn std::error::Error + Send + Sync>,
+    ),
+
+    #[error("Secret `{0}` could not be refreshed: {1}")]
+    SecretRefreshError(String, #[source] Box<dyn std::error::Error + Send + Sync>),
+
+    #[error(
+        "Config root {0} uses on-disk layout version {1}, which is newer than this version of \
+        ilo-config supports ({})",
+        protocol::PROTOCOL_VERSION
+    )]
+    IncompatibleLayoutError(PathBuf, u32),
+
+    #[error("Saving would take {0} to {1} bytes, over its quota of {2} bytes")]
+    QuotaExceededError(PathBuf, u64, u64),
+
+    #[error(
+        "Config file {0} is accessible by other users (permissions {1:o}); restrict it to the \
+        owner, e.g. with Config::fix_permissions()"
+    )]
+    InsecurePermissionsError(PathBuf, u32),
+
+    #[error("Config file {0} could not be encrypted or decrypted: {1}")]
+    ConfigEncryptionError(PathBuf, String),
+
+    #[error("Signing key for config file {0} could not be retrieved: {1}")]
+    SigningKeyError(PathBuf, String),
+
+    #[error(
+        "Config file {0} is unsigned or doesn't match its signature; it may have been modified by \
+        another user or process"
+    )]
+    TamperDetected(PathBuf),
+
+    #[error("Config `{0}` was dropped by its owner and can no longer be accessed")]
+    ConfigDroppedError(String),
+
+    #[error("Config setup could not ask for values: {0}")]
+    ConfigSetupError(#[source] io::Error),
+
+    #[error("Refusing to purge {0}, which may hold other apps' configs; set a namespace or root")]
+    SharedRootPurgeError(PathBuf),
+
+    #[error("Another instance is already running (process {1}, see {0})")]
+    AlreadyRunningError(PathBuf, u32),
+
+    #[error("Value at `{0}` could not be set: {1}")]
+    InvalidPointerError(String, String),
+
+    #[error("Operation {0} of the patch could not be applied: {1}")]
+    ConfigPatchError(usize, String),
+
+    #[error("Changed config data is not valid for the config data type: {0}")]
+    InvalidDataError(#[source] serde_json::Error),
+
+    #[error("Config `{0}` was already loaded with a different data type")]
+    ConfigTypeMismatchError(String),
+
+    #[error(
+        "Config file {0} could not be accessed after {} attempts; the last error was: {}",
+        .1.len(),
+        .1.last().map_or(String::new(), ToString::to_string)
+    )]
+    RetriesExhausted(PathBuf, Vec<io::Error>),
 }
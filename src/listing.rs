@@ -0,0 +1,34 @@
+//! Finding the configs that exist in a config root.
+use serde_json::Value;
+
+use crate::{Config, ConfigError, ConfigOptions};
+
+/// The keys of all configs in the config root (or the namespace, if set), sorted.
+///
+/// Only files in the config's [`format`](ConfigOptions::format) count; schemas written with
+/// `save_schema` and hidden files are left out.  Returns an empty list if the root doesn't exist
+/// yet.
+pub fn list_keys(options: &ConfigOptions) -> Result<Vec<String>, ConfigError> {
+    let config_root = Config::<Value>::get_config_root(options)?;
+    let filesystem = &options.filesystem;
+    if !filesystem
+        .try_exists(&config_root)
+        .map_err(|e| ConfigError::ConfigRootLoadError(config_root.clone(), e))?
+    {
+        return Ok(Vec::new());
+    }
+    let entries = filesystem
+        .read_dir(&config_root)
+        .map_err(|e| ConfigError::ConfigRootLoadError(config_root.clone(), e))?;
+
+    let suffix = format!(".{}", options.format.extension());
+    let mut keys: Vec<String> = entries
+        .iter()
+        .filter(|path| filesystem.is_file(path))
+        .filter_map(|path| path.file_name()?.to_str()?.strip_suffix(&suffix))
+        .filter(|key| !key.is_empty() && !key.starts_with('.') && !key.ends_with(".schema"))
+        .map(String::from)
+        .collect();
+    keys.sort();
+    Ok(keys)
+}
@@ -0,0 +1,166 @@
+//! Lock-free snapshots of configs that are reloaded when their file changes, for servers that
+//! read their config on every request.
+use std::{
+    sync::{Arc, Condvar, Mutex, PoisonError},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use arc_swap::ArcSwap;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{telemetry::Feature, Config, ConfigError, ConfigOptions};
+
+/// A config whose data is swapped out atomically when its file changes, as returned by
+/// [`Config::watch`].
+///
+/// Readers grab the current data as an `Arc` with [`snapshot`](Self::snapshot), without taking
+/// any lock, and keep working with that version for as long as they hold on to it.
+///
+/// The file is checked for changes in a background thread until the `LiveConfig` is dropped.
+/// Only the config file itself is watched, not included files or other layers.  A file that
+/// can't be loaded (e.g. after a bad hand edit) or that was removed leaves the current data in
+/// place; the last error is kept for [`take_error`](Self::take_error).
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use ilo_config::Config;
+/// # #[derive(Default, serde::Serialize, serde::Deserialize)]
+/// # struct ServerConfig { max_connections: usize }
+/// let live = Config::<ServerConfig>::load("server")?.watch(Duration::from_secs(2));
+///
+/// // On every request
+/// let config = live.snapshot();
+/// println!("{}", config.max_connections);
+/// # Ok::<(), ilo_config::ConfigError>(())
+/// ```
+pub struct LiveConfig<TConfigData: Serialize + DeserializeOwned + Send + Sync + 'static> {
+    inner: Arc<Inner<TConfigData>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+/// Shared between a [`LiveConfig`] and its background thread.
+struct Inner<TConfigData> {
+    config_file_key: String,
+    options: ConfigOptions,
+    current: ArcSwap<TConfigData>,
+    contents: Mutex<Option<Vec<u8>>>, // Raw contents the current data was loaded from
+    error: Mutex<Option<ConfigError>>,
+    stopped: Mutex<bool>,
+    stop: Condvar,
+}
+
+impl<TConfigData: Serialize + DeserializeOwned + Send + Sync + 'static> Config<TConfigData> {
+    /// Turn the config into a [`LiveConfig`] that checks its file for changes every
+    /// `poll_interval` and reloads it when it has changed.
+    pub fn watch(self, poll_interval: Duration) -> LiveConfig<TConfigData> {
+        self.report(Feature::Watch);
+        let contents = self
+            .saved
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        let inner = Arc::new(Inner {
+            config_file_key: self.config_file_key.clone(),
+            options: self.options.clone(),
+            current: ArcSwap::from_pointee(self.config_data),
+            contents: Mutex::new(contents),
+            error: Mutex::new(None),
+            stopped: Mutex::new(false),
+            stop: Condvar::new(),
+        });
+        let worker = {
+            let inner = Arc::clone(&inner);
+            thread::spawn(move || inner.run(poll_interval))
+        };
+        LiveConfig {
+            inner,
+            worker: Some(worker),
+        }
+    }
+}
+
+impl<TConfigData: Serialize + DeserializeOwned + Send + Sync + 'static> LiveConfig<TConfigData> {
+    /// The current version of the config data.
+    pub fn snapshot(&self) -> Arc<TConfigData> {
+        self.inner.current.load_full()
+    }
+
+    /// Check for changes now instead of waiting for the next poll, e.g. on `SIGHUP`.  Returns
+    /// whether the data was replaced.
+    pub fn reload(&self) -> Result<bool, ConfigError> {
+        self.inner.reload()
+    }
+
+    /// Take the error from the last background reload that failed, if any.
+    pub fn take_error(&self) -> Option<ConfigError> {
+        self.inner
+            .error
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+    }
+}
+
+impl<TConfigData: Serialize + DeserializeOwned + Send + Sync + 'static> Drop
+    for LiveConfig<TConfigData>
+{
+    fn drop(&mut self) {
+        *self
+            .inner
+            .stopped
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = true;
+        self.inner.stop.notify_all();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl<TConfigData: Serialize + DeserializeOwned> Inner<TConfigData> {
+    fn run(&self, poll_interval: Duration) {
+        let mut stopped = self.stopped.lock().unwrap_or_else(PoisonError::into_inner);
+        loop {
+            stopped = self
+                .stop
+                .wait_timeout(stopped, poll_interval)
+                .unwrap_or_else(PoisonError::into_inner)
+                .0;
+            if *stopped {
+                return;
+            }
+            if let Err(e) = self.reload() {
+                *self.error.lock().unwrap_or_else(PoisonError::into_inner) = Some(e);
+            }
+        }
+    }
+
+    fn reload(&self) -> Result<bool, ConfigError> {
+        let mut contents = self.contents.lock().unwrap_or_else(PoisonError::into_inner);
+        let current = self.read_contents()?;
+        if current.is_none() || current == *contents {
+            return Ok(false);
+        }
+        let config = Config::<TConfigData>::load_required_with_options(
+            &self.config_file_key,
+            self.options.clone(),
+        )?;
+        self.current.store(Arc::new(config.config_data));
+        *contents = current;
+        Ok(true)
+    }
+
+    /// The raw contents of the config file, or `None` if it doesn't exist.
+    fn read_contents(&self) -> Result<Option<Vec<u8>>, ConfigError> {
+        if let Some(backend) = &self.options.backend {
+            return backend.load(&self.config_file_key);
+        }
+        let config_path =
+            Config::<TConfigData>::get_config_path(&self.config_file_key, &self.options)?;
+        if !self.options.filesystem.is_file(&config_path) {
+            return Ok(None);
+        }
+        Config::<TConfigData>::read_config_file(&config_path, &self.options).map(Some)
+    }
+}
@@ -0,0 +1,164 @@
+//! Managing many configs from one place, e.g. in a host application with plugins.
+use std::{
+    any::Any,
+    collections::BTreeMap,
+    fmt::{self, Debug},
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{Config, ConfigError, ConfigOptions, SharedConfig};
+
+/// Owns the configs loaded through it, keyed by name, and hands out [`SharedConfig`] handles.
+///
+/// Each key is only loaded once; later requests for it get a handle to the same config, so
+/// plugins that use overlapping configs see each other's changes.  Unsaved changes can be saved
+/// in one go with [`save_all_dirty`](Self::save_all_dirty).
+pub struct ConfigManager {
+    options: ConfigOptions,
+    configs: Mutex<BTreeMap<String, Box<dyn Managed>>>,
+}
+
+impl Debug for ConfigManager {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConfigManager")
+            .field("keys", &self.keys())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for ConfigManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConfigManager {
+    pub fn new() -> Self {
+        Self::with_options(ConfigOptions::default())
+    }
+
+    /// A manager that loads all of its configs with `options`.
+    pub fn with_options(options: ConfigOptions) -> Self {
+        Self {
+            options,
+            configs: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// A handle to the config for `config_file_key`, loading it if this is the first request for
+    /// it.
+    ///
+    /// Fails with `ConfigError::ConfigTypeMismatchError` if the config was already loaded with a
+    /// different data type.
+    pub fn get<TConfigData>(
+        &self,
+        config_file_key: &str,
+    ) -> Result<SharedConfig<TConfigData>, ConfigError>
+    where
+        TConfigData: Serialize + DeserializeOwned + Default + Send + Sync + 'static,
+    {
+        let mut configs = self.lock_configs();
+        if let Some(managed) = configs.get(config_file_key) {
+            return managed
+                .as_any()
+                .downcast_ref::<Entry<TConfigData>>()
+                .map(|entry| entry.config.clone())
+                .ok_or_else(|| ConfigError::ConfigTypeMismatchError(config_file_key.to_string()));
+        }
+
+        let config = Config::load_with_options(config_file_key, self.options.clone())?;
+        let entry = Entry {
+            config: SharedConfig::new(config),
+        };
+        let shared = entry.config.clone();
+        configs.insert(config_file_key.to_string(), Box::new(entry));
+        Ok(shared)
+    }
+
+    /// Whether the config for `config_file_key` has been loaded through this manager.
+    pub fn is_loaded(&self, config_file_key: &str) -> bool {
+        self.lock_configs().contains_key(config_file_key)
+    }
+
+    /// Keys of all configs loaded through this manager, in sorted order.
+    pub fn keys(&self) -> Vec<String> {
+        self.lock_configs().keys().cloned().collect()
+    }
+
+    /// Keys of the configs that have unsaved changes, see [`Config::has_unsaved_changes`].
+    pub fn dirty_keys(&self) -> Vec<String> {
+        self.lock_configs()
+            .iter()
+            .filter(|(_, managed)| managed.is_dirty())
+            .map(|(key, _)| key.clone())
+            .collect()
+    }
+
+    /// Save every config that has unsaved changes, and return their keys.
+    ///
+    /// All dirty configs are attempted even if saving one of them fails; the first error is
+    /// returned in that case.
+    pub fn save_all_dirty(&self) -> Result<Vec<String>, ConfigError> {
+        let configs = self.lock_configs();
+        let mut saved = Vec::new();
+        let mut first_error = None;
+        for (key, managed) in configs.iter() {
+            match managed.save_if_dirty() {
+                Ok(true) => saved.push(key.clone()),
+                Ok(false) => (),
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        match first_error {
+            Some(e) => Err(e),
+            None => Ok(saved),
+        }
+    }
+
+    /// Stop managing the config for `config_file_key`, without saving it.  Handles that were
+    /// already given out keep working; the next [`get`](Self::get) loads it from disk again.
+    pub fn evict(&self, config_file_key: &str) -> bool {
+        self.lock_configs().remove(config_file_key).is_some()
+    }
+
+    fn lock_configs(&self) -> MutexGuard<'_, BTreeMap<String, Box<dyn Managed>>> {
+        self.configs.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// A managed config, with its data type erased.
+trait Managed: Send + Sync {
+    fn as_any(&self) -> &dyn Any;
+    fn is_dirty(&self) -> bool;
+    fn save_if_dirty(&self) -> Result<bool, ConfigError>;
+}
+
+struct Entry<TConfigData: Serialize + DeserializeOwned> {
+    config: SharedConfig<TConfigData>,
+}
+
+impl<TConfigData> Managed for Entry<TConfigData>
+where
+    TConfigData: Serialize + DeserializeOwned + Send + Sync + 'static,
+{
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.config.read().has_unsaved_changes()
+    }
+
+    fn save_if_dirty(&self) -> Result<bool, ConfigError> {
+        let config = self.config.read();
+        if !config.has_unsaved_changes() {
+            return Ok(false);
+        }
+        config.save()?;
+        Ok(true)
+    }
+}
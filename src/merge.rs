@@ -0,0 +1,281 @@
+//! Three-way merging of a config with changes that were made to its file since it was loaded.
+use std::sync::PoisonError;
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{telemetry::Feature, Config, ConfigError};
+
+impl<TConfigData: Serialize + DeserializeOwned> Config<TConfigData> {
+    /// Flush config changes to disk, merging them with changes that someone else made to the file
+    /// since it was loaded (or last saved).
+    ///
+    /// The file as it was loaded is the common base: values that only one side changed take that
+    /// side's version, so e.g. two processes that set different fields both keep their change.
+    /// Objects are merged member by member, while arrays and other values are replaced as a whole.
+    /// If both sides changed the same value differently, nothing is written and
+    /// `ConfigError::MergeConflictError` lists the JSON Pointers of the contested values (a
+    /// conflict file is written as for [`save`](Self::save) with
+    /// [`ConfigOptions::conflict_markers`](crate::ConfigOptions::conflict_markers)).
+    ///
+    /// On success the config data is updated to the merged version.
+    ///
+    /// ```no_run
+    /// # use ilo_config::{Config, ConfigError};
+    /// let mut config: Config<serde_json::Value> = Config::load("jira")?;
+    /// config.data_mut()["board"] = "OPS".into();
+    /// match config.save_merged() {
+    ///     Err(ConfigError::MergeConflictError(path, pointers)) => {
+    ///         eprintln!("{} has conflicting changes at {}", path.display(), pointers.join(", "));
+    ///     }
+    ///     result => result?,
+    /// }
+    /// # Ok::<(), ConfigError>(())
+    /// ```
+    pub fn save_merged(&mut self) -> Result<(), ConfigError> {
+        // Backends don't detect conflicts
+        if self.options.backend.is_some() {
+            return self.save();
+        }
+
+        let config_path = Self::get_config_path(&self.config_file_key, &self.options)?;
+        if !self.options.filesystem.is_file(&config_path) {
+            return self.save();
+        }
+        let current = Self::read_config_file(&config_path, &self.options)?;
+        let saved = self
+            .saved
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        if saved.as_ref() == Some(&current) {
+            return self.save();
+        }
+
+        self.report(Feature::Merge);
+        let document = |contents: Vec<u8>| {
+            let parsed = Self::parse_config_file(contents, &config_path, &self.options)?;
+            serde_json::to_value(parsed.config_data).map_err(ConfigError::ConfigFileSerializeError)
+        };
+        // A base that no longer parses (e.g. a repaired corrupt file) means there's nothing in
+        // common, so every difference is contested
+        let base = saved.and_then(|saved| document(saved).ok());
+        let theirs = document(current.clone())?;
+        let ours = self.data_document()?;
+
+        let mut contested = Vec::new();
+        let merged = merge(
+            String::new(),
+            base.as_ref(),
+            Some(&ours),
+            Some(&theirs),
+            &mut contested,
+        );
+        if !contested.is_empty() {
+            if self.options.conflict_markers && !self.is_encrypted() && !self.is_compressed() {
+                let bytes = self.encode(&config_path, self.serialize()?)?;
+                self.write_conflict_file(&config_path, &bytes)?;
+            }
+            return Err(ConfigError::MergeConflictError(config_path, contested));
+        }
+
+        self.replace_data(merged.unwrap_or(Value::Null))?;
+        *self.saved.lock().unwrap_or_else(PoisonError::into_inner) = Some(current);
+        self.save()
+    }
+}
+
+/// Merge the values at `pointer`, where `None` means the value doesn't exist on that side.
+///
+/// Contested pointers are collected in `contested`, keeping our version of those values.
+fn merge(
+    pointer: String,
+    base: Option<&Value>,
+    ours: Option<&Value>,
+    theirs: Option<&Value>,
+    contested: &mut Vec<String>,
+) -> Option<Value> {
+    if ours == theirs || theirs == base {
+        return ours.cloned();
+    }
+    if ours == base {
+        return theirs.cloned();
+    }
+
+    let (Some(Value::Object(ours)), Some(Value::Object(theirs))) = (ours, theirs) else {
+        contested.push(pointer);
+        return ours.cloned();
+    };
+    let empty = Map::new();
+    let base = match base {
+        Some(Value::Object(base)) => base,
+        _ => &empty,
+    };
+    let mut merged = Map::new();
+    let keys = ours
+        .keys()
+        .chain(theirs.keys().filter(|key| !ours.contains_key(*key)));
+    for key in keys {
+        let child = format!("{pointer}/{}", key.replace('~', "~0").replace('/', "~1"));
+        if let Some(value) = merge(
+            child,
+            base.get(key),
+            ours.get(key),
+            theirs.get(key),
+            contested,
+        ) {
+            merged.insert(key.clone(), value);
+        }
+    }
+    Some(Value::Object(merged))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::merge;
+    use crate::{Config, ConfigError, ConfigOptions};
+
+    /// Merge whole documents, returning the result and the contested pointers.
+    fn merge_documents(base: &Value, ours: &Value, theirs: &Value) -> (Value, Vec<String>) {
+        let mut contested = Vec::new();
+        let merged = merge(
+            String::new(),
+            Some(base),
+            Some(ours),
+            Some(theirs),
+            &mut contested,
+        );
+        (merged.unwrap_or(Value::Null), contested)
+    }
+
+    #[test]
+    fn changes_to_different_fields_are_both_kept() {
+        let base = json!({"board": "OPS", "user": {"name": "ana", "email": "ana@example.com"}});
+        let ours = json!({"board": "DEV", "user": {"name": "ana", "email": "ana@example.com"}});
+        let theirs = json!({"board": "OPS", "user": {"name": "ana", "email": "ana@example.org"}});
+
+        let (merged, contested) = merge_documents(&base, &ours, &theirs);
+        assert_eq!(
+            merged,
+            json!({"board": "DEV", "user": {"name": "ana", "email": "ana@example.org"}})
+        );
+        assert!(contested.is_empty());
+    }
+
+    #[test]
+    fn conflicting_changes_are_contested_and_keep_ours() {
+        let base = json!({"board": "OPS", "limit": 10});
+        let ours = json!({"board": "DEV", "limit": 10});
+        let theirs = json!({"board": "QA", "limit": 20});
+
+        let (merged, contested) = merge_documents(&base, &ours, &theirs);
+        assert_eq!(merged, json!({"board": "DEV", "limit": 20}));
+        assert_eq!(contested, ["/board"]);
+    }
+
+    #[test]
+    fn identical_changes_do_not_conflict() {
+        let base = json!({"board": "OPS"});
+        let both = json!({"board": "DEV"});
+
+        let (merged, contested) = merge_documents(&base, &both, &both);
+        assert_eq!(merged, both);
+        assert!(contested.is_empty());
+    }
+
+    #[test]
+    fn deletion_of_an_unchanged_value_is_kept() {
+        let base = json!({"board": "OPS", "token": "abc"});
+        let ours = json!({"board": "DEV", "token": "abc"});
+        let theirs = json!({"board": "OPS"});
+
+        let (merged, contested) = merge_documents(&base, &ours, &theirs);
+        assert_eq!(merged, json!({"board": "DEV"}));
+        assert!(contested.is_empty());
+    }
+
+    #[test]
+    fn deleting_a_value_the_other_side_edited_is_contested() {
+        let base = json!({"token": "abc"});
+        let ours = json!({"token": "def"});
+        let theirs = json!({});
+
+        let (merged, contested) = merge_documents(&base, &ours, &theirs);
+        assert_eq!(merged, json!({"token": "def"}));
+        assert_eq!(contested, ["/token"]);
+
+        // The same the other way around: our deletion is kept, but contested
+        let (merged, contested) = merge_documents(&base, &theirs, &ours);
+        assert_eq!(merged, json!({}));
+        assert_eq!(contested, ["/token"]);
+    }
+
+    #[test]
+    fn arrays_are_replaced_as_a_whole() {
+        let base = json!({"tags": ["a", "b"]});
+        let ours = json!({"tags": ["a", "b", "c"]});
+        let theirs = json!({"tags": ["a"]});
+
+        let (merged, contested) = merge_documents(&base, &ours, &theirs);
+        assert_eq!(merged, ours);
+        assert_eq!(contested, ["/tags"]);
+
+        let (merged, contested) = merge_documents(&base, &base, &theirs);
+        assert_eq!(merged, theirs);
+        assert!(contested.is_empty());
+    }
+
+    #[test]
+    fn added_members_are_merged_without_a_base() {
+        let mut contested = Vec::new();
+        let merged = merge(
+            String::new(),
+            None,
+            Some(&json!({"a": 1})),
+            Some(&json!({"b": 2})),
+            &mut contested,
+        );
+        assert_eq!(merged, Some(json!({"a": 1, "b": 2})));
+        assert!(contested.is_empty());
+    }
+
+    #[test]
+    fn save_merged_combines_concurrent_saves() {
+        let root = tempfile::tempdir().unwrap();
+        let options = ConfigOptions {
+            root: Some(root.path().to_path_buf()),
+            ..Default::default()
+        };
+        let mut first: Config<Value> = Config::load_with_options("jira", options.clone()).unwrap();
+        *first.data_mut() = json!({"board": "OPS", "limit": 10});
+        first.save().unwrap();
+
+        let mut second: Config<Value> = Config::load_with_options("jira", options.clone()).unwrap();
+        second.data_mut()["limit"] = json!(20);
+        second.save().unwrap();
+
+        first.data_mut()["board"] = json!("DEV");
+        first.save_merged().unwrap();
+        assert_eq!(first.data(), &json!({"board": "DEV", "limit": 20}));
+
+        second.data_mut()["board"] = json!("QA");
+        match second.save_merged() {
+            Err(ConfigError::MergeConflictError(_, pointers)) => assert_eq!(pointers, ["/board"]),
+            result => panic!("expected a merge conflict, got {:?}", result),
+        }
+        let saved: Config<Value> = Config::load_with_options("jira", options).unwrap();
+        assert_eq!(saved.data(), &json!({"board": "DEV", "limit": 20}));
+    }
+
+    #[test]
+    fn contested_pointers_are_escaped() {
+        let base = json!({"a/b": {"c~d": 1}});
+        let ours = json!({"a/b": {"c~d": 2}});
+        let theirs = json!({"a/b": {"c~d": 3}});
+
+        let (_, contested) = merge_documents(&base, &ours, &theirs);
+        assert_eq!(contested, ["/a~1b/c~0d"]);
+    }
+}
@@ -0,0 +1,132 @@
+//! Previewing how a config file will change when it is next saved, e.g. to check the effect of
+//! [`ConfigOptions::renamed_fields`](crate::ConfigOptions::renamed_fields) in CI before rolling
+//! out a new version of a config data type.
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+use crate::{
+    diff::{self, Change},
+    jsonc, Config, ConfigError, ConfigFormat, ConfigOptions,
+};
+
+/// What saving a config would change in its file, as returned by
+/// [`Config::migrate_dry_run`].
+///
+/// Its `Display` output is a readable summary, with the renamed fields followed by a diff of the
+/// document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MigrationReport {
+    /// The config file, which may not exist yet.
+    pub path: PathBuf,
+
+    /// Fields that would be moved from their old name to their new one, as `(old, new)` pairs of
+    /// dot-separated paths.
+    pub renames: Vec<(String, String)>,
+
+    /// The differences between the document on disk and the one that would be saved.
+    pub changes: Vec<Change>,
+}
+
+impl MigrationReport {
+    /// Whether saving would leave the file as it is (apart from formatting).
+    pub fn is_empty(&self) -> bool {
+        self.renames.is_empty() && self.changes.is_empty()
+    }
+}
+
+impl fmt::Display for MigrationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "{}: up to date", self.path.display());
+        }
+        for (old, new) in &self.renames {
+            writeln!(f, "rename `{}` to `{}`", old, new)?;
+        }
+        writeln!(f, "--- {}", self.path.display())?;
+        writeln!(f, "+++ {} (migrated)", self.path.display())?;
+        for change in &self.changes {
+            match change {
+                Change::Added { pointer, new } => writeln!(f, "+ {}: {}", pointer, new)?,
+                Change::Removed { pointer, old } => writeln!(f, "- {}: {}", pointer, old)?,
+                Change::Modified { pointer, old, new } => {
+                    writeln!(f, "- {}: {}", pointer, old)?;
+                    writeln!(f, "+ {}: {}", pointer, new)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<TConfigData: Serialize + DeserializeOwned> Config<TConfigData> {
+    /// Report what the next [`save`](Self::save) would change in the config file, without writing
+    /// anything: which [renamed fields](crate::ConfigOptions::renamed_fields) it still uses, and
+    /// a diff between the document on disk and the one that would be written.
+    ///
+    /// The diff also covers fields that the data type drops or fills in with defaults, and
+    /// changes that were made in memory since loading.
+    ///
+    /// ```no_run
+    /// # use ilo_config::{Config, ConfigOptions, RenamedFields};
+    /// let options = ConfigOptions {
+    ///     renamed_fields: RenamedFields::new().rename("hostname", "server.host"),
+    ///     ..Default::default()
+    /// };
+    /// let config: Config<serde_json::Value> = Config::load_with_options("jira", options)?;
+    /// let report = config.migrate_dry_run()?;
+    /// print!("{}", report);
+    /// # Ok::<(), ilo_config::ConfigError>(())
+    /// ```
+    pub fn migrate_dry_run(&self) -> Result<MigrationReport, ConfigError> {
+        let path = Self::get_config_path(&self.config_file_key, &self.options)?;
+        let migrated = self.to_document()?;
+        let on_disk = match &self.options.backend {
+            Some(backend) => backend
+                .load(&self.config_file_key)?
+                .map(|contents| raw_document(contents, &path, &self.options))
+                .transpose()?,
+            None if self.options.filesystem.is_file(&path) => {
+                let contents = Self::read_config_file(&path, &self.options)?;
+                Some(raw_document(contents, &path, &self.options)?)
+            }
+            None => None,
+        };
+
+        let on_disk = on_disk.unwrap_or_else(|| match migrated {
+            Value::Object(_) => Value::Object(Default::default()),
+            Value::Array(_) => Value::Array(Vec::new()),
+            _ => Value::Null,
+        });
+        Ok(MigrationReport {
+            renames: self.options.renamed_fields.pending(&on_disk),
+            changes: diff::diff(&on_disk, &migrated),
+            path,
+        })
+    }
+}
+
+/// The document in a config file as it is written, before any load-time options are applied.
+fn raw_document(
+    contents: Vec<u8>,
+    source: &Path,
+    options: &ConfigOptions,
+) -> Result<Value, ConfigError> {
+    #[cfg(feature = "encryption")]
+    let contents = crate::encryption::open_if_sealed(contents, source, options)?;
+    #[cfg(any(feature = "gzip", feature = "zstd"))]
+    let contents = crate::compression::decompress_if_compressed(contents, source)?;
+
+    let parse_error = |e| ConfigError::ConfigFileParseError(source.to_path_buf(), e, None);
+    match options.format {
+        ConfigFormat::Json => serde_json::from_slice(&contents).map_err(parse_error),
+        ConfigFormat::Jsonc => {
+            let (stripped, _) = jsonc::strip(&String::from_utf8_lossy(&contents));
+            serde_json::from_str(&stripped).map_err(parse_error)
+        }
+    }
+}
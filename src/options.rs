@@ -0,0 +1,435 @@
+//! Options for customizing how a config is loaded and saved.
+use std::{
+    borrow::Cow,
+    env,
+    fmt::Debug,
+    path::{Path, PathBuf},
+    sync::{Arc, Once},
+};
+
+use crate::{
+    backend::Backend, hooks::Hooks, resolve::Resolver, telemetry::Telemetry, Clock, ConfigFormat,
+    CorruptFilePolicy, DotEnv, FileSystem, Formatting, HistoryOptions, RenamedFields, RetryPolicy,
+    SecretRefresher, StdFileSystem, SystemClock, TemplateVariables, UnsavedChangesPolicy,
+};
+
+/// The environment variable that overrides the config root unless
+/// [`ConfigOptions::root_env_var`] says otherwise.
+pub const ROOT_ENV_VAR: &str = "ILO_CONFIG_HOME";
+
+/// The conventional location of system-wide configs, see [`ConfigOptions::system_root`].
+pub const SYSTEM_ROOT: &str = "/etc/ilo";
+
+/// Behavior switches for a [`Config`](crate::Config), passed to
+/// [`Config::load_with_options`](crate::Config::load_with_options).
+///
+/// All options are off by default, which matches the behavior of
+/// [`Config::load`](crate::Config::load).
+#[derive(Debug, Clone)]
+pub struct ConfigOptions {
+    /// Fail to load if the file contains fields that are not present in the config data type.
+    ///
+    /// Without this, unknown fields (e.g. typos in a hand-edited file) are silently dropped and
+    /// then erased from the file on the next save.
+    pub strict: bool,
+
+    /// Directory to use as the config root instead of `$ILO_CONFIG_HOME` or `~/.config/ilo/`.
+    pub root: Option<PathBuf>,
+
+    /// Environment variable that users can set to move the config root, instead of
+    /// `ILO_CONFIG_HOME` (see [`ROOT_ENV_VAR`]).  Apps can use their own name here, e.g.
+    /// `MYTOOL_CONFIG_HOME`, so their users don't need to know about ilo-config.  The variable is
+    /// read each time a config is loaded or saved.
+    pub root_env_var: Cow<'static, str>,
+
+    /// Keep configs in a subdirectory of the config root named after the app (or plugin), so
+    /// that apps sharing a root don't step on each other's keys.
+    pub namespace: Option<String>,
+
+    /// Maximum number of bytes that all files in the config root (or the namespace, if set) may
+    /// take up, including backups, history and caches.  The caches of remote documents and
+    /// secrets in the state directory (`http-cache/` and `aws-cache/`) count as well.
+    ///
+    /// Saves that would exceed the quota fail with `ConfigError::QuotaExceededError`.  See
+    /// [`Config::usage`](crate::Config::usage).
+    pub quota: Option<u64>,
+
+    /// Keep fields that are present in the file but not in the config data type, and write them
+    /// back on save.
+    ///
+    /// This makes it safe for an older version of an app to save a config that a newer version
+    /// added fields to.  Has no effect in `strict` mode, where unknown fields are an error.
+    pub preserve_unknown_fields: bool,
+
+    /// Config roots to try, in order, when `$ILO_CONFIG_HOME` is not set and the user's home
+    /// directory can't be determined (e.g. in some CI or container environments).
+    ///
+    /// If empty or if none of the fallbacks are usable, loading fails with `ConfigError::NoHome`.
+    pub root_fallbacks: Vec<RootFallback>,
+
+    /// After saving, read the file back and check that it matches the in-memory data.
+    ///
+    /// The previous version of the file is kept as `<key>.json.bak` until the check passes, and
+    /// left in place if it fails, for flaky filesystems where silent corruption is a concern.
+    pub verify_writes: bool,
+
+    /// How hard saves try to make sure the config ends up on disk.
+    pub save: SaveOptions,
+
+    /// Write the config file on every save.  By default, saving leaves the file untouched
+    /// (keeping its modification time) if its contents wouldn't change, so that apps which save
+    /// unconditionally don't churn backups and file watchers.  Encrypted configs are always
+    /// written, as are configs stored in a [`backend`](Self::backend).
+    pub always_write: bool,
+
+    /// When a save fails because the file was changed by someone else, also write both versions
+    /// with git-style conflict markers to `<key>.json.conflict`.
+    ///
+    /// Once resolved by hand, the file can be re-ingested with
+    /// [`Config::resolve_conflict`](crate::Config::resolve_conflict).
+    pub conflict_markers: bool,
+
+    /// Keep a snapshot of every saved version of the config, pruned according to the given
+    /// retention policy.  See [`Config::revisions`](crate::Config::revisions).
+    pub history: Option<HistoryOptions>,
+
+    /// Make the config root a git repository and commit every save, so the full history of the
+    /// config can be inspected with [`Config::git_log`](crate::Config::git_log) and synced with
+    /// the usual git tools.  Requires the `git` command line tool.
+    pub git: bool,
+
+    /// Document to load when the config file doesn't exist yet, instead of the config data
+    /// type's `Default` value, e.g. `Some(include_str!("defaults.json").into())`.
+    ///
+    /// The document is parsed like a config file in the configured format, so JSONC defaults can
+    /// carry comments that end up in the file on the first save.  With the `schemars` feature,
+    /// [`Config::template`](crate::Config::template) generates such a document from the doc
+    /// comments of the config data type.
+    pub defaults: Option<Cow<'static, str>>,
+
+    /// Substitute `{name}` placeholders (e.g. `{username}`) in the default document when the
+    /// config file doesn't exist yet, so that first-run configs are personalized.  Applies to
+    /// [`defaults`](Self::defaults) if set, or else to the config data type's `Default` value.
+    pub template_variables: Option<TemplateVariables>,
+
+    /// Resolve `${name}` references in string values when loading, e.g.
+    /// `"${HOME}/projects"` or `"${server.host}:8080"`.  A name is first looked up as a dotted
+    /// path in the same config file and then as an environment variable; unresolvable references
+    /// are an error.  Write `$${` for a literal `${`.
+    ///
+    /// Strings are saved in their raw form unless they were changed, and the raw form can be
+    /// inspected with [`Config::raw_value`](crate::Config::raw_value).
+    pub interpolate: bool,
+
+    /// Replace string values of the form `<scheme>://<reference>` with the values fetched by the
+    /// resolver for that scheme, e.g. `vault://secret/data/jira#token` with
+    /// [`VaultResolver`](crate::resolve::vault::VaultResolver), so secrets don't have to be
+    /// stored on disk.  Saving writes the references back.  See the [`resolve`](crate::resolve)
+    /// module.
+    pub resolvers: Vec<Arc<dyn Resolver>>,
+
+    /// Fields that were renamed in the config data type, which are still accepted under their
+    /// old names with a deprecation warning.
+    pub renamed_fields: RenamedFields,
+
+    /// Honor an `"$include": ["base.json"]` member at the top of config files: the named files
+    /// are read from the config root and deep-merged underneath the config's own values.  Names
+    /// outside the config root (absolute or with `..`) are rejected.  On save, only the values
+    /// that differ from the included files are written back.
+    pub includes: bool,
+
+    /// Merge the fragments in the `<key>.json.d/` directory next to the config file over the
+    /// config, in lexical order of their file names (like systemd and apt do), e.g.
+    /// `10-proxy.json` before `50-mirrors.json`.  Saving only writes to the config file itself:
+    /// values that still equal what a fragment set are left out.  Ignored with a
+    /// [`backend`](Self::backend).
+    pub drop_ins: bool,
+
+    /// Merge `<key>.<hostname>.json` next to the config file over the config, if it exists, so
+    /// that a config root that is synced between machines (e.g. with dotfiles) can carry
+    /// per-machine differences.  The host name is shortened to its first label and lowercased.
+    /// Saving only writes to the config file itself: values that still equal what the override
+    /// file set are left out.  Ignored with a [`backend`](Self::backend).
+    pub host_overrides: bool,
+
+    /// Merge `<key>.<platform>.json` next to the config file over the config, if it exists,
+    /// where the platform is e.g. `linux`, `macos` or `windows` (see [`std::env::consts::OS`]),
+    /// so that a config root that is synced between machines can have OS-specific values.
+    /// Host overrides are merged over platform overrides.  Saving works like with
+    /// [`host_overrides`](Self::host_overrides).  Ignored with a [`backend`](Self::backend).
+    pub platform_overrides: bool,
+
+    /// Directory with read-only, system-wide configs (usually [`SYSTEM_ROOT`]), e.g. for
+    /// org-wide defaults set by admins.  The config with the same key (in the same namespace) is
+    /// merged underneath the user's config but over the app's defaults; saving only ever writes
+    /// the user's config, leaving out values that are the same as in the system config.  Ignored
+    /// with a [`backend`](Self::backend).
+    pub system_root: Option<PathBuf>,
+
+    /// Merge the variables in a `.env` file over the config, with the config's prefix (e.g.
+    /// `JIRA_BASE_URL` for `base_url` of `jira`), so that development setups don't need to export
+    /// them; see [`DotEnv`] for where the file is found and how variables map to fields.
+    /// Variables that are set in the environment take precedence over the file.  Saving leaves
+    /// out values that still equal what the file set.  Ignored with a [`backend`](Self::backend).
+    pub dotenv: Option<DotEnv>,
+
+    /// UNIX permissions for config files (and their backups, history and exports) when they are
+    /// created; 0600 (user-only) by default, in case they contain sensitive data.  Use e.g. 0644
+    /// for configs that are meant to be shared with other users.
+    ///
+    /// Existing files keep their permissions.  The config root is always created as 0700.
+    pub file_mode: u32,
+
+    /// What to do when a config file is readable or writable by other users, the way ssh
+    /// refuses to use private keys with loose permissions.  Off by default.
+    ///
+    /// Use [`Config::fix_permissions`](crate::Config::fix_permissions) to tighten the
+    /// permissions.
+    pub permission_check: PermissionCheck,
+
+    /// Retry reads and writes of the config file that fail with errors that may be transient,
+    /// e.g. on network home directories.  Off by default.
+    pub retry: Option<RetryPolicy>,
+
+    /// What to do when the config file can't be parsed, e.g. after a bad hand edit: fail to load
+    /// (the default), move the file aside and load the defaults, or let a handler repair it.
+    pub corrupt_file_policy: CorruptFilePolicy,
+
+    /// What to do when a config is dropped with changes that were never saved: nothing (the
+    /// default), print a warning, panic in debug builds, or let a handler report it.
+    pub unsaved_changes: UnsavedChangesPolicy,
+
+    /// Append an entry to an audit log each time the config file is loaded, saved or deleted,
+    /// with a timestamp and the SHA-256 of the file, e.g. as evidence of when credentials
+    /// changed.  Operations fail if their entry can't be written.  Ignored with a
+    /// [`backend`](Self::backend).
+    #[cfg(feature = "audit-log")]
+    pub audit_log: Option<crate::audit::AuditLog>,
+
+    /// Encrypt the config file with a key derived from a passphrase.  Existing plaintext files
+    /// are still loaded and get encrypted on the next save.
+    #[cfg(feature = "encryption")]
+    pub encryption: Option<crate::Encryption>,
+
+    /// Sign the config file with an HMAC on every save, and fail to load it with
+    /// `ConfigError::TamperDetected` if it was changed by anyone without the key.  Ignored with a
+    /// [`backend`](Self::backend).
+    #[cfg(feature = "signing")]
+    pub signing: Option<crate::Signing>,
+
+    /// Move configs to the OS trash (recycle bin) instead of removing them for good when they are
+    /// deleted with [`Config::delete_file`](crate::Config::delete_file) or
+    /// [`purge_root`](crate::purge_root).  Bypasses [`filesystem`](Self::filesystem).
+    #[cfg(feature = "trash")]
+    pub trash: bool,
+
+    /// Compress the config file, e.g. for configs that hold large amounts of data.  The
+    /// compression's extension is appended to the file name (`<key>.json.zst`).  Compressed files
+    /// are decompressed when loading regardless of this option.
+    #[cfg(any(feature = "gzip", feature = "zstd"))]
+    pub compression: Option<crate::Compression>,
+
+    /// Format of the config file, which also determines its file extension.
+    pub format: ConfigFormat,
+
+    /// Layout of the config file when it is saved: key order, indentation and a trailing
+    /// newline.
+    pub formatting: Formatting,
+
+    /// Filesystem used for all file access; the real filesystem by default.
+    pub filesystem: Arc<dyn FileSystem>,
+
+    /// Source of the current time for timestamps; the system time by default.
+    pub clock: Arc<dyn Clock>,
+
+    /// Store the config document in a [`Backend`] (e.g. a remote server) instead of a file in the
+    /// config root.
+    ///
+    /// Conflict detection, write verification and revision history only apply to local files and
+    /// are skipped when a backend is used.
+    pub backend: Option<Arc<dyn Backend>>,
+
+    /// Fetches new values for expired secrets, see
+    /// [`Config::fresh_secret`](crate::Config::fresh_secret).
+    pub secret_refresher: Option<Arc<dyn SecretRefresher>>,
+
+    /// Receiver for reports about which optional features are used, for feeding into the app's
+    /// own telemetry.  See the [`telemetry`](crate::telemetry) module.
+    pub telemetry: Option<Arc<dyn Telemetry>>,
+
+    /// Receiver for the timings and failures of loads and saves, for recording them in the app's
+    /// metrics.  See the [`hooks`](crate::hooks) module.
+    pub hooks: Option<Arc<dyn Hooks>>,
+}
+
+impl Default for ConfigOptions {
+    fn default() -> Self {
+        Self {
+            strict: false,
+            root: None,
+            root_env_var: Cow::Borrowed(ROOT_ENV_VAR),
+            namespace: None,
+            quota: None,
+            preserve_unknown_fields: false,
+            root_fallbacks: Vec::new(),
+            verify_writes: false,
+            save: SaveOptions::default(),
+            always_write: false,
+            conflict_markers: false,
+            history: None,
+            git: false,
+            defaults: None,
+            template_variables: None,
+            interpolate: false,
+            resolvers: Vec::new(),
+            renamed_fields: RenamedFields::default(),
+            includes: false,
+            drop_ins: false,
+            host_overrides: false,
+            platform_overrides: false,
+            system_root: None,
+            dotenv: None,
+            file_mode: 0o600,
+            permission_check: PermissionCheck::Off,
+            corrupt_file_policy: CorruptFilePolicy::Error,
+            unsaved_changes: UnsavedChangesPolicy::Ignore,
+            retry: None,
+            #[cfg(feature = "audit-log")]
+            audit_log: None,
+            #[cfg(feature = "encryption")]
+            encryption: None,
+            #[cfg(feature = "signing")]
+            signing: None,
+            #[cfg(feature = "trash")]
+            trash: false,
+            #[cfg(any(feature = "gzip", feature = "zstd"))]
+            compression: None,
+            format: ConfigFormat::default(),
+            formatting: Formatting::default(),
+            filesystem: Arc::new(StdFileSystem),
+            clock: Arc::new(SystemClock),
+            backend: None,
+            secret_refresher: None,
+            telemetry: None,
+            hooks: None,
+        }
+    }
+}
+
+/// Durability settings for saving a config, set with [`ConfigOptions::save`].
+#[derive(Debug, Clone, Default)]
+pub struct SaveOptions {
+    /// Flush the config file and its parent directory to stable storage (fsync) before
+    /// [`Config::save`](crate::Config::save) returns, so a power loss right after the save can't
+    /// lose the data.  Off by default since it makes saves considerably slower.
+    pub fsync: bool,
+}
+
+/// How to react to config files with permissions that let other users access them, set with
+/// [`ConfigOptions::permission_check`].
+#[derive(Debug, Clone, Default)]
+pub enum PermissionCheck {
+    #[default]
+    Off,
+
+    /// Log a warning and load the config anyway.  The warning is a `tracing` event with the
+    /// `tracing` feature, or printed to stderr without it.
+    Warn,
+
+    /// Pass the file to the handler and load the config anyway.
+    Report(Arc<dyn PermissionHandler>),
+
+    /// Fail to load with `ConfigError::InsecurePermissionsError`.
+    Deny,
+}
+
+/// Receives a report each time a config file is loaded that other users can access, see
+/// [`PermissionCheck::Report`].
+pub trait PermissionHandler: Debug + Send + Sync {
+    /// Called when the file at `path` has the UNIX permissions `mode`.
+    fn insecure_permissions(&self, path: &Path, mode: u32);
+}
+
+/// A config root to use when the usual config root can't be determined.
+#[derive(Debug, Clone)]
+pub enum RootFallback {
+    /// Use the given directory, as long as it exists or its parent directory does.
+    Path(PathBuf),
+
+    /// Use a directory that only the current user can access in a temporary location:
+    /// `$XDG_RUNTIME_DIR/ilo/` if it is set, or `ilo-<uid>` in the system temp directory (e.g.
+    /// `$TMPDIR`).  On other platforms than UNIX, where the temp directory is per-user, it is
+    /// `ilo` in the temp directory.
+    ///
+    /// A directory that already exists is only used if it belongs to the current user and has
+    /// mode 0700, so other users on the machine can't plant configs or read them.  Configs
+    /// stored there are ephemeral, so a warning is logged the first time this fallback is used:
+    /// a `tracing` event with the `tracing` feature, or printed to stderr without it.
+    TempDir,
+}
+
+static TEMP_DIR_WARNING: Once = Once::new();
+
+impl RootFallback {
+    /// Get the root directory for this fallback, or `None` if it isn't usable.
+    pub(crate) fn resolve(&self) -> Option<PathBuf> {
+        match self {
+            RootFallback::Path(path) => {
+                let is_usable = path.is_dir() || path.parent().is_some_and(|p| p.is_dir());
+                is_usable.then(|| path.clone())
+            }
+            RootFallback::TempDir => {
+                let root = private_temp_dir()?;
+                TEMP_DIR_WARNING.call_once(|| {
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(
+                        path = %root.display(),
+                        "home directory could not be determined; configs are stored in a \
+                        temporary directory and may be lost"
+                    );
+                    #[cfg(not(feature = "tracing"))]
+                    eprintln!(
+                        "WARNING: home directory could not be determined; configs are stored in \
+                        {} and may be lost",
+                        root.display()
+                    );
+                });
+                Some(root)
+            }
+        }
+    }
+}
+
+/// The directory for [`RootFallback::TempDir`], created if needed, or `None` if there is none
+/// that only the current user can access.
+#[cfg(unix)]
+fn private_temp_dir() -> Option<PathBuf> {
+    use std::{
+        fs::{self, DirBuilder},
+        io,
+        os::unix::fs::{DirBuilderExt, MetadataExt},
+    };
+
+    // SAFETY: geteuid has no preconditions and can't fail
+    let uid = unsafe { libc::geteuid() };
+    let runtime_dir = env::var_os("XDG_RUNTIME_DIR")
+        .filter(|dir| !dir.is_empty())
+        .map(|dir| PathBuf::from(dir).join("ilo"));
+    let temp_dir = env::temp_dir().join(format!("ilo-{}", uid));
+    runtime_dir.into_iter().chain([temp_dir]).find(|dir| {
+        match DirBuilder::new().mode(0o700).create(dir) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+            Err(_) => return false,
+        }
+        // Symlinks aren't followed, so one planted by another user is refused too
+        fs::symlink_metadata(dir).is_ok_and(|metadata| {
+            metadata.is_dir() && metadata.uid() == uid && metadata.mode() & 0o777 == 0o700
+        })
+    })
+}
+
+#[cfg(not(unix))]
+fn private_temp_dir() -> Option<PathBuf> {
+    Some(env::temp_dir().join("ilo"))
+}
@@ -0,0 +1,128 @@
+//! Applying JSON Patch (RFC 6902) documents to config data, e.g. for changes pushed by an admin
+//! tool.
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+use crate::{
+    pointer::{self, array_index},
+    Config, ConfigError,
+};
+
+impl<TConfigData: Serialize + DeserializeOwned> Config<TConfigData> {
+    /// Apply a JSON Patch, an array of operations such as
+    /// `{ "op": "replace", "path": "/server/port", "value": 8443 }`.
+    ///
+    /// All six operations (`add`, `remove`, `replace`, `move`, `copy` and `test`) are supported.
+    /// The patch is applied atomically: if an operation fails (including a failed `test`), the
+    /// config is left unchanged and `ConfigError::ConfigPatchError` names the operation.  It is
+    /// also left unchanged, failing with `ConfigError::InvalidDataError`, if the result isn't
+    /// valid for the config data type.  Call [`save`](Self::save) to persist the change.
+    ///
+    /// ```no_run
+    /// # use ilo_config::Config;
+    /// # use serde_json::json;
+    /// let mut config: Config<serde_json::Value> = Config::load("deploy")?;
+    /// config.apply_patch(&json!([
+    ///     { "op": "test", "path": "/version", "value": 3 },
+    ///     { "op": "replace", "path": "/version", "value": 4 },
+    ///     { "op": "add", "path": "/mirrors/-", "value": "https://mirror.example.com" },
+    /// ]))?;
+    /// config.save()?;
+    /// # Ok::<(), ilo_config::ConfigError>(())
+    /// ```
+    pub fn apply_patch(&mut self, patch: &Value) -> Result<(), ConfigError> {
+        let operations = patch.as_array().ok_or_else(|| {
+            ConfigError::ConfigPatchError(0, String::from("a patch must be an array of operations"))
+        })?;
+        let mut document = self.data_document()?;
+        for (i, operation) in operations.iter().enumerate() {
+            apply(&mut document, operation).map_err(|e| ConfigError::ConfigPatchError(i, e))?;
+        }
+        self.replace_data(document)
+    }
+}
+
+fn apply(document: &mut Value, operation: &Value) -> Result<(), String> {
+    let member = |name: &str| {
+        operation
+            .get(name)
+            .ok_or_else(|| format!("operation has no `{}`", name))
+    };
+    let string_member = |name: &str| {
+        member(name)?
+            .as_str()
+            .ok_or_else(|| format!("`{}` must be a string", name))
+    };
+    let path = string_member("path")?;
+    match string_member("op")? {
+        "add" => add(document, path, member("value")?.clone()),
+        "remove" => remove(document, path).map(|_| ()),
+        "replace" => {
+            let target = document
+                .pointer_mut(path)
+                .ok_or_else(|| format!("no value at `{}` to replace", path))?;
+            *target = member("value")?.clone();
+            Ok(())
+        }
+        "move" => {
+            let from = string_member("from")?;
+            if path.starts_with(&format!("{}/", from)) {
+                return Err(format!("can't move `{}` into itself", from));
+            }
+            let value = remove(document, from)?;
+            add(document, path, value)
+        }
+        "copy" => {
+            let from = string_member("from")?;
+            let value = document
+                .pointer(from)
+                .ok_or_else(|| format!("no value at `{}` to copy", from))?
+                .clone();
+            add(document, path, value)
+        }
+        "test" => {
+            let expected = member("value")?;
+            match document.pointer(path) {
+                Some(actual) if actual == expected => Ok(()),
+                Some(actual) => Err(format!(
+                    "test failed: `{}` is {}, not {}",
+                    path, actual, expected
+                )),
+                None => Err(format!("test failed: there is no value at `{}`", path)),
+            }
+        }
+        op => Err(format!("unknown operation `{}`", op)),
+    }
+}
+
+/// Add a value: object members are added or replaced, array elements are inserted.
+fn add(document: &mut Value, path: &str, value: Value) -> Result<(), String> {
+    let Some((parent, token)) = pointer::split(path)? else {
+        *document = value;
+        return Ok(());
+    };
+    match document.pointer_mut(parent) {
+        Some(Value::Array(items)) => {
+            let index = array_index(&token, items.len(), true)?;
+            items.insert(index, value);
+            Ok(())
+        }
+        _ => pointer::set(document, path, value),
+    }
+}
+
+fn remove(document: &mut Value, path: &str) -> Result<Value, String> {
+    let Some((parent, token)) = pointer::split(path)? else {
+        return Err(String::from("can't remove the whole config"));
+    };
+    match document.pointer_mut(parent) {
+        Some(Value::Object(members)) => members
+            .shift_remove(&token)
+            .ok_or_else(|| format!("no value at `{}` to remove", path)),
+        Some(Value::Array(items)) => {
+            let index = array_index(&token, items.len(), false)?;
+            Ok(items.remove(index))
+        }
+        _ => Err(format!("no value at `{}` to remove", path)),
+    }
+}
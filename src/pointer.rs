@@ -0,0 +1,97 @@
+//! Addressing values in config data by JSON Pointer (RFC 6901), for generic tooling that doesn't
+//! know the config data type.
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+use crate::{Config, ConfigError};
+
+impl<TConfigData: Serialize + DeserializeOwned> Config<TConfigData> {
+    /// The value at a JSON Pointer such as `/servers/0/url`, or `None` if there is none.  The
+    /// empty pointer `""` refers to the whole config.
+    pub fn get_pointer(&self, pointer: &str) -> Result<Option<Value>, ConfigError> {
+        let mut document = self.data_document()?;
+        Ok(document.pointer_mut(pointer).map(Value::take))
+    }
+
+    /// Set the value at a JSON Pointer such as `/servers/0/url`.
+    ///
+    /// Object members are added or replaced, array elements replaced; `-` as the last token
+    /// appends to an array.  The parent must already exist.  Fails with
+    /// `ConfigError::InvalidDataError`, leaving the config unchanged, if the result isn't valid
+    /// for the config data type.  Call [`save`](Self::save) to persist the change.
+    pub fn set_pointer(&mut self, pointer: &str, value: Value) -> Result<(), ConfigError> {
+        let mut document = self.data_document()?;
+        set(&mut document, pointer, value)
+            .map_err(|message| ConfigError::InvalidPointerError(pointer.to_string(), message))?;
+        self.replace_data(document)
+    }
+
+    /// The config data as a JSON document.
+    pub(crate) fn data_document(&self) -> Result<Value, ConfigError> {
+        serde_json::to_value(&self.config_data).map_err(ConfigError::ConfigFileSerializeError)
+    }
+
+    /// Replace the config data with a JSON document, if it is valid for the config data type.
+    pub(crate) fn replace_data(&mut self, document: Value) -> Result<(), ConfigError> {
+        self.config_data =
+            serde_json::from_value(document).map_err(ConfigError::InvalidDataError)?;
+        self.unsaved.mark();
+        Ok(())
+    }
+}
+
+/// Split a pointer into the pointer to the parent and the unescaped last token, or `None` for
+/// the whole document.
+pub(crate) fn split(pointer: &str) -> Result<Option<(&str, String)>, String> {
+    if pointer.is_empty() {
+        return Ok(None);
+    }
+    if !pointer.starts_with('/') {
+        return Err(String::from(
+            "JSON Pointers must be empty or start with `/`",
+        ));
+    }
+    let (parent, token) = pointer.rsplit_once('/').expect("pointer starts with `/`");
+    Ok(Some((parent, token.replace("~1", "/").replace("~0", "~"))))
+}
+
+/// Set (add or replace) the value at a pointer.  The parent must already exist.
+pub(crate) fn set(document: &mut Value, pointer: &str, value: Value) -> Result<(), String> {
+    let Some((parent, token)) = split(pointer)? else {
+        *document = value;
+        return Ok(());
+    };
+    match document.pointer_mut(parent) {
+        Some(Value::Object(members)) => {
+            members.insert(token, value);
+            Ok(())
+        }
+        Some(Value::Array(items)) => {
+            let index = array_index(&token, items.len(), true)?;
+            if index == items.len() {
+                items.push(value);
+            } else {
+                items[index] = value;
+            }
+            Ok(())
+        }
+        Some(_) => Err(String::from("parent is not an object or array")),
+        None => Err(String::from("parent does not exist")),
+    }
+}
+
+/// Parse an array index token, where `-` stands for the end of the array.  With `allow_end`, the
+/// index just past the last element is valid as well.
+pub(crate) fn array_index(token: &str, len: usize, allow_end: bool) -> Result<usize, String> {
+    let index = match token {
+        "-" => len,
+        // RFC 6901 doesn't allow leading zeros
+        _ if token.len() > 1 && token.starts_with('0') => usize::MAX,
+        _ => token.parse().unwrap_or(usize::MAX),
+    };
+    if index < len || (allow_end && index == len) {
+        Ok(index)
+    } else {
+        Err(format!("index `{}` is out of bounds", token))
+    }
+}
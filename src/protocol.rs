@@ -0,0 +1,76 @@
+//! Versioning of the on-disk layout of a config root.
+//!
+//! Several versions of this crate may use the same config root at once, e.g. when two
+//! dependencies of an app embed different versions.  Everything they need to agree on (file
+//! names, lock files, sidecar files, history layout) is covered by a protocol version that is
+//! recorded in `<root>/.ilo-config.json`.  A version of the crate refuses to touch a root that
+//! was set up with a newer protocol than it understands instead of corrupting it.
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{ConfigError, ConfigOptions, RetryPolicy};
+
+/// Version of the on-disk layout written by this version of the crate.
+///
+/// Only bumped for changes that older versions can't safely work with.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+const METADATA_FILE: &str = ".ilo-config.json";
+
+#[derive(Serialize, Deserialize)]
+struct Metadata {
+    protocol: u32,
+}
+
+/// Check that the config root doesn't use a newer layout than this version of the crate.
+pub(crate) fn check(config_root: &Path, options: &ConfigOptions) -> Result<(), ConfigError> {
+    read(config_root, options).map(|_| ())
+}
+
+/// Like [`check`], and also record the protocol version if the root doesn't have one yet.
+pub(crate) fn ensure(config_root: &Path, options: &ConfigOptions) -> Result<(), ConfigError> {
+    if read(config_root, options)?.is_some_and(|protocol| protocol >= PROTOCOL_VERSION) {
+        return Ok(());
+    }
+
+    let metadata_path = config_root.join(METADATA_FILE);
+    let bytes = serde_json::to_vec_pretty(&Metadata {
+        protocol: PROTOCOL_VERSION,
+    })
+    .map_err(ConfigError::ConfigFileSerializeError)?;
+    RetryPolicy::run(
+        options.retry.as_ref(),
+        &metadata_path,
+        || {
+            options
+                .filesystem
+                .write(&metadata_path, &bytes, Some(0o600))
+        },
+        ConfigError::ConfigFileWriteError,
+    )
+}
+
+/// The protocol version recorded in the config root, if any.
+fn read(config_root: &Path, options: &ConfigOptions) -> Result<Option<u32>, ConfigError> {
+    let metadata_path = config_root.join(METADATA_FILE);
+    if !options.filesystem.is_file(&metadata_path) {
+        return Ok(None);
+    }
+
+    let contents = RetryPolicy::run(
+        options.retry.as_ref(),
+        &metadata_path,
+        || options.filesystem.read(&metadata_path),
+        ConfigError::ConfigFileLoadError,
+    )?;
+    let metadata: Metadata = serde_json::from_slice(&contents)
+        .map_err(|e| ConfigError::ConfigFileParseError(metadata_path.clone(), e, None))?;
+    if metadata.protocol > PROTOCOL_VERSION {
+        return Err(ConfigError::IncompatibleLayoutError(
+            config_root.to_path_buf(),
+            metadata.protocol,
+        ));
+    }
+    Ok(Some(metadata.protocol))
+}
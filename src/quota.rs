@@ -0,0 +1,74 @@
+//! Disk usage accounting for [`ConfigOptions::quota`](crate::ConfigOptions::quota).
+use std::path::Path;
+
+use crate::{environment, ConfigError, ConfigOptions};
+
+/// Caches of remotely stored documents and secrets in the state directory, which are filled
+/// through configs and so count towards their quota.
+const STATE_CACHES: [&str; 2] = ["http-cache", "aws-cache"];
+
+/// Total size in bytes of all files under the config root `config_root`, including
+/// subdirectories such as the revision history, and of the caches in the state directory.
+pub(crate) fn usage(config_root: &Path, options: &ConfigOptions) -> Result<u64, ConfigError> {
+    let mut total = dir_usage(config_root, options)?;
+    // Without a state directory, there are no caches in it either
+    if let Ok(state_home) = environment::state_home() {
+        for cache in STATE_CACHES {
+            total += dir_usage(&state_home.join(cache), options)?;
+        }
+    }
+    Ok(total)
+}
+
+/// Total size in bytes of all files under `dir`.  Symbolic links are neither counted nor
+/// followed, so links out of the directory (or back into it) can't inflate the total or loop.
+fn dir_usage(dir: &Path, options: &ConfigOptions) -> Result<u64, ConfigError> {
+    let load_error = |e| ConfigError::ConfigRootLoadError(dir.to_path_buf(), e);
+    if !options.filesystem.try_exists(dir).map_err(load_error)? {
+        return Ok(0);
+    }
+
+    let mut total = 0;
+    for path in options.filesystem.read_dir(dir).map_err(load_error)? {
+        total += if options.filesystem.is_symlink(&path) {
+            0
+        } else if options.filesystem.is_file(&path) {
+            options
+                .filesystem
+                .len(&path)
+                .map_err(|e| ConfigError::ConfigFileLoadError(path.clone(), e))?
+        } else {
+            dir_usage(&path, options)?
+        };
+    }
+    Ok(total)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::{fs, os::unix::fs::symlink};
+
+    use super::dir_usage;
+    use crate::ConfigOptions;
+
+    #[test]
+    fn symlinks_are_not_followed() {
+        let root = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        fs::create_dir(root.path().join("nested")).unwrap();
+        fs::write(root.path().join("a.json"), "[1, 2]").unwrap();
+        fs::write(root.path().join("nested/b.json"), "{}").unwrap();
+        fs::write(outside.path().join("big.json"), vec![b' '; 4096]).unwrap();
+        // A cycle back to the root, and links to files and directories outside of it
+        symlink(root.path(), root.path().join("nested/loop")).unwrap();
+        symlink(outside.path(), root.path().join("outside")).unwrap();
+        symlink(
+            outside.path().join("big.json"),
+            root.path().join("big.json"),
+        )
+        .unwrap();
+
+        let usage = dir_usage(root.path(), &ConfigOptions::default()).unwrap();
+        assert_eq!(usage, 8);
+    }
+}
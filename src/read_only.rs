@@ -0,0 +1,69 @@
+//! Read-only access to configs, e.g. for diagnostic tooling.
+use std::fmt::{self, Debug};
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{diff, Config, ConfigError, ConfigOptions};
+
+/// A config that can be inspected but not changed or saved.
+///
+/// Loading one never creates directories or files in the config root, which makes it safe to use
+/// in sandboxed contexts or on configs that belong to another app.  (A
+/// [`backend`](crate::ConfigOptions::backend) may still update its own cache.)
+pub struct ReadOnlyConfig<TConfigData: Serialize + DeserializeOwned> {
+    config: Config<TConfigData>,
+}
+
+impl<TConfigData: Serialize + DeserializeOwned + Debug> Debug for ReadOnlyConfig<TConfigData> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.config.fmt(f)
+    }
+}
+
+impl<TConfigData: Serialize + DeserializeOwned + Default> Config<TConfigData> {
+    /// Load a config for inspection only, without ever touching the filesystem beyond reading.
+    pub fn load_read_only(
+        config_file_key: &str,
+    ) -> Result<ReadOnlyConfig<TConfigData>, ConfigError> {
+        Self::load_read_only_with_options(config_file_key, ConfigOptions::default())
+    }
+
+    /// Like [`load_read_only`](Self::load_read_only), customizing the behavior with `options`.
+    pub fn load_read_only_with_options(
+        config_file_key: &str,
+        options: ConfigOptions,
+    ) -> Result<ReadOnlyConfig<TConfigData>, ConfigError> {
+        Ok(ReadOnlyConfig {
+            config: Self::load_with_options(config_file_key, options)?,
+        })
+    }
+}
+
+impl<TConfigData: Serialize + DeserializeOwned> ReadOnlyConfig<TConfigData> {
+    #[inline]
+    pub fn data(&self) -> &TConfigData {
+        self.config.data()
+    }
+
+    #[inline]
+    pub fn key(&self) -> &str {
+        self.config.key()
+    }
+
+    /// See [`Config::unknown_fields`].
+    #[inline]
+    pub fn unknown_fields(&self) -> &Map<String, Value> {
+        self.config.unknown_fields()
+    }
+
+    /// See [`Config::diff`].
+    pub fn diff(&self) -> Result<Vec<diff::Change>, ConfigError> {
+        self.config.diff()
+    }
+
+    /// Take the data out of the handle.
+    pub fn into_data(self) -> TConfigData {
+        self.config.config_data
+    }
+}
@@ -0,0 +1,79 @@
+//! Persistent "remember my last choice" lists for interactive CLIs.
+use std::collections::VecDeque;
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{environment, Config, ConfigError, ConfigOptions};
+
+/// A recently used value along with when it was last used.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecentEntry<T> {
+    pub value: T,
+
+    /// Seconds since the UNIX epoch.
+    pub used_at: u64,
+}
+
+/// Recently used values (last opened project, last selected server, ...), most recent first.
+///
+/// Stored as `<key>.json` in the state directory, which is `$ILO_STATE_HOME` or
+/// `~/.local/state/ilo/`, rather than alongside the configs.  At most `max_len` values are kept;
+/// using a value again moves it to the front instead of adding a duplicate.
+pub struct Recents<T: Serialize + DeserializeOwned + PartialEq> {
+    config: Config<VecDeque<RecentEntry<T>>>,
+    max_len: usize,
+}
+
+impl<T: Serialize + DeserializeOwned + PartialEq> Recents<T> {
+    /// Load the recently used values stored under a key.
+    pub fn load(key: &str, max_len: usize) -> Result<Self, ConfigError> {
+        Self::load_with_options(key, max_len, ConfigOptions::default())
+    }
+
+    /// Like [`load`](Self::load), customizing the behavior with `options`.
+    ///
+    /// Unless `options.root` is set, the state directory is used as the root.
+    pub fn load_with_options(
+        key: &str,
+        max_len: usize,
+        mut options: ConfigOptions,
+    ) -> Result<Self, ConfigError> {
+        if options.root.is_none() {
+            options.root = Some(environment::state_home()?);
+        }
+        let mut recents = Self {
+            config: Config::load_with_options(key, options)?,
+            max_len,
+        };
+        recents.config.data_mut().truncate(max_len);
+        Ok(recents)
+    }
+
+    /// Record that a value was just used.  Call [`save`](Self::save) to persist the change.
+    pub fn push(&mut self, value: T) {
+        let used_at = self.config.options.clock.unix_timestamp();
+        let entries = self.config.data_mut();
+        entries.retain(|entry| entry.value != value);
+        entries.push_front(RecentEntry { value, used_at });
+        entries.truncate(self.max_len);
+    }
+
+    /// The most recently used value, if any.
+    pub fn latest(&self) -> Option<&T> {
+        self.config.data().front().map(|entry| &entry.value)
+    }
+
+    /// All remembered values, most recent first.
+    pub fn iter(&self) -> impl Iterator<Item = &RecentEntry<T>> {
+        self.config.data().iter()
+    }
+
+    /// Forget all remembered values.
+    pub fn clear(&mut self) {
+        self.config.data_mut().clear();
+    }
+
+    pub fn save(&self) -> Result<(), ConfigError> {
+        self.config.save()
+    }
+}
@@ -0,0 +1,71 @@
+//! Recovering from config files that can't be parsed, see
+//! [`ConfigOptions::corrupt_file_policy`](crate::ConfigOptions::corrupt_file_policy).
+use std::{
+    fmt::Debug,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{ConfigError, ConfigOptions};
+
+/// Repairs config files that can't be parsed, e.g. by asking the user or by trying a lenient
+/// parser.
+pub trait CorruptFileHandler: Debug + Send + Sync {
+    /// Called with the raw contents of a config file that failed to parse with `error`.  Return
+    /// repaired contents to load instead (the file itself is only changed on the next save), or
+    /// `None` to fail with the error.
+    fn recover(&self, config_path: &Path, contents: &[u8], error: &ConfigError) -> Option<Vec<u8>>;
+}
+
+/// How to react to config files that can't be parsed.
+#[derive(Debug, Clone, Default)]
+pub enum CorruptFilePolicy {
+    /// Fail to load with `ConfigError::ConfigFileParseError`.
+    #[default]
+    Error,
+
+    /// Move the file aside to `<key>.json.corrupt-<timestamp>`, log a warning (a `tracing` event
+    /// with the `tracing` feature, or printed to stderr without it) and load the defaults, so
+    /// that the app keeps working.  Behaves like `Error` with a
+    /// [`backend`](crate::ConfigOptions::backend).
+    Quarantine,
+
+    /// Let a handler try to repair the contents.
+    Handler(Arc<dyn CorruptFileHandler>),
+}
+
+/// Move a corrupt config file aside, keeping its contents for manual recovery.
+pub(crate) fn quarantine(
+    config_path: &Path,
+    contents: &[u8],
+    error: &ConfigError,
+    options: &ConfigOptions,
+) -> Result<PathBuf, ConfigError> {
+    let mut file_name = config_path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(format!(".corrupt-{}", options.clock.unix_timestamp()));
+    let quarantine_path = config_path.with_file_name(file_name);
+
+    options
+        .filesystem
+        .write(&quarantine_path, contents, Some(options.file_mode))
+        .map_err(|e| ConfigError::ConfigFileWriteError(quarantine_path.clone(), e))?;
+    options
+        .filesystem
+        .remove_file(config_path)
+        .map_err(|e| ConfigError::ConfigFileWriteError(config_path.to_path_buf(), e))?;
+
+    #[cfg(feature = "tracing")]
+    tracing::warn!(
+        path = %config_path.display(),
+        quarantine_path = %quarantine_path.display(),
+        %error,
+        "moved corrupt config file aside and loaded the defaults instead"
+    );
+    #[cfg(not(feature = "tracing"))]
+    eprintln!(
+        "WARNING: {}; moved it to {} and loaded the defaults instead",
+        error,
+        quarantine_path.display()
+    );
+    Ok(quarantine_path)
+}
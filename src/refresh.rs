@@ -0,0 +1,93 @@
+//! Secrets that are fetched again when they expire, such as OAuth access tokens.
+use std::{error::Error, fmt::Debug, time::Duration};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{Config, ConfigError, Expiring, Secret};
+
+/// A secret along with its expiry time, refreshed by [`Config::fresh_secret`].
+pub type RefreshableSecret = Expiring<Secret>;
+
+/// Fetches new values for expired [`RefreshableSecret`]s, see
+/// [`ConfigOptions::secret_refresher`](crate::ConfigOptions::secret_refresher).
+pub trait SecretRefresher: Debug + Send + Sync {
+    /// Fetch a new value for the secret called `name` in the config `config_file_key`, along
+    /// with how long it stays valid.
+    fn refresh(
+        &self,
+        config_file_key: &str,
+        name: &str,
+    ) -> Result<(Secret, Duration), Box<dyn Error + Send + Sync>>;
+}
+
+impl<TConfigData: Serialize + DeserializeOwned> Config<TConfigData> {
+    /// The current value of a secret field, refreshed first if it is missing or has expired.
+    ///
+    /// `field` picks the secret out of the config data and `name` identifies it to the
+    /// [`secret_refresher`](crate::ConfigOptions::secret_refresher).  A refreshed secret is saved
+    /// right away, so other runs of the app can use it until it expires.  If the config already
+    /// had other unsaved changes, it is only marked as changed instead, so those aren't saved
+    /// behind the caller's back.  If saving fails, the error is returned and the new secret stays
+    /// in memory as an unsaved change.  Expiry is checked against
+    /// [`ConfigOptions::clock`](crate::ConfigOptions::clock).
+    ///
+    /// ```no_run
+    /// # use std::{error::Error, sync::Arc, time::Duration};
+    /// # use serde::{Deserialize, Serialize};
+    /// use ilo_config::{Config, ConfigOptions, RefreshableSecret, Secret, SecretRefresher};
+    ///
+    /// #[derive(Debug)]
+    /// struct OAuth;
+    ///
+    /// impl SecretRefresher for OAuth {
+    ///     fn refresh(
+    ///         &self,
+    ///         _config_file_key: &str,
+    ///         _name: &str,
+    ///     ) -> Result<(Secret, Duration), Box<dyn Error + Send + Sync>> {
+    ///         // Exchange a refresh token for a new access token here.
+    ///         Ok((Secret::new("new-token"), Duration::from_secs(3600)))
+    ///     }
+    /// }
+    ///
+    /// #[derive(Default, Serialize, Deserialize)]
+    /// struct Jira {
+    ///     access_token: Option<RefreshableSecret>,
+    /// }
+    ///
+    /// let options = ConfigOptions {
+    ///     secret_refresher: Some(Arc::new(OAuth)),
+    ///     ..Default::default()
+    /// };
+    /// let mut config: Config<Jira> = Config::load_with_options("jira", options)?;
+    /// let token = config.fresh_secret("access_token", |jira| &mut jira.access_token)?;
+    /// # Ok::<(), ilo_config::ConfigError>(())
+    /// ```
+    pub fn fresh_secret(
+        &mut self,
+        name: &str,
+        field: impl Fn(&mut TConfigData) -> &mut Option<RefreshableSecret>,
+    ) -> Result<Secret, ConfigError> {
+        let now = self.options.clock.now();
+        if let Some(secret) = field(&mut self.config_data)
+            .as_ref()
+            .and_then(|secret| secret.get_at(now))
+        {
+            return Ok(secret.clone());
+        }
+
+        let refresher = self.options.secret_refresher.clone().ok_or_else(|| {
+            ConfigError::SecretRefreshError(name.to_string(), "no secret refresher is set".into())
+        })?;
+        let (secret, ttl) = refresher
+            .refresh(&self.config_file_key, name)
+            .map_err(|e| ConfigError::SecretRefreshError(name.to_string(), e))?;
+        let had_unsaved_changes = self.has_unsaved_changes();
+        *field(&mut self.config_data) = Some(Expiring::with_ttl_at(secret.clone(), ttl, now));
+        self.unsaved.mark();
+        if !had_unsaved_changes {
+            self.save()?;
+        }
+        Ok(secret)
+    }
+}
@@ -0,0 +1,128 @@
+//! Renamed fields, so config data types can evolve without breaking existing config files, see
+//! [`ConfigOptions::renamed_fields`](crate::ConfigOptions::renamed_fields).
+use std::{fmt::Debug, path::Path, sync::Arc};
+
+use serde_json::Value;
+
+/// Receives a report each time a config file is loaded that still uses an old field name.
+pub trait DeprecationHandler: Debug + Send + Sync {
+    /// Called when `old` (a dot-separated path) was read from `source` as `new`.
+    fn renamed_field(&self, source: &Path, old: &str, new: &str);
+}
+
+/// Fields that were renamed (or moved) in the config data type.
+///
+/// When loading, a value under an old name is moved to its new name before deserializing, and a
+/// deprecation warning is logged (a `tracing` event with the `tracing` feature, or printed to
+/// stderr without it) or passed to a [`DeprecationHandler`].  Saving only ever writes the new
+/// name, so files migrate on the first save.  If a file has both names, the value under the new
+/// name wins.
+///
+/// ```
+/// # use ilo_config::{ConfigOptions, RenamedFields};
+/// let options = ConfigOptions {
+///     renamed_fields: RenamedFields::new()
+///         .rename("hostname", "server.host")
+///         .rename("server.user", "server.username"),
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RenamedFields {
+    renames: Vec<(String, String)>,
+    handler: Option<Arc<dyn DeprecationHandler>>,
+}
+
+impl RenamedFields {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read the field at the dot-separated path `old` as `new`.  Renames are applied in the
+    /// order they are added, so a field renamed twice can be given as `a` to `b` and `b` to `c`.
+    pub fn rename(mut self, old: impl Into<String>, new: impl Into<String>) -> Self {
+        self.renames.push((old.into(), new.into()));
+        self
+    }
+
+    /// Report old field names to `handler` instead of logging warnings.
+    pub fn with_handler(mut self, handler: Arc<dyn DeprecationHandler>) -> Self {
+        self.handler = Some(handler);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.renames.is_empty()
+    }
+
+    /// Move values from old to new names in a document read from `source`.
+    pub(crate) fn apply(&self, document: &mut Value, source: &Path) {
+        self.rename_all(document, |old, new| match &self.handler {
+            Some(handler) => handler.renamed_field(source, old, new),
+            None => {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(path = %source.display(), old, new, "deprecated field name");
+                #[cfg(not(feature = "tracing"))]
+                eprintln!(
+                    "WARNING: {}: `{}` is deprecated, use `{}` instead",
+                    source.display(),
+                    old,
+                    new
+                );
+            }
+        });
+    }
+
+    /// The renames that loading `document` would apply, as `(old, new)` pairs, without reporting
+    /// them.
+    pub(crate) fn pending(&self, document: &Value) -> Vec<(String, String)> {
+        let mut pending = Vec::new();
+        self.rename_all(&mut document.clone(), |old, new| {
+            pending.push((old.to_string(), new.to_string()))
+        });
+        pending
+    }
+
+    fn rename_all(&self, document: &mut Value, mut renamed: impl FnMut(&str, &str)) {
+        for (old, new) in &self.renames {
+            let Some(value) = take(document, old) else {
+                continue;
+            };
+            renamed(old, new);
+            insert(document, new, value);
+        }
+    }
+}
+
+/// Remove the value at a dot-separated path.
+fn take(document: &mut Value, path: &str) -> Option<Value> {
+    let (parent, key) = match path.rsplit_once('.') {
+        Some((parent, key)) => (
+            parent
+                .split('.')
+                .try_fold(document, |value, key| value.get_mut(key))?,
+            key,
+        ),
+        None => (document, path),
+    };
+    parent.as_object_mut()?.shift_remove(key)
+}
+
+/// Set the value at a dot-separated path unless there already is one, creating missing parent
+/// objects.
+fn insert(document: &mut Value, path: &str, value: Value) {
+    let mut parent = document;
+    let mut keys = path.split('.').peekable();
+    while let Some(key) = keys.next() {
+        let Some(map) = parent.as_object_mut() else {
+            return;
+        };
+        if keys.peek().is_none() {
+            map.entry(key).or_insert(value);
+            return;
+        }
+        parent = map
+            .entry(key)
+            .or_insert_with(|| Value::Object(Default::default()));
+    }
+}
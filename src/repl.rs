@@ -0,0 +1,181 @@
+//! Interactive prompt for inspecting and editing a loaded config, for debugging.
+//!
+//! Changes made with `set` are staged and validated against the config data type, and are only
+//! applied (and saved) with `commit`.
+//!
+//! ```text
+//! > get servers.0
+//! > set servers.0.port 8080
+//! > diff
+//! > commit
+//! ```
+use std::io::{self, BufRead, Write};
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+use crate::{
+    diff::{self, Change},
+    Config, ConfigError,
+};
+
+const HELP: &str = "\
+Commands:
+  get [path]          Print the staged value at a dot-separated path, e.g. `servers.0.url`
+  set <path> <json>   Stage a new value; strings may be given without quotes
+  unset <path>        Stage the removal of an object member
+  diff                Show staged changes compared to the loaded config
+  reset               Discard staged changes
+  commit              Apply staged changes and save the config
+  help                Show this message
+  quit                Leave without applying staged changes";
+
+/// Run the interactive prompt on stdin and stdout.
+pub fn run<TConfigData: Serialize + DeserializeOwned>(
+    config: &mut Config<TConfigData>,
+) -> Result<(), ConfigError> {
+    run_with(config, io::stdin().lock(), io::stdout().lock())
+}
+
+/// Run the interactive prompt with the given input and output, e.g. for scripted sessions.
+///
+/// Returns an error only if the config can't be serialized or saved; problems with individual
+/// commands are reported to `output`.
+pub fn run_with<TConfigData: Serialize + DeserializeOwned>(
+    config: &mut Config<TConfigData>,
+    input: impl BufRead,
+    mut output: impl Write,
+) -> Result<(), ConfigError> {
+    let mut loaded = to_document(config)?;
+    let mut staged = loaded.clone();
+
+    // Write errors on the output are not interesting to the caller; the session just ends.
+    let _ = writeln!(
+        output,
+        "Editing config `{}`. Type `help` for commands.",
+        config.key()
+    );
+    let mut lines = input.lines();
+    loop {
+        let _ = write!(output, "> ").and_then(|_| output.flush());
+        let Some(Ok(line)) = lines.next() else {
+            break;
+        };
+        let line = line.trim();
+        let (command, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let rest = rest.trim();
+
+        let message = match command {
+            "" => continue,
+            "help" => HELP.to_string(),
+            "quit" | "exit" => break,
+            "get" => match staged.pointer(&to_pointer(rest)) {
+                Some(value) => pretty(value),
+                None => format!("No value at `{}`", rest),
+            },
+            "set" | "unset" => {
+                let (path, raw_value) = rest.split_once(' ').unwrap_or((rest, ""));
+                let new_value = (command == "set").then(|| {
+                    serde_json::from_str(raw_value.trim())
+                        .unwrap_or_else(|_| Value::String(raw_value.trim().to_string()))
+                });
+                let mut candidate = staged.clone();
+                match edit(&mut candidate, &to_pointer(path), new_value) {
+                    Err(message) => message,
+                    Ok(()) => match serde_json::from_value::<TConfigData>(candidate.clone()) {
+                        Ok(_) => {
+                            staged = candidate;
+                            format!("Staged change to `{}`", path)
+                        }
+                        Err(e) => format!("Rejected, config would not be valid: {}", e),
+                    },
+                }
+            }
+            "diff" => describe(&diff::diff(&loaded, &staged)),
+            "reset" => {
+                staged = loaded.clone();
+                "Discarded staged changes".to_string()
+            }
+            "commit" => {
+                *config.data_mut() = serde_json::from_value(staged.clone())
+                    .map_err(ConfigError::ConfigFileSerializeError)?;
+                config.save()?;
+                loaded = staged.clone();
+                "Saved".to_string()
+            }
+            _ => format!("Unknown command `{}`. Type `help` for commands.", command),
+        };
+        let _ = writeln!(output, "{}", message);
+    }
+    Ok(())
+}
+
+fn to_document<TConfigData: Serialize + DeserializeOwned>(
+    config: &Config<TConfigData>,
+) -> Result<Value, ConfigError> {
+    serde_json::to_value(config.data()).map_err(ConfigError::ConfigFileSerializeError)
+}
+
+/// Convert a dot-separated path (`servers.0.url`) into a JSON Pointer (`/servers/0/url`).
+fn to_pointer(path: &str) -> String {
+    path.split('.')
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| format!("/{}", segment.replace('~', "~0").replace('/', "~1")))
+        .collect()
+}
+
+/// Set (or with `None`, remove) the value at a pointer.  The parent must already exist.
+fn edit(document: &mut Value, pointer: &str, value: Option<Value>) -> Result<(), String> {
+    let Some((parent_pointer, key)) = pointer.rsplit_once('/') else {
+        return match value {
+            Some(value) => {
+                *document = value;
+                Ok(())
+            }
+            None => Err("Can't remove the whole config".to_string()),
+        };
+    };
+    let key = key.replace("~1", "/").replace("~0", "~");
+    match (document.pointer_mut(parent_pointer), value) {
+        (Some(Value::Object(map)), Some(value)) => {
+            map.insert(key, value);
+            Ok(())
+        }
+        (Some(Value::Object(map)), None) => map
+            .shift_remove(&key)
+            .map(|_| ())
+            .ok_or_else(|| format!("No member `{}` to remove", key)),
+        (Some(Value::Array(items)), Some(value)) => match key.parse::<usize>() {
+            Ok(index) if index < items.len() => {
+                items[index] = value;
+                Ok(())
+            }
+            Ok(index) if index == items.len() => {
+                items.push(value);
+                Ok(())
+            }
+            _ => Err(format!("Index `{}` is out of bounds", key)),
+        },
+        (Some(_), _) => Err("Parent is not an object or array".to_string()),
+        (None, _) => Err("Parent does not exist".to_string()),
+    }
+}
+
+fn pretty(value: &Value) -> String {
+    serde_json::to_string_pretty(value).expect("JSON values always serialize")
+}
+
+fn describe(changes: &[Change]) -> String {
+    if changes.is_empty() {
+        return "No staged changes".to_string();
+    }
+    changes
+        .iter()
+        .map(|change| match change {
+            Change::Added { pointer, new } => format!("+ {}: {}", pointer, new),
+            Change::Removed { pointer, old } => format!("- {}: {}", pointer, old),
+            Change::Modified { pointer, old, new } => format!("~ {}: {} -> {}", pointer, old, new),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
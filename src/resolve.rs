@@ -0,0 +1,89 @@
+//! Resolvers for references to values stored outside the config file, such as secrets in a
+//! vault.
+//!
+//! With [`ConfigOptions::resolvers`](crate::ConfigOptions::resolvers), string values of the form
+//! `<scheme>://<reference>` are replaced at load time with the value fetched by the resolver for
+//! that scheme.  The fetched values are never written to disk: saving writes the reference back,
+//! unless the value was changed.
+use std::{error::Error, fmt::Debug, path::Path, sync::Arc};
+
+use serde_json::Value;
+
+use crate::{interpolate::Interpolations, ConfigError};
+
+#[cfg(feature = "aws")]
+pub mod aws;
+#[cfg(feature = "vault")]
+pub mod vault;
+
+/// Fetches the values that references with a particular scheme refer to.
+pub trait Resolver: Debug + Send + Sync {
+    /// URI scheme of the references this resolver handles, e.g. `vault` for `vault://...`.
+    fn scheme(&self) -> &str;
+
+    /// Fetch the value that `reference` (without the `<scheme>://` prefix) refers to.
+    fn resolve(&self, reference: &str) -> Result<String, Box<dyn Error + Send + Sync>>;
+}
+
+/// Replace all references in a document with the values they refer to.
+pub(crate) fn resolve(
+    document: &mut Value,
+    resolvers: &[Arc<dyn Resolver>],
+    source: &Path,
+) -> Result<Interpolations, ConfigError> {
+    let mut resolved = Interpolations::default();
+    visit(document, String::new(), resolvers, source, &mut resolved)?;
+    Ok(resolved)
+}
+
+fn visit(
+    value: &mut Value,
+    pointer: String,
+    resolvers: &[Arc<dyn Resolver>],
+    source: &Path,
+    resolved: &mut Interpolations,
+) -> Result<(), ConfigError> {
+    match value {
+        Value::String(raw) => {
+            let Some((scheme, reference)) = raw.split_once("://") else {
+                return Ok(());
+            };
+            let Some(resolver) = resolvers
+                .iter()
+                .find(|resolver| resolver.scheme() == scheme)
+            else {
+                return Ok(());
+            };
+            let value = resolver.resolve(reference).map_err(|e| {
+                ConfigError::ConfigResolveError(source.to_path_buf(), raw.clone(), e)
+            })?;
+            resolved.insert(pointer, raw.clone(), value.clone());
+            *raw = value;
+        }
+        Value::Object(map) => {
+            for (key, child) in map.iter_mut() {
+                let escaped = key.replace('~', "~0").replace('/', "~1");
+                visit(
+                    child,
+                    format!("{}/{}", pointer, escaped),
+                    resolvers,
+                    source,
+                    resolved,
+                )?;
+            }
+        }
+        Value::Array(items) => {
+            for (i, child) in items.iter_mut().enumerate() {
+                visit(
+                    child,
+                    format!("{}/{}", pointer, i),
+                    resolvers,
+                    source,
+                    resolved,
+                )?;
+            }
+        }
+        _ => (),
+    }
+    Ok(())
+}
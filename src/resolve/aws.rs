@@ -0,0 +1,313 @@
+//! Resolvers for secrets in AWS Systems Manager Parameter Store and AWS Secrets Manager.
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt,
+    path::PathBuf,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, SystemTime},
+};
+
+use reqwest::{blocking::Client, Method, Url};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    aws::{self, Credentials, Signer},
+    environment,
+    resolve::Resolver,
+    Clock, ConfigOptions, FileSystem,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Service {
+    ParameterStore,
+    SecretsManager,
+}
+
+/// Resolves `ssm://<name>` references to SSM Parameter Store parameters, or
+/// `aws-secrets://<name>` references to Secrets Manager secrets, depending on how it was
+/// created.  Requests are signed with the credentials in `$AWS_ACCESS_KEY_ID`,
+/// `$AWS_SECRET_ACCESS_KEY` and (optionally) `$AWS_SESSION_TOKEN` unless others are given with
+/// [`credentials`](Self::credentials).
+///
+/// Parameter names are hierarchical, so a leading `/` is added to names that contain one, e.g.
+/// `ssm://ci/deploy/token` refers to `/ci/deploy/token`.  Parameters are decrypted.  For secrets
+/// that hold JSON, a single field can be picked with `aws-secrets://<name>#<field>`.
+///
+/// Fetched values are cached in memory for [`cache_ttl`](Self::cache_ttl), and the last fetched
+/// value of each reference is kept in `aws-cache/` under the state directory (`$ILO_STATE_HOME`
+/// or `~/.local/state/ilo/`, user-only), to fall back on when AWS can't be reached.  Use
+/// [`cache_options`](Self::cache_options) to take the time and access the files through the
+/// options the resolver is used with.
+///
+/// ```no_run
+/// # use std::sync::Arc;
+/// # use ilo_config::{resolve::aws::AwsResolver, Config, ConfigOptions};
+/// let options = ConfigOptions {
+///     resolvers: vec![
+///         Arc::new(AwsResolver::parameter_store("eu-west-1")),
+///         Arc::new(AwsResolver::secrets_manager("eu-west-1")),
+///     ],
+///     ..Default::default()
+/// };
+/// let config: Config<serde_json::Value> = Config::load_with_options("deploy", options).unwrap();
+/// ```
+pub struct AwsResolver {
+    service: Service,
+    region: String,
+    endpoint: String,
+    credentials: Credentials,
+    timeout: Duration,
+    cache_ttl: Duration,
+    cache: Mutex<HashMap<String, (String, SystemTime)>>,
+    offline_dir: Option<PathBuf>,
+    filesystem: Arc<dyn FileSystem>,
+    file_mode: u32,
+    clock: Arc<dyn Clock>,
+}
+
+impl fmt::Debug for AwsResolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AwsResolver")
+            .field("service", &self.service)
+            .field("region", &self.region)
+            .field("endpoint", &self.endpoint)
+            .field("access_key_id", &self.credentials.access_key_id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AwsResolver {
+    /// Resolver for `ssm://` references to SSM Parameter Store parameters.
+    pub fn parameter_store(region: impl Into<String>) -> Self {
+        Self::new(Service::ParameterStore, region.into())
+    }
+
+    /// Resolver for `aws-secrets://` references to Secrets Manager secrets.
+    pub fn secrets_manager(region: impl Into<String>) -> Self {
+        Self::new(Service::SecretsManager, region.into())
+    }
+
+    fn new(service: Service, region: String) -> Self {
+        let prefix = match service {
+            Service::ParameterStore => "ssm",
+            Service::SecretsManager => "secretsmanager",
+        };
+        let options = ConfigOptions::default();
+        Self {
+            service,
+            endpoint: format!("https://{}.{}.amazonaws.com", prefix, region),
+            region,
+            credentials: Credentials::from_env(),
+            timeout: Duration::from_secs(10),
+            cache_ttl: Duration::from_secs(300),
+            cache: Mutex::new(HashMap::new()),
+            offline_dir: environment::state_home()
+                .ok()
+                .map(|state_home| state_home.join("aws-cache")),
+            filesystem: options.filesystem,
+            file_mode: options.file_mode,
+            clock: options.clock,
+        }
+    }
+
+    /// Send requests to this base URL instead, e.g. for a VPC endpoint or LocalStack.
+    pub fn endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.endpoint = endpoint.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn credentials(
+        mut self,
+        access_key_id: impl Into<String>,
+        secret_access_key: impl Into<String>,
+        session_token: Option<String>,
+    ) -> Self {
+        self.credentials = Credentials {
+            access_key_id: access_key_id.into(),
+            secret_access_key: secret_access_key.into(),
+            session_token,
+        };
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// How long fetched values are reused before fetching them again; 5 minutes by default.
+    pub fn cache_ttl(mut self, cache_ttl: Duration) -> Self {
+        self.cache_ttl = cache_ttl;
+        self
+    }
+
+    /// Keep the last fetched values in this directory instead of the default one.
+    pub fn offline_cache_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.offline_dir = Some(dir.into());
+        self
+    }
+
+    /// Don't keep fetched values on disk, so loading fails when AWS can't be reached.
+    pub fn no_offline_cache(mut self) -> Self {
+        self.offline_dir = None;
+        self
+    }
+
+    /// Expire cached values by the [`clock`](ConfigOptions::clock), and access the offline cache
+    /// through the [`filesystem`](ConfigOptions::filesystem) and with the
+    /// [`file_mode`](ConfigOptions::file_mode) of `options`, usually the options the resolver is
+    /// used with.  By default the system time, the real filesystem and mode 0600 are used.
+    pub fn cache_options(mut self, options: &ConfigOptions) -> Self {
+        self.filesystem = options.filesystem.clone();
+        self.file_mode = options.file_mode;
+        self.clock = options.clock.clone();
+        self
+    }
+
+    fn fetch(&self, reference: &str) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let (target, body, name) = match self.service {
+            Service::ParameterStore => {
+                let name = if reference.contains('/') && !reference.starts_with('/') {
+                    format!("/{}", reference)
+                } else {
+                    reference.to_string()
+                };
+                let body = json!({ "Name": name, "WithDecryption": true });
+                ("AmazonSSM.GetParameter", body, name)
+            }
+            Service::SecretsManager => {
+                let name = reference.split('#').next().unwrap_or_default().to_string();
+                let body = json!({ "SecretId": name });
+                ("secretsmanager.GetSecretValue", body, name)
+            }
+        };
+        let service = match self.service {
+            Service::ParameterStore => "ssm",
+            Service::SecretsManager => "secretsmanager",
+        };
+
+        let client = Client::builder().timeout(self.timeout).build()?;
+        let headers = vec![
+            ("content-type", String::from("application/x-amz-json-1.1")),
+            ("x-amz-target", target.to_string()),
+        ];
+        let signer = Signer {
+            service,
+            region: &self.region,
+            credentials: &self.credentials,
+        };
+        let response = signer
+            .request(
+                &client,
+                Method::POST,
+                Url::parse(&format!("{}/", self.endpoint))?,
+                headers,
+                serde_json::to_vec(&body)?,
+            )?
+            .send()?;
+        let status = response.status();
+        let body = response.bytes()?;
+        if !status.is_success() {
+            return Err(format!("{} ({})", status, String::from_utf8_lossy(&body)).into());
+        }
+
+        let response: Value = serde_json::from_slice(&body)?;
+        let value = match self.service {
+            Service::ParameterStore => response["Parameter"]["Value"].as_str(),
+            Service::SecretsManager => response["SecretString"].as_str(),
+        }
+        .ok_or_else(|| format!("{} has no string value", name))?;
+        match reference.split_once('#') {
+            Some((_, field)) if self.service == Service::SecretsManager => {
+                let secret: Value = serde_json::from_str(value)
+                    .map_err(|e| format!("secret {} is not a JSON object: {}", name, e))?;
+                match secret.get(field) {
+                    Some(Value::String(value)) => Ok(value.clone()),
+                    Some(value) => Ok(value.to_string()),
+                    None => Err(format!("secret {} has no field `{}`", name, field).into()),
+                }
+            }
+            _ => Ok(value.to_string()),
+        }
+    }
+
+    fn offline_path(&self, reference: &str) -> Option<PathBuf> {
+        let id = format!("{}://{}", self.scheme(), reference);
+        self.offline_dir
+            .as_ref()
+            .map(|dir| dir.join(aws::hex(&Sha256::digest(id.as_bytes()))))
+    }
+
+    /// Keep the last fetched value on disk.  This is best-effort, so failures are ignored.
+    fn store_offline(&self, reference: &str, value: &str) {
+        let Some(path) = self.offline_path(reference) else {
+            return;
+        };
+        let _ = path
+            .parent()
+            .map_or(Ok(()), |dir| {
+                self.filesystem.create_dir_all(dir, Some(0o700))
+            })
+            .and_then(|_| {
+                self.filesystem
+                    .write(&path, value.as_bytes(), Some(self.file_mode))
+            })
+            // Copies written by older versions may be readable by others
+            .and_then(|_| self.filesystem.set_permissions(&path, self.file_mode));
+    }
+}
+
+impl Resolver for AwsResolver {
+    fn scheme(&self) -> &str {
+        match self.service {
+            Service::ParameterStore => "ssm",
+            Service::SecretsManager => "aws-secrets",
+        }
+    }
+
+    fn resolve(&self, reference: &str) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let cache = || self.cache.lock().unwrap_or_else(PoisonError::into_inner);
+        let now = self.clock.now();
+        if let Some((value, fetched_at)) = cache().get(reference) {
+            if now
+                .duration_since(*fetched_at)
+                .is_ok_and(|age| age < self.cache_ttl)
+            {
+                return Ok(value.clone());
+            }
+        }
+
+        // Not holding the lock, so other references can be resolved in the meantime
+        match self.fetch(reference) {
+            Ok(value) => {
+                self.store_offline(reference, &value);
+                cache().insert(reference.to_string(), (value.clone(), self.clock.now()));
+                Ok(value)
+            }
+            Err(e) => match self
+                .offline_path(reference)
+                .filter(|path| self.filesystem.is_file(path))
+            {
+                Some(path) => {
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(
+                        reference = %format_args!("{}://{}", self.scheme(), reference),
+                        error = %e,
+                        "could not fetch secret; using the last fetched value"
+                    );
+                    #[cfg(not(feature = "tracing"))]
+                    eprintln!(
+                        "WARNING: could not fetch {}://{} ({}); using the last fetched value",
+                        self.scheme(),
+                        reference,
+                        e
+                    );
+                    Ok(String::from_utf8(self.filesystem.read(&path)?)?)
+                }
+                None => Err(e),
+            },
+        }
+    }
+}
@@ -0,0 +1,109 @@
+//! Resolver for secrets stored in HashiCorp Vault.
+use std::{env, error::Error, time::Duration};
+
+use reqwest::blocking::Client;
+use serde_json::Value;
+
+use crate::{resolve::Resolver, Secret};
+
+/// Resolves `vault://<path>#<key>` references by reading the secret at `<path>` from Vault's
+/// HTTP API and taking its `<key>` field.  Both KV version 1 and 2 secret engines are supported;
+/// with version 2 the path includes `data/`, e.g. `vault://secret/data/jira#token`.
+///
+/// ```no_run
+/// # use std::sync::Arc;
+/// # use ilo_config::{resolve::vault::VaultResolver, Config, ConfigOptions};
+/// // With `VAULT_ADDR` and `VAULT_TOKEN` set, as for the `vault` CLI.
+/// let options = ConfigOptions {
+///     resolvers: vec![Arc::new(VaultResolver::from_env())],
+///     ..Default::default()
+/// };
+/// let config: Config<serde_json::Value> = Config::load_with_options("jira", options).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct VaultResolver {
+    address: String,
+    token: Option<Secret>,
+    namespace: Option<String>,
+    timeout: Duration,
+}
+
+impl VaultResolver {
+    /// Create a resolver for the Vault server at `address`, e.g. `https://vault.example.com:8200`.
+    pub fn new(address: impl Into<String>) -> Self {
+        Self {
+            address: address.into().trim_end_matches('/').to_string(),
+            token: None,
+            namespace: None,
+            timeout: Duration::from_secs(10),
+        }
+    }
+
+    /// Create a resolver configured like the `vault` CLI, from the `VAULT_ADDR`, `VAULT_TOKEN`
+    /// and `VAULT_NAMESPACE` environment variables.  The address defaults to
+    /// `https://127.0.0.1:8200`.
+    pub fn from_env() -> Self {
+        let address =
+            env::var("VAULT_ADDR").unwrap_or_else(|_| String::from("https://127.0.0.1:8200"));
+        Self {
+            token: env::var("VAULT_TOKEN").ok().map(Secret::new),
+            namespace: env::var("VAULT_NAMESPACE").ok(),
+            ..Self::new(address)
+        }
+    }
+
+    pub fn token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(Secret::new(token));
+        self
+    }
+
+    /// Vault Enterprise namespace to send with every request.
+    pub fn namespace(mut self, namespace: impl Into<String>) -> Self {
+        self.namespace = Some(namespace.into());
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    fn fetch(&self, path: &str) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let client = Client::builder().timeout(self.timeout).build()?;
+        let mut request = client.get(format!("{}/v1/{}", self.address, path));
+        if let Some(token) = &self.token {
+            request = request.header("X-Vault-Token", token.expose());
+        }
+        if let Some(namespace) = &self.namespace {
+            request = request.header("X-Vault-Namespace", namespace);
+        }
+        let body = request.send()?.error_for_status()?.bytes()?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+}
+
+impl Resolver for VaultResolver {
+    fn scheme(&self) -> &str {
+        "vault"
+    }
+
+    fn resolve(&self, reference: &str) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let (path, key) = reference
+            .split_once('#')
+            .ok_or("Vault references must have the form vault://<path>#<key>")?;
+        let secret = self.fetch(path.trim_start_matches('/'))?;
+
+        // KV version 2 nests the secret's fields one level deeper than version 1
+        let data = &secret["data"];
+        let value = match data.get("data").and_then(|data| data.get(key)) {
+            Some(value) => value,
+            None => data
+                .get(key)
+                .ok_or_else(|| format!("secret {} has no field `{}`", path, key))?,
+        };
+        Ok(match value {
+            Value::String(value) => value.clone(),
+            value => value.to_string(),
+        })
+    }
+}
@@ -0,0 +1,83 @@
+//! Retrying IO operations that fail transiently, e.g. on network home directories or flaky NFS
+//! mounts.  See [`ConfigOptions::retry`](crate::ConfigOptions::retry).
+use std::{
+    io,
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+use crate::ConfigError;
+
+/// How often and how patiently to retry failed reads and writes of config files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one.
+    pub attempts: u32,
+
+    /// Time to wait before the first retry.  The wait doubles for every further retry.
+    pub initial_backoff: Duration,
+
+    /// Upper limit for the time to wait between attempts.
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(2),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Run `operation` until it succeeds, fails with an error that retrying won't fix (e.g.
+    /// `NotFound`), or runs out of attempts.
+    ///
+    /// Errors that aren't retried are wrapped with `error`; running out of attempts results in
+    /// `ConfigError::RetriesExhausted` with the error of every attempt.
+    pub(crate) fn run<T>(
+        policy: Option<&Self>,
+        path: &Path,
+        mut operation: impl FnMut() -> io::Result<T>,
+        error: impl FnOnce(PathBuf, io::Error) -> ConfigError,
+    ) -> Result<T, ConfigError> {
+        let Some(policy) = policy else {
+            return operation().map_err(|e| error(path.to_path_buf(), e));
+        };
+
+        let mut errors = Vec::new();
+        let mut backoff = policy.initial_backoff;
+        loop {
+            match operation() {
+                Ok(value) => return Ok(value),
+                Err(e) if !is_transient(&e) => return Err(error(path.to_path_buf(), e)),
+                Err(e) => errors.push(e),
+            }
+            if errors.len() >= policy.attempts as usize {
+                return Err(ConfigError::RetriesExhausted(path.to_path_buf(), errors));
+            }
+            thread::sleep(backoff);
+            backoff = (backoff * 2).min(policy.max_backoff);
+        }
+    }
+}
+
+/// Whether an error might go away on its own, as opposed to e.g. a missing file.
+fn is_transient(error: &io::Error) -> bool {
+    !matches!(
+        error.kind(),
+        io::ErrorKind::NotFound
+            | io::ErrorKind::PermissionDenied
+            | io::ErrorKind::AlreadyExists
+            | io::ErrorKind::InvalidInput
+            | io::ErrorKind::InvalidData
+            | io::ErrorKind::Unsupported
+            | io::ErrorKind::StorageFull
+            | io::ErrorKind::ReadOnlyFilesystem
+            | io::ErrorKind::IsADirectory
+            | io::ErrorKind::NotADirectory
+    )
+}
@@ -0,0 +1,40 @@
+//! Operations on a config root as a whole, rather than on a single config.
+use std::path::PathBuf;
+
+use serde_json::Value;
+
+use crate::{listing::list_keys, Config, ConfigError, ConfigOptions};
+
+/// A config root (or a namespace in it, if [`ConfigOptions::namespace`] is set).
+///
+/// The root's location and the way it is accessed are determined by the options, the same way
+/// as for a [`Config`] loaded with them.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigRoot {
+    pub(crate) options: ConfigOptions,
+}
+
+impl ConfigRoot {
+    /// The default config root.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_options(options: ConfigOptions) -> Self {
+        Self { options }
+    }
+
+    pub fn options(&self) -> &ConfigOptions {
+        &self.options
+    }
+
+    /// The directory of the config root.  It may not exist yet.
+    pub fn path(&self) -> Result<PathBuf, ConfigError> {
+        Config::<Value>::get_config_root(&self.options)
+    }
+
+    /// The keys of all configs in the root, as for [`list_keys`].
+    pub fn keys(&self) -> Result<Vec<String>, ConfigError> {
+        list_keys(&self.options)
+    }
+}
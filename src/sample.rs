@@ -0,0 +1,56 @@
+//! Example config files for apps to hand out, e.g. from an `--init-config` command.
+use std::{
+    fs::OpenOptions,
+    io::{self, Write},
+    path::Path,
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+#[cfg(feature = "schemars")]
+use crate::ConfigFormat;
+use crate::{Config, ConfigError};
+
+impl<TConfigData: Serialize + DeserializeOwned + Default> Config<TConfigData> {
+    /// Write an example config file holding the config data type's `Default` value to `path`.
+    ///
+    /// Fails with `ConfigError::ConfigFileWriteError` if the file already exists, so that an
+    /// `--init-config` command can't clobber a user's config.  With the `schemars` feature,
+    /// [`write_annotated_sample`](Self::write_annotated_sample) also includes the doc comments
+    /// of the config data type.
+    pub fn write_sample(path: impl AsRef<Path>) -> Result<(), ConfigError> {
+        let value = serde_json::to_string_pretty(&TConfigData::default())
+            .map_err(ConfigError::ConfigFileSerializeError)?;
+        write_new(path.as_ref(), &format!("{}\n", value))
+    }
+}
+
+#[cfg(feature = "schemars")]
+impl<TConfigData: Serialize + DeserializeOwned + Default + schemars::JsonSchema>
+    Config<TConfigData>
+{
+    /// Like [`write_sample`](Self::write_sample), rendering the sample like
+    /// [`template`](Self::template): if `path` has a `.jsonc` extension, the doc comments of the
+    /// config data type's fields and their example values are included as comments.
+    pub fn write_annotated_sample(path: impl AsRef<Path>) -> Result<(), ConfigError> {
+        let path = path.as_ref();
+        let format = match path.extension() {
+            Some(extension) if extension == ConfigFormat::Jsonc.extension() => ConfigFormat::Jsonc,
+            _ => ConfigFormat::Json,
+        };
+        let mut sample = Self::template(format)?;
+        if !sample.ends_with('\n') {
+            sample.push('\n');
+        }
+        write_new(path, &sample)
+    }
+}
+
+/// Write `contents` to a file that must not exist yet.
+fn write_new(path: &Path, contents: &str) -> Result<(), ConfigError> {
+    let write = || -> io::Result<()> {
+        let mut file = OpenOptions::new().write(true).create_new(true).open(path)?;
+        file.write_all(contents.as_bytes())
+    };
+    write().map_err(|e| ConfigError::ConfigFileWriteError(path.to_path_buf(), e))
+}
@@ -0,0 +1,76 @@
+//! Wrapper for sensitive config values such as passwords and API tokens.
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroize;
+
+/// A string that is redacted when printed and wiped from memory when dropped.
+///
+/// Serializes like a plain string, so it can be used for fields of a config data type without
+/// changing the file format, but its `Debug` and `Display` output is `***` (which also keeps it
+/// out of the `Debug` output of a [`Config`](crate::Config)).  Use [`expose`](Self::expose) to
+/// get at the actual value.
+///
+/// With the `secrecy` feature, it converts to and from `secrecy::SecretString` and implements
+/// `secrecy::ExposeSecret`.
+#[derive(Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: impl Into<String>) -> Self {
+        Self(value.into())
+    }
+
+    #[inline]
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Drop for Secret {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl From<String> for Secret {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret(***)")
+    }
+}
+
+impl fmt::Display for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("***")
+    }
+}
+
+#[cfg(feature = "secrecy")]
+impl secrecy::ExposeSecret<str> for Secret {
+    fn expose_secret(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(feature = "secrecy")]
+impl From<Secret> for secrecy::SecretString {
+    fn from(secret: Secret) -> Self {
+        secrecy::SecretString::from(secret.expose().to_string())
+    }
+}
+
+#[cfg(feature = "secrecy")]
+impl From<secrecy::SecretString> for Secret {
+    fn from(secret: secrecy::SecretString) -> Self {
+        use secrecy::ExposeSecret;
+        Self::new(secret.expose_secret())
+    }
+}
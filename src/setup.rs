@@ -0,0 +1,278 @@
+//! Interactive first-run setup, asking the user for the config values that have no default.
+//!
+//! [`Config::interactive_init`] walks the config data type's JSON Schema and prompts for every
+//! required field (one without `#[serde(default)]` that isn't an `Option`), e.g. a server URL or
+//! an API token, then saves the config.  Prompts go through a [`PromptProvider`], so apps can
+//! plug in their own UI; [`LinePrompts`] asks on stdin and stdout.
+//!
+//! ```no_run
+//! # use ilo_config::{setup::LinePrompts, Config};
+//! /// Connection to the Jira server.
+//! #[derive(serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+//! struct JiraConfig {
+//!     /// Base URL of the Jira server, e.g. https://example.atlassian.net
+//!     url: String,
+//!     email: String,
+//!     token: String,
+//!     #[serde(default)]
+//!     project: Option<String>,
+//! }
+//!
+//! // Only asks for the URL, email and token if the config doesn't exist yet.
+//! let config: Config<JiraConfig> = Config::interactive_init("jira", &mut LinePrompts::stdio())?;
+//! # Ok::<(), ilo_config::ConfigError>(())
+//! ```
+use std::io::{self, BufRead, Write};
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{Map, Number, Value};
+
+use crate::{
+    template::{example, object_schema, resolve},
+    Config, ConfigError, ConfigOptions,
+};
+
+/// A config field that the user is asked for.
+#[derive(Debug, Clone)]
+pub struct Field<'a> {
+    /// Dot-separated path of the field, e.g. `server.url`.
+    pub path: &'a str,
+
+    /// The field's doc comment, if it has one.
+    pub description: Option<&'a str>,
+
+    pub kind: FieldKind,
+
+    /// Example value from the schema (e.g. `#[schemars(example = ...)]`), if it has one.
+    pub example: Option<&'a Value>,
+}
+
+/// The kind of value a [`Field`] holds, which determines how answers are parsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldKind {
+    String,
+    Integer,
+    Number,
+
+    /// Answered with `yes`/`no` (or `y`/`n`, `true`/`false`).
+    Boolean,
+
+    /// Anything else (e.g. lists or enums), answered as JSON.
+    Json,
+}
+
+/// The UI for asking the user for config values.
+pub trait PromptProvider {
+    /// Ask the user for the value of `field`, returning their answer as typed.  `error` explains
+    /// why the previous answer for the same field was rejected, if it was.
+    fn prompt(&mut self, field: &Field<'_>, error: Option<&str>) -> io::Result<String>;
+}
+
+/// [`PromptProvider`] that writes prompts to an output and reads one line per answer.
+#[derive(Debug)]
+pub struct LinePrompts<R: BufRead, W: Write> {
+    input: R,
+    output: W,
+}
+
+impl LinePrompts<io::StdinLock<'static>, io::Stdout> {
+    /// Prompt on stdout and read answers from stdin.
+    pub fn stdio() -> Self {
+        Self::new(io::stdin().lock(), io::stdout())
+    }
+}
+
+impl<R: BufRead, W: Write> LinePrompts<R, W> {
+    pub fn new(input: R, output: W) -> Self {
+        Self { input, output }
+    }
+}
+
+impl<R: BufRead, W: Write> PromptProvider for LinePrompts<R, W> {
+    fn prompt(&mut self, field: &Field<'_>, error: Option<&str>) -> io::Result<String> {
+        if let Some(error) = error {
+            writeln!(self.output, "{}", error)?;
+        }
+        if let Some(description) = field.description {
+            writeln!(self.output, "{}", description)?;
+        }
+        match field.example {
+            Some(example) => write!(self.output, "{} (e.g. {example}): ", field.path)?,
+            None => write!(self.output, "{}: ", field.path)?,
+        }
+        self.output.flush()?;
+
+        let mut answer = String::new();
+        if self.input.read_line(&mut answer)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("no answer for {}", field.path),
+            ));
+        }
+        Ok(answer.trim_end_matches(['\r', '\n']).to_string())
+    }
+}
+
+impl<TConfigData: Serialize + DeserializeOwned + schemars::JsonSchema> Config<TConfigData> {
+    /// Load a config, or if it doesn't exist yet, ask the user for its required fields with
+    /// `prompts` and save it.  See the [`setup`](crate::setup) module.
+    pub fn interactive_init(
+        config_file_key: &str,
+        prompts: &mut impl PromptProvider,
+    ) -> Result<Self, ConfigError> {
+        Self::interactive_init_with_options(config_file_key, ConfigOptions::default(), prompts)
+    }
+
+    /// Like [`interactive_init`](Self::interactive_init), customizing the behavior with
+    /// `options`.
+    ///
+    /// The answers take the place of [`ConfigOptions::defaults`], which is ignored when the
+    /// config is created.
+    pub fn interactive_init_with_options(
+        config_file_key: &str,
+        options: ConfigOptions,
+        prompts: &mut impl PromptProvider,
+    ) -> Result<Self, ConfigError> {
+        let config_path = match Self::load_required_with_options(config_file_key, options.clone()) {
+            Err(ConfigError::NotFound(config_path)) => config_path,
+            result => return result,
+        };
+
+        let schema = schemars::schema_for!(TConfigData);
+        let root = schema.as_value();
+        let mut document = Map::new();
+        ask_required(root, root, "", &mut document, prompts)
+            .map_err(ConfigError::ConfigSetupError)?;
+        let config_data: TConfigData = serde_json::from_value(Value::Object(document))
+            .map_err(|e| ConfigError::ConfigFileParseError(config_path, e, None))?;
+
+        let options = ConfigOptions {
+            defaults: None,
+            ..options
+        };
+        let config = Self::load_or_init_with_options(config_file_key, options, || config_data)?;
+        config.save()?;
+        Ok(config)
+    }
+}
+
+/// Ask for the required members of the object described by `schema`, recursing into required
+/// members that are objects themselves.
+fn ask_required(
+    root: &Value,
+    schema: &Value,
+    prefix: &str,
+    document: &mut Map<String, Value>,
+    prompts: &mut impl PromptProvider,
+) -> io::Result<()> {
+    let Some(schema) = object_schema(root, schema) else {
+        return Ok(());
+    };
+    let required: Vec<&str> = schema
+        .get("required")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .collect();
+    let Some(properties) = schema.get("properties").and_then(Value::as_object) else {
+        return Ok(());
+    };
+
+    for (key, property) in properties {
+        if !required.contains(&key.as_str()) {
+            continue;
+        }
+        let path = match prefix {
+            "" => key.clone(),
+            prefix => format!("{}.{}", prefix, key),
+        };
+        if resolve(root, property).get("properties").is_some() {
+            let mut child = Map::new();
+            ask_required(root, property, &path, &mut child, prompts)?;
+            document.insert(key.clone(), Value::Object(child));
+            continue;
+        }
+
+        let resolved = resolve(root, property);
+        let field = Field {
+            path: &path,
+            // A description next to a `$ref` describes the field rather than the referenced type
+            description: property
+                .get("description")
+                .or_else(|| resolved.get("description"))
+                .and_then(Value::as_str),
+            kind: kind(resolved),
+            example: example(resolved),
+        };
+        let mut error = None;
+        let value = loop {
+            let answer = prompts.prompt(&field, error.as_deref())?;
+            match parse(&answer, field.kind, resolved) {
+                Ok(value) => break value,
+                Err(e) => error = Some(e),
+            }
+        };
+        document.insert(key.clone(), value);
+    }
+    Ok(())
+}
+
+fn kind(schema: &Value) -> FieldKind {
+    let types: Vec<&str> = match schema.get("type") {
+        Some(Value::String(kind)) => vec![kind],
+        Some(Value::Array(kinds)) => kinds.iter().filter_map(Value::as_str).collect(),
+        _ => Vec::new(),
+    };
+    match types.iter().find(|kind| **kind != "null") {
+        Some(&"string") if schema.get("enum").is_none() => FieldKind::String,
+        Some(&"integer") => FieldKind::Integer,
+        Some(&"number") => FieldKind::Number,
+        Some(&"boolean") => FieldKind::Boolean,
+        _ => FieldKind::Json,
+    }
+}
+
+/// Parse an answer, returning a message for the user if it isn't valid.
+fn parse(answer: &str, kind: FieldKind, schema: &Value) -> Result<Value, String> {
+    let number = match kind {
+        FieldKind::String => return Ok(Value::String(answer.to_string())),
+        FieldKind::Integer => answer
+            .trim()
+            .parse::<i64>()
+            .map(Number::from)
+            .or_else(|_| answer.trim().parse::<u64>().map(Number::from))
+            .map_err(|_| "Please enter a whole number".to_string())?,
+        FieldKind::Number => answer
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .and_then(Number::from_f64)
+            .ok_or_else(|| "Please enter a number".to_string())?,
+        FieldKind::Boolean => {
+            return match answer.trim().to_lowercase().as_str() {
+                "y" | "yes" | "true" => Ok(Value::Bool(true)),
+                "n" | "no" | "false" => Ok(Value::Bool(false)),
+                _ => Err("Please answer yes or no".to_string()),
+            }
+        }
+        FieldKind::Json => {
+            return serde_json::from_str(answer).map_err(|e| format!("Please enter JSON: {}", e))
+        }
+    };
+
+    let value = number.as_f64().unwrap_or_default();
+    let minimum = schema.get("minimum").and_then(Value::as_f64);
+    let maximum = schema.get("maximum").and_then(Value::as_f64);
+    if minimum.is_some_and(|minimum| value < minimum)
+        || maximum.is_some_and(|maximum| value > maximum)
+    {
+        let range = |bound: Option<f64>| bound.map_or(String::from("…"), |b| b.to_string());
+        return Err(format!(
+            "Please enter a number from {} to {}",
+            range(minimum),
+            range(maximum)
+        ));
+    }
+    Ok(Value::Number(number))
+}
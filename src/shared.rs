@@ -0,0 +1,166 @@
+//! Shared handles for configs that are accessed from several places at once.
+//!
+//! A host application owns a [`SharedConfig`] and can hand out cloned strong handles, or
+//! [`WeakConfig`] handles to plugins that should not keep the config alive on their own.
+use std::{
+    ops::{Deref, DerefMut},
+    sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard, Weak},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    autosave::{self, Autosaver, Debouncer},
+    Config, ConfigError,
+};
+
+/// Reference-counted, thread-safe handle to a [`Config`].
+///
+/// Cloning the handle is cheap and every clone refers to the same config.  Any number of threads
+/// can [`read`](Self::read) at once; changes are made through [`write`](Self::write).
+///
+/// ```no_run
+/// # use std::thread;
+/// # use ilo_config::{Config, SharedConfig};
+/// let config = SharedConfig::new(Config::<Vec<String>>::load("hosts")?);
+/// let handle = config.clone();
+/// thread::spawn(move || handle.write().data_mut().push(String::from("a.example.com")))
+///     .join()
+///     .unwrap();
+/// config.flush()?;
+/// # Ok::<(), ilo_config::ConfigError>(())
+/// ```
+pub struct SharedConfig<TConfigData: Serialize + DeserializeOwned> {
+    inner: Arc<RwLock<Config<TConfigData>>>,
+    autosave: Option<Arc<Autosaver<TConfigData>>>, // Only set for `Config::autosave`
+}
+
+/// Write access to a [`SharedConfig`], as returned by [`SharedConfig::write`].
+///
+/// Derefs to the [`Config`], so the data is changed with [`data_mut`](Config::data_mut) as usual.
+/// If the config is [autosaved](Config::autosave) and the data was changed, dropping the guard
+/// schedules a save.
+pub struct ConfigWriteGuard<'a, TConfigData: Serialize + DeserializeOwned> {
+    config: RwLockWriteGuard<'a, Config<TConfigData>>,
+    debouncer: Option<&'a Debouncer>,
+}
+
+impl<TConfigData: Serialize + DeserializeOwned> SharedConfig<TConfigData> {
+    pub fn new(config: Config<TConfigData>) -> Self {
+        Self::from_parts(Arc::new(RwLock::new(config)), None)
+    }
+
+    pub(crate) fn from_parts(
+        inner: Arc<RwLock<Config<TConfigData>>>,
+        autosave: Option<Arc<Autosaver<TConfigData>>>,
+    ) -> Self {
+        Self { inner, autosave }
+    }
+
+    /// Lock the config for reading.
+    ///
+    /// A panic in another thread while it held the lock does not make the config inaccessible;
+    /// the data is plain config data, so it is handed out as-is.
+    pub fn read(&self) -> RwLockReadGuard<'_, Config<TConfigData>> {
+        self.inner.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Lock the config for writing.
+    pub fn write(&self) -> ConfigWriteGuard<'_, TConfigData> {
+        ConfigWriteGuard {
+            config: self.inner.write().unwrap_or_else(PoisonError::into_inner),
+            debouncer: self.debouncer(),
+        }
+    }
+
+    /// Save the config now if it has unsaved changes, see [`Config::has_unsaved_changes`].  For
+    /// an autosaved config, this also cancels the pending save.
+    pub fn flush(&self) -> Result<(), ConfigError> {
+        autosave::flush(&self.read(), self.debouncer())
+    }
+
+    /// Take the error from the last background save that failed, if the config is
+    /// [autosaved](Config::autosave).
+    pub fn take_autosave_error(&self) -> Option<ConfigError> {
+        self.debouncer().and_then(Debouncer::take_error)
+    }
+
+    /// Create a weak handle that does not keep the config alive.
+    pub fn downgrade(&self) -> WeakConfig<TConfigData> {
+        WeakConfig {
+            inner: Arc::downgrade(&self.inner),
+            autosave: self.autosave.as_ref().map(Arc::downgrade),
+            config_file_key: self.read().key().to_string(),
+        }
+    }
+
+    fn debouncer(&self) -> Option<&Debouncer> {
+        self.autosave
+            .as_deref()
+            .map(|autosave| autosave.debouncer.as_ref())
+    }
+}
+
+impl<TConfigData: Serialize + DeserializeOwned> Clone for SharedConfig<TConfigData> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+            autosave: self.autosave.clone(),
+        }
+    }
+}
+
+impl<TConfigData: Serialize + DeserializeOwned> Deref for ConfigWriteGuard<'_, TConfigData> {
+    type Target = Config<TConfigData>;
+
+    fn deref(&self) -> &Config<TConfigData> {
+        &self.config
+    }
+}
+
+impl<TConfigData: Serialize + DeserializeOwned> DerefMut for ConfigWriteGuard<'_, TConfigData> {
+    fn deref_mut(&mut self) -> &mut Config<TConfigData> {
+        &mut self.config
+    }
+}
+
+impl<TConfigData: Serialize + DeserializeOwned> Drop for ConfigWriteGuard<'_, TConfigData> {
+    fn drop(&mut self) {
+        if let Some(debouncer) = self.debouncer {
+            if self.config.has_unsaved_changes() {
+                debouncer.touch();
+            }
+        }
+    }
+}
+
+/// Non-owning handle to a [`SharedConfig`], e.g. for plugins holding on to host-owned configs.
+pub struct WeakConfig<TConfigData: Serialize + DeserializeOwned> {
+    inner: Weak<RwLock<Config<TConfigData>>>,
+    autosave: Option<Weak<Autosaver<TConfigData>>>,
+    config_file_key: String, // Kept around for the error message once the config is gone
+}
+
+impl<TConfigData: Serialize + DeserializeOwned> WeakConfig<TConfigData> {
+    /// Get a strong handle to the config, or `ConfigError::ConfigDroppedError` if every strong
+    /// handle has already been dropped.
+    pub fn upgrade(&self) -> Result<SharedConfig<TConfigData>, ConfigError> {
+        self.inner
+            .upgrade()
+            .map(|inner| SharedConfig {
+                inner,
+                autosave: self.autosave.as_ref().and_then(Weak::upgrade),
+            })
+            .ok_or_else(|| ConfigError::ConfigDroppedError(self.config_file_key.clone()))
+    }
+}
+
+impl<TConfigData: Serialize + DeserializeOwned> Clone for WeakConfig<TConfigData> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Weak::clone(&self.inner),
+            autosave: self.autosave.clone(),
+            config_file_key: self.config_file_key.clone(),
+        }
+    }
+}
@@ -0,0 +1,520 @@
+//! HMAC signatures of config files, for detecting changes made by anyone who doesn't hold the
+//! signing key.
+//!
+//! The signature is the HMAC-SHA256 of the config's namespace and key, a counter that goes up
+//! with every save, and the file as it is stored on disk (after compression and encryption).  It
+//! is kept in a `<key>.json.sig` file next to the config, along with the counter.  Since the
+//! signature covers the raw bytes, it works for every format.
+use std::{
+    collections::HashMap,
+    env,
+    error::Error,
+    fmt::Debug,
+    io::{self, Write},
+    iter,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::Sha256;
+
+use crate::{Config, ConfigError, ConfigOptions, Secret};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Source of the key that config files are signed with, e.g. the OS keyring.
+pub trait SigningKeyProvider: Debug + Send + Sync {
+    fn signing_key(&self, config_path: &Path) -> Result<Secret, Box<dyn Error + Send + Sync>>;
+}
+
+/// [`SigningKeyProvider`] that reads the key from an environment variable each time it is
+/// needed, e.g. one set by a service manager from a credential store.
+#[derive(Debug, Clone)]
+pub struct EnvSigningKey(pub String);
+
+impl SigningKeyProvider for EnvSigningKey {
+    fn signing_key(&self, _config_path: &Path) -> Result<Secret, Box<dyn Error + Send + Sync>> {
+        match env::var(&self.0) {
+            Ok(key) if !key.is_empty() => Ok(Secret::new(key)),
+            _ => Err(format!("environment variable {} is not set", self.0).into()),
+        }
+    }
+}
+
+/// [`SigningKeyProvider`] that always returns the same key.
+#[derive(Debug, Clone)]
+pub struct StaticSigningKey(pub Secret);
+
+impl SigningKeyProvider for StaticSigningKey {
+    fn signing_key(&self, _config_path: &Path) -> Result<Secret, Box<dyn Error + Send + Sync>> {
+        Ok(self.0.clone())
+    }
+}
+
+/// Signing settings, set with [`ConfigOptions::signing`](crate::ConfigOptions::signing).
+///
+/// Each save writes the signature of the config file to a `<key>.json.sig` file next to it, and
+/// loading fails with `ConfigError::TamperDetected` if the file doesn't match its signature, or
+/// has none.  Only the holder of the key can write a valid signature, so this catches changes by
+/// other local users or processes that can write to the config root but can't read the key.
+/// Keep the key out of the config root, e.g. in the OS keyring (by implementing
+/// [`SigningKeyProvider`] with the `keyring` crate) or in an environment variable
+/// ([`EnvSigningKey`]).
+///
+/// Signatures are bound to the config's key and namespace, so a signed file copied over another
+/// config is rejected.  Each save also signs a counter that goes up with every save, and a
+/// `Signing` (and its clones) rejects a file signed with a lower counter than one it has already
+/// seen, so a file rolled back to an older signed version is caught while the app runs.  Across
+/// restarts, nothing outside the config root remembers the counter, so a rollback there can't be
+/// told apart from an unchanged file.
+///
+/// The signature is written (atomically) before the config file, and keeps the previous
+/// signature, so a save that is interrupted before the config file was written leaves a file
+/// that still loads.  Signing is ignored with a [`backend`](crate::ConfigOptions::backend).
+///
+/// ```no_run
+/// # use ilo_config::{Config, ConfigOptions, EnvSigningKey, Signing};
+/// let options = ConfigOptions {
+///     signing: Some(Signing::new(EnvSigningKey(String::from("MYAPP_SIGNING_KEY")))),
+///     ..Default::default()
+/// };
+/// let config: Config<serde_json::Value> = Config::load_with_options("jira", options)?;
+/// # Ok::<(), ilo_config::ConfigError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct Signing {
+    provider: Arc<dyn SigningKeyProvider>,
+    allow_unsigned: bool,
+    counters: Arc<Mutex<HashMap<PathBuf, u64>>>,
+}
+
+/// The contents of a `.sig` file.
+#[derive(Serialize, Deserialize)]
+struct Signatures {
+    #[serde(flatten)]
+    current: Signature,
+    /// The signature of the file before the last save, in case the save was interrupted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    previous: Option<Signature>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Signature {
+    counter: u64,
+    hmac: String,
+}
+
+/// What is signed or verified: the bytes of a config file, or the file itself, which is read
+/// without loading it into memory at once (e.g. for large collections).
+#[derive(Clone, Copy)]
+pub(crate) enum Contents<'a> {
+    Bytes(&'a [u8]),
+    File(&'a Path),
+}
+
+impl Signing {
+    pub fn new(provider: impl SigningKeyProvider + 'static) -> Self {
+        Self {
+            provider: Arc::new(provider),
+            allow_unsigned: false,
+            counters: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Load config files that have no signature yet instead of failing, so signing can be turned
+    /// on for existing configs.  They are signed the next time they are saved, even if nothing
+    /// changed.  Files with a signature that doesn't match still fail to load.
+    pub fn allow_unsigned(mut self) -> Self {
+        self.allow_unsigned = true;
+        self
+    }
+
+    /// Write the signature for the config `config_file_key` at `config_path`, which is about to
+    /// be written with `contents`.  Call this before writing the config file, and pass the
+    /// returned counter to [`written`](Self::written) once it is written.
+    pub(crate) fn sign(
+        &self,
+        config_file_key: &str,
+        config_path: &Path,
+        contents: Contents,
+        options: &ConfigOptions,
+    ) -> Result<u64, ConfigError> {
+        // Unverified, but a forged entry can't match anything anyway
+        let previous = read_signatures(config_path, options).ok().flatten();
+        let counter = previous
+            .as_ref()
+            .map_or(0, |signatures| signatures.current.counter)
+            .max(self.seen(config_path))
+            + 1;
+        let mut macs = Macs(vec![self.mac(
+            config_file_key,
+            config_path,
+            counter,
+            options,
+        )?]);
+        contents.feed(&mut macs, options)?;
+        let signatures = Signatures {
+            current: Signature {
+                counter,
+                hmac: hex(&macs.0.remove(0).finalize().into_bytes()),
+            },
+            previous: previous.map(|signatures| signatures.current),
+        };
+        write_signatures(config_path, &signatures, options)?;
+        Ok(counter)
+    }
+
+    /// Record that the config file signed with `counter` was written, so older versions are
+    /// rejected from now on.  Until then, the file that was there before still loads, in case
+    /// writing it failed.
+    pub(crate) fn written(&self, config_path: &Path, counter: u64) {
+        self.record(config_path, counter);
+    }
+
+    /// Check `contents`, read from the config `config_file_key` at `config_path`, against its
+    /// signature.
+    pub(crate) fn verify(
+        &self,
+        config_file_key: &str,
+        config_path: &Path,
+        contents: Contents,
+        options: &ConfigOptions,
+    ) -> Result<(), ConfigError> {
+        let tampered = || ConfigError::TamperDetected(config_path.to_path_buf());
+        let Some(signatures) = read_signatures(config_path, options)? else {
+            return if self.allow_unsigned {
+                Ok(())
+            } else {
+                Err(tampered())
+            };
+        };
+        let seen = self.seen(config_path);
+        let candidates: Vec<Signature> = iter::once(signatures.current)
+            .chain(signatures.previous)
+            .filter(|signature| signature.counter >= seen)
+            .collect();
+        if candidates.is_empty() {
+            return Err(tampered());
+        }
+        let mut macs = Macs(
+            candidates
+                .iter()
+                .map(|signature| self.mac(config_file_key, config_path, signature.counter, options))
+                .collect::<Result<_, _>>()?,
+        );
+        contents.feed(&mut macs, options)?;
+        let matched = candidates.iter().zip(macs.0).find(|(signature, mac)| {
+            unhex(&signature.hmac).is_some_and(|hmac| mac.clone().verify_slice(&hmac).is_ok())
+        });
+        match matched {
+            Some((signature, _)) => {
+                self.record(config_path, signature.counter);
+                Ok(())
+            }
+            None => Err(tampered()),
+        }
+    }
+
+    /// Sign `contents` if they don't match the signature at `config_path` yet, e.g. when a file
+    /// loaded with [`allow_unsigned`](Self::allow_unsigned) is saved unchanged.
+    pub(crate) fn ensure_signed(
+        &self,
+        config_file_key: &str,
+        config_path: &Path,
+        contents: Contents,
+        options: &ConfigOptions,
+    ) -> Result<(), ConfigError> {
+        let strict = Self {
+            allow_unsigned: false,
+            ..self.clone()
+        };
+        match strict.verify(config_file_key, config_path, contents, options) {
+            Err(ConfigError::TamperDetected(_)) => {
+                let counter = self.sign(config_file_key, config_path, contents, options)?;
+                self.written(config_path, counter);
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    fn mac(
+        &self,
+        config_file_key: &str,
+        config_path: &Path,
+        counter: u64,
+        options: &ConfigOptions,
+    ) -> Result<HmacSha256, ConfigError> {
+        let key = self
+            .provider
+            .signing_key(config_path)
+            .map_err(|e| ConfigError::SigningKeyError(config_path.to_path_buf(), e.to_string()))?;
+        let mut mac = HmacSha256::new_from_slice(key.expose().as_bytes())
+            .map_err(|e| ConfigError::SigningKeyError(config_path.to_path_buf(), e.to_string()))?;
+        mac.update(b"ilo-config signature v1\0");
+        mac.update(options.namespace.as_deref().unwrap_or_default().as_bytes());
+        mac.update(b"\0");
+        mac.update(config_file_key.as_bytes());
+        mac.update(b"\0");
+        mac.update(&counter.to_be_bytes());
+        Ok(mac)
+    }
+
+    /// The highest counter seen for the config at `config_path`.
+    fn seen(&self, config_path: &Path) -> u64 {
+        let counters = self.counters.lock().unwrap_or_else(PoisonError::into_inner);
+        counters.get(config_path).copied().unwrap_or_default()
+    }
+
+    fn record(&self, config_path: &Path, counter: u64) {
+        let mut counters = self.counters.lock().unwrap_or_else(PoisonError::into_inner);
+        let seen = counters.entry(config_path.to_path_buf()).or_default();
+        *seen = counter.max(*seen);
+    }
+}
+
+impl Contents<'_> {
+    fn feed(self, macs: &mut Macs, options: &ConfigOptions) -> Result<(), ConfigError> {
+        match self {
+            Contents::Bytes(bytes) => {
+                macs.update(bytes);
+                Ok(())
+            }
+            Contents::File(path) => options
+                .filesystem
+                .open(path)
+                .and_then(|mut file| io::copy(&mut file, macs))
+                .map(|_| ())
+                .map_err(|e| ConfigError::ConfigFileLoadError(path.to_path_buf(), e)),
+        }
+    }
+}
+
+/// HMACs that are computed over the same contents at once.
+struct Macs(Vec<HmacSha256>);
+
+impl Macs {
+    fn update(&mut self, bytes: &[u8]) {
+        for mac in &mut self.0 {
+            mac.update(bytes);
+        }
+    }
+}
+
+impl Write for Macs {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn signature_path(config_path: &Path) -> PathBuf {
+    Config::<Value>::sidecar_path(config_path, "sig")
+}
+
+/// The signatures of the config at `config_path`, or `None` if it has none.
+fn read_signatures(
+    config_path: &Path,
+    options: &ConfigOptions,
+) -> Result<Option<Signatures>, ConfigError> {
+    let signature_path = signature_path(config_path);
+    let contents = match options.filesystem.read(&signature_path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(ConfigError::ConfigFileLoadError(signature_path, e)),
+    };
+    serde_json::from_slice(&contents)
+        .map(Some)
+        .map_err(|_| ConfigError::TamperDetected(config_path.to_path_buf()))
+}
+
+/// Replace the signatures of the config at `config_path` in one atomic rename.
+fn write_signatures(
+    config_path: &Path,
+    signatures: &Signatures,
+    options: &ConfigOptions,
+) -> Result<(), ConfigError> {
+    let signature_path = signature_path(config_path);
+    let temp_path = Config::<Value>::sidecar_path(config_path, "sig.tmp");
+    let mut contents =
+        serde_json::to_vec(signatures).map_err(ConfigError::ConfigFileSerializeError)?;
+    contents.push(b'\n');
+    options
+        .filesystem
+        .write(&temp_path, &contents, Some(options.file_mode))
+        .map_err(|e| ConfigError::ConfigFileWriteError(temp_path.clone(), e))?;
+    match options.filesystem.rename(&temp_path, &signature_path) {
+        Ok(()) => Ok(()),
+        // Filesystems that can't rename get the signature written in place
+        Err(e) if e.kind() == io::ErrorKind::Unsupported => {
+            let _ = options.filesystem.remove_file(&temp_path);
+            options
+                .filesystem
+                .write(&signature_path, &contents, Some(options.file_mode))
+                .map_err(|e| ConfigError::ConfigFileWriteError(signature_path, e))
+        }
+        Err(e) => Err(ConfigError::ConfigFileWriteError(signature_path, e)),
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn unhex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use serde_json::{json, Value};
+
+    use super::{Contents, Signing, StaticSigningKey};
+    use crate::{Config, ConfigError, ConfigOptions, Secret};
+
+    fn options(root: &Path, signing: Signing) -> ConfigOptions {
+        ConfigOptions {
+            root: Some(root.to_path_buf()),
+            signing: Some(signing),
+            ..Default::default()
+        }
+    }
+
+    fn signing(key: &str) -> Signing {
+        Signing::new(StaticSigningKey(Secret::new(key)))
+    }
+
+    fn save(key: &str, data: Value, options: &ConfigOptions) {
+        let mut config: Config<Value> = Config::load_with_options(key, options.clone()).unwrap();
+        *config.data_mut() = data;
+        config.save().unwrap();
+    }
+
+    fn load(key: &str, options: &ConfigOptions) -> Result<Value, ConfigError> {
+        Config::<Value>::load_with_options(key, options.clone()).map(|config| config.data().clone())
+    }
+
+    fn assert_tampered(result: Result<Value, ConfigError>) {
+        match result {
+            Err(ConfigError::TamperDetected(_)) => {}
+            result => panic!("expected tampering to be detected, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn signed_files_load() {
+        let root = tempfile::tempdir().unwrap();
+        let options = options(root.path(), signing("key"));
+        save("jira", json!({"board": "OPS"}), &options);
+        save("jira", json!({"board": "DEV"}), &options);
+
+        assert!(root.path().join("jira.json.sig").is_file());
+        assert_eq!(load("jira", &options).unwrap(), json!({"board": "DEV"}));
+    }
+
+    #[test]
+    fn edited_files_are_rejected() {
+        let root = tempfile::tempdir().unwrap();
+        let options = options(root.path(), signing("key"));
+        save("jira", json!({"admin": false}), &options);
+
+        fs::write(root.path().join("jira.json"), r#"{"admin": true}"#).unwrap();
+        assert_tampered(load("jira", &options));
+    }
+
+    #[test]
+    fn files_signed_with_another_key_are_rejected() {
+        let root = tempfile::tempdir().unwrap();
+        save("jira", json!({}), &options(root.path(), signing("key")));
+        assert_tampered(load("jira", &options(root.path(), signing("other key"))));
+    }
+
+    #[test]
+    fn unsigned_files_are_only_loaded_when_allowed() {
+        let root = tempfile::tempdir().unwrap();
+        fs::write(root.path().join("jira.json"), r#"{"board": "OPS"}"#).unwrap();
+        assert_tampered(load("jira", &options(root.path(), signing("key"))));
+
+        let options = options(root.path(), signing("key").allow_unsigned());
+        let config: Config<Value> = Config::load_with_options("jira", options.clone()).unwrap();
+        config.save().unwrap();
+        // Saving unchanged signed the file, so it must match from now on
+        fs::write(root.path().join("jira.json"), r#"{"board": "DEV"}"#).unwrap();
+        assert_tampered(load("jira", &options));
+    }
+
+    #[test]
+    fn files_copied_over_other_configs_are_rejected() {
+        let root = tempfile::tempdir().unwrap();
+        let options = options(root.path(), signing("key"));
+        save("public", json!({"admin": true}), &options);
+        save("private", json!({"admin": false}), &options);
+
+        for extension in ["json", "json.sig"] {
+            fs::copy(
+                root.path().join(format!("public.{}", extension)),
+                root.path().join(format!("private.{}", extension)),
+            )
+            .unwrap();
+        }
+        assert_tampered(load("private", &options));
+
+        let namespaced = ConfigOptions {
+            namespace: Some(String::from("app")),
+            ..options.clone()
+        };
+        fs::create_dir(root.path().join("app")).unwrap();
+        for extension in ["json", "json.sig"] {
+            fs::copy(
+                root.path().join(format!("public.{}", extension)),
+                root.path().join(format!("app/public.{}", extension)),
+            )
+            .unwrap();
+        }
+        assert_tampered(load("public", &namespaced));
+    }
+
+    #[test]
+    fn rollbacks_are_rejected_while_running() {
+        let root = tempfile::tempdir().unwrap();
+        let options = options(root.path(), signing("key"));
+        save("jira", json!({"token": "revoked"}), &options);
+        let old_file = fs::read(root.path().join("jira.json")).unwrap();
+        let old_signature = fs::read(root.path().join("jira.json.sig")).unwrap();
+        save("jira", json!({"token": "current"}), &options);
+        save("jira", json!({"token": "newest"}), &options);
+
+        fs::write(root.path().join("jira.json"), old_file).unwrap();
+        fs::write(root.path().join("jira.json.sig"), old_signature).unwrap();
+        assert_tampered(load("jira", &options));
+    }
+
+    #[test]
+    fn interrupted_saves_still_load() {
+        let root = tempfile::tempdir().unwrap();
+        let options = options(root.path(), signing("key"));
+        save("jira", json!({"board": "OPS"}), &options);
+
+        // The signature of the next version is written, but the config file isn't
+        let config_path = root.path().join("jira.json");
+        let signing = options.signing.as_ref().unwrap();
+        let next = Contents::Bytes(br#"{"board": "DEV"}"#);
+        signing.sign("jira", &config_path, next, &options).unwrap();
+        assert_eq!(load("jira", &options).unwrap(), json!({"board": "OPS"}));
+    }
+}
@@ -0,0 +1,846 @@
+//! Streaming access to collection configs that are too large to load at once.
+//!
+//! A collection config is a top-level JSON array (the usual serialization of a `Vec`), or a file
+//! of whitespace-separated JSON values such as JSON Lines.  A map config is a top-level JSON
+//! object (the serialization of a `BTreeMap` or `HashMap` with string keys).  [`Entries`] and
+//! [`MapEntries`] parse one element at a time, and the `rewrite` functions write them back one at
+//! a time, so memory use is bounded by the largest element rather than the whole file.
+use std::{
+    collections::BTreeMap,
+    io::{self, BufRead, BufReader, Read, Write},
+    marker::PhantomData,
+    path::{Path, PathBuf},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+use crate::{jsonc, Config, ConfigError, ConfigFormat, ConfigOptions, Formatting, Indent};
+
+/// Iterator over the elements of a collection config, parsed lazily from disk.
+pub struct Entries<T: DeserializeOwned> {
+    raw: RawEntries,
+    _item: PhantomData<fn() -> T>,
+}
+
+/// Iterator over the `(key, value)` entries of a map config (a top-level JSON object), parsed
+/// lazily from disk in file order.
+pub struct MapEntries<T: DeserializeOwned> {
+    raw: RawEntries,
+    _item: PhantomData<fn() -> T>,
+}
+
+/// Splits a collection config into the raw bytes of its elements, with their keys for objects.
+struct RawEntries {
+    reader: BufReader<Box<dyn Read + Send>>,
+    config_path: PathBuf,
+    objects: bool,
+    state: State,
+
+    /// The layout of the file once it is known, for writing it back the same way.
+    shape: Option<State>,
+}
+
+/// The key (for maps) and unparsed value of an element.
+type RawEntry = (Option<String>, Vec<u8>);
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    Start,
+    Array,
+    Object,
+    Lines,
+    Done,
+}
+
+/// One page of elements from a collection config.
+#[derive(Debug, Clone)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+
+    /// Offset to pass to get the next page, or `None` if this is the last page.
+    pub next_offset: Option<usize>,
+}
+
+impl<T: DeserializeOwned> Entries<T> {
+    fn new(reader: Box<dyn Read + Send>, config_path: PathBuf) -> Self {
+        Self {
+            raw: RawEntries::new(reader, config_path, false),
+            _item: PhantomData,
+        }
+    }
+
+    /// An iterator that yields nothing, for configs whose file doesn't exist yet.
+    fn empty(config_path: PathBuf) -> Self {
+        Self::new(Box::new(io::empty()), config_path)
+    }
+
+    /// Collect the `limit` elements starting at `offset` into a page.
+    pub fn page(self, offset: usize, limit: usize) -> Result<Page<T>, ConfigError> {
+        page(self, offset, limit)
+    }
+}
+
+impl<T: DeserializeOwned> MapEntries<T> {
+    fn new(reader: Box<dyn Read + Send>, config_path: PathBuf) -> Self {
+        Self {
+            raw: RawEntries::new(reader, config_path, true),
+            _item: PhantomData,
+        }
+    }
+
+    /// Collect the `limit` entries starting at `offset` into a page.
+    pub fn page(self, offset: usize, limit: usize) -> Result<Page<(String, T)>, ConfigError> {
+        page(self, offset, limit)
+    }
+}
+
+fn page<T>(
+    entries: impl Iterator<Item = Result<T, ConfigError>>,
+    offset: usize,
+    limit: usize,
+) -> Result<Page<T>, ConfigError> {
+    let mut rest = entries.skip(offset);
+    let items = rest.by_ref().take(limit).collect::<Result<Vec<T>, _>>()?;
+    let next_offset = match rest.next() {
+        Some(item) => item.map(|_| Some(offset + limit))?,
+        None => None,
+    };
+    Ok(Page { items, next_offset })
+}
+
+impl RawEntries {
+    fn new(reader: Box<dyn Read + Send>, config_path: PathBuf, objects: bool) -> Self {
+        Self {
+            reader: BufReader::new(reader),
+            config_path,
+            objects,
+            state: State::Start,
+            shape: None,
+        }
+    }
+
+    fn peek(&mut self) -> io::Result<Option<u8>> {
+        loop {
+            let buffer = self.reader.fill_buf()?;
+            match buffer.first() {
+                None => return Ok(None),
+                Some(b) if b.is_ascii_whitespace() => self.reader.consume(1),
+                Some(&b) => return Ok(Some(b)),
+            }
+        }
+    }
+
+    fn next_byte(&mut self) -> io::Result<Option<u8>> {
+        let b = self.reader.fill_buf()?.first().copied();
+        if b.is_some() {
+            self.reader.consume(1);
+        }
+        Ok(b)
+    }
+
+    /// Read the raw bytes of the next JSON value, stopping before a top-level `,`, `:`, `]` or
+    /// `}` or, in JSON Lines mode, whitespace.
+    fn read_value(&mut self) -> io::Result<Vec<u8>> {
+        let mut value = Vec::new();
+        let (mut depth, mut in_string, mut escaped) = (0usize, false, false);
+        loop {
+            let Some(&b) = self.reader.fill_buf()?.first() else {
+                return Ok(value);
+            };
+            if in_string {
+                match (escaped, b) {
+                    (true, _) => escaped = false,
+                    (false, b'\\') => escaped = true,
+                    (false, b'"') => in_string = false,
+                    _ => (),
+                }
+            } else {
+                match b {
+                    b'"' => in_string = true,
+                    b'{' | b'[' => depth += 1,
+                    b'}' | b']' if depth > 0 => depth -= 1,
+                    b',' | b':' | b']' | b'}' if depth == 0 => return Ok(value),
+                    b if b.is_ascii_whitespace() && depth == 0 && self.state == State::Lines => {
+                        return Ok(value)
+                    }
+                    _ => (),
+                }
+            }
+            value.push(b);
+            self.reader.consume(1);
+        }
+    }
+
+    fn malformed(&self, message: &str) -> ConfigError {
+        let e = io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+        ConfigError::ConfigFileLoadError(self.config_path.clone(), e)
+    }
+
+    /// Find out the layout of the file, consuming the opening bracket if there is one.
+    fn start(&mut self) -> Result<State, ConfigError> {
+        if let Some(shape) = self.shape {
+            return Ok(shape);
+        }
+        let first = self
+            .peek()
+            .map_err(|e| ConfigError::ConfigFileLoadError(self.config_path.clone(), e))?;
+        self.state = match (first, self.objects) {
+            (Some(b'['), false) => State::Array,
+            (Some(b'{'), true) => State::Object,
+            (Some(_), false) => State::Lines,
+            (Some(_), true) => return Err(self.malformed("expected a JSON object")),
+            (None, _) => State::Done,
+        };
+        if matches!(self.state, State::Array | State::Object) {
+            self.reader.consume(1);
+        }
+        self.shape = Some(self.state);
+        Ok(self.state)
+    }
+
+    /// The next element, along with its key if the config is a map.
+    fn next_raw(&mut self) -> Result<Option<RawEntry>, ConfigError> {
+        let config_path = self.config_path.clone();
+        let load_error = |e| ConfigError::ConfigFileLoadError(config_path.clone(), e);
+
+        self.start()?;
+        let close = if self.state == State::Object {
+            b'}'
+        } else {
+            b']'
+        };
+        match self.state {
+            State::Done => return Ok(None),
+            State::Array | State::Object => match self.peek().map_err(load_error)? {
+                Some(b) if b == close => {
+                    self.state = State::Done;
+                    return Ok(None);
+                }
+                None => return Err(self.malformed("collection is never closed")),
+                _ => (),
+            },
+            State::Lines => {
+                if self.peek().map_err(load_error)?.is_none() {
+                    self.state = State::Done;
+                    return Ok(None);
+                }
+            }
+            State::Start => unreachable!("state is set above"),
+        }
+
+        let key = if self.state == State::Object {
+            let key = self.read_value().map_err(load_error)?;
+            let key = serde_json::from_slice::<String>(&key)
+                .map_err(|e| ConfigError::ConfigFileParseError(config_path.clone(), e, None))?;
+            if self.peek().map_err(load_error)? != Some(b':') {
+                return Err(self.malformed("expected `:` after object key"));
+            }
+            self.reader.consume(1);
+            self.peek().map_err(load_error)?;
+            Some(key)
+        } else {
+            None
+        };
+        let value = self.read_value().map_err(load_error)?;
+
+        if self.state != State::Lines {
+            match self.peek().map_err(load_error)? {
+                Some(b',') => {
+                    self.next_byte().map_err(load_error)?;
+                }
+                Some(b) if b == close => (),
+                _ => return Err(self.malformed("expected `,` or the end of the collection")),
+            }
+        }
+        Ok(Some((key, value)))
+    }
+
+    fn parse<T: DeserializeOwned>(&self, value: &[u8]) -> Result<T, ConfigError> {
+        serde_json::from_slice(value)
+            .map_err(|e| ConfigError::ConfigFileParseError(self.config_path.clone(), e, None))
+    }
+
+    /// Stop yielding entries after an error, since the stream is in an unknown state.
+    fn fuse<T>(
+        &mut self,
+        result: Result<Option<T>, ConfigError>,
+    ) -> Option<Result<T, ConfigError>> {
+        match result {
+            Ok(entry) => entry.map(Ok),
+            Err(e) => {
+                self.state = State::Done;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl<T: DeserializeOwned> Iterator for Entries<T> {
+    type Item = Result<T, ConfigError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self
+            .raw
+            .next_raw()
+            .and_then(|entry| entry.map(|(_, value)| self.raw.parse(&value)).transpose());
+        self.raw.fuse(entry)
+    }
+}
+
+impl<T: DeserializeOwned> Iterator for MapEntries<T> {
+    type Item = Result<(String, T), ConfigError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.raw.next_raw().and_then(|entry| {
+            entry
+                .map(|(key, value)| Ok((key.unwrap_or_default(), self.raw.parse(&value)?)))
+                .transpose()
+        });
+        self.raw.fuse(entry)
+    }
+}
+
+impl<TItem: Serialize + DeserializeOwned> Config<Vec<TItem>> {
+    /// Iterate over the elements of a collection config without loading the whole file.
+    ///
+    /// Besides a JSON array, the file may also contain whitespace-separated values such as JSON
+    /// Lines.  JSONC files are read into memory first to strip their comments.
+    pub fn stream(
+        config_file_key: &str,
+        options: &ConfigOptions,
+    ) -> Result<Entries<TItem>, ConfigError> {
+        let config_path = Self::get_config_path(config_file_key, options)?;
+        Ok(match open(config_file_key, &config_path, options)? {
+            Some(reader) => Entries::new(reader, config_path),
+            None => Entries::empty(config_path),
+        })
+    }
+
+    /// Read `limit` elements of a collection config starting at `offset`, without loading the
+    /// whole file.
+    pub fn page(
+        config_file_key: &str,
+        options: &ConfigOptions,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Page<TItem>, ConfigError> {
+        Self::stream(config_file_key, options)?.page(offset, limit)
+    }
+
+    /// Update a collection config one element at a time without loading the whole file,
+    /// returning the number of elements left.
+    ///
+    /// `f` is called with each element in turn and returns the element to keep in its place, or
+    /// `None` to remove it.  The new file is written next to the old one and moved over it once
+    /// every element has been processed, while holding the same lock as
+    /// [`update`](Config::update).  If `f` panics or an element can't be read, the file is left as
+    /// it was.  A file that doesn't exist yet is left alone.
+    ///
+    /// Only plain JSON files can be rewritten this way; JSONC, compressed and encrypted configs
+    /// fail with an `Unsupported` write error.  Loaded configs see the rewrite as a change made
+    /// by someone else.
+    ///
+    /// ```no_run
+    /// # use ilo_config::{Config, ConfigOptions};
+    /// // Remove completed items from a large todo list
+    /// let left = Config::<Vec<(String, bool)>>::rewrite("todo", &ConfigOptions::default(), |item| {
+    ///     (!item.1).then_some(item)
+    /// })?;
+    /// # Ok::<(), ilo_config::ConfigError>(())
+    /// ```
+    pub fn rewrite(
+        config_file_key: &str,
+        options: &ConfigOptions,
+        mut f: impl FnMut(TItem) -> Option<TItem>,
+    ) -> Result<usize, ConfigError> {
+        rewrite(config_file_key, options, false, |_, item| f(item))
+    }
+}
+
+impl<TItem: Serialize + DeserializeOwned> Config<BTreeMap<String, TItem>> {
+    /// Iterate over the entries of a map config in file order, without loading the whole file.
+    ///
+    /// The file format is the same for any map with string keys, so this works just as well for
+    /// configs that are loaded as a `HashMap`.  JSONC files are read into memory first to strip
+    /// their comments.
+    pub fn stream(
+        config_file_key: &str,
+        options: &ConfigOptions,
+    ) -> Result<MapEntries<TItem>, ConfigError> {
+        let config_path = Self::get_config_path(config_file_key, options)?;
+        let reader =
+            open(config_file_key, &config_path, options)?.unwrap_or_else(|| Box::new(io::empty()));
+        Ok(MapEntries::new(reader, config_path))
+    }
+
+    /// Read `limit` entries of a map config starting at `offset`, without loading the whole
+    /// file.
+    pub fn page(
+        config_file_key: &str,
+        options: &ConfigOptions,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Page<(String, TItem)>, ConfigError> {
+        Self::stream(config_file_key, options)?.page(offset, limit)
+    }
+
+    /// Update a map config one entry at a time without loading the whole file, returning the
+    /// number of entries left.
+    ///
+    /// `f` is called with the key and value of each entry and returns the value to keep, or
+    /// `None` to remove the entry.  Entries stay in file order, even with
+    /// [`Formatting::sort_keys`].  Otherwise this works like
+    /// [`Config::<Vec<_>>::rewrite`](Config::rewrite).
+    pub fn rewrite(
+        config_file_key: &str,
+        options: &ConfigOptions,
+        mut f: impl FnMut(&str, TItem) -> Option<TItem>,
+    ) -> Result<usize, ConfigError> {
+        rewrite(config_file_key, options, true, |key, item| {
+            f(key.unwrap_or_default(), item)
+        })
+    }
+}
+
+/// Open a config file for streaming, or return `None` if it doesn't exist.
+fn open(
+    #[cfg_attr(not(feature = "signing"), allow(unused_variables))] config_file_key: &str,
+    config_path: &Path,
+    options: &ConfigOptions,
+) -> Result<Option<Box<dyn Read + Send>>, ConfigError> {
+    if !options.filesystem.is_file(config_path) {
+        return Ok(None);
+    }
+    #[cfg(feature = "signing")]
+    if let Some(signing) = &options.signing {
+        let signed = crate::signing::Contents::File(config_path);
+        signing.verify(config_file_key, config_path, signed, options)?;
+    }
+
+    let load_error = |e| ConfigError::ConfigFileLoadError(config_path.to_path_buf(), e);
+    let reader = match options.format {
+        ConfigFormat::Json => {
+            let reader = options.filesystem.open(config_path).map_err(load_error)?;
+            #[cfg(any(feature = "gzip", feature = "zstd"))]
+            let reader = match options.compression {
+                Some(compression) => compression.decoder(reader).map_err(load_error)?,
+                None => reader,
+            };
+            reader
+        }
+        ConfigFormat::Jsonc => {
+            let contents = Config::<Value>::read_config_file(config_path, options)?;
+            #[cfg(any(feature = "gzip", feature = "zstd"))]
+            let contents = crate::compression::decompress_if_compressed(contents, config_path)?;
+            let (stripped, _) = jsonc::strip(&String::from_utf8_lossy(&contents));
+            Box::new(io::Cursor::new(stripped.into_bytes()))
+        }
+    };
+    Ok(Some(reader))
+}
+
+fn rewrite<T: Serialize + DeserializeOwned>(
+    config_file_key: &str,
+    options: &ConfigOptions,
+    objects: bool,
+    mut f: impl FnMut(Option<&str>, T) -> Option<T>,
+) -> Result<usize, ConfigError> {
+    let config_path = Config::<Value>::get_config_path(config_file_key, options)?;
+    #[cfg(any(feature = "gzip", feature = "zstd"))]
+    let compressed = options.compression.is_some();
+    #[cfg(not(any(feature = "gzip", feature = "zstd")))]
+    let compressed = false;
+    #[cfg(feature = "encryption")]
+    let encrypted = options.encryption.is_some();
+    #[cfg(not(feature = "encryption"))]
+    let encrypted = false;
+    if options.format != ConfigFormat::Json || compressed || encrypted {
+        return Err(ConfigError::ConfigFileWriteError(
+            config_path,
+            io::Error::new(
+                io::ErrorKind::Unsupported,
+                "only plain JSON configs can be rewritten incrementally",
+            ),
+        ));
+    }
+
+    let lock_path =
+        Config::<Value>::ensure_config_root(options)?.join(format!("{}.lock", config_file_key));
+    let _lock = options
+        .filesystem
+        .lock(&lock_path)
+        .map_err(|e| ConfigError::ConfigLockError(lock_path.clone(), e))?;
+    let Some(reader) = open(config_file_key, &config_path, options)? else {
+        return Ok(0);
+    };
+    let mut entries = RawEntries::new(reader, config_path.clone(), objects);
+    let shape = entries.start()?;
+
+    let temp_path = Config::<Value>::sidecar_path(&config_path, "tmp");
+    let output = options
+        .filesystem
+        .create(&temp_path, Some(options.file_mode))
+        .map_err(|e| ConfigError::ConfigFileWriteError(temp_path.clone(), e))?;
+    let mut writer = CollectionWriter::new(output, &temp_path, shape, &options.formatting)?;
+    let written = (|| {
+        while let Some((key, value)) = entries.next_raw()? {
+            if let Some(value) = f(key.as_deref(), entries.parse(&value)?) {
+                writer.push(key.as_deref(), &value)?;
+            }
+        }
+        let len = writer.len;
+        writer.finish().map(|()| len)
+    })();
+    let len = match written {
+        Ok(len) => len,
+        Err(e) => {
+            let _ = options.filesystem.remove_file(&temp_path);
+            return Err(e);
+        }
+    };
+
+    // Existing files keep their permissions when written in place, so carry them over
+    if let Ok(Some(mode)) = options.filesystem.permissions(&config_path) {
+        options
+            .filesystem
+            .set_permissions(&temp_path, mode)
+            .map_err(|e| ConfigError::ConfigFileWriteError(temp_path.clone(), e))?;
+    }
+    #[cfg(feature = "signing")]
+    let signed = match &options.signing {
+        Some(signing) => {
+            let contents = crate::signing::Contents::File(&temp_path);
+            let counter = signing.sign(config_file_key, &config_path, contents, options)?;
+            Some((signing, counter))
+        }
+        None => None,
+    };
+    options
+        .filesystem
+        .rename(&temp_path, &config_path)
+        .map_err(|e| ConfigError::ConfigFileWriteError(config_path.clone(), e))?;
+    #[cfg(feature = "signing")]
+    if let Some((signing, counter)) = signed {
+        signing.written(&config_path, counter);
+    }
+    if options.save.fsync {
+        Config::<Value>::sync_to_disk(&config_path, options)?;
+    }
+    #[cfg(feature = "audit-log")]
+    crate::audit::log::record_file(
+        config_file_key,
+        crate::audit::AuditOperation::Save,
+        &config_path,
+        options,
+    )?;
+    Ok(len)
+}
+
+/// Writes the elements of a collection config one at a time, in the layout it was read in.
+struct CollectionWriter<'a> {
+    output: Box<dyn Write + Send>,
+    path: &'a Path,
+    shape: State,
+    formatting: Formatting,
+    indent: Option<String>,
+    trailing_newline: bool,
+    len: usize,
+}
+
+impl<'a> CollectionWriter<'a> {
+    fn new(
+        mut output: Box<dyn Write + Send>,
+        path: &'a Path,
+        shape: State,
+        formatting: &Formatting,
+    ) -> Result<Self, ConfigError> {
+        let opening: &[u8] = match shape {
+            State::Array => b"[",
+            State::Object => b"{",
+            _ => b"",
+        };
+        output
+            .write_all(opening)
+            .map_err(|e| ConfigError::ConfigFileWriteError(path.to_path_buf(), e))?;
+        // Each line of a JSON Lines file must hold a whole element
+        let indent = match shape {
+            State::Lines => Indent::Compact,
+            _ => formatting.indent,
+        };
+        Ok(Self {
+            output,
+            path,
+            shape,
+            formatting: Formatting {
+                indent,
+                trailing_newline: false,
+                ..formatting.clone()
+            },
+            indent: indent.unit(),
+            trailing_newline: formatting.trailing_newline,
+            len: 0,
+        })
+    }
+
+    fn push<T: Serialize>(&mut self, key: Option<&str>, value: &T) -> Result<(), ConfigError> {
+        let value = self
+            .formatting
+            .to_vec(value)
+            .map_err(ConfigError::ConfigFileSerializeError)?;
+        let mut element = Vec::new();
+        if self.shape == State::Lines {
+            element.extend(value);
+            element.push(b'\n');
+        } else {
+            if self.len > 0 {
+                element.push(b',');
+            }
+            if let Some(indent) = &self.indent {
+                element.push(b'\n');
+                element.extend(indent.as_bytes());
+            }
+            if let Some(key) = key {
+                serde_json::to_writer(&mut element, key)
+                    .map_err(ConfigError::ConfigFileSerializeError)?;
+                element.extend(if self.indent.is_some() {
+                    &b": "[..]
+                } else {
+                    b":"
+                });
+            }
+            // Nested lines move one level in; newlines in strings are always escaped
+            for b in value {
+                element.push(b);
+                if let (b'\n', Some(indent)) = (b, &self.indent) {
+                    element.extend(indent.as_bytes());
+                }
+            }
+        }
+        self.len += 1;
+        self.write(&element)
+    }
+
+    fn finish(mut self) -> Result<(), ConfigError> {
+        let mut closing = Vec::new();
+        if matches!(self.shape, State::Array | State::Object) {
+            if self.len > 0 && self.indent.is_some() {
+                closing.push(b'\n');
+            }
+            closing.push(if self.shape == State::Array {
+                b']'
+            } else {
+                b'}'
+            });
+            if self.trailing_newline {
+                closing.push(b'\n');
+            }
+        }
+        self.write(&closing)?;
+        self.output
+            .flush()
+            .map_err(|e| ConfigError::ConfigFileWriteError(self.path.to_path_buf(), e))
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), ConfigError> {
+        self.output
+            .write_all(bytes)
+            .map_err(|e| ConfigError::ConfigFileWriteError(self.path.to_path_buf(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, fs, io, panic, path::Path};
+
+    use crate::{Config, ConfigError, ConfigFormat, ConfigOptions};
+
+    fn options(root: &Path) -> ConfigOptions {
+        ConfigOptions {
+            root: Some(root.to_path_buf()),
+            ..Default::default()
+        }
+    }
+
+    fn collect<T: serde::de::DeserializeOwned>(
+        entries: impl Iterator<Item = Result<T, ConfigError>>,
+    ) -> Vec<T> {
+        entries.collect::<Result<_, _>>().unwrap()
+    }
+
+    #[test]
+    fn arrays_and_json_lines_stream_the_same() {
+        let root = tempfile::tempdir().unwrap();
+        let options = options(root.path());
+        fs::write(root.path().join("array.json"), "[1, 2,\n 3]\n").unwrap();
+        fs::write(root.path().join("lines.json"), "1\n2\n\n3\n").unwrap();
+
+        let array = Config::<Vec<u32>>::stream("array", &options).unwrap();
+        assert_eq!(collect(array), [1, 2, 3]);
+        let lines = Config::<Vec<u32>>::stream("lines", &options).unwrap();
+        assert_eq!(collect(lines), [1, 2, 3]);
+    }
+
+    #[test]
+    fn missing_files_stream_nothing() {
+        let root = tempfile::tempdir().unwrap();
+        let options = options(root.path());
+        assert!(collect(Config::<Vec<u32>>::stream("missing", &options).unwrap()).is_empty());
+        let map = Config::<BTreeMap<String, u32>>::stream("missing", &options).unwrap();
+        assert!(collect(map).is_empty());
+    }
+
+    #[test]
+    fn maps_stream_in_file_order() {
+        let root = tempfile::tempdir().unwrap();
+        let options = options(root.path());
+        fs::write(
+            root.path().join("ports.json"),
+            r#"{"web": 80, "api": {"nested": "ignored"}, "db": 5432}"#,
+        )
+        .unwrap();
+
+        let entries = Config::<BTreeMap<String, serde_json::Value>>::stream("ports", &options)
+            .unwrap()
+            .map(|entry| entry.map(|(key, _)| key));
+        assert_eq!(collect(entries), ["web", "api", "db"]);
+    }
+
+    #[test]
+    fn pages_report_the_next_offset() {
+        let root = tempfile::tempdir().unwrap();
+        let options = options(root.path());
+        fs::write(root.path().join("numbers.json"), "[1, 2, 3, 4, 5]").unwrap();
+
+        let first = Config::<Vec<u32>>::page("numbers", &options, 0, 2).unwrap();
+        assert_eq!(first.items, [1, 2]);
+        assert_eq!(first.next_offset, Some(2));
+        let last = Config::<Vec<u32>>::page("numbers", &options, 4, 2).unwrap();
+        assert_eq!(last.items, [5]);
+        assert_eq!(last.next_offset, None);
+        let exact = Config::<Vec<u32>>::page("numbers", &options, 3, 2).unwrap();
+        assert_eq!(exact.items, [4, 5]);
+        assert_eq!(exact.next_offset, None);
+    }
+
+    #[test]
+    fn malformed_elements_are_reported() {
+        let root = tempfile::tempdir().unwrap();
+        let options = options(root.path());
+        fs::write(root.path().join("broken.json"), r#"[1, "two", 3]"#).unwrap();
+
+        let mut entries = Config::<Vec<u32>>::stream("broken", &options).unwrap();
+        assert_eq!(entries.next().unwrap().unwrap(), 1);
+        assert!(matches!(
+            entries.next(),
+            Some(Err(ConfigError::ConfigFileParseError(..)))
+        ));
+    }
+
+    #[test]
+    fn rewrite_updates_and_removes_elements() {
+        let root = tempfile::tempdir().unwrap();
+        let options = options(root.path());
+        fs::write(root.path().join("todo.json"), "[1, 2, 3, 4]").unwrap();
+
+        let left =
+            Config::<Vec<u32>>::rewrite("todo", &options, |n| (n % 2 == 0).then_some(n * 10))
+                .unwrap();
+        assert_eq!(left, 2);
+        let config: Config<Vec<u32>> = Config::load_with_options("todo", options).unwrap();
+        assert_eq!(config.data(), &[20, 40]);
+        assert!(!root.path().join("todo.json.tmp").exists());
+    }
+
+    #[test]
+    fn rewrite_keeps_json_lines() {
+        let root = tempfile::tempdir().unwrap();
+        let options = options(root.path());
+        fs::write(root.path().join("log.json"), "{\"n\":1}\n{\"n\":2}\n").unwrap();
+
+        Config::<Vec<serde_json::Value>>::rewrite("log", &options, |mut entry| {
+            entry["seen"] = true.into();
+            Some(entry)
+        })
+        .unwrap();
+        let contents = fs::read_to_string(root.path().join("log.json")).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines, [r#"{"n":1,"seen":true}"#, r#"{"n":2,"seen":true}"#]);
+    }
+
+    #[test]
+    fn rewrite_keeps_map_entries_in_file_order() {
+        let root = tempfile::tempdir().unwrap();
+        let options = options(root.path());
+        fs::write(
+            root.path().join("ports.json"),
+            r#"{"web": 80, "db": 5432, "api": 8080}"#,
+        )
+        .unwrap();
+
+        let left = Config::<BTreeMap<String, u32>>::rewrite("ports", &options, |key, port| {
+            (key != "db").then_some(port + 1)
+        })
+        .unwrap();
+        assert_eq!(left, 2);
+        let entries = Config::<BTreeMap<String, u32>>::stream("ports", &options).unwrap();
+        assert_eq!(
+            collect(entries),
+            [(String::from("web"), 81), (String::from("api"), 8081)]
+        );
+    }
+
+    #[test]
+    fn failed_rewrites_leave_the_file_alone() {
+        let root = tempfile::tempdir().unwrap();
+        let options = options(root.path());
+        let config_path = root.path().join("todo.json");
+        fs::write(&config_path, "[1, 2, 3]").unwrap();
+
+        let panicked = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            Config::<Vec<u32>>::rewrite("todo", &options, |n| {
+                assert!(n < 2, "element {} is too large", n);
+                Some(n)
+            })
+        }));
+        assert!(panicked.is_err());
+        assert_eq!(fs::read_to_string(&config_path).unwrap(), "[1, 2, 3]");
+
+        fs::write(&config_path, r#"[1, "two", 3]"#).unwrap();
+        let result = Config::<Vec<u32>>::rewrite("todo", &options, Some);
+        assert!(matches!(result, Err(ConfigError::ConfigFileParseError(..))));
+        assert_eq!(
+            fs::read_to_string(&config_path).unwrap(),
+            r#"[1, "two", 3]"#
+        );
+        assert!(!root.path().join("todo.json.tmp").exists());
+    }
+
+    #[test]
+    fn rewrite_of_a_missing_file_does_nothing() {
+        let root = tempfile::tempdir().unwrap();
+        let options = options(root.path());
+        assert_eq!(
+            Config::<Vec<u32>>::rewrite("todo", &options, Some).unwrap(),
+            0
+        );
+        assert!(!root.path().join("todo.json").exists());
+    }
+
+    #[test]
+    fn rewrite_refuses_jsonc() {
+        let root = tempfile::tempdir().unwrap();
+        let options = ConfigOptions {
+            format: ConfigFormat::Jsonc,
+            ..options(root.path())
+        };
+        fs::write(root.path().join("todo.jsonc"), "[1, // one\n 2]").unwrap();
+
+        match Config::<Vec<u32>>::rewrite("todo", &options, Some) {
+            Err(ConfigError::ConfigFileWriteError(_, e)) => {
+                assert_eq!(e.kind(), io::ErrorKind::Unsupported)
+            }
+            result => panic!("expected an unsupported write, got {:?}", result),
+        }
+    }
+}
@@ -0,0 +1,367 @@
+//! Synchronizing a config root between machines through a shared remote.
+//!
+//! A [`SyncEngine`] pushes local config files to a [`Backend`] and pulls the ones that other
+//! machines pushed, e.g. an [`S3Backend`](crate::backend::s3::S3Backend), an
+//! [`SshBackend`](crate::backend::ssh::SshBackend) or a WebDAV server through an
+//! [`HttpBackend`](crate::backend::http::HttpBackend) with PUT allowed.  Files are transferred
+//! as-is, so encrypted and compressed configs stay that way on the remote.
+//!
+//! Next to the documents, the remote holds a `.ilo-sync` document with a revision number for
+//! every key and who last wrote it, and each machine records the revisions it last synced in
+//! `.ilo-sync.json` in its config root.  That way a key that was changed both locally and on the
+//! remote since the last sync is reported as a conflict instead of one side silently replacing
+//! the other.  The `.ilo-sync` document is updated with
+//! [`save_if_unchanged`](Backend::save_if_unchanged), so machines that push at the same time
+//! don't lose each other's revisions, and documents are only uploaded after their revision is
+//! recorded there.
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    backend::Backend, listing::list_keys, variables, Config, ConfigError, ConfigOptions,
+    RetryPolicy,
+};
+
+/// Key of the metadata document on the remote.
+const REMOTE_METADATA_KEY: &str = ".ilo-sync";
+
+/// Local sync state, kept in the config root.
+const STATE_FILE: &str = ".ilo-sync.json";
+
+/// Last-writer metadata of a key on the remote.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct RemoteEntry {
+    /// Incremented on every push.
+    revision: u64,
+    hash: String,
+    writer: String,
+    /// Seconds since the UNIX epoch.
+    written_at: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct RemoteMetadata {
+    keys: BTreeMap<String, RemoteEntry>,
+}
+
+/// What this machine last synced for a key.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct StateEntry {
+    revision: u64,
+    hash: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct State {
+    keys: BTreeMap<String, StateEntry>,
+}
+
+/// A key that was changed both locally and on the remote since the last sync.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncConflict {
+    pub key: String,
+
+    /// The machine that last pushed the remote version.
+    pub writer: String,
+
+    /// When the remote version was pushed.
+    pub written_at: SystemTime,
+}
+
+/// The outcome of a sync.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SyncReport {
+    /// Keys whose local version was uploaded.
+    pub pushed: Vec<String>,
+
+    /// Keys whose remote version was downloaded.
+    pub pulled: Vec<String>,
+
+    /// Keys that were left alone on both sides because they conflict.
+    pub conflicts: Vec<SyncConflict>,
+}
+
+/// Pushes and pulls the configs in a config root (or the namespace, if set) to and from a
+/// remote.
+///
+/// ```no_run
+/// # use std::sync::Arc;
+/// # use ilo_config::{backend::ssh::SshBackend, sync::SyncEngine};
+/// let remote = SshBackend::new("me@nas.local", "ilo-sync");
+/// let report = SyncEngine::new(Arc::new(remote)).run()?;
+/// for conflict in &report.conflicts {
+///     eprintln!("{} was also changed on {}", conflict.key, conflict.writer);
+/// }
+/// # Ok::<(), ilo_config::ConfigError>(())
+/// ```
+///
+/// Deleting a config isn't synced: a key that exists on only one side is copied to the other.
+#[derive(Debug, Clone)]
+pub struct SyncEngine {
+    remote: Arc<dyn Backend>,
+    options: ConfigOptions,
+    keys: Option<Vec<String>>,
+    device: String,
+}
+
+impl SyncEngine {
+    /// Sync the default config root with `remote`.
+    pub fn new(remote: Arc<dyn Backend>) -> Self {
+        Self {
+            remote,
+            options: ConfigOptions::default(),
+            keys: None,
+            device: variables::hostname().unwrap_or_else(|| String::from("unknown")),
+        }
+    }
+
+    /// Sync the config root selected by `options` (root, namespace, format, filesystem, ...).
+    pub fn options(mut self, options: ConfigOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Only sync these keys.  By default, every key that exists locally or on the remote is
+    /// synced.
+    pub fn keys<I, S>(mut self, keys: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.keys = Some(keys.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Name this machine in the last-writer metadata instead of using its hostname.
+    pub fn device(mut self, device: impl Into<String>) -> Self {
+        self.device = device.into();
+        self
+    }
+
+    /// Upload local changes and download remote ones.
+    pub fn run(&self) -> Result<SyncReport, ConfigError> {
+        self.sync(true, true)
+    }
+
+    /// Only upload local changes.  Keys that changed on the remote are left alone.
+    pub fn push(&self) -> Result<SyncReport, ConfigError> {
+        self.sync(true, false)
+    }
+
+    /// Only download remote changes.  Keys that changed locally are left alone.
+    pub fn pull(&self) -> Result<SyncReport, ConfigError> {
+        self.sync(false, true)
+    }
+
+    fn sync(&self, push: bool, pull: bool) -> Result<SyncReport, ConfigError> {
+        let options = &self.options;
+        let config_root = Config::<Value>::ensure_config_root(options)?;
+        let lock_path = config_root.join(".ilo-sync.lock");
+        let _lock = options
+            .filesystem
+            .lock(&lock_path)
+            .map_err(|e| ConfigError::ConfigLockError(lock_path.clone(), e))?;
+
+        let state_path = config_root.join(STATE_FILE);
+        let mut state: State = read(&state_path, options)?.unwrap_or_default();
+        let mut loaded_metadata = self.remote.load(REMOTE_METADATA_KEY)?;
+        let mut metadata = self.parse_metadata(loaded_metadata.as_deref())?;
+
+        let keys = match &self.keys {
+            Some(keys) => keys.clone(),
+            None => {
+                let mut keys = list_keys(options)?;
+                keys.extend(metadata.keys.keys().cloned());
+                keys.sort();
+                keys.dedup();
+                keys
+            }
+        };
+
+        let mut report = SyncReport::default();
+        // The remote revision each pushed key replaced, what was synced for it before and the
+        // document to upload
+        let mut pushed = BTreeMap::new();
+        for key in keys {
+            let config_path = Config::<Value>::get_config_path(&key, options)?;
+            let local = if options.filesystem.is_file(&config_path) {
+                Some(Config::<Value>::read_config_file(&config_path, options)?)
+            } else {
+                None
+            };
+            let local_hash = local.as_deref().map(hash);
+            let synced = state.keys.get(&key);
+            let remote = metadata.keys.get(&key);
+
+            let local_changed =
+                local_hash.is_some() && local_hash.as_ref() != synced.map(|entry| &entry.hash);
+            let remote_changed =
+                remote.is_some() && remote.map(|e| e.revision) != synced.map(|e| e.revision);
+
+            match (local_changed, remote_changed) {
+                (false, false) => {}
+                (true, true) if remote.map(|e| &e.hash) == local_hash.as_ref() => {
+                    // Both sides made the same change, or this machine recorded its push but
+                    // failed to upload the document afterwards
+                    let remote = remote.expect("remote_changed implies an entry");
+                    let contents = local.expect("local_changed implies a local file");
+                    if push && self.remote.load(&key)?.map(|c| hash(&c)) != local_hash {
+                        self.remote.save(&key, &contents)?;
+                    }
+                    state.keys.insert(key, StateEntry::from(remote));
+                }
+                (true, true) => {
+                    let remote = remote.expect("remote_changed implies an entry");
+                    report.conflicts.push(SyncConflict {
+                        key,
+                        writer: remote.writer.clone(),
+                        written_at: UNIX_EPOCH + Duration::from_secs(remote.written_at),
+                    });
+                }
+                (true, false) if push => {
+                    let contents = local.expect("local_changed implies a local file");
+                    let entry = RemoteEntry {
+                        revision: remote.map_or(0, |e| e.revision) + 1,
+                        hash: hash(&contents),
+                        writer: self.device.clone(),
+                        written_at: options.clock.unix_timestamp(),
+                    };
+                    let previous = state.keys.insert(key.clone(), StateEntry::from(&entry));
+                    let replaced = remote.map(|e| e.revision);
+                    pushed.insert(key.clone(), (replaced, previous, contents));
+                    metadata.keys.insert(key.clone(), entry);
+                    report.pushed.push(key);
+                }
+                (false, true) if pull => {
+                    let remote = remote.expect("remote_changed implies an entry");
+                    let Some(contents) = self.remote.load(&key)? else {
+                        continue;
+                    };
+                    // The writer uploads the document after recording it in the metadata, so
+                    // it may not have arrived yet
+                    if hash(&contents) != remote.hash {
+                        continue;
+                    }
+                    write(&config_path, &contents, options)?;
+                    state.keys.insert(key.clone(), StateEntry::from(remote));
+                    report.pulled.push(key);
+                }
+                _ => {}
+            }
+        }
+
+        while !pushed.is_empty() {
+            let contents = serde_json::to_vec_pretty(&metadata)
+                .map_err(ConfigError::ConfigFileSerializeError)?;
+            if self.remote.save_if_unchanged(
+                REMOTE_METADATA_KEY,
+                loaded_metadata.as_deref(),
+                &contents,
+            )? {
+                break;
+            }
+
+            // Another machine synced in the meantime, so apply this push to its metadata instead.
+            // A key it pushed as well is a conflict after all.
+            loaded_metadata = self.remote.load(REMOTE_METADATA_KEY)?;
+            let mut latest = self.parse_metadata(loaded_metadata.as_deref())?;
+            pushed.retain(|key, (replaced, previous, _)| match latest.keys.get(key) {
+                Some(theirs) if Some(theirs.revision) != *replaced => {
+                    report.pushed.retain(|pushed_key| pushed_key != key);
+                    report.conflicts.push(SyncConflict {
+                        key: key.clone(),
+                        writer: theirs.writer.clone(),
+                        written_at: UNIX_EPOCH + Duration::from_secs(theirs.written_at),
+                    });
+                    match previous.take() {
+                        Some(previous) => state.keys.insert(key.clone(), previous),
+                        None => state.keys.remove(key),
+                    };
+                    false
+                }
+                _ => {
+                    latest.keys.insert(key.clone(), metadata.keys[key].clone());
+                    true
+                }
+            });
+            metadata = latest;
+        }
+
+        // Documents are only uploaded once their revision is recorded, so a key that turned
+        // out to conflict never replaces the other machine's document
+        for (key, (_, _, contents)) in &pushed {
+            self.remote.save(key, contents)?;
+        }
+        let contents =
+            serde_json::to_vec_pretty(&state).map_err(ConfigError::ConfigFileSerializeError)?;
+        write(&state_path, &contents, options)?;
+        Ok(report)
+    }
+
+    /// Parse the remote metadata document, if there is one.
+    fn parse_metadata(&self, contents: Option<&[u8]>) -> Result<RemoteMetadata, ConfigError> {
+        match contents {
+            Some(contents) => serde_json::from_slice(contents).map_err(|e| {
+                let location = PathBuf::from(self.remote.location(REMOTE_METADATA_KEY));
+                ConfigError::ConfigFileParseError(location, e, None)
+            }),
+            None => Ok(RemoteMetadata::default()),
+        }
+    }
+}
+
+impl From<&RemoteEntry> for StateEntry {
+    fn from(entry: &RemoteEntry) -> Self {
+        Self {
+            revision: entry.revision,
+            hash: entry.hash.clone(),
+        }
+    }
+}
+
+/// Read a JSON file from the config root, or `None` if it doesn't exist.
+fn read<T: DeserializeOwned>(
+    path: &Path,
+    options: &ConfigOptions,
+) -> Result<Option<T>, ConfigError> {
+    if !options.filesystem.is_file(path) {
+        return Ok(None);
+    }
+    let contents = RetryPolicy::run(
+        options.retry.as_ref(),
+        path,
+        || options.filesystem.read(path),
+        ConfigError::ConfigFileLoadError,
+    )?;
+    serde_json::from_slice(&contents)
+        .map(Some)
+        .map_err(|e| ConfigError::ConfigFileParseError(path.to_path_buf(), e, None))
+}
+
+fn write(path: &Path, contents: &[u8], options: &ConfigOptions) -> Result<(), ConfigError> {
+    RetryPolicy::run(
+        options.retry.as_ref(),
+        path,
+        || {
+            options
+                .filesystem
+                .write(path, contents, Some(options.file_mode))
+        },
+        ConfigError::ConfigFileWriteError,
+    )
+}
+
+/// Hash of a file's contents that is the same on every machine (64-bit FNV-1a).
+fn hash(contents: &[u8]) -> String {
+    let hash = contents.iter().fold(0xcbf29ce484222325_u64, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    });
+    format!("{:016x}", hash)
+}
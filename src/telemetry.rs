@@ -0,0 +1,103 @@
+//! Hook for letting an app's own telemetry know which config features are in use.
+//!
+//! Nothing is ever sent anywhere by this crate; reports only go to the [`Telemetry`]
+//! implementation the app plugs into
+//! [`ConfigOptions::telemetry`](crate::ConfigOptions::telemetry).
+use std::fmt::Debug;
+
+use crate::{ConfigFormat, ConfigOptions};
+
+/// An optional config feature that was exercised at runtime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Feature {
+    Strict,
+    PreserveUnknownFields,
+    VerifyWrites,
+    ConflictMarkers,
+    History,
+    Git,
+    Jsonc,
+    Backend,
+    Namespace,
+    Quota,
+    EmbeddedDefaults,
+    TemplateVariables,
+    Interpolation,
+    Resolvers,
+    Includes,
+    DropIns,
+    HostOverrides,
+    PlatformOverrides,
+    SystemLayer,
+    DotEnv,
+    Encryption,
+    Signing,
+    Compression,
+    LockedUpdate,
+    Transaction,
+    ConflictResolution,
+    Merge,
+    Autosave,
+    Watch,
+    RevisionRestore,
+    Import,
+    Export,
+}
+
+/// Receiver for feature usage reports.
+pub trait Telemetry: Debug + Send + Sync {
+    /// Called when a config (identified by its key) uses a feature.  Options that are turned on
+    /// are reported each time a config is loaded with them.
+    fn feature_used(&self, config_file_key: &str, feature: Feature);
+}
+
+/// Report the features turned on in `options`.
+pub(crate) fn report_options(config_file_key: &str, options: &ConfigOptions) {
+    let Some(telemetry) = &options.telemetry else {
+        return;
+    };
+    let enabled = [
+        (options.strict, Feature::Strict),
+        (
+            options.preserve_unknown_fields,
+            Feature::PreserveUnknownFields,
+        ),
+        (options.verify_writes, Feature::VerifyWrites),
+        (options.conflict_markers, Feature::ConflictMarkers),
+        (options.history.is_some(), Feature::History),
+        (options.git, Feature::Git),
+        (options.interpolate, Feature::Interpolation),
+        (!options.resolvers.is_empty(), Feature::Resolvers),
+        (options.includes, Feature::Includes),
+        (options.drop_ins, Feature::DropIns),
+        (options.host_overrides, Feature::HostOverrides),
+        (options.platform_overrides, Feature::PlatformOverrides),
+        (options.system_root.is_some(), Feature::SystemLayer),
+        (options.dotenv.is_some(), Feature::DotEnv),
+        (options.format == ConfigFormat::Jsonc, Feature::Jsonc),
+        (options.backend.is_some(), Feature::Backend),
+        (options.namespace.is_some(), Feature::Namespace),
+        (options.quota.is_some(), Feature::Quota),
+        (options.defaults.is_some(), Feature::EmbeddedDefaults),
+        (
+            options.template_variables.is_some(),
+            Feature::TemplateVariables,
+        ),
+    ];
+    #[cfg(feature = "encryption")]
+    if options.encryption.is_some() {
+        telemetry.feature_used(config_file_key, Feature::Encryption);
+    }
+    #[cfg(feature = "signing")]
+    if options.signing.is_some() {
+        telemetry.feature_used(config_file_key, Feature::Signing);
+    }
+    #[cfg(any(feature = "gzip", feature = "zstd"))]
+    if options.compression.is_some() {
+        telemetry.feature_used(config_file_key, Feature::Compression);
+    }
+    for (_, feature) in enabled.into_iter().filter(|(enabled, _)| *enabled) {
+        telemetry.feature_used(config_file_key, feature);
+    }
+}
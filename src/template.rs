@@ -0,0 +1,160 @@
+//! Annotated first-run templates generated from a config data type's JSON Schema.
+//!
+//! A template is a JSONC document holding the default config data, with the doc comments of
+//! each field (the schema's `description`) as `//` comments above it.  Fields without a value
+//! that have `examples` in the schema are included as commented-out members, so new users can
+//! see what's configurable without those values taking effect.
+use serde_json::{Map, Value};
+
+/// Render a template for `value` described by `schema`.
+pub(crate) fn render(schema: &Value, value: &Value) -> String {
+    let mut output = String::new();
+    if let Some(description) = schema.get("description").and_then(Value::as_str) {
+        write_description(&mut output, description, 0);
+    }
+    write_value(&mut output, schema, Some(schema), value, 0);
+    output.push('\n');
+    output
+}
+
+/// Follow a local `$ref`, e.g. `#/$defs/Server`.
+pub(crate) fn resolve<'a>(root: &'a Value, schema: &'a Value) -> &'a Value {
+    schema
+        .get("$ref")
+        .and_then(Value::as_str)
+        .and_then(|reference| reference.strip_prefix('#'))
+        .and_then(|pointer| root.pointer(pointer))
+        .unwrap_or(schema)
+}
+
+/// The schema describing an object's properties, looking through `Option` and similar
+/// wrappers (`anyOf`/`oneOf`/`allOf`).
+pub(crate) fn object_schema<'a>(root: &'a Value, schema: &'a Value) -> Option<&'a Value> {
+    let schema = resolve(root, schema);
+    if schema.get("properties").is_some() {
+        return Some(schema);
+    }
+    ["anyOf", "oneOf", "allOf"]
+        .iter()
+        .filter_map(|keyword| schema.get(keyword).and_then(Value::as_array))
+        .flatten()
+        .find_map(|variant| object_schema(root, variant))
+}
+
+fn write_description(output: &mut String, description: &str, depth: usize) {
+    for line in description.lines() {
+        output.push_str(&"  ".repeat(depth));
+        output.push_str("//");
+        if !line.is_empty() {
+            output.push(' ');
+            output.push_str(line);
+        }
+        output.push('\n');
+    }
+}
+
+fn write_value(
+    output: &mut String,
+    root: &Value,
+    schema: Option<&Value>,
+    value: &Value,
+    depth: usize,
+) {
+    match (value, schema.and_then(|schema| object_schema(root, schema))) {
+        (Value::Object(map), Some(schema)) if !map.is_empty() || has_examples(root, schema) => {
+            write_object(output, root, schema, map, depth)
+        }
+        _ => {
+            let pretty = serde_json::to_string_pretty(value).expect("JSON values always serialize");
+            output.push_str(&pretty.replace('\n', &format!("\n{}", "  ".repeat(depth))));
+        }
+    }
+}
+
+fn has_examples(root: &Value, schema: &Value) -> bool {
+    schema
+        .get("properties")
+        .and_then(Value::as_object)
+        .is_some_and(|properties| {
+            properties
+                .values()
+                .any(|property| example(resolve(root, property)).is_some())
+        })
+}
+
+pub(crate) fn example(schema: &Value) -> Option<&Value> {
+    schema
+        .get("examples")
+        .and_then(Value::as_array)
+        .and_then(|examples| examples.first())
+}
+
+fn write_object(
+    output: &mut String,
+    root: &Value,
+    schema: &Value,
+    map: &Map<String, Value>,
+    depth: usize,
+) {
+    let empty = Map::new();
+    let properties = schema
+        .get("properties")
+        .and_then(Value::as_object)
+        .unwrap_or(&empty);
+    // Fields that are left out when they have no value only show up as examples
+    let examples: Vec<(&String, &Value)> = properties
+        .iter()
+        .filter(|(key, _)| !map.contains_key(*key))
+        .filter_map(|(key, property)| Some((key, example(resolve(root, property))?)))
+        .collect();
+
+    let indent = "  ".repeat(depth + 1);
+    output.push_str("{\n");
+    for (i, (key, value)) in map.iter().enumerate() {
+        let property = properties.get(key);
+        write_member_docs(output, root, key, property, value.is_null(), depth + 1);
+        output.push_str(&indent);
+        output.push_str(&serde_json::to_string(key).expect("strings always serialize"));
+        output.push_str(": ");
+        write_value(output, root, property, value, depth + 1);
+        if i + 1 < map.len() {
+            output.push(',');
+        }
+        output.push('\n');
+    }
+    for (key, _) in examples {
+        write_member_docs(output, root, key, properties.get(key), true, depth + 1);
+    }
+    output.push_str(&"  ".repeat(depth));
+    output.push('}');
+}
+
+/// Write a member's description and, if it has no value, its commented-out example value.
+fn write_member_docs(
+    output: &mut String,
+    root: &Value,
+    key: &str,
+    property: Option<&Value>,
+    show_example: bool,
+    depth: usize,
+) {
+    let Some(property) = property else {
+        return;
+    };
+    // A description next to a `$ref` describes the field rather than the referenced type
+    let description = property
+        .get("description")
+        .or_else(|| resolve(root, property).get("description"))
+        .and_then(Value::as_str);
+    if let Some(description) = description {
+        write_description(output, description, depth);
+    }
+    if let Some(example) = example(resolve(root, property)).filter(|_| show_example) {
+        output.push_str(&format!(
+            "{}// {}: {}\n",
+            "  ".repeat(depth),
+            serde_json::to_string(key).expect("strings always serialize"),
+            serde_json::to_string(example).expect("JSON values always serialize")
+        ));
+    }
+}
@@ -0,0 +1,232 @@
+//! Utilities for testing how applications handle config IO failures.
+//!
+//! [`FaultyFileSystem`] wraps another [`FileSystem`] and can be scripted to fail specific
+//! operations, so error-handling paths can be exercised without root access or unusual mounts:
+//!
+//! ```no_run
+//! # use std::{io::ErrorKind, sync::Arc};
+//! # use ilo_config::{Config, ConfigOptions, StdFileSystem};
+//! use ilo_config::testing::{Fault, FaultyFileSystem, Operation};
+//!
+//! // The second write fails as if the disk were full.
+//! let filesystem = FaultyFileSystem::new(Arc::new(StdFileSystem))
+//!     .fail_nth(Operation::Write, 2, Fault::Error(ErrorKind::StorageFull));
+//! let options = ConfigOptions {
+//!     filesystem: Arc::new(filesystem),
+//!     ..Default::default()
+//! };
+//! let config: Config<Vec<String>> = Config::load_with_options("todo", options).unwrap();
+//! config.save().unwrap();
+//! assert!(config.save().is_err());
+//! ```
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+use crate::filesystem::{FileSystem, LockGuard};
+
+/// Filesystem operation that a fault can be injected into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    Read,
+    Write,
+    Copy,
+    Rename,
+    RemoveFile,
+    RemoveDirAll,
+    ReadDir,
+    CreateDirAll,
+    Sync,
+    Lock,
+}
+
+/// What happens when a fault is triggered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// Fail with an error of the given kind, e.g. `StorageFull` (ENOSPC) or `PermissionDenied`
+    /// (EACCES).
+    Error(io::ErrorKind),
+
+    /// Only for reads: succeed, but return at most this many bytes of the file.
+    PartialRead(usize),
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// How many times each operation has been called so far.
+    calls: HashMap<Operation, usize>,
+
+    /// Faults for a single call of an operation, by 1-based call number.
+    nth: HashMap<(Operation, usize), Fault>,
+
+    /// Faults for every call of an operation.
+    all: HashMap<Operation, Fault>,
+}
+
+/// [`FileSystem`] that passes operations through to another one, except for scripted faults.
+#[derive(Debug)]
+pub struct FaultyFileSystem {
+    inner: Arc<dyn FileSystem>,
+    state: Mutex<State>,
+}
+
+impl FaultyFileSystem {
+    pub fn new(inner: Arc<dyn FileSystem>) -> Self {
+        Self {
+            inner,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Trigger `fault` on the `n`th call (starting from 1) of `operation`.
+    pub fn fail_nth(self, operation: Operation, n: usize, fault: Fault) -> Self {
+        self.lock_state().nth.insert((operation, n), fault);
+        self
+    }
+
+    /// Trigger `fault` on every call of `operation`.
+    pub fn fail_all(self, operation: Operation, fault: Fault) -> Self {
+        self.lock_state().all.insert(operation, fault);
+        self
+    }
+
+    /// Remove all scripted faults.  Call counts are kept.
+    pub fn heal(&self) {
+        let mut state = self.lock_state();
+        state.nth.clear();
+        state.all.clear();
+    }
+
+    /// How many times `operation` has been called so far.
+    pub fn calls(&self, operation: Operation) -> usize {
+        self.lock_state()
+            .calls
+            .get(&operation)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    fn lock_state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Count a call of `operation` and return the fault to trigger for it, if any.
+    fn next_fault(&self, operation: Operation) -> Option<Fault> {
+        let mut state = self.lock_state();
+        let n = state.calls.entry(operation).or_insert(0);
+        *n += 1;
+        let n = *n;
+        state
+            .nth
+            .remove(&(operation, n))
+            .or_else(|| state.all.get(&operation).copied())
+    }
+
+    /// Count a call of `operation` and fail it if an error is scripted.
+    fn check(&self, operation: Operation) -> io::Result<()> {
+        match self.next_fault(operation) {
+            Some(Fault::Error(kind)) => Err(injected(kind)),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn injected(kind: io::ErrorKind) -> io::Error {
+    io::Error::new(kind, "injected fault")
+}
+
+impl FileSystem for FaultyFileSystem {
+    fn is_file(&self, path: &Path) -> bool {
+        self.inner.is_file(path)
+    }
+
+    fn is_symlink(&self, path: &Path) -> bool {
+        self.inner.is_symlink(path)
+    }
+
+    fn try_exists(&self, path: &Path) -> io::Result<bool> {
+        self.inner.try_exists(path)
+    }
+
+    fn create_dir_all(&self, path: &Path, mode: Option<u32>) -> io::Result<()> {
+        self.check(Operation::CreateDirAll)?;
+        self.inner.create_dir_all(path, mode)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        match self.next_fault(Operation::Read) {
+            Some(Fault::Error(kind)) => Err(injected(kind)),
+            Some(Fault::PartialRead(len)) => {
+                let mut contents = self.inner.read(path)?;
+                contents.truncate(len);
+                Ok(contents)
+            }
+            None => self.inner.read(path),
+        }
+    }
+
+    fn write(&self, path: &Path, contents: &[u8], mode: Option<u32>) -> io::Result<()> {
+        self.check(Operation::Write)?;
+        self.inner.write(path, contents, mode)
+    }
+
+    fn create(&self, path: &Path, mode: Option<u32>) -> io::Result<Box<dyn Write + Send>> {
+        self.check(Operation::Write)?;
+        self.inner.create(path, mode)
+    }
+
+    fn append(&self, path: &Path, contents: &[u8], mode: Option<u32>) -> io::Result<()> {
+        self.check(Operation::Write)?;
+        self.inner.append(path, contents, mode)
+    }
+
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.check(Operation::Copy)?;
+        self.inner.copy(from, to)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.check(Operation::Rename)?;
+        self.inner.rename(from, to)
+    }
+
+    fn len(&self, path: &Path) -> io::Result<u64> {
+        self.inner.len(path)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        self.check(Operation::RemoveFile)?;
+        self.inner.remove_file(path)
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()> {
+        self.check(Operation::RemoveDirAll)?;
+        self.inner.remove_dir_all(path)
+    }
+
+    fn permissions(&self, path: &Path) -> io::Result<Option<u32>> {
+        self.inner.permissions(path)
+    }
+
+    fn set_permissions(&self, path: &Path, mode: u32) -> io::Result<()> {
+        self.inner.set_permissions(path, mode)
+    }
+
+    fn sync(&self, path: &Path) -> io::Result<()> {
+        self.check(Operation::Sync)?;
+        self.inner.sync(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        self.check(Operation::ReadDir)?;
+        self.inner.read_dir(path)
+    }
+
+    fn lock(&self, path: &Path) -> io::Result<LockGuard> {
+        self.check(Operation::Lock)?;
+        self.inner.lock(path)
+    }
+}
@@ -0,0 +1,47 @@
+//! Bookkeeping for fields that are present in a config file but not in the config data type.
+//!
+//! Unknown fields are keyed by their JSON Pointer (RFC 6901), e.g. `/servers/0/proxy`, so that
+//! nested fields can be put back in the same place when the config is saved.
+use serde_json::{Map, Value};
+
+/// Convert a path reported by `serde_ignored` into a JSON Pointer.
+pub(crate) fn json_pointer(path: &serde_ignored::Path) -> String {
+    match path {
+        serde_ignored::Path::Root => String::new(),
+        serde_ignored::Path::Seq { parent, index } => format!("{}/{}", json_pointer(parent), index),
+        serde_ignored::Path::Map { parent, key } => format!(
+            "{}/{}",
+            json_pointer(parent),
+            key.replace('~', "~0").replace('/', "~1")
+        ),
+        serde_ignored::Path::Some { parent }
+        | serde_ignored::Path::NewtypeStruct { parent }
+        | serde_ignored::Path::NewtypeVariant { parent } => json_pointer(parent),
+    }
+}
+
+/// Put unknown fields back into a serialized document.
+///
+/// A field is only restored if its parent still exists and the document doesn't already have a
+/// value at that location.
+pub(crate) fn restore(document: &mut Value, unknown_fields: &Map<String, Value>) {
+    for (pointer, value) in unknown_fields {
+        let Some((parent_pointer, key)) = pointer.rsplit_once('/') else {
+            continue;
+        };
+        let key = key.replace("~1", "/").replace("~0", "~");
+        match document.pointer_mut(parent_pointer) {
+            Some(Value::Object(parent)) => {
+                parent.entry(key).or_insert_with(|| value.clone());
+            }
+            Some(Value::Array(parent)) => {
+                if let Ok(index) = key.parse::<usize>() {
+                    if index >= parent.len() {
+                        parent.push(value.clone());
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+}
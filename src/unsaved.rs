@@ -0,0 +1,94 @@
+//! Detecting configs that are dropped with changes that were never saved, see
+//! [`ConfigOptions::unsaved_changes`](crate::ConfigOptions::unsaved_changes).
+use std::{
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+};
+
+/// Reports configs that are dropped with unsaved changes, e.g. to the app's logger.
+pub trait UnsavedChangesHandler: Debug + Send + Sync {
+    /// Called when the config identified by `config_file_key` is dropped with unsaved changes.
+    fn unsaved_changes(&self, config_file_key: &str);
+}
+
+/// How to react to a config being dropped with unsaved changes.
+///
+/// A config counts as changed once its data was handed out with
+/// [`data_mut`](crate::Config::data_mut) or replaced (e.g. with
+/// [`set_pointer`](crate::Config::set_pointer)), until it is saved again, even if the data ended
+/// up the same.
+#[derive(Debug, Clone, Default)]
+pub enum UnsavedChangesPolicy {
+    #[default]
+    Ignore,
+
+    /// Log a warning: a `tracing` event with the `tracing` feature, or printed to stderr without
+    /// it.
+    Warn,
+
+    /// Panic in debug builds, to catch forgotten saves in tests, and log a warning like
+    /// [`Warn`](Self::Warn) in release builds.  Configs dropped while the thread is already
+    /// panicking only get the warning.
+    PanicInDebug,
+
+    /// Let a handler report it.
+    Handler(Arc<dyn UnsavedChangesHandler>),
+}
+
+/// Dirty flag of a config, which applies the [`UnsavedChangesPolicy`] when dropped while set.
+#[derive(Debug)]
+pub(crate) struct UnsavedChanges {
+    config_file_key: String,
+    policy: UnsavedChangesPolicy,
+    dirty: AtomicBool,
+}
+
+impl UnsavedChanges {
+    pub(crate) fn new(config_file_key: &str, policy: UnsavedChangesPolicy) -> Self {
+        Self {
+            config_file_key: config_file_key.to_string(),
+            policy,
+            dirty: AtomicBool::new(false),
+        }
+    }
+
+    pub(crate) fn mark(&self) {
+        self.dirty.store(true, Ordering::Relaxed);
+    }
+
+    pub(crate) fn clear(&self) {
+        self.dirty.store(false, Ordering::Relaxed);
+    }
+
+    pub(crate) fn is_set(&self) -> bool {
+        self.dirty.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for UnsavedChanges {
+    fn drop(&mut self) {
+        if !self.is_set() {
+            return;
+        }
+        let key = &self.config_file_key;
+        match &self.policy {
+            UnsavedChangesPolicy::Ignore => {}
+            UnsavedChangesPolicy::PanicInDebug
+                if cfg!(debug_assertions) && !thread::panicking() =>
+            {
+                panic!("config `{}` was dropped with unsaved changes", key)
+            }
+            UnsavedChangesPolicy::Warn | UnsavedChangesPolicy::PanicInDebug => {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(key = %key, "config was dropped with unsaved changes");
+                #[cfg(not(feature = "tracing"))]
+                eprintln!("WARNING: config `{}` was dropped with unsaved changes", key);
+            }
+            UnsavedChangesPolicy::Handler(handler) => handler.unsaved_changes(key),
+        }
+    }
+}
@@ -0,0 +1,234 @@
+//! Reporting every invalid field of a config file at once.
+//!
+//! Deserialization stops at the first invalid value, so someone fixing a hand-edited file would
+//! otherwise only learn about one problem per attempt.  When deserializing fails on a value, a
+//! placeholder that fits (e.g. an empty string where a string is expected) is put in its place,
+//! or else the value is taken out, and deserialization is tried again until it either succeeds
+//! or the problems can't be narrowed down any further.
+use std::fmt;
+
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+
+use crate::ConfigError;
+
+/// Upper bound on the number of problems collected for one file.
+const MAX_ERRORS: usize = 64;
+
+/// A value in a config file that doesn't fit the config data type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldError {
+    /// Dot-separated path of the value, e.g. `servers.0.port`, or an empty string for the whole
+    /// document.
+    pub path: String,
+
+    pub message: String,
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.path.as_str() {
+            "" => write!(f, "{}", self.message),
+            path => write!(f, "{}: {}", path, self.message),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Step {
+    Key(String),
+    Index(usize),
+}
+
+/// Turn a data error from deserializing `contents` into a `ConfigFileValidationError` if the
+/// contents have more than one invalid value.  Other errors are returned unchanged.
+pub(crate) fn aggregate<T: DeserializeOwned>(error: ConfigError, contents: &[u8]) -> ConfigError {
+    match &error {
+        ConfigError::ConfigFileParseError(path, e, None) if e.is_data() => {
+            let Ok(document) = serde_json::from_slice(contents) else {
+                return error;
+            };
+            let errors = field_errors::<T>(document);
+            if errors.len() < 2 {
+                return error;
+            }
+            ConfigError::ConfigFileValidationError(path.clone(), errors)
+        }
+        _ => error,
+    }
+}
+
+/// Collect the problems with deserializing `document` as a `T`.
+fn field_errors<T: DeserializeOwned>(mut document: Value) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+    // Paths of the values taken out so far, as they were at the time
+    let mut removed: Vec<Vec<Step>> = Vec::new();
+    let mut next = first_error::<T>(&document);
+    while let Some((path, message)) = next.take() {
+        if errors.len() == MAX_ERRORS {
+            break;
+        }
+        // A missing field is filled in where it's missing, anything else where it is
+        let target = match missing_field(&message) {
+            Some(key) => {
+                let mut member = path.clone();
+                member.push(Step::Key(key.to_string()));
+                member
+            }
+            None => path.clone(),
+        };
+        // Taking out a required value makes its parent fail with a missing field; that only
+        // means the parent's other values are fine
+        if !removed.contains(&target) {
+            errors.push(FieldError {
+                path: display(&original_path(&path, &removed)),
+                message,
+            });
+        }
+
+        if fill_in::<T>(&mut document, &target) {
+            next = first_error::<T>(&document);
+        } else if !path.is_empty() && remove(&mut document, &path) {
+            removed.push(path);
+            next = first_error::<T>(&document);
+        }
+    }
+    errors
+}
+
+/// The path and message of the first problem with deserializing `document` as a `T`, if any.
+fn first_error<T: DeserializeOwned>(document: &Value) -> Option<(Vec<Step>, String)> {
+    let error = serde_path_to_error::deserialize::<_, T>(document).err()?;
+    // Without a precise path there's no telling what to fill in, so stop there
+    let path = steps(error.path()).unwrap_or_default();
+    Some((path, error.inner().to_string()))
+}
+
+/// Put a placeholder value that `T` accepts at `target` (e.g. an empty string where a string is
+/// expected), so that deserializing gets past it.  Returns whether a placeholder was accepted.
+fn fill_in<T: DeserializeOwned>(document: &mut Value, target: &[Step]) -> bool {
+    if target.is_empty() {
+        return false;
+    }
+    let placeholders = [
+        Value::Null,
+        Value::Bool(false),
+        Value::from(0),
+        Value::from(""),
+        Value::Array(Vec::new()),
+        Value::Object(Map::new()),
+    ];
+    let original = get(document, target).cloned();
+    for placeholder in placeholders {
+        if !set(document, target, placeholder) {
+            return false;
+        }
+        match first_error::<T>(document) {
+            Some((path, _)) if path.starts_with(target) => continue,
+            _ => return true,
+        }
+    }
+    match original {
+        Some(original) => set(document, target, original),
+        None => remove(document, target),
+    };
+    false
+}
+
+fn get<'a>(document: &'a Value, path: &[Step]) -> Option<&'a Value> {
+    path.iter()
+        .try_fold(document, |value, step| match (value, step) {
+            (Value::Object(map), Step::Key(key)) => map.get(key),
+            (Value::Array(items), Step::Index(index)) => items.get(*index),
+            _ => None,
+        })
+}
+
+/// The parent of the value at `path`, along with the last step to it.
+fn parent_mut<'a>(document: &'a mut Value, path: &'a [Step]) -> Option<(&'a mut Value, &'a Step)> {
+    let (last, parent_path) = path.split_last()?;
+    let parent = parent_path
+        .iter()
+        .try_fold(document, |value, step| match (value, step) {
+            (Value::Object(map), Step::Key(key)) => map.get_mut(key),
+            (Value::Array(items), Step::Index(index)) => items.get_mut(*index),
+            _ => None,
+        })?;
+    Some((parent, last))
+}
+
+/// Set the value at `path`, adding the member if needed.  Returns whether the parent exists.
+fn set(document: &mut Value, path: &[Step], value: Value) -> bool {
+    match parent_mut(document, path) {
+        Some((Value::Object(map), Step::Key(key))) => {
+            map.insert(key.clone(), value);
+            true
+        }
+        Some((Value::Array(items), Step::Index(index))) if *index < items.len() => {
+            items[*index] = value;
+            true
+        }
+        _ => false,
+    }
+}
+
+fn steps(path: &serde_path_to_error::Path) -> Option<Vec<Step>> {
+    path.iter()
+        .map(|segment| match segment {
+            serde_path_to_error::Segment::Map { key } => Some(Step::Key(key.clone())),
+            // Externally tagged enum variants are keys in the document as well
+            serde_path_to_error::Segment::Enum { variant } => Some(Step::Key(variant.clone())),
+            serde_path_to_error::Segment::Seq { index } => Some(Step::Index(*index)),
+            serde_path_to_error::Segment::Unknown => None,
+        })
+        .collect()
+}
+
+/// The field named by a "missing field `name`" message.
+fn missing_field(message: &str) -> Option<&str> {
+    message
+        .strip_prefix("missing field `")?
+        .split_once('`')
+        .map(|(key, _)| key)
+}
+
+/// Remove the value at `path`, returning whether there was one.
+fn remove(document: &mut Value, path: &[Step]) -> bool {
+    match parent_mut(document, path) {
+        Some((Value::Object(map), Step::Key(key))) => map.shift_remove(key).is_some(),
+        Some((Value::Array(items), Step::Index(index))) if *index < items.len() => {
+            items.remove(*index);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Translate a path in the current document back to the original one, accounting for array
+/// elements that were taken out before it.
+fn original_path(path: &[Step], removed: &[Vec<Step>]) -> Vec<Step> {
+    let mut path = path.to_vec();
+    for removal in removed.iter().rev() {
+        let Some((Step::Index(removed_index), array)) = removal.split_last() else {
+            continue;
+        };
+        if path.len() > array.len() && path.starts_with(array) {
+            if let Step::Index(index) = &mut path[array.len()] {
+                if *index >= *removed_index {
+                    *index += 1;
+                }
+            }
+        }
+    }
+    path
+}
+
+fn display(path: &[Step]) -> String {
+    path.iter()
+        .map(|step| match step {
+            Step::Key(key) => key.clone(),
+            Step::Index(index) => index.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(".")
+}
@@ -0,0 +1,75 @@
+//! Variables substituted into the default document of a config on first run.
+use std::{collections::BTreeMap, env, fs, process::Command};
+
+/// Values for `{name}` placeholders in the default strings of a config, set with
+/// [`ConfigOptions::template_variables`](crate::ConfigOptions::template_variables).
+///
+/// [`new`](Self::new) provides `{username}`, `{hostname}` and `{home}` where they can be
+/// determined; apps add their own, such as `{app_version}`, with [`set`](Self::set).
+/// Placeholders without a value are left as they are.
+///
+/// ```
+/// # use ilo_config::TemplateVariables;
+/// let variables = TemplateVariables::new().set("app_version", env!("CARGO_PKG_VERSION"));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct TemplateVariables {
+    values: BTreeMap<String, String>,
+}
+
+impl TemplateVariables {
+    /// Variables describing the current user and machine.
+    pub fn new() -> Self {
+        let mut values = BTreeMap::new();
+        if let Ok(username) = env::var("USER").or_else(|_| env::var("USERNAME")) {
+            values.insert(String::from("username"), username);
+        }
+        if let Some(hostname) = hostname() {
+            values.insert(String::from("hostname"), hostname);
+        }
+        if let Some(home) = crate::environment::home_dir() {
+            values.insert(String::from("home"), home.to_string_lossy().into_owned());
+        }
+        Self { values }
+    }
+
+    /// Add or replace a variable.
+    pub fn set(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.values.insert(name.into(), value.into());
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(String::as_str)
+    }
+
+    /// Substitute the variables into a JSON document, escaping the values for use inside JSON
+    /// strings.
+    pub(crate) fn substitute(&self, document: &str) -> String {
+        let mut document = document.to_string();
+        for (name, value) in &self.values {
+            let escaped = serde_json::to_string(value).expect("strings always serialize");
+            let escaped = &escaped[1..escaped.len() - 1];
+            document = document.replace(&format!("{{{}}}", name), escaped);
+        }
+        document
+    }
+}
+
+/// The name of the machine, from the environment, `/etc/hostname` or the `hostname` command
+/// (e.g. on macOS, which has no `/etc/hostname` and doesn't export `$HOSTNAME`).
+pub(crate) fn hostname() -> Option<String> {
+    env::var("HOSTNAME")
+        .or_else(|_| env::var("COMPUTERNAME"))
+        .ok()
+        .or_else(|| fs::read_to_string("/etc/hostname").ok())
+        .or_else(|| {
+            let output = Command::new("hostname").output().ok()?;
+            output
+                .status
+                .success()
+                .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+        })
+        .map(|hostname| hostname.trim().to_string())
+        .filter(|hostname| !hostname.is_empty())
+}
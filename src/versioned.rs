@@ -0,0 +1,190 @@
+//! Configs whose data type is an enum of versions, as a type-safe alternative to rewriting the
+//! document on load.
+use std::{
+    fmt::{self, Debug},
+    ops::{Deref, DerefMut},
+};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// An enum with a variant for every version of a config data type, usually tagged with serde's
+/// `#[serde(tag = "version")]`.
+///
+/// Load it as a [`Versioned`] config to always work with the latest version.
+pub trait VersionedConfig: Sized {
+    /// The data type of the latest version.
+    type Latest;
+
+    /// Convert any version into the latest one, e.g. by chaining `From` impls between versions.
+    fn upgrade(self) -> Self::Latest;
+
+    /// Wrap the latest version in its variant.  This is what gets saved unless a different
+    /// version is chosen with [`Versioned::write_as`].
+    fn from_latest(latest: Self::Latest) -> Self;
+}
+
+/// Config data stored as one of the versions of a [`VersionedConfig`] enum.
+///
+/// Whichever version a file holds, it is upgraded to the latest one on load, and the config
+/// derefs to the latest version.  Saving writes the latest version, or the one chosen with
+/// [`write_as`](Self::write_as) for files that older versions of an app still need to read.
+///
+/// ```
+/// # use serde::{Deserialize, Serialize};
+/// use ilo_config::{Config, Versioned, VersionedConfig};
+///
+/// #[derive(Clone, Serialize, Deserialize)]
+/// struct SettingsV1 {
+///     host: String,
+/// }
+///
+/// #[derive(Clone, Serialize, Deserialize)]
+/// struct SettingsV2 {
+///     hosts: Vec<String>,
+/// }
+///
+/// #[derive(Serialize, Deserialize)]
+/// #[serde(tag = "version")]
+/// enum Settings {
+///     #[serde(rename = "1")]
+///     V1(SettingsV1),
+///     #[serde(rename = "2")]
+///     V2(SettingsV2),
+/// }
+///
+/// impl VersionedConfig for Settings {
+///     type Latest = SettingsV2;
+///
+///     fn upgrade(self) -> SettingsV2 {
+///         match self {
+///             Settings::V1(v1) => SettingsV2 { hosts: vec![v1.host] },
+///             Settings::V2(v2) => v2,
+///         }
+///     }
+///
+///     fn from_latest(latest: SettingsV2) -> Self {
+///         Settings::V2(latest)
+///     }
+/// }
+///
+/// let file = r#"{ "version": "1", "host": "a.example.com" }"#;
+/// let mut config: Config<Versioned<Settings>> = Config::from_reader("settings", file.as_bytes())?;
+/// assert_eq!(config.data().hosts, ["a.example.com"]);
+///
+/// config.data_mut().hosts.push(String::from("b.example.com"));
+/// let mut saved = Vec::new();
+/// config.to_writer(&mut saved)?;
+/// assert!(String::from_utf8(saved).unwrap().contains(r#""version": "2""#));
+/// # Ok::<(), ilo_config::ConfigError>(())
+/// ```
+pub struct Versioned<E: VersionedConfig> {
+    latest: E::Latest,
+    write_as: Option<fn(E::Latest) -> E>,
+}
+
+impl<E: VersionedConfig> Versioned<E> {
+    pub fn new(latest: E::Latest) -> Self {
+        Self {
+            latest,
+            write_as: None,
+        }
+    }
+
+    /// Save the data as the version returned by `downgrade`, instead of the latest version.
+    ///
+    /// ```
+    /// # use ilo_config::{Versioned, VersionedConfig};
+    /// # #[derive(Clone)]
+    /// # struct V1;
+    /// # enum Settings { V1(V1) }
+    /// # impl VersionedConfig for Settings {
+    /// #     type Latest = V1;
+    /// #     fn upgrade(self) -> V1 { match self { Settings::V1(v1) => v1 } }
+    /// #     fn from_latest(latest: V1) -> Self { Settings::V1(latest) }
+    /// # }
+    /// # let mut settings = Versioned::<Settings>::new(V1);
+    /// settings.write_as(|latest| Settings::V1(latest));
+    /// ```
+    pub fn write_as(&mut self, downgrade: fn(E::Latest) -> E) {
+        self.write_as = Some(downgrade);
+    }
+
+    /// Go back to saving the latest version.
+    pub fn write_latest(&mut self) {
+        self.write_as = None;
+    }
+
+    pub fn into_inner(self) -> E::Latest {
+        self.latest
+    }
+}
+
+impl<E: VersionedConfig> Deref for Versioned<E> {
+    type Target = E::Latest;
+
+    fn deref(&self) -> &E::Latest {
+        &self.latest
+    }
+}
+
+impl<E: VersionedConfig> DerefMut for Versioned<E> {
+    fn deref_mut(&mut self) -> &mut E::Latest {
+        &mut self.latest
+    }
+}
+
+impl<E: VersionedConfig> Default for Versioned<E>
+where
+    E::Latest: Default,
+{
+    fn default() -> Self {
+        Self::new(E::Latest::default())
+    }
+}
+
+impl<E: VersionedConfig> Clone for Versioned<E>
+where
+    E::Latest: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            latest: self.latest.clone(),
+            write_as: self.write_as,
+        }
+    }
+}
+
+impl<E: VersionedConfig> Debug for Versioned<E>
+where
+    E::Latest: Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Versioned")
+            .field("latest", &self.latest)
+            .field("write_as", &self.write_as.is_some())
+            .finish()
+    }
+}
+
+impl<E> Serialize for Versioned<E>
+where
+    E: VersionedConfig + Serialize,
+    E::Latest: Clone,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let latest = self.latest.clone();
+        match self.write_as {
+            Some(downgrade) => downgrade(latest).serialize(serializer),
+            None => E::from_latest(latest).serialize(serializer),
+        }
+    }
+}
+
+impl<'de, E> Deserialize<'de> for Versioned<E>
+where
+    E: VersionedConfig + Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        E::deserialize(deserializer).map(|version| Self::new(version.upgrade()))
+    }
+}
@@ -0,0 +1,44 @@
+#![cfg(feature = "derive")]
+
+use ilo_config::{ConfigFormat, ConfigKey, IloConfig};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize, IloConfig)]
+#[ilo_config(format = "jsonc", default = "defaults::server")]
+struct HTTPServerConfig {
+    port: u16,
+}
+
+mod defaults {
+    pub fn server() -> super::HTTPServerConfig {
+        super::HTTPServerConfig { port: 8080 }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize, IloConfig)]
+#[ilo_config(key = "jira")]
+struct JiraConfig {
+    url: Option<String>,
+}
+
+#[test]
+fn default_attribute_provides_the_initial_data() {
+    assert_eq!(HTTPServerConfig::KEY, "http-server-config");
+    assert_eq!(HTTPServerConfig::options().format, ConfigFormat::Jsonc);
+    assert_eq!(
+        HTTPServerConfig::default_data(),
+        HTTPServerConfig { port: 8080 }
+    );
+
+    // The only test that loads a config, so nothing else sees the changed config root
+    let root = tempfile::tempdir().unwrap();
+    std::env::set_var(ilo_config::ROOT_ENV_VAR, root.path());
+    let config = HTTPServerConfig::load().unwrap();
+    assert_eq!(config.data(), &HTTPServerConfig { port: 8080 });
+}
+
+#[test]
+fn data_defaults_to_the_default_value() {
+    assert_eq!(JiraConfig::KEY, "jira");
+    assert_eq!(JiraConfig::default_data(), JiraConfig::default());
+}